@@ -0,0 +1,185 @@
+use anchor_lang::{prelude::*, solana_program::{instruction::{AccountMeta, Instruction}, program::invoke_signed}};
+use anchor_spl::metadata::mpl_token_metadata::types::Creator;
+
+use crate::{errors::StakeError, nft::has_verified_creator};
+
+// There's no CPI crate for Bubblegum on anchor 0.31, so its instructions are built by hand
+pub const BUBBLEGUM_ID: Pubkey = pubkey!("BGUMAp9Gq7iTEuizy4pqaxsTyUCBK68MDfK752saRPUY");
+pub const ACCOUNT_COMPRESSION_ID: Pubkey = pubkey!("cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK");
+pub const NOOP_ID: Pubkey = pubkey!("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV");
+
+/// Anchor discriminator of Bubblegum's `transfer`, `sha256("global:transfer")[..8]`.
+const TRANSFER_DISCRIMINATOR: [u8; 8] = [163, 52, 200, 231, 140, 3, 69, 186];
+
+/// A creator as hashed into a leaf's `creator_hash`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct LeafCreator {
+    pub address: Pubkey,
+    pub verified: bool,
+    pub share: u8,
+}
+
+/// Everything Bubblegum needs to find and replace a leaf, read from the DAS API's `getAssetProof`
+/// and `getAsset`. The proof path itself goes in remaining_accounts, minus the tree's canopy.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct CompressedLeaf {
+    pub root: [u8; 32],
+    pub data_hash: [u8; 32],
+    pub creator_hash: [u8; 32],
+    pub nonce: u64,
+    pub index: u32,
+}
+
+/// The asset id Bubblegum derives for leaf `nonce` of `merkle_tree`. Stands in for the mint of
+/// a compressed stake.
+pub fn asset_id(merkle_tree: &Pubkey, nonce: u64) -> Pubkey {
+    Pubkey::find_program_address(&[b"asset", merkle_tree.as_ref(), &nonce.to_le_bytes()], &BUBBLEGUM_ID).0
+}
+
+/// Bubblegum's `creator_hash` of `creators`, so passed creators can be checked against the leaf.
+pub fn hash_creators(creators: &[LeafCreator]) -> [u8; 32] {
+    let creator_data: Vec<Vec<u8>> = creators
+        .iter()
+        .map(|creator| [creator.address.as_ref(), &[creator.verified as u8, creator.share]].concat())
+        .collect();
+    let slices: Vec<&[u8]> = creator_data.iter().map(Vec::as_slice).collect();
+    solana_keccak_hasher::hashv(&slices).to_bytes()
+}
+
+/// Whether `creators` hash to the leaf's `creator_hash` and include `creator` as verified.
+pub fn leaf_has_verified_creator(leaf: &CompressedLeaf, creators: &[LeafCreator], creator: &Pubkey) -> bool {
+    let creators_hashed = hash_creators(creators) == leaf.creator_hash;
+    let creators: Vec<Creator> = creators
+        .iter()
+        .map(|leaf_creator| Creator {
+            address: leaf_creator.address,
+            verified: leaf_creator.verified,
+            share: leaf_creator.share,
+        })
+        .collect();
+    creators_hashed && has_verified_creator(Some(&creators), creator)
+}
+
+pub struct BubblegumAccounts<'a, 'info> {
+    pub tree_config: &'a AccountInfo<'info>,
+    pub merkle_tree: &'a AccountInfo<'info>,
+    pub log_wrapper: &'a AccountInfo<'info>,
+    pub compression_program: &'a AccountInfo<'info>,
+    pub bubblegum_program: &'a AccountInfo<'info>,
+    pub system_program: &'a AccountInfo<'info>,
+    pub proof: &'a [AccountInfo<'info>],
+}
+
+impl<'a, 'info> BubblegumAccounts<'a, 'info> {
+
+    /// Moves `leaf` from `owner` to `new_owner`. Bubblegum rebuilds the leaf from the passed owner
+    /// and delegate and the compression program checks it against `root`, so this fails unless
+    /// `owner` really holds it. `owner` signs, through `signer_seeds` when it's a PDA.
+    pub fn transfer(
+        &self,
+        leaf: &CompressedLeaf,
+        owner: &'a AccountInfo<'info>,
+        delegate: &'a AccountInfo<'info>,
+        new_owner: &'a AccountInfo<'info>,
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+        require_keys_eq!(self.bubblegum_program.key(), BUBBLEGUM_ID, StakeError::InvalidCompressedProgram);
+        require_keys_eq!(self.compression_program.key(), ACCOUNT_COMPRESSION_ID, StakeError::InvalidCompressedProgram);
+        require_keys_eq!(self.log_wrapper.key(), NOOP_ID, StakeError::InvalidCompressedProgram);
+
+        let mut accounts = vec![
+            AccountMeta::new_readonly(self.tree_config.key(), false),
+            AccountMeta::new_readonly(owner.key(), true),
+            AccountMeta::new_readonly(delegate.key(), delegate.key() == owner.key()),
+            AccountMeta::new_readonly(new_owner.key(), false),
+            AccountMeta::new(self.merkle_tree.key(), false),
+            AccountMeta::new_readonly(self.log_wrapper.key(), false),
+            AccountMeta::new_readonly(self.compression_program.key(), false),
+            AccountMeta::new_readonly(self.system_program.key(), false),
+        ];
+        accounts.extend(self.proof.iter().map(|node| AccountMeta::new_readonly(node.key(), false)));
+
+        let mut infos = vec![
+            self.tree_config.clone(),
+            owner.clone(),
+            delegate.clone(),
+            new_owner.clone(),
+            self.merkle_tree.clone(),
+            self.log_wrapper.clone(),
+            self.compression_program.clone(),
+            self.system_program.clone(),
+        ];
+        infos.extend(self.proof.iter().cloned());
+
+        invoke_signed(
+            &Instruction {
+                program_id: BUBBLEGUM_ID,
+                accounts,
+                data: transfer_data(leaf)?,
+            },
+            &infos,
+            signer_seeds,
+        )?;
+
+        Ok(())
+    }
+}
+
+fn transfer_data(leaf: &CompressedLeaf) -> Result<Vec<u8>> {
+    let mut data = TRANSFER_DISCRIMINATOR.to_vec();
+    leaf.serialize(&mut data)?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::hash::hash;
+
+    fn leaf() -> CompressedLeaf {
+        CompressedLeaf { root: [1; 32], data_hash: [2; 32], creator_hash: [3; 32], nonce: 4, index: 5 }
+    }
+
+    #[test]
+    fn transfer_discriminator_matches_bubblegum() {
+        assert_eq!(hash(b"global:transfer").to_bytes()[..8], TRANSFER_DISCRIMINATOR);
+    }
+
+    #[test]
+    fn transfer_data_layout() {
+        let data = transfer_data(&leaf()).unwrap();
+
+        // discriminator, root, data_hash, creator_hash, nonce, index
+        assert_eq!(data.len(), 8 + 32 * 3 + 8 + 4);
+        assert_eq!(data[8..40], [1; 32]);
+        assert_eq!(data[104..112], 4_u64.to_le_bytes());
+        assert_eq!(data[112..], 5_u32.to_le_bytes());
+    }
+
+    #[test]
+    fn asset_id_is_per_tree_and_nonce() {
+        let tree = Pubkey::new_unique();
+
+        assert_eq!(asset_id(&tree, 0), asset_id(&tree, 0));
+        assert_ne!(asset_id(&tree, 0), asset_id(&tree, 1));
+        assert_ne!(asset_id(&tree, 0), asset_id(&Pubkey::new_unique(), 0));
+    }
+
+    #[test]
+    fn creators_must_hash_to_the_leaf_and_be_verified() {
+        let creator = Pubkey::new_unique();
+        let creators = vec![
+            LeafCreator { address: Pubkey::new_unique(), verified: false, share: 40 },
+            LeafCreator { address: creator, verified: true, share: 60 },
+        ];
+        let leaf = CompressedLeaf { creator_hash: hash_creators(&creators), ..leaf() };
+
+        assert!(leaf_has_verified_creator(&leaf, &creators, &creator));
+        assert!(!leaf_has_verified_creator(&leaf, &creators, &creators[0].address));
+
+        // Claiming a verification the leaf doesn't carry changes the hash
+        let mut forged = creators.clone();
+        forged[0].verified = true;
+        assert!(!leaf_has_verified_creator(&leaf, &forged, &forged[0].address));
+    }
+}
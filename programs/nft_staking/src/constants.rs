@@ -0,0 +1,60 @@
+/// Upper bound on NFTs per `stake_batch` or `unstake_many` call, keeps us within the compute budget and tx size.
+pub const MAX_BATCH_SIZE: usize = 3;
+
+/// Accounts passed per NFT in `stake_batch`: mint, mint_ata, metadata, master_edition, stake_account,
+/// mint_record.
+pub const STAKE_BATCH_ACCOUNTS: usize = 6;
+
+/// Accounts passed per stake in `unstake_many`: stake_account, mint, mint_ata, metadata,
+/// master_edition, mint_record.
+pub const UNSTAKE_BATCH_ACCOUNTS: usize = 6;
+
+/// Upper bound on users per `claim_many` call, each claim is up to three transfers.
+pub const MAX_CLAIM_BATCH_SIZE: usize = 5;
+
+/// Accounts passed per user in `claim_many`: user_account, rewards_ata, blacklist.
+pub const CLAIM_BATCH_ACCOUNTS: usize = 3;
+
+/// Number of `StakeConfig.reward_tiers` slots.
+pub const REWARD_TIERS: usize = 4;
+
+/// Number of `StakeConfig.lockup_options` slots, one per `LockupKind`.
+pub const LOCKUP_KINDS: usize = 3;
+
+/// Longest `StakeConfig.freeze_period_secs` the admin can set, a year.
+pub const MAX_FREEZE_PERIOD_SECS: u64 = 365 * 86400;
+
+/// Halvings after which `points_per_stake` stops decaying, emissions floor at 1/64 of the base rate.
+pub const MAX_HALVINGS: i64 = 6;
+
+/// Days one `StakeAccount::accrue` settles at most, `crank_accrue` catches longer gaps up over
+/// several calls.
+pub const MAX_ACCRUAL_DAYS: u32 = 365;
+
+/// Number of `StakeConfig.set_collections` slots.
+pub const SET_COLLECTIONS: usize = 5;
+
+/// Number of `Leaderboard.entries` slots.
+pub const LEADERBOARD_SIZE: usize = 100;
+
+/// Mints a `UserPositions` has room for, `StakeConfig.max_stake` can't go past it.
+pub const MAX_POSITIONS: usize = 32;
+
+/// Number of `EpochHistory.entries` slots, the last this many epochs are kept.
+pub const EPOCH_HISTORY_SIZE: usize = 64;
+
+pub const BPS_DENOMINATOR: u16 = 10_000;
+
+pub const SECONDS_PER_DAY: i64 = 86400;
+
+/// Seed of the PDA that Token-2022 group member NFTs set as freeze authority to be frozen in place.
+pub const FREEZE_AUTHORITY_SEED: &[u8] = b"freeze_authority";
+
+/// `StakeConfig.pause_flags` bit that blocks `stake` and `stake_batch`.
+pub const PAUSE_STAKE: u8 = 1 << 0;
+
+/// `StakeConfig.pause_flags` bit that blocks `claim`.
+pub const PAUSE_CLAIM: u8 = 1 << 1;
+
+/// `StakeConfig.early_unstake_penalty_bps` sentinel that turns `emergency_unstake` off.
+pub const EMERGENCY_UNSTAKE_DISABLED: u16 = u16::MAX;
@@ -5,5 +5,190 @@ pub enum StakeError{
     #[msg("Max Stake Reached")]
     MaxStakeReached,
     #[msg("Freeze Time Not Passed")]
-    FreezePeriodNotPassed
+    FreezePeriodNotPassed,
+    #[msg("Invalid Batch Size")]
+    InvalidBatchSize,
+    #[msg("Remaining Accounts Do Not Match The Batch")]
+    InvalidRemainingAccounts,
+    #[msg("NFT Is Not From The Configured Collection")]
+    InvalidCollection,
+    #[msg("Arithmetic Overflow")]
+    ArithmeticOverflow,
+    #[msg("Nothing To Claim")]
+    NothingToClaim,
+    #[msg("Unauthorized")]
+    Unauthorized,
+    #[msg("Emergency Unstake Disabled")]
+    EmergencyUnstakeDisabled,
+    #[msg("Penalty Must Be At Most 10000 Bps")]
+    InvalidPenaltyBps,
+    // No longer returned, kept so the codes after it don't shift
+    #[msg("Collection Not Whitelisted")]
+    CollectionNotWhitelisted,
+    #[msg("Claim Cooldown Active")]
+    ClaimCooldownActive,
+    #[msg("NFT Is Still Frozen Or Delegated")]
+    NftStillFrozen,
+    #[msg("Reward Tiers Must Be Sorted By Ascending min_days")]
+    InvalidRewardTiers,
+    #[msg("Program Is Paused")]
+    ProgramPaused,
+    #[msg("NFT Collection Not Verified")]
+    CollectionNotVerified,
+    #[msg("Unsupported Token Standard")]
+    UnsupportedTokenStandard,
+    #[msg("Missing Programmable NFT Accounts")]
+    MissingPnftAccounts,
+    #[msg("Max Stake Must Be Greater Than Zero")]
+    InvalidMaxStake,
+    #[msg("Freeze Period Exceeds The Maximum")]
+    InvalidFreezePeriod,
+    #[msg("Unknown Pause Flags")]
+    InvalidPauseFlags,
+    #[msg("User Still Has Staked NFTs Or Unclaimed Points")]
+    OutstandingStakeOrRewards,
+    #[msg("Stake Account Belongs To A Different Pool")]
+    PoolMismatch,
+    #[msg("Accounts Don't Match The Stake's Custody Mode")]
+    CustodyModeMismatch,
+    #[msg("Insufficient Points")]
+    InsufficientPoints,
+    #[msg("Redemption Sold Out")]
+    RedemptionSoldOut,
+    #[msg("User Account Already Migrated")]
+    UserAlreadyMigrated,
+    #[msg("Insufficient Reward Vault Balance")]
+    InsufficientRewardVault,
+    #[msg("Signer Is Not The Stake Owner")]
+    NotStakeOwner,
+    #[msg("Global Max Stake Reached")]
+    GlobalMaxStakeReached,
+    #[msg("NFT Is Missing The Verified Creator")]
+    UnverifiedNft,
+    #[msg("Users Can't Refer Themselves")]
+    SelfReferral,
+    #[msg("Referrals Only Apply To A First Stake")]
+    ReferralAlreadyUsed,
+    #[msg("Invalid Referrer")]
+    InvalidReferrer,
+    #[msg("Referral Cut Must Be At Most 10000 Bps")]
+    InvalidReferralBps,
+    #[msg("Halving Interval Can't Be Negative")]
+    InvalidEmissionSchedule,
+    #[msg("No Pending Admin")]
+    NoPendingAdmin,
+    #[msg("Adjustment Too Large")]
+    AdjustmentTooLarge,
+    #[msg("Token Staking Disabled")]
+    TokenStakingDisabled,
+    #[msg("Invalid Amount")]
+    InvalidAmount,
+    #[msg("Compounding Disabled")]
+    CompoundingDisabled,
+    #[msg("Invalid Compressed Program")]
+    InvalidCompressedProgram,
+    #[msg("Asset Mismatch")]
+    AssetMismatch,
+    #[msg("Not Pending Admin")]
+    NotPendingAdmin,
+    #[msg("Mint Is Not A Member Of The Collection's Token Group")]
+    NotGroupMember,
+    #[msg("Mint's Freeze Authority Must Be The Program's Freeze Authority PDA")]
+    InvalidFreezeAuthority,
+    #[msg("Pass Both Metadata And Master Edition Or Neither")]
+    MissingMetadataAccounts,
+    #[msg("NFT Is Already Staked, Unstake It Before Staking It Again")]
+    AlreadyStaked,
+    #[msg("Flexible Must Have No Freeze And Longer Lockups Must Hold And Pay At Least As Much")]
+    InvalidLockupOptions,
+    #[msg("Lockup Not Offered By This Pool")]
+    InvalidLockupKind,
+    #[msg("Wallet Is Blacklisted")]
+    WalletBlacklisted,
+    #[msg("Snapshot Pool Is Empty Or Nothing Is Staked")]
+    NothingToDistribute,
+    #[msg("Snapshot Already Claimed")]
+    SnapshotAlreadyClaimed,
+    #[msg("Compounded Points Are Still Locked")]
+    CompoundStillLocked,
+    #[msg("Restake Cooldown Active")]
+    RestakeCooldownActive,
+    #[msg("No Claim Delegate Set")]
+    NoClaimDelegate,
+    #[msg("Stake Receipt Missing")]
+    MissingReceipt,
+    #[msg("Receipt Mint Must Be A Non-Transferable Token-2022 Mint With No Decimals")]
+    InvalidReceiptMint,
+    #[msg("Treasury Fee Must Be At Most 10000 Bps")]
+    InvalidFeeBps,
+    #[msg("Treasury Account Doesn't Belong To The Configured Treasury Or Reward Mint")]
+    InvalidTreasury,
+    #[msg("User Account Needs Migrating, Call migrate_user")]
+    AccountNeedsMigration,
+    #[msg("Set Collections Must Not Repeat")]
+    InvalidSetCollections,
+    #[msg("Emission Cap Reached")]
+    EmissionCapReached,
+    #[msg("Insufficient Funds For Claim Fee")]
+    InsufficientFeeFunds,
+    #[msg("Invalid Rarity Proof")]
+    InvalidRarityProof,
+    #[msg("Decay Rate Must Not Exceed 10000 Bps")]
+    InvalidDecayRate,
+    #[msg("Mint Is Not An NFT")]
+    NotAnNft,
+    #[msg("Reward Mint Does Not Match Config")]
+    RewardMintMismatch,
+    #[msg("Leaderboard Has No More Slots")]
+    LeaderboardFull,
+    #[msg("Boosting Disabled")]
+    BoostingDisabled,
+    #[msg("A Boost Is Already Locked")]
+    BoostAlreadyLocked,
+    #[msg("Boost Tokens Are Still Locked")]
+    BoostStillLocked,
+    #[msg("Config Already Migrated")]
+    ConfigAlreadyMigrated,
+    #[msg("NFT Token Account Is Already Frozen")]
+    TokenAccountFrozen,
+    #[msg("Stake Already Has A Receipt")]
+    AlreadyTokenized,
+    #[msg("Position Receipt Required")]
+    ReceiptRequired,
+    #[msg("Invalid Position Receipt")]
+    InvalidReceipt,
+    #[msg("Wallet Is Not Allowlisted")]
+    NotAllowlisted,
+    #[msg("Max Stake Exceeds Position Capacity")]
+    MaxStakeExceedsCapacity,
+    #[msg("Points Below The Minimum Claim")]
+    BelowMinClaim,
+    #[msg("Open Positions Are Still Locked")]
+    PositionsStillLocked,
+    #[msg("NFT Is Already Delegated Elsewhere")]
+    ExistingDelegate,
+    #[msg("Destination Doesn't Hold The Staked Mint")]
+    DestinationMintMismatch,
+    #[msg("Epoch Has Not Elapsed")]
+    EpochNotElapsed,
+    #[msg("Stake Rate Limited")]
+    StakeRateLimited,
+    #[msg("Invalid Full Set")]
+    InvalidFullSet,
+    #[msg("Signer Is Not The Upgrade Authority")]
+    NotUpgradeAuthority,
+    #[msg("Invalid Mint Authority")]
+    InvalidMintAuthority,
+    #[msg("Points Expiry Must Not Be Negative")]
+    InvalidPointsExpiry,
+    #[msg("Points Per Stake Must Be Positive")]
+    InvalidPointsPerStake,
+    #[msg("Freeze Period Or Lockup Exceeds Max Stake Duration")]
+    FreezeExceedsStakeDuration,
+    #[msg("Stake Window Length Required With A Stake Limit")]
+    InvalidStakeWindow,
+    #[msg("Boost Cap Required With A Boost")]
+    InvalidBoostCap,
+    #[msg("Too Many Days To Settle, Call crank_accrue First")]
+    AccrualTooLong,
 }
@@ -0,0 +1,375 @@
+use anchor_lang::prelude::*;
+
+// Every event carries the `StakeConfig` it came from so one indexer can follow several pools
+
+#[event]
+pub struct NftStaked {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub staked_at: i64,
+    pub slot: u64,
+    pub total_staked: u8,
+}
+
+#[event]
+pub struct NftUnstaked {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub unstaked_at: i64,
+    pub slot: u64,
+    // Seconds between stake and unstake
+    pub duration: i64,
+    pub points_earned: u64,
+    pub rewards_paid: u64,
+    // Credited to the user's referrer
+    pub referral_points: u64,
+}
+
+#[event]
+pub struct BatchUnstaked {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    // Mints unstaked, in the order they were passed
+    pub mints: Vec<Pubkey>,
+    // Mints passed while still locked, left staked
+    pub skipped_locked: Vec<Pubkey>,
+    pub unstaked_at: i64,
+    pub slot: u64,
+    // Summed over `mints`
+    pub points_earned: u64,
+    pub rewards_paid: u64,
+    pub referral_points: u64,
+}
+
+#[event]
+pub struct StakeHarvested {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub harvested_at: i64,
+    pub slot: u64,
+    // Seconds since the stake opened or was last harvested
+    pub duration: i64,
+    // Banked on the user account for a claim
+    pub points_earned: u64,
+    pub referral_points: u64,
+    pub unlocked_at: i64,
+}
+
+#[event]
+pub struct EmergencyUnstaked {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub unstaked_at: i64,
+    pub slot: u64,
+    pub duration: i64,
+    pub points_forfeited: u64,
+    pub points_penalty: u64,
+    // Set when the freeze period had already passed and a normal unstake would have paid out
+    pub freeze_period_passed: bool,
+}
+
+#[event]
+pub struct PauseToggled {
+    pub config: Pubkey,
+    pub admin: Pubkey,
+    pub pause_flags: u8,
+    pub toggled_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct TokensStaked {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub total_staked: u64,
+    pub staked_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct TokensUnstaked {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub total_staked: u64,
+    pub points_earned: u64,
+    pub unstaked_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct RewardsClaimed {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub claimed_by: Pubkey,
+    pub amount: u64,
+    // Paid to the treasury on top of `amount`
+    pub fee: u64,
+    // Paid to the treasury by `claimed_by`
+    pub claim_fee_lamports: u64,
+    pub points_burned: u64,
+    pub remaining_points: u64,
+    pub claimed_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct ClaimDelegateSet {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub delegate: Option<Pubkey>,
+    pub set_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct RewardsCompounded {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub points_burned: u64,
+    pub staked_reward_balance: u64,
+    pub compounded_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct PointsLocked {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub points_burned: u64,
+    // `points_burned` plus the compound bonus
+    pub points_locked: u64,
+    pub locked_points: u64,
+    pub locked_until: i64,
+    pub locked_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct LockedRewardsClaimed {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub points_burned: u64,
+    pub claimed_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct PointsRedeemed {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub redemption: Pubkey,
+    pub cost_points: u32,
+    pub remaining_points: u64,
+    pub redeemed_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct PointsAdjusted {
+    pub config: Pubkey,
+    pub admin: Pubkey,
+    pub user: Pubkey,
+    pub delta: i64,
+    pub reason_code: u8,
+    pub remaining_points: u64,
+    pub adjusted_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct AdminTransferred {
+    pub config: Pubkey,
+    pub previous_admin: Pubkey,
+    pub new_admin: Pubkey,
+    pub transferred_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct AdminTransferCancelled {
+    pub config: Pubkey,
+    pub admin: Pubkey,
+    pub cancelled_admin: Pubkey,
+    pub cancelled_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct RewardVaultFunded {
+    pub config: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+    pub vault_balance: u64,
+    pub funded_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct BlacklistUpdated {
+    pub config: Pubkey,
+    pub admin: Pubkey,
+    pub wallet: Pubkey,
+    // False when the wallet was removed
+    pub blacklisted: bool,
+    pub updated_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct AllowlistUpdated {
+    pub config: Pubkey,
+    pub admin: Pubkey,
+    pub wallet: Pubkey,
+    // False when the wallet was removed
+    pub allowed: bool,
+    pub updated_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct ForceUnstaked {
+    pub config: Pubkey,
+    pub admin: Pubkey,
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub unstaked_at: i64,
+    pub slot: u64,
+    pub duration: i64,
+    pub points_forfeited: u64,
+}
+
+#[event]
+pub struct AdminUnstaked {
+    pub config: Pubkey,
+    pub admin: Pubkey,
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub unstaked_at: i64,
+    pub slot: u64,
+    pub duration: i64,
+    // Credited to the user's `points`, claimable like any other
+    pub points_banked: u64,
+}
+
+#[event]
+pub struct SnapshotFunded {
+    pub config: Pubkey,
+    pub funder: Pubkey,
+    pub amount: u64,
+    // Funded and not yet distributed, `amount` included
+    pub snapshot_pool: u64,
+    pub funded_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct SnapshotDistributed {
+    pub config: Pubkey,
+    pub admin: Pubkey,
+    pub snapshot_id: u64,
+    // Reward token base units split over `total_weight`, rolled over leftovers included
+    pub amount: u64,
+    pub total_weight: u32,
+    pub taken_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct SnapshotClaimed {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub snapshot_id: u64,
+    pub weight: u32,
+    pub amount: u64,
+    pub claimed_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct BoostLocked {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub boost_expiry: i64,
+    pub locked_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct BoostUnlocked {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub amount: u64,
+    pub unlocked_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct PositionTokenized {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub position_mint: Pubkey,
+    pub tokenized_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct PositionDetokenized {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub position_mint: Pubkey,
+    pub detokenized_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct EpochSnapshotTaken {
+    pub config: Pubkey,
+    pub epoch: u64,
+    pub total_staked: u32,
+    pub timestamp: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct MintAuthorityTransferred {
+    pub config: Pubkey,
+    pub reward_mint: Pubkey,
+    pub new_authority: Pubkey,
+    pub transferred_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct PointsExpired {
+    pub config: Pubkey,
+    pub user: Pubkey,
+    pub points_forfeited: u64,
+    // When the forfeited points were last credited or spent
+    pub last_updated_at: i64,
+    pub expired_at: i64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct PointsAccrued {
+    pub config: Pubkey,
+    pub mint: Pubkey,
+    // Days settled by this call, at most `MAX_ACCRUAL_DAYS`
+    pub days_accrued: u32,
+    pub accrued_points: u64,
+    pub last_accrued_at: i64,
+    // Whether the stake is settled up to now, `crank_accrue` again otherwise
+    pub caught_up: bool,
+    pub slot: u64,
+}
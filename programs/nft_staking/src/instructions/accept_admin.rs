@@ -0,0 +1,42 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, events::AdminTransferred, state::StakeConfig};
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info>{
+
+    pub new_admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+}
+
+impl <'info>AcceptAdmin<'info> {
+
+    pub fn accept_admin(&mut self)->Result<()>{
+
+        let pending_admin = self.config.pending_admin.ok_or(StakeError::NoPendingAdmin)?;
+        require_keys_eq!(pending_admin, self.new_admin.key(), StakeError::NotPendingAdmin);
+
+        let previous_admin = self.config.admin;
+
+        self.config.admin = pending_admin;
+        self.config.pending_admin = None;
+
+        let clock = Clock::get()?;
+
+        emit!(AdminTransferred {
+            config: self.config.key(),
+            previous_admin,
+            new_admin: pending_admin,
+            transferred_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
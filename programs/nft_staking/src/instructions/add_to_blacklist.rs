@@ -0,0 +1,58 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, events::BlacklistUpdated, state::{Blacklist, StakeConfig}};
+
+#[derive(Accounts)]
+pub struct AddToBlacklist<'info>{
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// CHECK: the flagged wallet, only used to derive `blacklist`
+    pub wallet: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"blacklist".as_ref(), config.key().as_ref(), wallet.key().as_ref()],
+        bump,
+        space = Blacklist::INIT_SPACE,
+    )]
+    pub blacklist: Account<'info, Blacklist>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info>AddToBlacklist<'info> {
+
+    // Open stakes stay frozen until the owner unstakes them for no points or the admin force_unstakes them
+    pub fn add_to_blacklist(&mut self, bumps: &AddToBlacklistBumps)->Result<()>{
+
+        let clock = Clock::get()?;
+
+        self.blacklist.set_inner(Blacklist {
+            config: self.config.key(),
+            wallet: self.wallet.key(),
+            added_at: clock.unix_timestamp,
+            bump: bumps.blacklist,
+        });
+
+        emit!(BlacklistUpdated {
+            config: self.config.key(),
+            admin: self.admin.key(),
+            wallet: self.wallet.key(),
+            blacklisted: true,
+            updated_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, events::PointsAdjusted, rewards::expire_points, state::{Leaderboard, StakeConfig, UserAccount}};
+
+#[derive(Accounts)]
+pub struct AdjustPoints<'info>{
+
+    pub admin: Signer<'info>,
+
+    /// CHECK: only used to derive `user_account`
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    /// Pass to rank the user on the pool's leaderboard
+    #[account(
+        mut,
+        seeds = [b"leaderboard".as_ref(), config.key().as_ref()],
+        bump = leaderboard.load()?.bump,
+    )]
+    pub leaderboard: Option<AccountLoader<'info, Leaderboard>>,
+}
+
+impl <'info>AdjustPoints<'info> {
+
+    // Grants (`delta > 0`) or claws back points, `reason_code` is only recorded in the event
+    pub fn adjust_points(&mut self, delta: i64, reason_code: u8)->Result<()>{
+
+        let clock = Clock::get()?;
+        self.user_account.apply_decay(&self.config, clock.unix_timestamp)?;
+        expire_points(&self.config, &mut self.user_account, clock.unix_timestamp)?;
+        let points_before = self.user_account.points;
+
+        self.user_account.points = self.config.adjusted_points(self.user_account.points, delta)?;
+
+        // Ranks on points paid, which adjustments don't touch, this backfills users who claimed
+        // before the leaderboard existed or without passing it
+        if let Some(leaderboard) = &self.leaderboard {
+            leaderboard.load_mut()?.record(self.user.key(), self.user_account.points_paid);
+        }
+
+        self.user_account.restart_expiry_if_changed(points_before, clock.unix_timestamp);
+
+        emit!(PointsAdjusted {
+            config: self.config.key(),
+            admin: self.admin.key(),
+            user: self.user.key(),
+            delta,
+            reason_code,
+            remaining_points: self.user_account.points,
+            adjusted_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
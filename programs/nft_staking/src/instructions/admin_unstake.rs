@@ -0,0 +1,208 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{metadata::{MasterEditionAccount, Metadata, MetadataAccount}, token_interface::{Mint, TokenAccount, TokenInterface}};
+
+use crate::{errors::StakeError, events::AdminUnstaked, nft::{GroupMemberNftAccounts, NftAccounts}, rewards::expire_points, state::{CustodyMode, MintRecord, StakeAccount, StakeConfig, UserAccount, UserPositions}};
+
+#[derive(Accounts)]
+pub struct AdminUnstake<'info>{
+
+    // Pays for the mint's record if it doesn't have one yet
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// CHECK: the stake's stored owner, matched against `stake_account.owner`. Gets the NFT back
+    /// and the stake account's rent, the admin never can
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub mint_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"metadata", metadata_program.key().as_ref(), mint.key().as_ref()],
+        seeds::program = metadata_program.key(),
+        bump,
+    )]
+    pub metadata: Option<Account<'info, MetadataAccount>>,
+
+    #[account(
+        seeds = [
+            b"metadata",
+            metadata_program.key().as_ref(),
+            mint.key().as_ref(),
+            b"edition"
+        ],
+        seeds::program = metadata_program.key(),
+        bump,
+    )]
+    pub edition: Option<Account<'info, MasterEditionAccount>>,
+
+    /// Token-2022 group member NFTs frozen in place only, see `stake`
+    pub freeze_authority: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"stake", stake_account.config.as_ref(), mint.key().as_ref()],
+        bump = stake_account.bump,
+        has_one = config @ StakeError::PoolMismatch,
+        constraint = stake_account.owner == user.key() @ StakeError::NotStakeOwner,
+        // Tokenized positions leave only through `unstake` by the receipt's holder
+        constraint = stake_account.position_mint.is_none() @ StakeError::ReceiptRequired,
+        // Only the owner can burn a soulbound receipt, so those stakes wait for them to unstake
+        constraint = !stake_account.has_receipt @ StakeError::MissingReceipt,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+
+    // Created on the mint's first stake or unstake since records existed, never closed
+    #[account(
+        init_if_needed,
+        payer = admin,
+        seeds = [b"mint_record", config.key().as_ref(), mint.key().as_ref()],
+        bump,
+        space = MintRecord::INIT_SPACE,
+    )]
+    pub mint_record: Account<'info, MintRecord>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    // Created on the owner's first stake or unstake since positions existed
+    #[account(
+        init_if_needed,
+        payer = admin,
+        seeds = [b"positions", config.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = UserPositions::INIT_SPACE,
+    )]
+    pub user_positions: Account<'info, UserPositions>,
+
+    // Only read for its decimals, to boost the banked points like an unstake would
+    #[account(address = config.reward_mint @ StakeError::RewardMintMismatch)]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    /// VaultTransfer stakes only
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = stake_account,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub metadata_program: Program<'info, Metadata>,
+}
+
+impl <'info> AdminUnstake<'info> {
+
+    // For migrations and wind-downs: hands any stake's NFT back to its owner without their
+    // signature or the freeze period, banking what it accrued for them to claim. Like
+    // force_unstake, pNFTs, cNFTs and stakes issued a receipt need the owner to sign their
+    // release. The approval to the stake account is left for the owner to revoke.
+    pub fn admin_unstake(&mut self, bumps: &AdminUnstakeBumps) -> Result<()>{
+
+        require!(self.stake_account.custody_mode != CustodyMode::Compressed, StakeError::CustodyModeMismatch);
+
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+
+        self.user_account.apply_decay(&self.config, now)?;
+        expire_points(&self.config, &mut self.user_account, now)?;
+        let points_before = self.user_account.points;
+        self.stake_account.settle(&self.config, now)?;
+        let points_banked = self.config.boosted_points(
+            self.stake_account.pending_points_with_set_bonus(&self.config, self.user_account.set_complete_since, self.user_positions.full_set(self.stake_account.set_piece), now)?,
+            &self.user_account,
+            self.reward_mint.decimals,
+            now,
+        )?;
+        self.user_account.points = self.user_account.points
+            .checked_add(points_banked)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+
+        let seeds = &[
+            b"stake",
+            self.config.to_account_info().key.as_ref(),
+            self.mint.to_account_info().key.as_ref(),
+            &[self.stake_account.bump]
+        ];
+
+        let signer_seeds = &[&seeds[..]];
+
+        match (&self.metadata, &self.edition) {
+            (Some(metadata), Some(edition)) => NftAccounts{
+                owner: self.user.as_ref(),
+                delegate: self.stake_account.as_ref(),
+                mint: self.mint.as_ref(),
+                token_account: self.mint_ata.as_ref(),
+                metadata: metadata.as_ref(),
+                edition: edition.as_ref(),
+                token_program: self.token_program.as_ref(),
+                metadata_program: self.metadata_program.as_ref(),
+                system_program: self.system_program.as_ref(),
+                pnft: None,
+                vault: self.vault.as_ref().map(|vault| vault.as_ref()),
+            }.force_unlock(metadata.token_standard.as_ref(), self.stake_account.custody_mode, signer_seeds)?,
+            (None, None) => GroupMemberNftAccounts{
+                owner: self.user.as_ref(),
+                delegate: self.stake_account.as_ref(),
+                mint: self.mint.as_ref(),
+                token_account: self.mint_ata.as_ref(),
+                token_program: self.token_program.as_ref(),
+                freeze_authority: self.freeze_authority.as_deref(),
+                vault: self.vault.as_ref().map(|vault| vault.as_ref()),
+            }.force_unlock(self.stake_account.custody_mode, signer_seeds)?,
+            _ => return err!(StakeError::MissingMetadataAccounts),
+        }
+
+        self.mint_record.init_if_new(self.config.key(), self.mint.key(), bumps.mint_record);
+        self.mint_record.record_unstake(now);
+
+        self.user_positions.init_if_new(self.config.key(), self.user.key(), bumps.user_positions);
+        self.user_positions.remove(&self.stake_account.mint);
+
+        self.user_account.amount_staked = self.user_account.amount_staked
+            .checked_sub(1)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        self.user_account.remove_set_piece(&self.config, self.stake_account.set_piece, now);
+        self.user_positions.end_full_set(self.stake_account.set_piece);
+        self.config.remove_staked();
+
+        self.user_account.restart_expiry_if_changed(points_before, now);
+
+        emit!(AdminUnstaked {
+            config: self.config.key(),
+            admin: self.admin.key(),
+            user: self.user.key(),
+            mint: self.mint.key(),
+            unstaked_at: now,
+            slot: clock.slot,
+            duration: now.saturating_sub(self.stake_account.opened_at),
+            points_banked,
+        });
+
+        Ok(())
+    }
+}
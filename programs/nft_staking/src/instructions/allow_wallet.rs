@@ -0,0 +1,58 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, events::AllowlistUpdated, state::{AllowlistEntry, StakeConfig}};
+
+#[derive(Accounts)]
+pub struct AllowWallet<'info>{
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// CHECK: the allowed wallet, only used to derive `allowlist_entry`
+    pub wallet: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"allowlist".as_ref(), config.key().as_ref(), wallet.key().as_ref()],
+        bump,
+        space = AllowlistEntry::INIT_SPACE,
+    )]
+    pub allowlist_entry: Account<'info, AllowlistEntry>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info>AllowWallet<'info> {
+
+    // Can be added ahead of gating the pool
+    pub fn allow_wallet(&mut self, bumps: &AllowWalletBumps)->Result<()>{
+
+        let clock = Clock::get()?;
+
+        self.allowlist_entry.set_inner(AllowlistEntry {
+            config: self.config.key(),
+            wallet: self.wallet.key(),
+            added_at: clock.unix_timestamp,
+            bump: bumps.allowlist_entry,
+        });
+
+        emit!(AllowlistUpdated {
+            config: self.config.key(),
+            admin: self.admin.key(),
+            wallet: self.wallet.key(),
+            allowed: true,
+            updated_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,38 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, events::AdminTransferCancelled, state::StakeConfig};
+
+#[derive(Accounts)]
+pub struct CancelAdminTransfer<'info>{
+
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+}
+
+impl <'info>CancelAdminTransfer<'info> {
+
+    // Withdraws a proposal before it's accepted, e.g. one made to the wrong key
+    pub fn cancel_admin_transfer(&mut self)->Result<()>{
+
+        let cancelled_admin = self.config.pending_admin.take().ok_or(StakeError::NoPendingAdmin)?;
+
+        let clock = Clock::get()?;
+
+        emit!(AdminTransferCancelled {
+            config: self.config.key(),
+            admin: self.admin.key(),
+            cancelled_admin,
+            cancelled_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
@@ -1,61 +1,165 @@
-use anchor_lang::prelude::*;
-use anchor_spl::{associated_token::AssociatedToken, metadata::{mpl_token_metadata::instructions::{FreezeDelegatedAccountCpi, FreezeDelegatedAccountCpiAccounts, ThawDelegatedAccountCpi, ThawDelegatedAccountCpiAccounts}, MasterEditionAccount, Metadata, MetadataAccount}, token::{approve, mint_to, revoke, Approve, Mint, MintTo, Revoke, Token, TokenAccount}};
+use anchor_lang::{prelude::*, system_program::{transfer, Transfer}};
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked}};
 
-use crate::{errors::StakeError, state::{StakeAccount, StakeConfig, UserAccount}};
+use crate::{constants::PAUSE_CLAIM, errors::StakeError, events::RewardsClaimed, rewards::expire_points, state::{Blacklist, Leaderboard, StakeAccount, StakeConfig, TokenStakeAccount, UserAccount, UserPositions}};
 
 #[derive(Accounts)]
 pub struct Claim<'info>{
 
-    #[account(mut)]
-    pub user: Signer<'info>,
+    // The user or their `claim_delegate`, pays for the rewards ATA if it's missing and the claim fee
+    #[account(
+        mut,
+        constraint = authority.key() == user.key()
+            || user_account.claim_delegate == Some(authority.key()) @ StakeError::Unauthorized,
+    )]
+    pub authority: Signer<'info>,
+
+    /// CHECK: the user claiming, only used to derive their accounts. Rewards always go to their ATA
+    pub user: UncheckedAccount<'info>,
 
     #[account(
         init_if_needed,
-        payer = user,
+        payer = authority,
         associated_token::mint = reward_mint,
         associated_token::authority = user,
+        associated_token::token_program = token_program,
     )]
-    pub rewards_ata: Account<'info, TokenAccount>,
+    pub rewards_ata: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
-        seeds = [b"config"],
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
         bump = config.bump,
     )]
     pub config: Account<'info, StakeConfig>,
 
     #[account(
         mut,
-        seeds = [b"user", user.key().as_ref()],
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
         bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
     )]
     pub user_account: Account<'info, UserAccount>,
 
+    // Created on the user's first stake, unstake or claim since positions existed
+    #[account(
+        init_if_needed,
+        payer = authority,
+        seeds = [b"positions", config.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = UserPositions::INIT_SPACE,
+    )]
+    pub user_positions: Account<'info, UserPositions>,
+
+    /// CHECK: `user`'s blacklist PDA, only holds data while the wallet is blacklisted
+    #[account(
+        seeds = [b"blacklist".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub blacklist: UncheckedAccount<'info>,
+
+    // Pass to bank what a token stake has earned before paying out
+    #[account(
+        mut,
+        seeds = [b"token_stake", config.key().as_ref(), user.key().as_ref()],
+        bump = token_stake_account.bump,
+        has_one = config @ StakeError::PoolMismatch,
+    )]
+    pub token_stake_account: Option<Account<'info, TokenStakeAccount>>,
+
+    #[account(
+        address = config.reward_mint @ StakeError::RewardMintMismatch,
+        mint::token_program = token_program,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"reward_vault".as_ref(), config.key().as_ref()],
+        bump = config.reward_vault_bump,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Pools with a `fee_bps` only: the treasury's `reward_mint` ATA, checked in `pay_treasury_fee`
+    #[account(mut)]
+    pub treasury_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: pools with a `claim_fee_lamports` only, receives it
+    #[account(mut, address = config.treasury @ StakeError::InvalidTreasury)]
+    pub treasury: Option<UncheckedAccount<'info>>,
+
+    /// Pass to rank the user on the pool's leaderboard
     #[account(
         mut,
-        seeds = [b"rewards".as_ref(), config.key().as_ref()],
-        bump = config.rewards_bump,
+        seeds = [b"leaderboard".as_ref(), config.key().as_ref()],
+        bump = leaderboard.load()?.bump,
     )]
-    pub reward_mint: Account<'info, Mint>,
+    pub leaderboard: Option<AccountLoader<'info, Leaderboard>>,
 
-    
     pub associated_token_program: Program<'info, AssociatedToken>,
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
 impl <'info> Claim <'info> {
 
-    pub fn claim(&mut self)-> Result<()>{
+    // With `claim_requires_unlocked` set, remaining_accounts holds the stake account of every mint
+    // in `user_positions`, in its order
+    pub fn claim(&mut self, remaining_accounts: &'info [AccountInfo<'info>], bumps: &ClaimBumps)-> Result<()>{
+
+        require!(!self.config.is_paused(PAUSE_CLAIM), StakeError::ProgramPaused);
+        require!(!Blacklist::is_set(&self.blacklist), StakeError::WalletBlacklisted);
+
+        let clock = Clock::get()?;
+        self.user_account.apply_decay(&self.config, clock.unix_timestamp)?;
+        expire_points(&self.config, &mut self.user_account, clock.unix_timestamp)?;
+        let points_before = self.user_account.points;
+
+        if let Some(token_stake_account) = self.token_stake_account.as_mut() {
+            let points = token_stake_account.settle(&self.config, clock.unix_timestamp)?;
+            self.user_account.points = self.user_account.points
+                .checked_add(points)
+                .ok_or(StakeError::ArithmeticOverflow)?;
+        }
+
+        require!(self.user_account.points > 0, StakeError::NothingToClaim);
+        require!(self.user_account.points >= self.config.min_claim_points, StakeError::BelowMinClaim);
+
+        self.user_account.require_claim_cooldown_passed(&self.config, clock.unix_timestamp)?;
+
+        self.user_positions.init_if_new(self.config.key(), self.user.key(), bumps.user_positions);
+        if self.config.claim_requires_unlocked {
+            self.require_positions_unlocked(remaining_accounts, clock.unix_timestamp)?;
+        }
+
+        // Near `max_emission` or with a short vault only part of the balance is paid, the rest
+        // stays on the user account
+        require!(self.config.emission_room() > 0, StakeError::EmissionCapReached);
+        let points_burned = self.user_account.points
+            .min(self.config.emission_room())
+            .min(self.config.affordable_points(self.reward_vault.amount, self.reward_mint.decimals)?);
+        require!(points_burned > 0, StakeError::InsufficientRewardVault);
+
+        let amount = points_burned
+            .checked_mul(10_u64.checked_pow(self.reward_mint.decimals as u32).ok_or(StakeError::ArithmeticOverflow)?)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+
+        let fee = self.config.treasury_fee(amount)?;
+
+        pay_claim_fee(&self.config, &self.authority, self.treasury.as_ref(), &self.system_program)?;
 
         let cpi_program = self.token_program.to_account_info();
 
+        let pool_id = self.config.pool_id.to_le_bytes();
         let seeds = &[
             b"config".as_ref(),
+            pool_id.as_ref(),
             &[self.config.bump]
-        ];     
+        ];
         let signer_seeds = &[&seeds[..]];
 
-        let cpi_accounts = MintTo{
+        let cpi_accounts = TransferChecked{
+            from: self.reward_vault.to_account_info(),
             mint: self.reward_mint.to_account_info(),
             to: self.rewards_ata.to_account_info(),
             authority: self.config.to_account_info(),
@@ -63,11 +167,135 @@ impl <'info> Claim <'info> {
 
         let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer_seeds);
 
-        mint_to(cpi_ctx, self.user_account.points as u64 * 10_u64.pow(self.reward_mint.decimals as u32))?;
+        transfer_checked(cpi_ctx, amount, self.reward_mint.decimals)?;
+
+        pay_treasury_fee(
+            &self.config,
+            &self.reward_vault,
+            &self.reward_mint,
+            self.treasury_ata.as_ref(),
+            &self.token_program,
+            fee,
+            signer_seeds,
+        )?;
+
+        self.config.add_points_distributed(points_burned)?;
+        self.user_account.record_points_paid(points_burned)?;
+
+        if let Some(leaderboard) = &self.leaderboard {
+            leaderboard.load_mut()?.record(self.user.key(), self.user_account.points_paid);
+        }
+
+        self.user_account.points = self.user_account.points
+            .checked_sub(points_burned)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        self.user_account.last_claim_ts = clock.unix_timestamp;
+
+        self.user_account.restart_expiry_if_changed(points_before, clock.unix_timestamp);
+
+        emit!(RewardsClaimed {
+            config: self.config.key(),
+            user: self.user.key(),
+            claimed_by: self.authority.key(),
+            amount,
+            fee,
+            claim_fee_lamports: self.config.claim_fee_lamports,
+            points_burned,
+            remaining_points: self.user_account.points,
+            claimed_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+
+        Ok(())
+    }
+
+    // Stakes from before positions existed aren't listed, so they aren't checked either
+    fn require_positions_unlocked(&self, stake_accounts: &'info [AccountInfo<'info>], now: i64)->Result<()>{
+        let mints = &self.user_positions.mints;
+        require!(stake_accounts.len() == mints.len(), StakeError::InvalidRemainingAccounts);
 
-        self.user_account.points = 0;
+        let mut locked = 0;
+        for (mint, info) in mints.iter().zip(stake_accounts) {
+            // Stake PDAs are unique per pool and mint, so a program-owned stake account matching
+            // both is the listed one
+            let stake_account = Account::<StakeAccount>::try_from(info)?;
+            require!(
+                stake_account.config == self.config.key() && stake_account.mint == *mint,
+                StakeError::InvalidRemainingAccounts
+            );
 
+            if !stake_account.is_unlocked(now) {
+                locked += 1;
+            }
+        }
+
+        if locked > 0 {
+            msg!("{} positions still locked", locked);
+            return err!(StakeError::PositionsStillLocked);
+        }
 
         Ok(())
     }
+}
+
+/// Charges `payer` the pool's `claim_fee_lamports` into the treasury. Skipped when it's 0, the
+/// treasury can be left out then.
+pub(crate) fn pay_claim_fee<'info>(
+    config: &Account<'info, StakeConfig>,
+    payer: &Signer<'info>,
+    treasury: Option<&UncheckedAccount<'info>>,
+    system_program: &Program<'info, System>,
+)->Result<()>{
+    let fee = config.claim_fee_lamports;
+    if fee == 0 {
+        return Ok(());
+    }
+
+    let treasury = treasury.ok_or(StakeError::InvalidTreasury)?;
+    require!(payer.lamports() >= fee, StakeError::InsufficientFeeFunds);
+
+    let cpi_accounts = Transfer{
+        from: payer.to_account_info(),
+        to: treasury.to_account_info(),
+    };
+
+    transfer(CpiContext::new(system_program.to_account_info(), cpi_accounts), fee)
+}
+
+/// Pays a claim's `fee` out of the reward vault to the treasury's ATA. Skipped when `fee_bps` is
+/// 0, the treasury account can be left out then.
+pub(crate) fn pay_treasury_fee<'info>(
+    config: &Account<'info, StakeConfig>,
+    reward_vault: &InterfaceAccount<'info, TokenAccount>,
+    reward_mint: &InterfaceAccount<'info, Mint>,
+    treasury_ata: Option<&InterfaceAccount<'info, TokenAccount>>,
+    token_program: &Interface<'info, TokenInterface>,
+    fee: u64,
+    signer_seeds: &[&[&[u8]]],
+)->Result<()>{
+    if config.fee_bps == 0 {
+        return Ok(());
+    }
+
+    let treasury_ata = treasury_ata.ok_or(StakeError::InvalidTreasury)?;
+    require!(
+        treasury_ata.owner == config.treasury && treasury_ata.mint == reward_mint.key(),
+        StakeError::InvalidTreasury
+    );
+
+    if fee == 0 {
+        return Ok(());
+    }
+
+    let cpi_accounts = TransferChecked{
+        from: reward_vault.to_account_info(),
+        mint: reward_mint.to_account_info(),
+        to: treasury_ata.to_account_info(),
+        authority: config.to_account_info(),
+    };
+
+    let cpi_ctx = CpiContext::new_with_signer(token_program.to_account_info(), cpi_accounts, signer_seeds);
+
+    transfer_checked(cpi_ctx, fee, reward_mint.decimals)
 }
\ No newline at end of file
@@ -0,0 +1,116 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked}};
+
+use crate::{constants::PAUSE_CLAIM, errors::StakeError, events::LockedRewardsClaimed, state::{Blacklist, StakeConfig, UserAccount}};
+
+#[derive(Accounts)]
+pub struct ClaimLocked<'info>{
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = reward_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub rewards_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    /// CHECK: `user`'s blacklist PDA, only holds data while the wallet is blacklisted
+    #[account(
+        seeds = [b"blacklist".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub blacklist: UncheckedAccount<'info>,
+
+    #[account(
+        address = config.reward_mint @ StakeError::RewardMintMismatch,
+        mint::token_program = token_program,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"reward_vault".as_ref(), config.key().as_ref()],
+        bump = config.reward_vault_bump,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info> ClaimLocked<'info> {
+
+    // Pays out `locked_points` from the reward vault once `locked_until` has passed. Near
+    // `max_emission` the part left unpaid stays locked, claimable as the cap allows
+    pub fn claim_locked(&mut self)->Result<()>{
+
+        require!(!self.config.is_paused(PAUSE_CLAIM), StakeError::ProgramPaused);
+        require!(!Blacklist::is_set(&self.blacklist), StakeError::WalletBlacklisted);
+
+        let clock = Clock::get()?;
+
+        let unlocked = self.user_account.unlock_points(clock.unix_timestamp)?;
+        let points_burned = unlocked.min(self.config.emission_room());
+        require!(points_burned > 0, StakeError::EmissionCapReached);
+        self.user_account.locked_points = unlocked - points_burned;
+
+        let amount = points_burned
+            .checked_mul(10_u64.checked_pow(self.reward_mint.decimals as u32).ok_or(StakeError::ArithmeticOverflow)?)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+
+        require!(self.config.available_rewards(self.reward_vault.amount) >= amount, StakeError::InsufficientRewardVault);
+
+        let pool_id = self.config.pool_id.to_le_bytes();
+        let seeds = &[
+            b"config".as_ref(),
+            pool_id.as_ref(),
+            &[self.config.bump]
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked{
+            from: self.reward_vault.to_account_info(),
+            mint: self.reward_mint.to_account_info(),
+            to: self.rewards_ata.to_account_info(),
+            authority: self.config.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, signer_seeds);
+
+        transfer_checked(cpi_ctx, amount, self.reward_mint.decimals)?;
+
+        self.config.add_points_distributed(points_burned)?;
+        self.user_account.record_points_paid(points_burned)?;
+
+        emit!(LockedRewardsClaimed {
+            config: self.config.key(),
+            user: self.user.key(),
+            amount,
+            points_burned,
+            claimed_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
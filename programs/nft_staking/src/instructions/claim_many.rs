@@ -0,0 +1,206 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::get_associated_token_address_with_program_id, token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked}};
+
+use crate::{constants::{CLAIM_BATCH_ACCOUNTS, MAX_CLAIM_BATCH_SIZE, PAUSE_CLAIM}, errors::StakeError, events::RewardsClaimed, instructions::{pay_claim_fee, pay_treasury_fee}, rewards::expire_points, state::{Blacklist, Leaderboard, StakeConfig, UserAccount}};
+
+#[derive(Accounts)]
+pub struct ClaimMany<'info>{
+
+    // Every user's `claim_delegate`, pays each claim fee
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        address = config.reward_mint @ StakeError::RewardMintMismatch,
+        mint::token_program = token_program,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"reward_vault".as_ref(), config.key().as_ref()],
+        bump = config.reward_vault_bump,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Pools with a `fee_bps` only: the treasury's `reward_mint` ATA, checked in `pay_treasury_fee`
+    #[account(mut)]
+    pub treasury_ata: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: pools with a `claim_fee_lamports` only, receives it
+    #[account(mut, address = config.treasury @ StakeError::InvalidTreasury)]
+    pub treasury: Option<UncheckedAccount<'info>>,
+
+    /// Pass to rank the users on the pool's leaderboard
+    #[account(
+        mut,
+        seeds = [b"leaderboard".as_ref(), config.key().as_ref()],
+        bump = leaderboard.load()?.bump,
+    )]
+    pub leaderboard: Option<AccountLoader<'info, Leaderboard>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info> ClaimMany<'info> {
+
+    // remaining_accounts holds one [user_account, rewards_ata, blacklist] group per user, each
+    // rewards ATA must already exist. Users in their cooldown, blacklisted, whose points just expired
+    // or below `min_claim_points` are skipped rather than failing the batch. Token stakes aren't
+    // settled, `claim` banks those
+    pub fn claim_many(&mut self, remaining_accounts: &'info [AccountInfo<'info>])->Result<()>{
+        require!(!self.config.is_paused(PAUSE_CLAIM), StakeError::ProgramPaused);
+        // Batches don't carry each user's stakes to check, those pools claim one user at a time
+        require!(!self.config.claim_requires_unlocked, StakeError::PositionsStillLocked);
+        let users = remaining_accounts.len() / CLAIM_BATCH_ACCOUNTS;
+        require!(users > 0 && users <= MAX_CLAIM_BATCH_SIZE, StakeError::InvalidBatchSize);
+        require!(
+            remaining_accounts.len() == users * CLAIM_BATCH_ACCOUNTS,
+            StakeError::InvalidRemainingAccounts
+        );
+
+        let clock = Clock::get()?;
+        let mut seen: Vec<Pubkey> = Vec::with_capacity(users);
+
+        for accounts in remaining_accounts.chunks(CLAIM_BATCH_ACCOUNTS) {
+            // A user passed twice would be checked against a cooldown their first claim didn't write yet
+            require!(!seen.contains(accounts[0].key), StakeError::InvalidRemainingAccounts);
+            seen.push(accounts[0].key());
+
+            self.claim_one(accounts, &clock)?;
+        }
+
+        Ok(())
+    }
+
+    fn claim_one(&mut self, accounts: &'info [AccountInfo<'info>], clock: &Clock)->Result<()>{
+        let [user_info, rewards_ata_info, blacklist_info] = accounts else {
+            return err!(StakeError::InvalidRemainingAccounts);
+        };
+
+        // Anchor constraints don't run on remaining_accounts, so every account is checked by hand
+        let mut user_account = Account::<UserAccount>::try_from(user_info)?.into_inner();
+        require!(user_account.is_current(), StakeError::AccountNeedsMigration);
+        let user = user_account.owner;
+        let config_key = self.config.key();
+        require!(
+            UserAccount::is_address_of(&user_info.key(), &config_key, &user, user_account.bump),
+            StakeError::InvalidRemainingAccounts
+        );
+        require!(user_account.claim_delegate == Some(self.authority.key()), StakeError::Unauthorized);
+
+        let (blacklist_key, _) = Pubkey::find_program_address(
+            &[b"blacklist", config_key.as_ref(), user.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(blacklist_info.key(), blacklist_key, StakeError::InvalidRemainingAccounts);
+        require_keys_eq!(
+            rewards_ata_info.key(),
+            get_associated_token_address_with_program_id(&user, &self.reward_mint.key(), &self.token_program.key()),
+            StakeError::InvalidRemainingAccounts
+        );
+
+        let now = clock.unix_timestamp;
+        if Blacklist::is_set(blacklist_info) || user_account.require_claim_cooldown_passed(&self.config, now).is_err() {
+            return Ok(());
+        }
+
+        user_account.apply_decay(&self.config, now)?;
+        // As in `claim`, expired points are forfeited first. A user left with nothing is written
+        // back and skipped, so the forfeit sticks and its event goes out once
+        if expire_points(&self.config, &mut user_account, now)? > 0 {
+            user_account.try_serialize(&mut &mut user_info.try_borrow_mut_data()?[..])?;
+            return Ok(());
+        }
+        let points_before = user_account.points;
+
+        if user_account.points < self.config.min_claim_points {
+            return Ok(());
+        }
+
+        // As in `claim`, near `max_emission` or with a short vault only part of the balance is paid
+        let points_burned = user_account.points
+            .min(self.config.emission_room())
+            .min(self.config.affordable_points(self.reward_vault.amount, self.reward_mint.decimals)?);
+        if points_burned == 0 {
+            return Ok(());
+        }
+
+        let amount = points_burned
+            .checked_mul(10_u64.checked_pow(self.reward_mint.decimals as u32).ok_or(StakeError::ArithmeticOverflow)?)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+
+        let fee = self.config.treasury_fee(amount)?;
+
+        pay_claim_fee(&self.config, &self.authority, self.treasury.as_ref(), &self.system_program)?;
+
+        let pool_id = self.config.pool_id.to_le_bytes();
+        let seeds = &[
+            b"config".as_ref(),
+            pool_id.as_ref(),
+            &[self.config.bump]
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked{
+            from: self.reward_vault.to_account_info(),
+            mint: self.reward_mint.to_account_info(),
+            to: rewards_ata_info.clone(),
+            authority: self.config.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, signer_seeds);
+
+        transfer_checked(cpi_ctx, amount, self.reward_mint.decimals)?;
+
+        pay_treasury_fee(
+            &self.config,
+            &self.reward_vault,
+            &self.reward_mint,
+            self.treasury_ata.as_ref(),
+            &self.token_program,
+            fee,
+            signer_seeds,
+        )?;
+
+        // Later users in the batch see what this claim took out of the vault
+        self.reward_vault.reload()?;
+
+        self.config.add_points_distributed(points_burned)?;
+        user_account.record_points_paid(points_burned)?;
+
+        if let Some(leaderboard) = &self.leaderboard {
+            leaderboard.load_mut()?.record(user, user_account.points_paid);
+        }
+
+        user_account.points = user_account.points
+            .checked_sub(points_burned)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        user_account.last_claim_ts = now;
+        user_account.restart_expiry_if_changed(points_before, now);
+        user_account.try_serialize(&mut &mut user_info.try_borrow_mut_data()?[..])?;
+
+        emit!(RewardsClaimed {
+            config: config_key,
+            user,
+            claimed_by: self.authority.key(),
+            amount,
+            fee,
+            claim_fee_lamports: self.config.claim_fee_lamports,
+            points_burned,
+            remaining_points: user_account.points,
+            claimed_at: now,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
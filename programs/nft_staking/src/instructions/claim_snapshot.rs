@@ -0,0 +1,126 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked}};
+
+use crate::{constants::PAUSE_CLAIM, errors::StakeError, events::SnapshotClaimed, state::{Blacklist, StakeAccount, StakeConfig, UserAccount}};
+
+#[derive(Accounts)]
+pub struct ClaimSnapshot<'info>{
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = reward_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub rewards_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    /// CHECK: `user`'s blacklist PDA, only holds data while the wallet is blacklisted
+    #[account(
+        seeds = [b"blacklist".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub blacklist: UncheckedAccount<'info>,
+
+    #[account(
+        address = config.reward_mint @ StakeError::RewardMintMismatch,
+        mint::token_program = token_program,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"reward_vault".as_ref(), config.key().as_ref()],
+        bump = config.reward_vault_bump,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info> ClaimSnapshot<'info> {
+
+    // remaining_accounts holds the user's stake accounts in this pool, each one opened before the
+    // latest snapshot weighs one. Stakes left out forfeit their share, a snapshot is claimed once
+    pub fn claim_snapshot(&mut self, remaining_accounts: &'info [AccountInfo<'info>])->Result<()>{
+
+        require!(!self.config.is_paused(PAUSE_CLAIM), StakeError::ProgramPaused);
+        require!(!Blacklist::is_set(&self.blacklist), StakeError::WalletBlacklisted);
+        require!(self.config.snapshot_id > 0, StakeError::NothingToClaim);
+        require!(self.user_account.last_snapshot_id < self.config.snapshot_id, StakeError::SnapshotAlreadyClaimed);
+
+        let mut seen: Vec<Pubkey> = Vec::with_capacity(remaining_accounts.len());
+
+        for info in remaining_accounts {
+            require!(!seen.contains(info.key), StakeError::InvalidRemainingAccounts);
+            seen.push(info.key());
+
+            let stake_account = Account::<StakeAccount>::try_from(info)?;
+            require_keys_eq!(stake_account.config, self.config.key(), StakeError::PoolMismatch);
+            require_keys_eq!(stake_account.owner, self.user.key(), StakeError::NotStakeOwner);
+            // A tokenized position's owner sold it on, its share stays in the pool
+            require!(stake_account.position_mint.is_none(), StakeError::ReceiptRequired);
+            // Stakes opened since weren't counted in `snapshot_total_weight`
+            require!(stake_account.in_snapshot(self.config.snapshot_taken_at), StakeError::InvalidRemainingAccounts);
+        }
+
+        let weight = seen.len() as u32;
+        let amount = self.config.claim_snapshot_share(weight)?;
+        require!(amount > 0, StakeError::NothingToClaim);
+
+        self.user_account.last_snapshot_id = self.config.snapshot_id;
+
+        let pool_id = self.config.pool_id.to_le_bytes();
+        let seeds = &[
+            b"config".as_ref(),
+            pool_id.as_ref(),
+            &[self.config.bump]
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked{
+            from: self.reward_vault.to_account_info(),
+            mint: self.reward_mint.to_account_info(),
+            to: self.rewards_ata.to_account_info(),
+            authority: self.config.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, signer_seeds);
+
+        transfer_checked(cpi_ctx, amount, self.reward_mint.decimals)?;
+
+        let clock = Clock::get()?;
+
+        emit!(SnapshotClaimed {
+            config: self.config.key(),
+            user: self.user.key(),
+            snapshot_id: self.config.snapshot_id,
+            weight,
+            amount,
+            claimed_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,105 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{token_2022::Token2022, token_interface::{Mint, TokenAccount, TokenInterface}};
+
+use crate::{errors::StakeError, receipt::burn_held_receipt, state::{CustodyMode, StakeAccount, StakeConfig, UserAccount, UserPositions}};
+
+#[derive(Accounts)]
+pub struct CloseStakeAccount<'info>{
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        associated_token::mint = mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub mint_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"stake", stake_account.config.as_ref(), mint.key().as_ref()],
+        bump = stake_account.bump,
+        has_one = config @ StakeError::PoolMismatch,
+        constraint = stake_account.owner == user.key() @ StakeError::NotStakeOwner,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    // Created on the owner's first stake or unstake since positions existed
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"positions", config.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = UserPositions::INIT_SPACE,
+    )]
+    pub user_positions: Account<'info, UserPositions>,
+
+    /// CHECK: stakes opened with a receipt only, must be `config.receipt_mint`, checked in `burn_held_receipt`
+    #[account(mut)]
+    pub receipt_mint: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: stakes opened with a receipt only: `user`'s receipt ATA, checked in `ReceiptAccounts`
+    #[account(mut)]
+    pub receipt_ata: Option<UncheckedAccount<'info>>,
+
+    /// Stakes opened with a receipt only
+    pub receipt_token_program: Option<Program<'info, Token2022>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info> CloseStakeAccount<'info> {
+
+    // Cleans up stake accounts whose NFT was already released, no points are paid out
+    pub fn close_stake_account(&mut self, bumps: &CloseStakeAccountBumps) -> Result<()>{
+
+        // A vault stake still holds the NFT, closing it would strand the token in the vault
+        require!(self.stake_account.custody_mode == CustodyMode::FreezeDelegated, StakeError::CustodyModeMismatch);
+
+        // pNFT token accounts are always frozen by token metadata, so only the delegate tells us the stake still holds it
+        require!(
+            self.mint_ata.delegate != Some(self.stake_account.key()).into(),
+            StakeError::NftStillFrozen
+        );
+
+        burn_held_receipt(
+            &self.config,
+            &self.stake_account,
+            self.user.as_ref(),
+            self.receipt_mint.as_ref(),
+            self.receipt_ata.as_ref(),
+            self.receipt_token_program.as_ref(),
+        )?;
+
+        let now = Clock::get()?.unix_timestamp;
+        self.user_account.settle_governance_weight(&self.config, now);
+        self.user_account.amount_staked = self.user_account.amount_staked.saturating_sub(1);
+        self.user_account.remove_set_piece(&self.config, self.stake_account.set_piece, now);
+        self.user_positions.init_if_new(self.config.key(), self.user.key(), bumps.user_positions);
+        self.user_positions.remove(&self.stake_account.mint);
+        self.user_positions.end_full_set(self.stake_account.set_piece);
+        self.config.remove_staked();
+
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, state::{StakeConfig, UserAccount, UserPositions}};
+
+#[derive(Accounts)]
+pub struct CloseUser<'info>{
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"user".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    // Users who haven't staked or unstaked since positions existed have none
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"positions", config.key().as_ref(), user.key().as_ref()],
+        bump = user_positions.bump,
+    )]
+    pub user_positions: Option<Account<'info, UserPositions>>,
+
+    pub system_program: Program<'info, System>
+}
+
+impl <'info> CloseUser<'info> {
+
+    // Closing with NFTs still staked, points left unclaimed or locked or boost tokens still held would
+    // strand them, claim, unstake and unlock_boost first
+    pub fn close_user(&mut self)->Result<()>{
+
+        require!(
+            self.user_account.amount_staked == 0
+                && self.user_account.points == 0
+                && self.user_account.locked_points == 0
+                && self.user_account.boost_amount == 0,
+            StakeError::OutstandingStakeOrRewards
+        );
+
+        if self.user_account.has_staked_before {
+            self.config.remove_user();
+        }
+
+        Ok(())
+    }
+}
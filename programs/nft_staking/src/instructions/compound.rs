@@ -0,0 +1,81 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::{constants::PAUSE_CLAIM, errors::StakeError, events::RewardsCompounded, rewards::expire_points, state::{Blacklist, StakeConfig, UserAccount}};
+
+#[derive(Accounts)]
+pub struct Compound<'info>{
+
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    /// CHECK: `user`'s blacklist PDA, only holds data while the wallet is blacklisted
+    #[account(
+        seeds = [b"blacklist".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub blacklist: UncheckedAccount<'info>,
+
+    #[account(address = config.reward_mint @ StakeError::RewardMintMismatch)]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+}
+
+impl <'info> Compound<'info> {
+
+    // The claim alternative: the tokens stay in the vault and boost future points instead. It burns
+    // the same points and shares claim's cooldown, so a period's rewards go one way or the other.
+    pub fn compound(&mut self)->Result<()>{
+
+        require!(!self.config.is_paused(PAUSE_CLAIM), StakeError::ProgramPaused);
+        require!(!Blacklist::is_set(&self.blacklist), StakeError::WalletBlacklisted);
+        require!(self.config.compound_boost_bps > 0, StakeError::CompoundingDisabled);
+        let clock = Clock::get()?;
+        self.user_account.apply_decay(&self.config, clock.unix_timestamp)?;
+        expire_points(&self.config, &mut self.user_account, clock.unix_timestamp)?;
+        let points_before = self.user_account.points;
+
+        require!(self.user_account.points > 0, StakeError::NothingToClaim);
+
+        self.user_account.require_claim_cooldown_passed(&self.config, clock.unix_timestamp)?;
+
+        let amount = self.user_account.points
+            .checked_mul(10_u64.checked_pow(self.reward_mint.decimals as u32).ok_or(StakeError::ArithmeticOverflow)?)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+
+        self.user_account.staked_reward_balance = self.user_account.staked_reward_balance
+            .checked_add(amount)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+
+        let points_burned = self.user_account.points;
+
+        self.user_account.points = 0;
+        self.user_account.last_claim_ts = clock.unix_timestamp;
+
+        self.user_account.restart_expiry_if_changed(points_before, clock.unix_timestamp);
+
+        emit!(RewardsCompounded {
+            config: self.config.key(),
+            user: self.user.key(),
+            amount,
+            points_burned,
+            staked_reward_balance: self.user_account.staked_reward_balance,
+            compounded_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
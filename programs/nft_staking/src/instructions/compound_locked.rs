@@ -0,0 +1,70 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::PAUSE_CLAIM, errors::StakeError, events::PointsLocked, rewards::expire_points, state::{Blacklist, StakeConfig, UserAccount}};
+
+#[derive(Accounts)]
+pub struct CompoundLocked<'info>{
+
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    /// CHECK: `user`'s blacklist PDA, only holds data while the wallet is blacklisted
+    #[account(
+        seeds = [b"blacklist".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub blacklist: UncheckedAccount<'info>,
+}
+
+impl <'info> CompoundLocked<'info> {
+
+    // Another claim alternative: the points are locked for `compound_lock_secs` in exchange for
+    // `compound_bonus_bps` on top, then paid out as tokens with claim_locked. Shares claim's cooldown
+    pub fn compound_locked(&mut self)->Result<()>{
+
+        require!(!self.config.is_paused(PAUSE_CLAIM), StakeError::ProgramPaused);
+        require!(!Blacklist::is_set(&self.blacklist), StakeError::WalletBlacklisted);
+        require!(self.config.compound_bonus_bps > 0, StakeError::CompoundingDisabled);
+        let clock = Clock::get()?;
+        self.user_account.apply_decay(&self.config, clock.unix_timestamp)?;
+        expire_points(&self.config, &mut self.user_account, clock.unix_timestamp)?;
+        let points_before = self.user_account.points;
+
+        require!(self.user_account.points > 0, StakeError::NothingToClaim);
+
+        self.user_account.require_claim_cooldown_passed(&self.config, clock.unix_timestamp)?;
+
+        let points_burned = self.user_account.points;
+        let points_locked = self.user_account.lock_points(&self.config, clock.unix_timestamp)?;
+
+        self.user_account.last_claim_ts = clock.unix_timestamp;
+
+        self.user_account.restart_expiry_if_changed(points_before, clock.unix_timestamp);
+
+        emit!(PointsLocked {
+            config: self.config.key(),
+            user: self.user.key(),
+            points_burned,
+            points_locked,
+            locked_points: self.user_account.locked_points,
+            locked_until: self.user_account.locked_until,
+            locked_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
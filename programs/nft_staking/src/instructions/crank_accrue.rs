@@ -0,0 +1,43 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, events::PointsAccrued, state::{StakeAccount, StakeConfig}};
+
+#[derive(Accounts)]
+pub struct CrankAccrue<'info>{
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"stake", config.key().as_ref(), stake_account.mint.as_ref()],
+        bump = stake_account.bump,
+        has_one = config @ StakeError::PoolMismatch,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+}
+
+impl <'info> CrankAccrue<'info> {
+
+    // Permissionless, settles up to `MAX_ACCRUAL_DAYS` of the stake's unsettled days. Stakes left
+    // untouched for longer need a few of these before an unstake or harvest can settle them
+    pub fn crank_accrue(&mut self)->Result<()>{
+        let clock = Clock::get()?;
+        let days_accrued = self.stake_account.accrue(&self.config, clock.unix_timestamp)?;
+
+        emit!(PointsAccrued {
+            config: self.config.key(),
+            mint: self.stake_account.mint,
+            days_accrued,
+            accrued_points: self.stake_account.accrued_points,
+            last_accrued_at: self.stake_account.last_accrued_at,
+            caught_up: self.stake_account.is_accrued(&self.config, clock.unix_timestamp)?,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,46 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, state::{Redemption, StakeConfig}};
+
+#[derive(Accounts)]
+#[instruction(redemption_id: u64)]
+pub struct CreateRedemption<'info>{
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"redemption".as_ref(), config.key().as_ref(), redemption_id.to_le_bytes().as_ref()],
+        bump,
+        space = Redemption::INIT_SPACE,
+    )]
+    pub redemption: Account<'info, Redemption>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info>CreateRedemption<'info> {
+
+    pub fn create_redemption(&mut self, redemption_id: u64, cost_points: u32, max_redemptions: u32, bumps: &CreateRedemptionBumps)->Result<()>{
+
+        self.redemption.set_inner(Redemption {
+            config: self.config.key(),
+            redemption_id,
+            cost_points,
+            max_redemptions,
+            redeemed: 0,
+            bump: bumps.redemption,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_2022::Token2022;
+
+use crate::{errors::StakeError, events::PositionDetokenized, receipt::ReceiptAccounts, state::{StakeAccount, StakeConfig, UserAccount, UserPositions}};
+
+#[derive(Accounts)]
+pub struct DetokenizePosition<'info>{
+
+    // The receipt's holder, not necessarily the wallet that tokenized the stake
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"stake", config.key().as_ref(), stake_account.mint.as_ref()],
+        bump = stake_account.bump,
+        has_one = config @ StakeError::PoolMismatch,
+        constraint = stake_account.position_mint == Some(position_mint.key()) @ StakeError::InvalidReceipt,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    // The receipt's holder may not have one yet
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"positions", config.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = UserPositions::INIT_SPACE,
+    )]
+    pub user_positions: Account<'info, UserPositions>,
+
+    /// CHECK: must be `stake_account.position_mint`
+    #[account(mut)]
+    pub position_mint: UncheckedAccount<'info>,
+
+    /// CHECK: `user`'s position receipt ATA, checked in `ReceiptAccounts`
+    #[account(mut)]
+    pub position_ata: UncheckedAccount<'info>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info> DetokenizePosition<'info> {
+
+    // Burns the receipt and hands the stake to `user` as its owner, counted like one they staked
+    pub fn detokenize_position(&mut self, bumps: &DetokenizePositionBumps)->Result<()>{
+        require!(self.user_account.amount_staked < self.config.max_stake, StakeError::MaxStakeReached);
+
+        let clock = Clock::get()?;
+        self.user_account.apply_decay(&self.config, clock.unix_timestamp)?;
+
+        ReceiptAccounts{
+            owner: self.user.as_ref(),
+            mint: self.position_mint.as_ref(),
+            token_account: self.position_ata.as_ref(),
+            token_program: self.token_program.as_ref(),
+        }.redeem_position()?;
+
+        self.stake_account.owner = self.user.key();
+        self.stake_account.position_mint = None;
+
+        self.user_account.amount_staked = self.user_account.amount_staked
+            .checked_add(1)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        self.user_account.add_set_piece(&self.config, self.stake_account.set_piece, clock.unix_timestamp);
+
+        self.user_positions.init_if_new(self.config.key(), self.user.key(), bumps.user_positions);
+        self.user_positions.add(self.stake_account.mint)?;
+        // The collection's config isn't passed, a full set this completes earns no full-set bonus
+
+        emit!(PositionDetokenized {
+            config: self.config.key(),
+            user: self.user.key(),
+            mint: self.stake_account.mint,
+            position_mint: self.position_mint.key(),
+            detokenized_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
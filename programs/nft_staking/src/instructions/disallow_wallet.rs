@@ -0,0 +1,45 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, events::AllowlistUpdated, state::{AllowlistEntry, StakeConfig}};
+
+#[derive(Accounts)]
+pub struct DisallowWallet<'info>{
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"allowlist".as_ref(), config.key().as_ref(), allowlist_entry.wallet.as_ref()],
+        bump = allowlist_entry.bump,
+    )]
+    pub allowlist_entry: Account<'info, AllowlistEntry>,
+}
+
+impl <'info>DisallowWallet<'info> {
+
+    // Stakes already open stay open, the wallet just can't stake more while the pool is gated
+    pub fn disallow_wallet(&mut self)->Result<()>{
+
+        let clock = Clock::get()?;
+
+        emit!(AllowlistUpdated {
+            config: self.config.key(),
+            admin: self.admin.key(),
+            wallet: self.allowlist_entry.wallet,
+            allowed: false,
+            updated_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,41 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, events::SnapshotDistributed, state::StakeConfig};
+
+#[derive(Accounts)]
+pub struct DistributeSnapshot<'info>{
+
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+}
+
+impl <'info> DistributeSnapshot<'info> {
+
+    // Snapshots `total_staked` as the weight the funded pool is split over, stakers then pull their
+    // share with claim_snapshot. Anything the previous snapshot left unclaimed rolls into this one
+    pub fn distribute_snapshot(&mut self)->Result<()>{
+
+        let clock = Clock::get()?;
+
+        self.config.take_snapshot(clock.unix_timestamp)?;
+
+        emit!(SnapshotDistributed {
+            config: self.config.key(),
+            admin: self.admin.key(),
+            snapshot_id: self.config.snapshot_id,
+            amount: self.config.snapshot_amount,
+            total_weight: self.config.snapshot_total_weight,
+            taken_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,233 @@
+use anchor_lang::{prelude::*, solana_program::sysvar};
+use anchor_spl::{metadata::{MasterEditionAccount, Metadata, MetadataAccount}, token_2022::Token2022, token_interface::{Mint, TokenAccount, TokenInterface}};
+
+use crate::{constants::EMERGENCY_UNSTAKE_DISABLED, errors::StakeError, nft::{GroupMemberNftAccounts, NftAccounts, PnftAccounts}, events::EmergencyUnstaked, receipt::burn_held_receipt, rewards::expire_points, state::{MintRecord, StakeAccount, StakeConfig, UserAccount, UserPositions}};
+
+#[derive(Accounts)]
+pub struct EmergencyUnstake<'info>{
+    
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub mint_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"metadata", metadata_program.key().as_ref(), mint.key().as_ref()],
+        seeds::program = metadata_program.key(),
+        bump,
+    )]
+    pub metadata: Option<Account<'info, MetadataAccount>>,
+
+    #[account(
+        seeds = [
+            b"metadata",
+            metadata_program.key().as_ref(),
+            mint.key().as_ref(),
+            b"edition"
+        ],
+        seeds::program = metadata_program.key(),
+        bump,
+    )]
+    pub edition: Option<Account<'info, MasterEditionAccount>>,
+
+    /// Token-2022 group member NFTs frozen in place only, see `stake`
+    pub freeze_authority: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"stake", stake_account.config.as_ref(), mint.key().as_ref()],
+        bump = stake_account.bump,
+        has_one = config @ StakeError::PoolMismatch,
+        constraint = stake_account.owner == user.key() @ StakeError::NotStakeOwner,
+        // Tokenized positions leave only through `unstake` by the receipt's holder
+        constraint = stake_account.position_mint.is_none() @ StakeError::ReceiptRequired,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+
+    // Created on the mint's first stake or unstake since records existed, never closed
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"mint_record", config.key().as_ref(), mint.key().as_ref()],
+        bump,
+        space = MintRecord::INIT_SPACE,
+    )]
+    pub mint_record: Account<'info, MintRecord>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    // Created on the owner's first stake or unstake since positions existed
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"positions", config.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = UserPositions::INIT_SPACE,
+    )]
+    pub user_positions: Account<'info, UserPositions>,
+
+    /// pNFT only: token record PDA of `mint_ata`
+    #[account(mut)]
+    pub token_record: Option<UncheckedAccount<'info>>,
+
+    /// pNFT only
+    #[account(address = sysvar::instructions::ID)]
+    pub sysvar_instructions: Option<UncheckedAccount<'info>>,
+
+    /// pNFT only, when the NFT has a rule set
+    pub authorization_rules_program: Option<UncheckedAccount<'info>>,
+
+    /// pNFT only, when the NFT has a rule set
+    pub authorization_rules: Option<UncheckedAccount<'info>>,
+
+    /// VaultTransfer stakes only
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = stake_account,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: stakes opened with a receipt only, must be `config.receipt_mint`, checked in `burn_held_receipt`
+    #[account(mut)]
+    pub receipt_mint: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: stakes opened with a receipt only: `user`'s receipt ATA, checked in `ReceiptAccounts`
+    #[account(mut)]
+    pub receipt_ata: Option<UncheckedAccount<'info>>,
+
+    /// Stakes opened with a receipt only
+    pub receipt_token_program: Option<Program<'info, Token2022>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub metadata_program: Program<'info, Metadata>,
+}
+
+
+
+impl <'info> EmergencyUnstake<'info> {
+    // Returns the NFT before the freeze period ends, forfeiting this stake's points
+    // and burning early_unstake_penalty_bps of the points the user has already banked
+    pub fn emergency_unstake(&mut self, bumps: &EmergencyUnstakeBumps) -> Result<()>{
+
+        require!(self.config.early_unstake_penalty_bps != EMERGENCY_UNSTAKE_DISABLED, StakeError::EmergencyUnstakeDisabled);
+
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+        self.user_account.apply_decay(&self.config, now)?;
+        expire_points(&self.config, &mut self.user_account, now)?;
+        let points_before = self.user_account.points;
+
+        let freeze_period_passed = self.stake_account.is_unlocked(now);
+        let points_forfeited = self.stake_account.pending_points_with_set_bonus(&self.config, self.user_account.set_complete_since, self.user_positions.full_set(self.stake_account.set_piece), now)?;
+
+        let seeds = &[
+            b"stake",
+            self.config.to_account_info().key.as_ref(),
+            self.mint.to_account_info().key.as_ref(),
+            &[self.stake_account.bump]
+        ];
+
+        let signer_seeds = &[&seeds[..]];
+
+        // Whichever way `stake` locked it, the wrong way fails on the freeze authority
+        match (&self.metadata, &self.edition) {
+            (Some(metadata), Some(edition)) => NftAccounts{
+                owner: self.user.as_ref(),
+                delegate: self.stake_account.as_ref(),
+                mint: self.mint.as_ref(),
+                token_account: self.mint_ata.as_ref(),
+                metadata: metadata.as_ref(),
+                edition: edition.as_ref(),
+                token_program: self.token_program.as_ref(),
+                metadata_program: self.metadata_program.as_ref(),
+                system_program: self.system_program.as_ref(),
+                pnft: PnftAccounts::from_optional(
+                    self.token_record.as_deref(),
+                    self.sysvar_instructions.as_deref(),
+                    self.authorization_rules_program.as_deref(),
+                    self.authorization_rules.as_deref(),
+                ),
+                vault: self.vault.as_ref().map(|vault| vault.as_ref()),
+            }.unlock(metadata.token_standard.as_ref(), self.stake_account.custody_mode, signer_seeds)?,
+            (None, None) => GroupMemberNftAccounts{
+                owner: self.user.as_ref(),
+                delegate: self.stake_account.as_ref(),
+                mint: self.mint.as_ref(),
+                token_account: self.mint_ata.as_ref(),
+                token_program: self.token_program.as_ref(),
+                freeze_authority: self.freeze_authority.as_deref(),
+                vault: self.vault.as_ref().map(|vault| vault.as_ref()),
+            }.unlock(self.stake_account.custody_mode, signer_seeds)?,
+            _ => return err!(StakeError::MissingMetadataAccounts),
+        }
+
+        burn_held_receipt(
+            &self.config,
+            &self.stake_account,
+            self.user.as_ref(),
+            self.receipt_mint.as_ref(),
+            self.receipt_ata.as_ref(),
+            self.receipt_token_program.as_ref(),
+        )?;
+
+        self.mint_record.init_if_new(self.config.key(), self.mint.key(), bumps.mint_record);
+        self.mint_record.record_unstake(now);
+
+        self.user_positions.init_if_new(self.config.key(), self.user.key(), bumps.user_positions);
+        self.user_positions.remove(&self.stake_account.mint);
+
+        let points_penalty = self.config.early_unstake_penalty(self.user_account.points)?;
+
+        self.user_account.points = self.user_account.points.saturating_sub(points_penalty);
+
+        // Only this NFT's stake closes, the user's other stakes keep their own timers
+        self.user_account.amount_staked = self.user_account.amount_staked
+            .checked_sub(1)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        self.user_account.remove_set_piece(&self.config, self.stake_account.set_piece, now);
+        self.user_positions.end_full_set(self.stake_account.set_piece);
+        self.config.remove_staked();
+
+        self.user_account.restart_expiry_if_changed(points_before, now);
+
+        emit!(EmergencyUnstaked {
+            config: self.config.key(),
+            user: self.user.key(),
+            mint: self.mint.key(),
+            unstaked_at: now,
+            slot: clock.slot,
+            duration: now.saturating_sub(self.stake_account.opened_at),
+            points_forfeited,
+            points_penalty,
+            freeze_period_passed,
+        });
+
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -0,0 +1,196 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{metadata::{MasterEditionAccount, Metadata, MetadataAccount}, token_interface::{Mint, TokenAccount, TokenInterface}};
+
+use crate::{errors::StakeError, events::ForceUnstaked, nft::{GroupMemberNftAccounts, NftAccounts}, state::{Blacklist, CustodyMode, MintRecord, StakeAccount, StakeConfig, UserAccount, UserPositions}};
+
+#[derive(Accounts)]
+pub struct ForceUnstake<'info>{
+
+    // Pays for the mint's record if it doesn't have one yet
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// CHECK: the blacklisted stake owner, gets the NFT back and the stake account's rent
+    #[account(mut)]
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"blacklist".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump = blacklist.bump,
+    )]
+    pub blacklist: Account<'info, Blacklist>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub mint_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"metadata", metadata_program.key().as_ref(), mint.key().as_ref()],
+        seeds::program = metadata_program.key(),
+        bump,
+    )]
+    pub metadata: Option<Account<'info, MetadataAccount>>,
+
+    #[account(
+        seeds = [
+            b"metadata",
+            metadata_program.key().as_ref(),
+            mint.key().as_ref(),
+            b"edition"
+        ],
+        seeds::program = metadata_program.key(),
+        bump,
+    )]
+    pub edition: Option<Account<'info, MasterEditionAccount>>,
+
+    /// Token-2022 group member NFTs frozen in place only, see `stake`
+    pub freeze_authority: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"stake", stake_account.config.as_ref(), mint.key().as_ref()],
+        bump = stake_account.bump,
+        has_one = config @ StakeError::PoolMismatch,
+        constraint = stake_account.owner == user.key() @ StakeError::NotStakeOwner,
+        // Tokenized positions leave only through `unstake` by the receipt's holder
+        constraint = stake_account.position_mint.is_none() @ StakeError::ReceiptRequired,
+        // Only the owner can burn a soulbound receipt, so those stakes wait for them to unstake
+        constraint = !stake_account.has_receipt @ StakeError::MissingReceipt,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+
+    // Created on the mint's first stake or unstake since records existed, never closed
+    #[account(
+        init_if_needed,
+        payer = admin,
+        seeds = [b"mint_record", config.key().as_ref(), mint.key().as_ref()],
+        bump,
+        space = MintRecord::INIT_SPACE,
+    )]
+    pub mint_record: Account<'info, MintRecord>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    // Created on the owner's first stake or unstake since positions existed
+    #[account(
+        init_if_needed,
+        payer = admin,
+        seeds = [b"positions", config.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = UserPositions::INIT_SPACE,
+    )]
+    pub user_positions: Account<'info, UserPositions>,
+
+    /// VaultTransfer stakes only
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = stake_account,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub metadata_program: Program<'info, Metadata>,
+}
+
+impl <'info> ForceUnstake<'info> {
+
+    // Hands a blacklisted wallet's NFT back without its signature and forfeits everything the stake
+    // accrued. pNFTs, cNFTs and stakes issued a receipt need the owner to sign their release, they
+    // stay staked until the owner unstakes them for no points.
+    pub fn force_unstake(&mut self, bumps: &ForceUnstakeBumps) -> Result<()>{
+
+        require!(self.stake_account.custody_mode != CustodyMode::Compressed, StakeError::CustodyModeMismatch);
+
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+
+        let points_forfeited = self.stake_account.pending_points_with_set_bonus(&self.config, self.user_account.set_complete_since, self.user_positions.full_set(self.stake_account.set_piece), now)?;
+
+        let seeds = &[
+            b"stake",
+            self.config.to_account_info().key.as_ref(),
+            self.mint.to_account_info().key.as_ref(),
+            &[self.stake_account.bump]
+        ];
+
+        let signer_seeds = &[&seeds[..]];
+
+        match (&self.metadata, &self.edition) {
+            (Some(metadata), Some(edition)) => NftAccounts{
+                owner: self.user.as_ref(),
+                delegate: self.stake_account.as_ref(),
+                mint: self.mint.as_ref(),
+                token_account: self.mint_ata.as_ref(),
+                metadata: metadata.as_ref(),
+                edition: edition.as_ref(),
+                token_program: self.token_program.as_ref(),
+                metadata_program: self.metadata_program.as_ref(),
+                system_program: self.system_program.as_ref(),
+                pnft: None,
+                vault: self.vault.as_ref().map(|vault| vault.as_ref()),
+            }.force_unlock(metadata.token_standard.as_ref(), self.stake_account.custody_mode, signer_seeds)?,
+            (None, None) => GroupMemberNftAccounts{
+                owner: self.user.as_ref(),
+                delegate: self.stake_account.as_ref(),
+                mint: self.mint.as_ref(),
+                token_account: self.mint_ata.as_ref(),
+                token_program: self.token_program.as_ref(),
+                freeze_authority: self.freeze_authority.as_deref(),
+                vault: self.vault.as_ref().map(|vault| vault.as_ref()),
+            }.force_unlock(self.stake_account.custody_mode, signer_seeds)?,
+            _ => return err!(StakeError::MissingMetadataAccounts),
+        }
+
+        self.mint_record.init_if_new(self.config.key(), self.mint.key(), bumps.mint_record);
+        self.mint_record.record_unstake(now);
+
+        self.user_positions.init_if_new(self.config.key(), self.user.key(), bumps.user_positions);
+        self.user_positions.remove(&self.stake_account.mint);
+
+        // Nothing is credited, but the weight so far was earned with this stake counted
+        self.user_account.settle_governance_weight(&self.config, now);
+        self.user_account.amount_staked = self.user_account.amount_staked
+            .checked_sub(1)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        self.user_account.remove_set_piece(&self.config, self.stake_account.set_piece, now);
+        self.user_positions.end_full_set(self.stake_account.set_piece);
+        self.config.remove_staked();
+
+        emit!(ForceUnstaked {
+            config: self.config.key(),
+            admin: self.admin.key(),
+            user: self.user.key(),
+            mint: self.mint.key(),
+            unstaked_at: now,
+            slot: clock.slot,
+            duration: now.saturating_sub(self.stake_account.opened_at),
+            points_forfeited,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,73 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::{errors::StakeError, events::SnapshotFunded, state::StakeConfig};
+
+#[derive(Accounts)]
+pub struct FundSnapshot<'info>{
+
+    pub funder: Signer<'info>,
+
+    #[account(
+        mut,
+        token::mint = reward_mint,
+        token::authority = funder,
+        token::token_program = token_program,
+    )]
+    pub funder_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        address = config.reward_mint @ StakeError::RewardMintMismatch,
+        mint::token_program = token_program,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"reward_vault".as_ref(), config.key().as_ref()],
+        bump = config.reward_vault_bump,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl <'info> FundSnapshot<'info> {
+
+    // Like fund_vault, but `amount` is held back from points payouts for the next distribute_snapshot
+    pub fn fund_snapshot(&mut self, amount: u64)->Result<()>{
+
+        self.config.fund_snapshot(amount)?;
+
+        let cpi_accounts = TransferChecked{
+            from: self.funder_ata.to_account_info(),
+            mint: self.reward_mint.to_account_info(),
+            to: self.reward_vault.to_account_info(),
+            authority: self.funder.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+
+        transfer_checked(cpi_ctx, amount, self.reward_mint.decimals)?;
+
+        let clock = Clock::get()?;
+
+        emit!(SnapshotFunded {
+            config: self.config.key(),
+            funder: self.funder.key(),
+            amount,
+            snapshot_pool: self.config.snapshot_pool,
+            funded_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
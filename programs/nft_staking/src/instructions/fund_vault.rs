@@ -0,0 +1,72 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::{errors::StakeError, events::RewardVaultFunded, state::StakeConfig};
+
+#[derive(Accounts)]
+pub struct FundVault<'info>{
+
+    pub funder: Signer<'info>,
+
+    #[account(
+        mut,
+        token::mint = reward_mint,
+        token::authority = funder,
+        token::token_program = token_program,
+    )]
+    pub funder_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        address = config.reward_mint @ StakeError::RewardMintMismatch,
+        mint::token_program = token_program,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"reward_vault".as_ref(), config.key().as_ref()],
+        bump = config.reward_vault_bump,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl <'info> FundVault<'info> {
+
+    // Anyone can top up a pool's rewards, `amount` is in reward token base units
+    pub fn fund_vault(&mut self, amount: u64)->Result<()>{
+
+        let cpi_accounts = TransferChecked{
+            from: self.funder_ata.to_account_info(),
+            mint: self.reward_mint.to_account_info(),
+            to: self.reward_vault.to_account_info(),
+            authority: self.funder.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+
+        transfer_checked(cpi_ctx, amount, self.reward_mint.decimals)?;
+
+        self.reward_vault.reload()?;
+
+        let clock = Clock::get()?;
+
+        emit!(RewardVaultFunded {
+            config: self.config.key(),
+            funder: self.funder.key(),
+            amount,
+            vault_balance: self.reward_vault.amount,
+            funded_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::{errors::StakeError, rewards::tier_multiplier_bps, state::{FullSet, StakeAccount, StakeConfig, UserAccount, UserPositions}};
+
+/// What one passed stake would pay if it were unstaked now.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PendingStake {
+    pub stake_account: Pubkey,
+    /// Points `unstake` would pay for this stake, compound boost included.
+    pub points: u64,
+    /// Multiplier of the reward tier the stake is in today, in bps.
+    pub tier_multiplier_bps: u16,
+    /// The collection's multiplier snapshotted at stake time, in bps.
+    pub points_multiplier: u16,
+    /// Zero once `unstake` accepts the stake.
+    pub seconds_until_unlock: i64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct PendingRewards {
+    /// Banked points plus what the passed stakes have earned so far.
+    pub points: u64,
+    /// `points` in reward token base units.
+    pub amount: u64,
+    /// One entry per passed stake account, in order.
+    pub stakes: Vec<PendingStake>,
+    /// Voting weight settled up to now, for snapshot tooling. Not spendable, claims never touch it.
+    pub governance_weight: u64,
+}
+
+#[derive(Accounts)]
+pub struct GetPendingRewards<'info>{
+
+    /// CHECK: only used to derive `user_account` and match stake owners, nothing is signed or written
+    pub user: UncheckedAccount<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    /// Pass to count full-set bonuses, they're left out without it
+    #[account(
+        seeds = [b"positions", config.key().as_ref(), user.key().as_ref()],
+        bump = user_positions.bump,
+    )]
+    pub user_positions: Option<Account<'info, UserPositions>>,
+
+    #[account(address = config.reward_mint @ StakeError::RewardMintMismatch)]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+}
+
+impl <'info> GetPendingRewards<'info> {
+
+    // Read-only, meant to be simulated. remaining_accounts holds the user's stake accounts in this pool,
+    // points pending on stakes that aren't passed are left out. Return data caps out at 1024 bytes,
+    // so pass at most 19 stakes per call.
+    pub fn get_pending_rewards(&self, remaining_accounts: &'info [AccountInfo<'info>])->Result<PendingRewards>{
+
+        let now = Clock::get()?.unix_timestamp;
+
+        let mut pending = 0_u64;
+        let mut seen: Vec<Pubkey> = Vec::with_capacity(remaining_accounts.len());
+        let mut stakes = Vec::with_capacity(remaining_accounts.len());
+
+        for info in remaining_accounts {
+            require!(!seen.contains(info.key), StakeError::InvalidRemainingAccounts);
+            seen.push(info.key());
+
+            let stake_account = Account::<StakeAccount>::try_from(info)?;
+            require_keys_eq!(stake_account.config, self.config.key(), StakeError::PoolMismatch);
+            require_keys_eq!(stake_account.owner, self.user.key(), StakeError::NotStakeOwner);
+
+            let full_set = self.user_positions
+                .as_ref()
+                .map_or(FullSet::default(), |positions| positions.full_set(stake_account.set_piece));
+            let stake_points = stake_account.pending_points_with_set_bonus(&self.config, self.user_account.set_complete_since, full_set, now)?;
+            pending = pending
+                .checked_add(stake_points)
+                .ok_or(StakeError::ArithmeticOverflow)?;
+
+            stakes.push(PendingStake {
+                stake_account: info.key(),
+                points: self.config.boosted_points(stake_points, &self.user_account, self.reward_mint.decimals, now)?,
+                tier_multiplier_bps: tier_multiplier_bps(&self.config, stake_account.days_staked(now)?),
+                points_multiplier: stake_account.points_multiplier,
+                seconds_until_unlock: stake_account.seconds_until_unlock(now),
+            });
+        }
+
+        // Banked points were already boosted when they were earned, they're counted after decay and expiry
+        let points = self.config
+            .boosted_points(pending, &self.user_account, self.reward_mint.decimals, now)?
+            .checked_add(self.user_account.live_points(&self.config, now)?)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+
+        let amount = points
+            .checked_mul(10_u64.checked_pow(self.reward_mint.decimals as u32).ok_or(StakeError::ArithmeticOverflow)?)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+
+        let governance_weight = self.user_account.governance_weight_at(&self.config, now);
+
+        Ok(PendingRewards { points, amount, stakes, governance_weight })
+    }
+}
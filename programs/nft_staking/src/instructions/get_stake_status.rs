@@ -0,0 +1,48 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, state::{StakeAccount, StakeConfig}};
+
+/// Where one stake stands on its way to being unstakeable.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct StakeStatus {
+    pub staked_at: i64,
+    /// When `unstake` accepts the stake, past both the freeze period and the lockup.
+    pub unlocked_at: i64,
+    /// Zero once `unstake` accepts the stake.
+    pub seconds_until_unlock: i64,
+    /// Points the stake has earned so far, before the owner's set bonus and lock boost.
+    pub points: u64,
+}
+
+#[derive(Accounts)]
+pub struct GetStakeStatus<'info>{
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        seeds = [b"stake", config.key().as_ref(), stake_account.mint.as_ref()],
+        bump = stake_account.bump,
+        has_one = config @ StakeError::PoolMismatch,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+}
+
+impl <'info> GetStakeStatus<'info> {
+
+    // Read-only, meant to be simulated. Runs the same unlock check as `unstake`, so front-ends
+    // don't have to mirror FreezePeriodNotPassed
+    pub fn get_stake_status(&self)->Result<StakeStatus>{
+        let now = Clock::get()?.unix_timestamp;
+
+        Ok(StakeStatus {
+            staked_at: self.stake_account.staked_at,
+            unlocked_at: self.stake_account.unlocked_at,
+            seconds_until_unlock: self.stake_account.seconds_until_unlock(now),
+            points: self.stake_account.pending_points(&self.config, now)?,
+        })
+    }
+}
@@ -0,0 +1,118 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::{errors::StakeError, events::StakeHarvested, instructions::credit_referrer, rewards::expire_points, state::{Blacklist, FullSet, LockupKind, StakeAccount, StakeConfig, UserAccount, UserPositions}};
+
+#[derive(Accounts)]
+pub struct Harvest<'info>{
+
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"stake", config.key().as_ref(), stake_account.mint.as_ref()],
+        bump = stake_account.bump,
+        has_one = config @ StakeError::PoolMismatch,
+        constraint = stake_account.owner == user.key() @ StakeError::NotStakeOwner,
+        // A tokenized position's points belong to whoever holds the receipt when it unstakes
+        constraint = stake_account.position_mint.is_none() @ StakeError::ReceiptRequired,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    /// Pass for a full set's bonus, leaving it out banks the points without it
+    #[account(
+        seeds = [b"positions", config.key().as_ref(), user.key().as_ref()],
+        bump = user_positions.bump,
+    )]
+    pub user_positions: Option<Account<'info, UserPositions>>,
+
+    /// CHECK: `user`'s blacklist PDA, only holds data while the wallet is blacklisted
+    #[account(
+        seeds = [b"blacklist".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub blacklist: UncheckedAccount<'info>,
+
+    // Only read for its decimals, the compound boost is priced in whole reward tokens
+    #[account(address = config.reward_mint @ StakeError::RewardMintMismatch)]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    /// The user's stored referrer's user account in this pool, credited `referral_bps` of
+    /// this harvest's points. Leaving it out skips the cut rather than failing.
+    #[account(mut)]
+    pub referrer_account: Option<Account<'info, UserAccount>>,
+}
+
+impl <'info> Harvest<'info> {
+
+    // Banks the stake's points as unstake would and moves its checkpoint past the whole days
+    // banked, the NFT stays locked and no tokens move. Tiers and `max_stake_duration` count again
+    // from the checkpoint, a harvest with nothing to bank fails. `restart_lock` also reopens the
+    // freeze period under `lockup`, the stake's own when left out
+    pub fn harvest(&mut self, restart_lock: bool, lockup: Option<LockupKind>)->Result<()>{
+        require!(!Blacklist::is_set(&self.blacklist), StakeError::WalletBlacklisted);
+        require!(restart_lock || lockup.is_none(), StakeError::InvalidLockupKind);
+
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+        self.user_account.apply_decay(&self.config, now)?;
+        expire_points(&self.config, &mut self.user_account, now)?;
+        let points_before = self.user_account.points;
+        self.stake_account.settle(&self.config, now)?;
+
+        let full_set = self.user_positions
+            .as_ref()
+            .map_or(FullSet::default(), |positions| positions.full_set(self.stake_account.set_piece));
+        let points = self.config.boosted_points(
+            self.stake_account.pending_points_with_set_bonus(&self.config, self.user_account.set_complete_since, full_set, now)?,
+            &self.user_account,
+            self.reward_mint.decimals,
+            now,
+        )?;
+        require!(points > 0, StakeError::NothingToClaim);
+
+        self.user_account.points = self.user_account.points
+            .checked_add(points)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+
+        let referral_points = credit_referrer(&self.config, &self.user_account, self.referrer_account.as_mut(), points, now)?;
+
+        if restart_lock {
+            let lockup = lockup.unwrap_or(self.stake_account.lockup);
+            self.stake_account.relock(&self.config, lockup, now)?;
+        }
+
+        let duration = now.saturating_sub(self.stake_account.staked_at);
+        self.stake_account.advance_checkpoint(now)?;
+
+        self.user_account.restart_expiry_if_changed(points_before, now);
+
+        emit!(StakeHarvested {
+            config: self.config.key(),
+            user: self.user.key(),
+            mint: self.stake_account.mint,
+            harvested_at: now,
+            slot: clock.slot,
+            duration,
+            points_earned: points,
+            referral_points,
+            unlocked_at: self.stake_account.unlocked_at,
+        });
+
+        Ok(())
+    }
+}
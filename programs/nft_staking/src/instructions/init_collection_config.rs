@@ -0,0 +1,47 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::Mint;
+
+use crate::{errors::StakeError, state::{CollectionConfig, StakeConfig}};
+
+#[derive(Accounts)]
+pub struct InitCollectionConfig<'info>{
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub collection_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"collection".as_ref(), config.key().as_ref(), collection_mint.key().as_ref()],
+        bump,
+        space = CollectionConfig::INIT_SPACE,
+    )]
+    pub collection_config: Account<'info, CollectionConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info>InitCollectionConfig<'info> {
+
+    pub fn init_collection_config(&mut self, points_multiplier: u16, bumps: &InitCollectionConfigBumps)->Result<()>{
+
+        self.collection_config.set_inner(CollectionConfig {
+            collection_mint: self.collection_mint.key(),
+            points_multiplier,
+            bump: bumps.collection_config,
+            collection_size: 0,
+            full_set_bonus_bps: 0,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, state::{EpochHistory, StakeConfig}};
+
+#[derive(Accounts)]
+pub struct InitEpochHistory<'info>{
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"epoch_history".as_ref(), config.key().as_ref()],
+        bump,
+        space = EpochHistory::INIT_SPACE,
+    )]
+    pub epoch_history: AccountLoader<'info, EpochHistory>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info>InitEpochHistory<'info> {
+
+    // Epochs count from now, the first `snapshot_epoch` lands once `epoch_length_secs` has passed
+    pub fn init_epoch_history(&mut self, bumps: &InitEpochHistoryBumps)->Result<()>{
+
+        let mut epoch_history = self.epoch_history.load_init()?;
+        epoch_history.config = self.config.key();
+        epoch_history.started_at = Clock::get()?.unix_timestamp;
+        epoch_history.bump = bumps.epoch_history;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::LEADERBOARD_SIZE, errors::StakeError, state::{Leaderboard, StakeConfig}};
+
+#[derive(Accounts)]
+pub struct InitLeaderboard<'info>{
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"leaderboard".as_ref(), config.key().as_ref()],
+        bump,
+        space = Leaderboard::INIT_SPACE,
+    )]
+    pub leaderboard: AccountLoader<'info, Leaderboard>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info>InitLeaderboard<'info> {
+
+    // Users rank from their next claim or unstake, points paid before that aren't backfilled
+    pub fn init_leaderboard(&mut self, bumps: &InitLeaderboardBumps)->Result<()>{
+
+        // Entries start zeroed, only the header needs setting
+        let mut leaderboard = self.leaderboard.load_init()?;
+        leaderboard.config = self.config.key();
+        leaderboard.capacity = LEADERBOARD_SIZE as u32;
+        leaderboard.bump = bumps.leaderboard;
+
+        Ok(())
+    }
+}
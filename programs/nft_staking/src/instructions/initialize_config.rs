@@ -1,47 +1,220 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, Token};
+use anchor_spl::token_interface::{Mint, TokenAccount, TokenInterface};
 
-use crate::state::StakeConfig;
+use crate::{constants::{BPS_DENOMINATOR, LOCKUP_KINDS, REWARD_TIERS, SET_COLLECTIONS}, errors::StakeError, program::NftStaking, state::{CollectionConfig, CustodyMode, LockupOption, RewardTier, StakeConfig}};
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct InitializeConfigArgs {
+    // Distinguishes pools sharing this program, part of the config PDA seeds
+    pub pool_id: u64,
+    pub creator: Pubkey,
+    pub points_per_stake: u8,
+    pub referral_bonus: u32,
+    pub referral_bps: u16,
+    pub treasury: Pubkey,
+    pub fee_bps: u16,
+    pub claim_fee_lamports: u64,
+    pub max_stake: u8,
+    pub freeze_period_secs: u64,
+    // Indexed by `LockupKind`, Flexible must be offered
+    pub lockup_options: [LockupOption; LOCKUP_KINDS],
+    // 0 lets stakes accrue for as long as they stay open
+    pub max_stake_duration: u32,
+    // 0 leaves the pool-wide stake count uncapped
+    pub global_max_stake: u32,
+    // Most points the pool ever pays out as reward tokens, 0 leaves it uncapped
+    pub max_emission: u64,
+    pub early_unstake_penalty_bps: u16,
+    pub claim_cooldown: u32,
+    pub reward_tiers: [RewardTier; REWARD_TIERS],
+    pub emission_start: i64,
+    // 0 keeps `points_per_stake` flat
+    pub halving_interval_secs: i64,
+    pub custody_mode: CustodyMode,
+    pub max_clawback: u64,
+    pub stake_token_mint: Option<Pubkey>,
+    pub max_token_stake: u64,
+    pub receipt_mint: Option<Pubkey>,
+    pub compound_boost_bps: u16,
+    pub max_compound_boost_bps: u16,
+    // 0 turns compound_locked off
+    pub compound_bonus_bps: u16,
+    pub compound_lock_secs: u32,
+    // 0 turns lock_boost off
+    pub lock_boost_bps: u16,
+    pub max_lock_boost_bps: u16,
+    pub boost_lock_secs: u32,
+    // 0 lets a mint be restaked right after it's unstaked
+    pub restake_cooldown_secs: u32,
+    pub min_reward_duration: u32,
+    // `Pubkey::default()` for unused slots, all unused turns the set bonus off
+    pub set_collections: [Pubkey; SET_COLLECTIONS],
+    pub set_bonus_bps: u16,
+    pub decay_rate_bps_per_day: u16,
+    // Limits staking to wallets given an `AllowlistEntry` with allow_wallet
+    pub gated: bool,
+    // 0 lets any balance be claimed
+    pub min_claim_points: u64,
+    pub claim_requires_unlocked: bool,
+    // 0 leaves epoch snapshots off
+    pub epoch_length_secs: u32,
+    // 0 leaves stakes per wallet unlimited
+    pub max_stakes_per_window: u16,
+    pub window_len: u32,
+    // 0 never expires points
+    pub points_expiry_secs: i64,
+    // 0 leaves governance weight off
+    pub weight_per_day: u64,
+    pub weight_decay_per_day: u64,
+}
+
+impl InitializeConfigArgs {
+
+    /// The config these args describe, before its accounts are known. Addresses and bumps are left
+    /// default and every counter starts at zero, which is all `StakeConfig::validate` needs.
+    pub fn to_config(&self) -> StakeConfig {
+        StakeConfig {
+            pool_id: self.pool_id,
+            creator: self.creator,
+            treasury: self.treasury,
+            fee_bps: self.fee_bps,
+            claim_fee_lamports: self.claim_fee_lamports,
+            points_per_stake: self.points_per_stake,
+            referral_bonus: self.referral_bonus,
+            referral_bps: self.referral_bps,
+            max_stake: self.max_stake,
+            freeze_period_secs: self.freeze_period_secs,
+            lockup_options: self.lockup_options,
+            max_stake_duration: self.max_stake_duration,
+            global_max_stake: self.global_max_stake,
+            max_emission: self.max_emission,
+            early_unstake_penalty_bps: self.early_unstake_penalty_bps,
+            claim_cooldown: self.claim_cooldown,
+            reward_tiers: self.reward_tiers,
+            emission_start: self.emission_start,
+            halving_interval_secs: self.halving_interval_secs,
+            stake_token_mint: self.stake_token_mint,
+            max_token_stake: self.max_token_stake,
+            receipt_mint: self.receipt_mint,
+            compound_boost_bps: self.compound_boost_bps,
+            max_compound_boost_bps: self.max_compound_boost_bps,
+            compound_bonus_bps: self.compound_bonus_bps,
+            compound_lock_secs: self.compound_lock_secs,
+            lock_boost_bps: self.lock_boost_bps,
+            max_lock_boost_bps: self.max_lock_boost_bps,
+            boost_lock_secs: self.boost_lock_secs,
+            restake_cooldown_secs: self.restake_cooldown_secs,
+            min_reward_duration: self.min_reward_duration,
+            set_collections: self.set_collections,
+            set_bonus_bps: self.set_bonus_bps,
+            decay_rate_bps_per_day: self.decay_rate_bps_per_day,
+            max_clawback: self.max_clawback,
+            custody_mode: self.custody_mode,
+            gated: self.gated,
+            min_claim_points: self.min_claim_points,
+            claim_requires_unlocked: self.claim_requires_unlocked,
+            epoch_length_secs: self.epoch_length_secs,
+            max_stakes_per_window: self.max_stakes_per_window,
+            window_len: self.window_len,
+            points_expiry_secs: self.points_expiry_secs,
+            weight_per_day: self.weight_per_day,
+            weight_decay_per_day: self.weight_decay_per_day,
+            ..Default::default()
+        }
+    }
+}
 
 #[derive(Accounts)]
+#[instruction(args: InitializeConfigArgs)]
 pub struct InitializeConfig<'info>{
 
+    // Must be the program's upgrade authority, otherwise anyone could claim a pool id first
     #[account(mut)]
     pub admin: Signer<'info>,
 
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()) @ StakeError::NotUpgradeAuthority)]
+    pub program: Program<'info, NftStaking>,
+
+    #[account(constraint = program_data.upgrade_authority_address == Some(admin.key()) @ StakeError::NotUpgradeAuthority)]
+    pub program_data: Account<'info, ProgramData>,
+
     #[account(
         init,
         payer = admin,
-        seeds = [b"config".as_ref()],
+        seeds = [b"config".as_ref(), args.pool_id.to_le_bytes().as_ref()],
         bump,
         space = StakeConfig::INIT_SPACE,
     )]
     pub config: Account<'info, StakeConfig>,
 
+    // Any existing mint under either token program. Claims pay out the gross amount, so a transfer
+    // fee extension on it would short users.
+    #[account(mint::token_program = token_program)]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    // Fixed supply: holds whatever has been sent in with fund_vault and only the config can pay out of it
     #[account(
         init,
         payer = admin,
-        seeds = [b"rewards".as_ref(), config.key().as_ref()],
+        seeds = [b"reward_vault".as_ref(), config.key().as_ref()],
         bump,
-        mint::decimals = 6,
-        mint::authority = config,
+        token::mint = reward_mint,
+        token::authority = config,
+        token::token_program = token_program,
     )]
-    pub reward_mint: Account<'info, Mint>,
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
 
-    pub token_program: Program<'info, Token>,
+    pub collection_mint: InterfaceAccount<'info, Mint>,
+
+    // The pool's own collection is whitelisted at 1x, more can be added with init_collection_config
+    #[account(
+        init,
+        payer = admin,
+        seeds = [b"collection".as_ref(), config.key().as_ref(), collection_mint.key().as_ref()],
+        bump,
+        space = CollectionConfig::INIT_SPACE,
+    )]
+    pub collection_config: Account<'info, CollectionConfig>,
+
+    /// CHECK: the original config from before pools, still in its `BASELINE_SPACE` layout, so it's
+    /// decoded by hand. Its address can't take a pool id, so passing it opens this pool with its
+    /// points, max stake and freeze period in place of the args'. It's left as is, it stays the
+    /// authority of the original reward mint.
+    #[account(owner = crate::ID, seeds = [b"config".as_ref()], bump)]
+    pub legacy_config: Option<UncheckedAccount<'info>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
 }
 
 impl <'info>InitializeConfig<'info> {
 
-    pub fn initialize_config(&mut self, points_per_stake: u8, max_stake: u8, freeze_period: u32,bumps: &InitializeConfigBumps)->Result<()>{
-      
-        self.config.set_inner(StakeConfig { 
-            points_per_stake, 
-            max_stake, 
-            freeze_period, 
-            rewards_bump: bumps.reward_mint, 
-            bump: bumps.config, 
+    pub fn initialize_config(&mut self, args: InitializeConfigArgs, bumps: &InitializeConfigBumps)->Result<()>{
+
+        let mut config = StakeConfig {
+            admin: self.admin.key(),
+            reward_mint: self.reward_mint.key(),
+            reward_vault_bump: bumps.reward_vault,
+            bump: bumps.config,
+            ..args.to_config()
+        };
+
+        if let Some(legacy_config) = &self.legacy_config {
+            let legacy = StakeConfig::try_from_baseline(&legacy_config.try_borrow_data()?)?;
+            config.points_per_stake = legacy.points_per_stake;
+            config.max_stake = legacy.max_stake;
+            config.freeze_period_secs = legacy.freeze_period_secs;
+        }
+
+        config.validate()?;
+        self.config.set_inner(config);
+
+        self.collection_config.set_inner(CollectionConfig {
+            collection_mint: self.collection_mint.key(),
+            points_multiplier: BPS_DENOMINATOR,
+            bump: bumps.collection_config,
+            collection_size: 0,
+            full_set_bonus_bps: 0,
         });
 
         Ok(())
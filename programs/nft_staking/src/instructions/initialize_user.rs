@@ -1,6 +1,6 @@
 use anchor_lang::prelude::*;
 
-use crate::state::UserAccount;
+use crate::{errors::StakeError, state::{AllowlistEntry, StakeConfig, UserAccount, UserPositions}};
 
 #[derive(Accounts)]
 pub struct Initialize<'info>{
@@ -8,27 +8,51 @@ pub struct Initialize<'info>{
     #[account(mut)]
     pub user: Signer<'info>,
 
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
     #[account(
         init,
         payer = user,
-        seeds = [b"user".as_ref(), user.key().as_ref()],
+        seeds = [b"user".as_ref(), config.key().as_ref(), user.key().as_ref()],
         bump,
         space = UserAccount::INIT_SPACE,
     )]
     pub user_account: Account<'info, UserAccount>,
 
+    // `stake` or an unstake may have created it already for a user who closed theirs
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"positions", config.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = UserPositions::INIT_SPACE,
+    )]
+    pub user_positions: Account<'info, UserPositions>,
+
+    /// CHECK: `user`'s allowlist PDA, required while the pool is gated
+    #[account(
+        seeds = [b"allowlist".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub allowlist_entry: Option<UncheckedAccount<'info>>,
+
     pub system_program: Program<'info, System>
 }
 
 impl <'info> Initialize<'info> {
 
-    pub fn initialize_user_account(&mut self, bumps: &InitializeBumps)->Result<()>{
-        
-        self.user_account.set_inner(UserAccount { 
-            points: 0, 
-            amount_staked: 0, 
-            bump: bumps.user_account,
-        });
+    // `referrer` earns `referral_bps` of the points this user earns from then on
+    pub fn initialize_user_account(&mut self, referrer: Option<Pubkey>, bumps: &InitializeBumps)->Result<()>{
+
+        require!(referrer != Some(self.user.key()), StakeError::SelfReferral);
+        AllowlistEntry::require_allowed(&self.config, self.allowlist_entry.as_deref())?;
+
+        self.user_account.set_inner(UserAccount::new(self.user.key(), referrer, bumps.user_account));
+        self.user_positions.init_if_new(self.config.key(), self.user.key(), bumps.user_positions);
 
         Ok(())
     }
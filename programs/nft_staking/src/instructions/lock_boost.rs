@@ -0,0 +1,104 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::{constants::PAUSE_STAKE, errors::StakeError, events::BoostLocked, state::{Blacklist, StakeConfig, UserAccount}};
+
+#[derive(Accounts)]
+pub struct LockBoost<'info>{
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        address = config.reward_mint @ StakeError::RewardMintMismatch,
+        mint::token_program = token_program,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = reward_mint,
+        token::authority = user,
+        token::token_program = token_program,
+    )]
+    pub user_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    /// CHECK: `user`'s blacklist PDA, only holds data while the wallet is blacklisted
+    #[account(
+        seeds = [b"blacklist".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub blacklist: UncheckedAccount<'info>,
+
+    // Kept apart from `reward_vault` so locked tokens are never paid out as rewards
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"boost_vault".as_ref(), config.key().as_ref()],
+        bump,
+        token::mint = reward_mint,
+        token::authority = config,
+        token::token_program = token_program,
+    )]
+    pub boost_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info> LockBoost<'info> {
+
+    // `amount` is in reward token base units, held for `config.boost_lock_secs`. A boost can't be
+    // topped up, unlock the expired one first
+    pub fn lock_boost(&mut self, amount: u64)->Result<()>{
+        require!(!self.config.is_paused(PAUSE_STAKE), StakeError::ProgramPaused);
+        require!(!Blacklist::is_set(&self.blacklist), StakeError::WalletBlacklisted);
+        require!(self.config.lock_boost_bps > 0, StakeError::BoostingDisabled);
+        require!(amount > 0, StakeError::InvalidAmount);
+        require!(self.user_account.boost_amount == 0, StakeError::BoostAlreadyLocked);
+
+        let clock = Clock::get()?;
+        self.user_account.apply_decay(&self.config, clock.unix_timestamp)?;
+
+        let cpi_accounts = TransferChecked{
+            from: self.user_ata.to_account_info(),
+            mint: self.reward_mint.to_account_info(),
+            to: self.boost_vault.to_account_info(),
+            authority: self.user.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+
+        transfer_checked(cpi_ctx, amount, self.reward_mint.decimals)?;
+
+        self.user_account.boost_amount = amount;
+        self.user_account.boost_expiry = clock.unix_timestamp
+            .checked_add(self.config.boost_lock_secs as i64)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+
+        emit!(BoostLocked {
+            config: self.config.key(),
+            user: self.user.key(),
+            amount,
+            boost_expiry: self.user_account.boost_expiry,
+            locked_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+use anchor_lang::{prelude::*, system_program::{transfer, Transfer}};
+
+use crate::{errors::StakeError, state::StakeConfig};
+
+#[derive(Accounts)]
+#[instruction(pool_id: u64)]
+pub struct MigrateConfig<'info>{
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    /// CHECK: still in the legacy layout, which `Account<StakeConfig>` can't decode, so it's checked by hand
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"config".as_ref(), pool_id.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>
+}
+
+impl <'info> MigrateConfig<'info> {
+
+    // Rewrites a config from before `freeze_period_secs`, `gated` or dropping `collection_mint`,
+    // converting a freeze period from days and zeroing any fields after the bumps it lacked. The
+    // admin tops up the extra rent. Open stakes keep the unlock time they were staked with. The
+    // baseline config at `[b"config"]` is carried over by `initialize_config` instead.
+    pub fn migrate_config(&mut self)->Result<()>{
+        let info = self.config.to_account_info();
+
+        let config = StakeConfig::try_from_legacy(&info.try_borrow_data()?)?;
+        require_keys_eq!(config.admin, self.admin.key(), StakeError::Unauthorized);
+
+        let rent = Rent::get()?
+            .minimum_balance(StakeConfig::INIT_SPACE)
+            .saturating_sub(info.lamports());
+
+        if rent > 0 {
+            let cpi_accounts = Transfer{
+                from: self.admin.to_account_info(),
+                to: info.clone(),
+            };
+
+            let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+
+            transfer(cpi_ctx, rent)?;
+        }
+
+        info.realloc(StakeConfig::INIT_SPACE, false)?;
+
+        config.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+
+        Ok(())
+    }
+}
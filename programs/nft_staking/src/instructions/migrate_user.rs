@@ -0,0 +1,61 @@
+use anchor_lang::{prelude::*, system_program::{transfer, Transfer}};
+
+use crate::state::{StakeConfig, UserAccount};
+
+#[derive(Accounts)]
+pub struct MigrateUser<'info>{
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    /// CHECK: still in a legacy layout, which `Account<UserAccount>` can't decode, so it's checked by hand
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"user".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub user_account: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>
+}
+
+impl <'info> MigrateUser<'info> {
+
+    // Rewrites a user account from an older layout into the current one, the user tops up the extra rent
+    pub fn migrate_user(&mut self)->Result<()>{
+        let info = self.user_account.to_account_info();
+
+        let user_account = UserAccount {
+            owner: self.user.key(),
+            ..UserAccount::try_from_legacy(&info.try_borrow_data()?)?
+        };
+
+        let rent = Rent::get()?
+            .minimum_balance(UserAccount::INIT_SPACE)
+            .saturating_sub(info.lamports());
+
+        if rent > 0 {
+            let cpi_accounts = Transfer{
+                from: self.user.to_account_info(),
+                to: info.clone(),
+            };
+
+            let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+
+            transfer(cpi_ctx, rent)?;
+        }
+
+        info.realloc(UserAccount::INIT_SPACE, false)?;
+
+        user_account.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+
+        Ok(())
+    }
+}
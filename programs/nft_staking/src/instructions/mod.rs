@@ -4,11 +4,166 @@ pub use initialize_user::*;
 pub mod initialize_config;
 pub use initialize_config::*;
 
+pub mod update_config;
+pub use update_config::*;
+
+pub mod init_collection_config;
+pub use init_collection_config::*;
+
+pub mod init_leaderboard;
+pub use init_leaderboard::*;
+pub mod resize_leaderboard;
+pub use resize_leaderboard::*;
+pub mod lock_boost;
+pub use lock_boost::*;
+pub mod unlock_boost;
+pub use unlock_boost::*;
+
+pub mod set_rarity_root;
+pub use set_rarity_root::*;
+
+pub mod set_pause;
+pub use set_pause::*;
+
+pub mod sync_totals;
+pub use sync_totals::*;
+
+pub mod propose_admin;
+pub use propose_admin::*;
+
+pub mod accept_admin;
+pub use accept_admin::*;
+
+pub mod cancel_admin_transfer;
+pub use cancel_admin_transfer::*;
+
+pub mod adjust_points;
+pub use adjust_points::*;
+
+pub mod add_to_blacklist;
+pub use add_to_blacklist::*;
+
+pub mod remove_from_blacklist;
+pub use remove_from_blacklist::*;
+
+pub mod distribute_snapshot;
+pub use distribute_snapshot::*;
+
 pub mod stake;
 pub use stake::*;
 
+pub mod stake_batch;
+pub use stake_batch::*;
+
 pub mod unstake;
 pub use unstake::*;
 
+pub mod emergency_unstake;
+pub use emergency_unstake::*;
+
+pub mod force_unstake;
+pub use force_unstake::*;
+
+pub mod admin_unstake;
+pub use admin_unstake::*;
+
+pub mod close_stake_account;
+pub use close_stake_account::*;
+
+pub mod stake_compressed;
+pub use stake_compressed::*;
+
+pub mod unstake_compressed;
+pub use unstake_compressed::*;
+
+pub mod stake_tokens;
+pub use stake_tokens::*;
+
+pub mod unstake_tokens;
+pub use unstake_tokens::*;
+
 pub mod claim;
 pub use claim::*;
+
+pub mod set_claim_delegate;
+pub use set_claim_delegate::*;
+
+pub mod revoke_claim_delegate;
+pub use revoke_claim_delegate::*;
+
+pub mod compound;
+pub use compound::*;
+
+pub mod compound_locked;
+pub use compound_locked::*;
+
+pub mod claim_locked;
+pub use claim_locked::*;
+
+pub mod fund_vault;
+pub use fund_vault::*;
+
+pub mod fund_snapshot;
+pub use fund_snapshot::*;
+
+pub mod claim_snapshot;
+pub use claim_snapshot::*;
+
+pub mod close_user;
+pub use close_user::*;
+
+pub mod migrate_user;
+pub use migrate_user::*;
+pub mod migrate_config;
+pub use migrate_config::*;
+
+pub mod get_pending_rewards;
+pub use get_pending_rewards::*;
+
+pub mod create_redemption;
+pub use create_redemption::*;
+
+pub mod redeem;
+pub use redeem::*;
+
+pub mod tokenize_position;
+pub use tokenize_position::*;
+
+pub mod detokenize_position;
+pub use detokenize_position::*;
+
+pub mod claim_many;
+pub use claim_many::*;
+
+pub mod get_stake_status;
+pub use get_stake_status::*;
+
+pub mod allow_wallet;
+pub use allow_wallet::*;
+
+pub mod disallow_wallet;
+pub use disallow_wallet::*;
+
+pub mod harvest;
+pub use harvest::*;
+
+pub mod init_epoch_history;
+pub use init_epoch_history::*;
+
+pub mod snapshot_epoch;
+pub use snapshot_epoch::*;
+
+pub mod set_full_set_bonus;
+pub use set_full_set_bonus::*;
+
+pub mod unstake_many;
+pub use unstake_many::*;
+
+pub mod transfer_mint_authority;
+pub use transfer_mint_authority::*;
+
+pub mod validate_config;
+pub use validate_config::*;
+
+pub mod crank_accrue;
+pub use crank_accrue::*;
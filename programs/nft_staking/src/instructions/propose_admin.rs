@@ -0,0 +1,28 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, state::StakeConfig};
+
+#[derive(Accounts)]
+pub struct ProposeAdmin<'info>{
+
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+}
+
+impl <'info>ProposeAdmin<'info> {
+
+    // Nothing changes hands until `new_admin` accepts, proposing again replaces the pending key
+    pub fn propose_admin(&mut self, new_admin: Pubkey)->Result<()>{
+
+        self.config.pending_admin = Some(new_admin);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,85 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, events::PointsRedeemed, rewards::expire_points, state::{Redemption, RedemptionReceipt, StakeConfig, UserAccount}};
+
+#[derive(Accounts)]
+pub struct Redeem<'info>{
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"redemption".as_ref(), config.key().as_ref(), redemption.redemption_id.to_le_bytes().as_ref()],
+        bump = redemption.bump,
+    )]
+    pub redemption: Account<'info, Redemption>,
+
+    // `init` makes a second redeem by the same user fail
+    #[account(
+        init,
+        payer = user,
+        seeds = [b"receipt".as_ref(), redemption.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = RedemptionReceipt::INIT_SPACE,
+    )]
+    pub receipt: Account<'info, RedemptionReceipt>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info>Redeem<'info> {
+
+    pub fn redeem(&mut self, bumps: &RedeemBumps)->Result<()>{
+
+        require!(self.redemption.redeemed < self.redemption.max_redemptions, StakeError::RedemptionSoldOut);
+
+        let clock = Clock::get()?;
+        self.user_account.apply_decay(&self.config, clock.unix_timestamp)?;
+        expire_points(&self.config, &mut self.user_account, clock.unix_timestamp)?;
+        let points_before = self.user_account.points;
+
+        self.user_account.points = self.user_account.points
+            .checked_sub(self.redemption.cost_points as u64)
+            .ok_or(StakeError::InsufficientPoints)?;
+
+        self.redemption.redeemed = self.redemption.redeemed
+            .checked_add(1)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+
+        self.receipt.set_inner(RedemptionReceipt {
+            redemption: self.redemption.key(),
+            user: self.user.key(),
+            redeemed_at: clock.unix_timestamp,
+            bump: bumps.receipt,
+        });
+
+        self.user_account.restart_expiry_if_changed(points_before, clock.unix_timestamp);
+
+        emit!(PointsRedeemed {
+            config: self.config.key(),
+            user: self.user.key(),
+            redemption: self.redemption.key(),
+            cost_points: self.redemption.cost_points,
+            remaining_points: self.user_account.points,
+            redeemed_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
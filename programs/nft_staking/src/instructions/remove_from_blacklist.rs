@@ -0,0 +1,44 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, events::BlacklistUpdated, state::{Blacklist, StakeConfig}};
+
+#[derive(Accounts)]
+pub struct RemoveFromBlacklist<'info>{
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"blacklist".as_ref(), config.key().as_ref(), blacklist.wallet.as_ref()],
+        bump = blacklist.bump,
+    )]
+    pub blacklist: Account<'info, Blacklist>,
+}
+
+impl <'info>RemoveFromBlacklist<'info> {
+
+    pub fn remove_from_blacklist(&mut self)->Result<()>{
+
+        let clock = Clock::get()?;
+
+        emit!(BlacklistUpdated {
+            config: self.config.key(),
+            admin: self.admin.key(),
+            wallet: self.blacklist.wallet,
+            blacklisted: false,
+            updated_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
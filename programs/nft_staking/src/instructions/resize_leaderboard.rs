@@ -0,0 +1,34 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, state::{Leaderboard, StakeConfig}};
+
+#[derive(Accounts)]
+pub struct ResizeLeaderboard<'info>{
+
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"leaderboard".as_ref(), config.key().as_ref()],
+        bump = leaderboard.load()?.bump,
+    )]
+    pub leaderboard: AccountLoader<'info, Leaderboard>,
+}
+
+impl <'info>ResizeLeaderboard<'info> {
+
+    // Shrinking drops the lowest entries, growing back doesn't restore them
+    pub fn resize_leaderboard(&mut self, capacity: u32)->Result<()>{
+
+        self.leaderboard.load_mut()?.resize(capacity)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,45 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, events::ClaimDelegateSet, state::{StakeConfig, UserAccount}};
+
+#[derive(Accounts)]
+pub struct RevokeClaimDelegate<'info>{
+
+    // Only the user, a delegate can't revoke or replace itself
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+}
+
+impl <'info> RevokeClaimDelegate<'info> {
+
+    // Same as set_claim_delegate(None), but fails when there's nothing to revoke
+    pub fn revoke_claim_delegate(&mut self)->Result<()>{
+
+        self.user_account.claim_delegate.take().ok_or(StakeError::NoClaimDelegate)?;
+
+        let clock = Clock::get()?;
+
+        emit!(ClaimDelegateSet {
+            config: self.config.key(),
+            user: self.user.key(),
+            delegate: None,
+            set_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,44 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, events::ClaimDelegateSet, state::{StakeConfig, UserAccount}};
+
+#[derive(Accounts)]
+pub struct SetClaimDelegate<'info>{
+
+    pub user: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+}
+
+impl <'info> SetClaimDelegate<'info> {
+
+    // `None` revokes the current delegate. A delegate can only trigger claims, never redirect them.
+    pub fn set_claim_delegate(&mut self, delegate: Option<Pubkey>)->Result<()>{
+
+        self.user_account.claim_delegate = delegate;
+
+        let clock = Clock::get()?;
+
+        emit!(ClaimDelegateSet {
+            config: self.config.key(),
+            user: self.user.key(),
+            delegate,
+            set_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use anchor_lang::{prelude::*, system_program::{transfer, Transfer}};
+use anchor_spl::token_interface::Mint;
+
+use crate::{errors::StakeError, state::{CollectionConfig, StakeConfig}};
+
+#[derive(Accounts)]
+pub struct SetFullSetBonus<'info>{
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    pub collection_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    /// CHECK: may still be in the legacy layout, which `Account<CollectionConfig>` can't decode, so it's checked by hand
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"collection".as_ref(), config.key().as_ref(), collection_mint.key().as_ref()],
+        bump,
+    )]
+    pub collection_config: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info> SetFullSetBonus<'info> {
+
+    // Also migrates a collection from before the full-set bonus, the admin tops up the extra rent.
+    // Sets already held keep the bonus they were completed with, `collection_size` 0 turns it off
+    pub fn set_full_set_bonus(&mut self, collection_size: u16, full_set_bonus_bps: u16)->Result<()>{
+        // Only set collections have their pieces counted per user
+        require!(
+            collection_size == 0 || (
+                self.config.set_piece(&self.collection_mint.key()).is_some()
+                && collection_size <= self.config.max_stake as u16
+            ),
+            StakeError::InvalidFullSet
+        );
+
+        let info = self.collection_config.to_account_info();
+
+        let mut collection_config = if info.data_len() == CollectionConfig::V1_SPACE {
+            let collection_config = CollectionConfig::try_from_legacy(&info.try_borrow_data()?)?;
+
+            let rent = Rent::get()?
+                .minimum_balance(CollectionConfig::INIT_SPACE)
+                .saturating_sub(info.lamports());
+
+            if rent > 0 {
+                let cpi_accounts = Transfer{
+                    from: self.admin.to_account_info(),
+                    to: info.clone(),
+                };
+
+                let cpi_ctx = CpiContext::new(self.system_program.to_account_info(), cpi_accounts);
+
+                transfer(cpi_ctx, rent)?;
+            }
+
+            info.realloc(CollectionConfig::INIT_SPACE, false)?;
+            collection_config
+        } else {
+            CollectionConfig::try_deserialize(&mut &info.try_borrow_data()?[..])?
+        };
+
+        collection_config.collection_size = collection_size;
+        collection_config.full_set_bonus_bps = full_set_bonus_bps;
+        collection_config.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+
+        Ok(())
+    }
+}
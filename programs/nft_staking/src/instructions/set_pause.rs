@@ -0,0 +1,41 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::{PAUSE_CLAIM, PAUSE_STAKE}, errors::StakeError, events::PauseToggled, state::StakeConfig};
+
+#[derive(Accounts)]
+pub struct SetPause<'info>{
+
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+}
+
+impl <'info>SetPause<'info> {
+
+    // `pause_flags` replaces the current flags, pass 0 to unpause everything.
+    // Unstake and emergency unstake never check them so users can always exit.
+    pub fn set_pause(&mut self, pause_flags: u8)->Result<()>{
+
+        require!(pause_flags & !(PAUSE_STAKE | PAUSE_CLAIM) == 0, StakeError::InvalidPauseFlags);
+
+        self.config.pause_flags = pause_flags;
+
+        let clock = Clock::get()?;
+
+        emit!(PauseToggled {
+            config: self.config.key(),
+            admin: self.admin.key(),
+            pause_flags,
+            toggled_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
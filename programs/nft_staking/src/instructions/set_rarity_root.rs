@@ -0,0 +1,43 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, state::{RarityMap, StakeConfig}};
+
+#[derive(Accounts)]
+pub struct SetRarityRoot<'info>{
+
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        init_if_needed,
+        payer = admin,
+        seeds = [b"rarity".as_ref(), config.key().as_ref()],
+        bump,
+        space = RarityMap::INIT_SPACE,
+    )]
+    pub rarity_map: Account<'info, RarityMap>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info>SetRarityRoot<'info> {
+
+    // NFTs already staked keep the multiplier they were staked with
+    pub fn set_rarity_root(&mut self, root: [u8; 32], bumps: &SetRarityRootBumps)->Result<()>{
+
+        self.rarity_map.set_inner(RarityMap {
+            config: self.config.key(),
+            root,
+            bump: bumps.rarity_map,
+        });
+
+        Ok(())
+    }
+}
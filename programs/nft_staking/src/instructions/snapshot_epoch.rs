@@ -0,0 +1,54 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, events::EpochSnapshotTaken, state::{EpochHistory, EpochSnapshot, StakeConfig}};
+
+#[derive(Accounts)]
+pub struct SnapshotEpoch<'info>{
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"epoch_history".as_ref(), config.key().as_ref()],
+        bump = epoch_history.load()?.bump,
+    )]
+    pub epoch_history: AccountLoader<'info, EpochHistory>,
+}
+
+impl <'info> SnapshotEpoch<'info> {
+
+    // Permissionless, records `total_staked` once per epoch. Epochs nobody snapshotted are skipped,
+    // the next call records the epoch it lands in
+    pub fn snapshot_epoch(&mut self)->Result<()>{
+        let clock = Clock::get()?;
+        let mut epoch_history = self.epoch_history.load_mut()?;
+
+        let epoch = epoch_history
+            .epoch_at(self.config.epoch_length_secs, clock.unix_timestamp)
+            .ok_or(StakeError::EpochNotElapsed)?;
+        require!(epoch > self.config.current_epoch, StakeError::EpochNotElapsed);
+
+        epoch_history.record(EpochSnapshot {
+            epoch,
+            timestamp: clock.unix_timestamp,
+            total_staked: self.config.total_staked,
+            padding: [0; 4],
+        });
+        self.config.current_epoch = epoch;
+
+        emit!(EpochSnapshotTaken {
+            config: self.config.key(),
+            epoch,
+            total_staked: self.config.total_staked,
+            timestamp: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
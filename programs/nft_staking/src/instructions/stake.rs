@@ -1,7 +1,7 @@
-use anchor_lang::prelude::*;
-use anchor_spl::{metadata::{mpl_token_metadata::instructions::{FreezeDelegatedAccountCpi, FreezeDelegatedAccountCpiAccounts}, MasterEditionAccount, Metadata, MetadataAccount}, token::{approve, Approve, Mint, Token, TokenAccount}};
+use anchor_lang::{prelude::*, solana_program::sysvar};
+use anchor_spl::{associated_token::AssociatedToken, metadata::{MasterEditionAccount, Metadata, MetadataAccount}, token_2022::Token2022, token_interface::{Mint, TokenAccount, TokenInterface}};
 
-use crate::{errors::StakeError, state::{StakeAccount, StakeConfig, UserAccount}};
+use crate::{constants::{BPS_DENOMINATOR, PAUSE_STAKE}, errors::StakeError, events::NftStaked, nft::{has_verified_creator, token_group_of, GroupMemberNftAccounts, NftAccounts, PnftAccounts}, receipt::ReceiptAccounts, rewards::expire_points, state::{AllowlistEntry, Blacklist, CollectionConfig, LockupKind, MintRecord, RarityMap, RarityProof, StakeAccount, StakeConfig, UserAccount, UserPositions}};
 
 #[derive(Accounts)]
 pub struct Stake<'info>{
@@ -9,36 +9,50 @@ pub struct Stake<'info>{
     #[account(mut)]
     pub user: Signer<'info>,
 
-    pub mint: Account<'info, Mint>,
-    pub collection_mint: Account<'info, Mint>,
+    // Metaplex NFTs are further pinned by their master edition, group members in `token_group_of`
+    #[account(constraint = mint.decimals == 0 && mint.supply == 1 @ StakeError::NotAnNft)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    pub collection_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         associated_token::mint = mint,
         associated_token::authority = user,
+        associated_token::token_program = token_program,
+        constraint = mint_ata.amount == 1 @ StakeError::NotAnNft,
     )]
-    pub mint_ata: Account<'info, TokenAccount>,
+    pub mint_ata: InterfaceAccount<'info, TokenAccount>,
 
 
+    /// Metaplex NFTs only, Token-2022 NFTs without it are checked by token group instead
     #[account(
+        mut,
         seeds = [b"metadata", metadata_program.key().as_ref(), mint.key().as_ref()],
         seeds::program = metadata_program.key(),
         bump,
-        constraint = metadata.collection.as_ref().unwrap().key.as_ref() == collection_mint.key().as_ref(),
-        constraint = metadata .collection.as_ref().unwrap().verified == true,
+        constraint = metadata.collection.as_ref().is_some_and(|collection| collection.key == collection_config.collection_mint) @ StakeError::InvalidCollection,
+        constraint = metadata.collection.as_ref().is_some_and(|collection| collection.verified) @ StakeError::CollectionNotVerified,
+        constraint = has_verified_creator(metadata.creators.as_deref(), &config.creator) @ StakeError::UnverifiedNft,
     )]
-    pub metadata: Account<'info, MetadataAccount>,
+    pub metadata: Option<Account<'info, MetadataAccount>>,
 
+    /// Metaplex NFTs only
     #[account(
         seeds = [b"metadata", metadata_program.key().as_ref(), mint.key().as_ref(), b"edition"],
         seeds::program = metadata_program.key(),
         bump,
     )]
-    pub master_edition: Account<'info, MasterEditionAccount>,
+    pub master_edition: Option<Account<'info, MasterEditionAccount>>,
+
+    /// Token-2022 group member NFTs in FreezeDelegated pools only: the program's freeze
+    /// authority PDA, checked in `GroupMemberNftAccounts`
+    pub freeze_authority: Option<UncheckedAccount<'info>>,
 
 
+    // init_if_needed so staking a live stake again fails with AlreadyStaked rather than
+    // the system program's opaque "already in use"
     #[account(
-        init,
+        init_if_needed,
         payer = user,
         seeds = [b"stake", config.key().as_ref(), mint.key().as_ref()],
         bump,
@@ -46,78 +60,322 @@ pub struct Stake<'info>{
     )]
     pub stake_account: Account<'info, StakeAccount>,
 
+    // Created on the mint's first stake or unstake since records existed, never closed
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"mint_record", config.key().as_ref(), mint.key().as_ref()],
+        bump,
+        space = MintRecord::INIT_SPACE,
+    )]
+    pub mint_record: Account<'info, MintRecord>,
+
     #[account(
-        seeds = [b"config"],
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
         bump = config.bump,
     )]
     pub config: Account<'info, StakeConfig>,
 
     #[account(
-        mut,
-        seeds = [b"user", user.key().as_ref()],
-        bump = user_account.bump,
+        seeds = [b"collection", config.key().as_ref(), collection_mint.key().as_ref()],
+        bump = collection_config.bump,
+    )]
+    pub collection_config: Account<'info, CollectionConfig>,
+
+    // Created here on a first stake made without `initialize`. Seeds already tie an existing
+    // account to `user`, its stored owner is checked on top
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = UserAccount::INIT_SPACE,
+        constraint = user_account.is_new() || user_account.is_current() @ StakeError::AccountNeedsMigration,
+        constraint = user_account.is_new() || user_account.owner == user.key() @ StakeError::NotStakeOwner,
     )]
     pub user_account: Account<'info, UserAccount>,
 
+    // Created here if `initialize` came before positions existed
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"positions", config.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = UserPositions::INIT_SPACE,
+    )]
+    pub user_positions: Account<'info, UserPositions>,
+
+    /// CHECK: `user`'s blacklist PDA, only holds data while the wallet is blacklisted
+    #[account(
+        seeds = [b"blacklist".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub blacklist: UncheckedAccount<'info>,
+
+    /// CHECK: `user`'s allowlist PDA, required while the pool is gated
+    #[account(
+        seeds = [b"allowlist".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub allowlist_entry: Option<UncheckedAccount<'info>>,
+
+    /// pNFT only: token record PDA of `mint_ata`
+    #[account(mut)]
+    pub token_record: Option<UncheckedAccount<'info>>,
+
+    /// pNFT only
+    #[account(address = sysvar::instructions::ID)]
+    pub sysvar_instructions: Option<UncheckedAccount<'info>>,
 
-    pub token_program: Program<'info, Token>,
+    /// pNFT only, when the NFT has a rule set
+    pub authorization_rules_program: Option<UncheckedAccount<'info>>,
+
+    /// pNFT only, when the NFT has a rule set
+    pub authorization_rules: Option<UncheckedAccount<'info>>,
+
+    /// VaultTransfer mode only: the stake account's ATA the NFT moves into
+    #[account(
+        init,
+        payer = user,
+        associated_token::mint = mint,
+        associated_token::authority = stake_account,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub associated_token_program: Option<Program<'info, AssociatedToken>>,
+
+    /// Pools with a `receipt_mint` only
+    #[account(
+        mut,
+        address = config.receipt_mint.ok_or(StakeError::MissingReceipt)? @ StakeError::InvalidReceiptMint,
+    )]
+    pub receipt_mint: Option<InterfaceAccount<'info, Mint>>,
+
+    /// CHECK: pools with a `receipt_mint` only: `user`'s receipt ATA, created if it doesn't exist
+    /// yet and checked in `ReceiptAccounts`
+    #[account(mut)]
+    pub receipt_ata: Option<UncheckedAccount<'info>>,
+
+    /// Pools with a `receipt_mint` only
+    pub receipt_token_program: Option<Program<'info, Token2022>>,
+
+    /// Rare NFTs only: the pool's rarity map their `RarityProof` is checked against
+    #[account(
+        seeds = [b"rarity".as_ref(), config.key().as_ref()],
+        bump = rarity_map.bump,
+    )]
+    pub rarity_map: Option<Account<'info, RarityMap>>,
+
+    /// First stake only: the wallet that referred this user
+    pub referrer: Option<UncheckedAccount<'info>>,
+
+    /// First stake only: `referrer`'s user account in this pool
+    #[account(mut)]
+    pub referrer_account: Option<Account<'info, UserAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
     pub metadata_program: Program<'info, Metadata>,
     pub system_program: Program<'info, System>,
 }
 
 impl <'info> Stake<'info>{
     
-    pub fn stake(&mut self, bumps: &StakeBumps)->Result<()>{
-        require!(self.user_account.amount_staked <= self.config.max_stake, StakeError::MaxStakeReached);
+    // `rarity` proves the NFT's rarity multiplier, common NFTs leave it out and stake at 1x
+    pub fn stake(&mut self, lockup: LockupKind, rarity: Option<RarityProof>, bumps: &StakeBumps)->Result<()>{
+        require!(!self.config.is_paused(PAUSE_STAKE), StakeError::ProgramPaused);
+        require!(!Blacklist::is_set(&self.blacklist), StakeError::WalletBlacklisted);
+        AllowlistEntry::require_allowed(&self.config, self.allowlist_entry.as_deref())?;
 
-        let cpi_program = self.token_program.to_account_info();
+        if self.user_account.is_new() {
+            // As `initialize` would, a referrer passed for the first-stake bonus is stored too
+            let referrer = self.referrer.as_ref().map(|referrer| referrer.key());
+            require!(referrer != Some(self.user.key()), StakeError::SelfReferral);
+            self.user_account.set_inner(UserAccount::new(self.user.key(), referrer, bumps.user_account));
+        }
 
-        let cpi_accounts = Approve{
-            to: self.mint_ata.to_account_info(),
-            delegate: self.stake_account.to_account_info(),
-            authority: self.user.to_account_info(),
-        };
+        // A freshly created stake account is still zeroed, an active one already has its owner
+        require_keys_eq!(self.stake_account.owner, Pubkey::default(), StakeError::AlreadyStaked);
+        require!(self.user_account.amount_staked < self.config.max_stake, StakeError::MaxStakeReached);
+
+        let clock = Clock::get()?;
+        self.user_account.apply_decay(&self.config, clock.unix_timestamp)?;
+        self.user_account.record_window_stakes(&self.config, 1, clock.unix_timestamp)?;
+
+        self.mint_record.init_if_new(self.config.key(), self.mint.key(), bumps.mint_record);
+        self.mint_record.record_stake(&self.config, clock.unix_timestamp)?;
 
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        self.user_positions.init_if_new(self.config.key(), self.user.key(), bumps.user_positions);
+        self.user_positions.add(self.mint.key())?;
 
-        approve(cpi_ctx, 1)?;
+        let rarity_bps = RarityMap::multiplier_bps(self.rarity_map.as_deref(), &self.mint.key(), rarity.as_ref())?;
+        let base_multiplier = (self.collection_config.points_multiplier as u64)
+            .checked_mul(rarity_bps as u64)
+            .ok_or(StakeError::ArithmeticOverflow)?
+            / BPS_DENOMINATOR as u64;
+        let base_multiplier = u16::try_from(base_multiplier).map_err(|_| error!(StakeError::ArithmeticOverflow))?;
 
-        let delegate = &self.stake_account.to_account_info();
-        let token_account = &self.mint_ata.to_account_info();
-        let edition = &self.master_edition.to_account_info();
-        let mint = &self.mint.to_account_info();
-        let token_program = &self.token_program.to_account_info();
-        let metadata = &self.metadata.to_account_info();
+        let (lockup_secs, points_multiplier) = self.config.lockup_terms(lockup, base_multiplier)?;
+        self.config.add_staked(1)?;
 
         let seeds = &[
-            b"state",
+            b"stake",
             self.config.to_account_info().key.as_ref(),
             self.mint.to_account_info().key.as_ref(),
-            &[self.stake_account.bump]
+            &[bumps.stake_account]
         ];
 
         let signer_seeds = &[&seeds[..]];
 
-        FreezeDelegatedAccountCpi::new(
-            metadata, 
-            FreezeDelegatedAccountCpiAccounts{
-                delegate,
-                token_account,
-                edition,
-                mint,
-                token_program,
+        match (&self.metadata, &self.master_edition) {
+            (Some(metadata), Some(master_edition)) => NftAccounts{
+                owner: self.user.as_ref(),
+                delegate: self.stake_account.as_ref(),
+                mint: self.mint.as_ref(),
+                token_account: self.mint_ata.as_ref(),
+                metadata: metadata.as_ref(),
+                edition: master_edition.as_ref(),
+                token_program: self.token_program.as_ref(),
+                metadata_program: self.metadata_program.as_ref(),
+                system_program: self.system_program.as_ref(),
+                pnft: PnftAccounts::from_optional(
+                    self.token_record.as_deref(),
+                    self.sysvar_instructions.as_deref(),
+                    self.authorization_rules_program.as_deref(),
+                    self.authorization_rules.as_deref(),
+                ),
+                vault: self.vault.as_ref().map(|vault| vault.as_ref()),
+            }.lock(metadata.token_standard.as_ref(), self.config.custody_mode, signer_seeds)?,
+            (None, None) => {
+                require_keys_eq!(token_group_of(self.mint.as_ref())?, self.collection_config.collection_mint, StakeError::NotGroupMember);
+
+                GroupMemberNftAccounts{
+                    owner: self.user.as_ref(),
+                    delegate: self.stake_account.as_ref(),
+                    mint: self.mint.as_ref(),
+                    token_account: self.mint_ata.as_ref(),
+                    token_program: self.token_program.as_ref(),
+                    freeze_authority: self.freeze_authority.as_deref(),
+                    vault: self.vault.as_ref().map(|vault| vault.as_ref()),
+                }.lock(self.config.custody_mode)?;
             }
-        ).invoke_signed(signer_seeds)?;
+            _ => return err!(StakeError::MissingMetadataAccounts),
+        }
 
         self.stake_account.set_inner(StakeAccount { 
+            config: self.config.key(),
             owner: self.user.key(), 
             mint: self.mint.key(), 
-            staked_at: Clock::get()?.unix_timestamp, 
+            staked_at: clock.unix_timestamp, 
+            unlocked_at: self.config.unlocked_at(clock.unix_timestamp, lockup_secs)?,
+            lockup,
+            lockup_secs,
+            points_multiplier,
+            custody_mode: self.config.custody_mode,
+            has_receipt: self.config.receipt_mint.is_some(),
+            position_mint: None,
+            set_piece: self.config.set_piece(&self.collection_config.collection_mint),
+            last_accrued_at: clock.unix_timestamp,
+            accrued_points: 0,
+            opened_at: clock.unix_timestamp,
             bump: bumps.stake_account, 
         });
 
-        self.user_account.amount_staked += 1;
+        self.issue_receipt()?;
+
+        self.user_account.amount_staked = self.user_account.amount_staked
+            .checked_add(1)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        self.user_account.add_set_piece(&self.config, self.stake_account.set_piece, clock.unix_timestamp);
+        self.user_positions.start_full_set(self.stake_account.set_piece, &self.user_account.set_pieces_staked, &self.collection_config, clock.unix_timestamp);
+
+        self.credit_referral()?;
+        self.user_account.record_stake(&mut self.config)?;
+
+        emit!(NftStaked {
+            config: self.config.key(),
+            user: self.user.key(),
+            mint: self.mint.key(),
+            staked_at: clock.unix_timestamp,
+            slot: clock.slot,
+            total_staked: self.user_account.amount_staked,
+        });
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    // Mints the user a soulbound receipt for this stake in pools that issue them
+    fn issue_receipt(&self)->Result<()>{
+        if self.config.receipt_mint.is_none() {
+            return Ok(());
+        }
+
+        let (Some(receipt_mint), Some(receipt_ata), Some(receipt_token_program), Some(associated_token_program)) = (
+            &self.receipt_mint,
+            &self.receipt_ata,
+            &self.receipt_token_program,
+            &self.associated_token_program,
+        ) else {
+            return err!(StakeError::MissingReceipt);
+        };
+
+        let pool_id = self.config.pool_id.to_le_bytes();
+        let seeds = &[
+            b"config".as_ref(),
+            pool_id.as_ref(),
+            &[self.config.bump]
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        ReceiptAccounts{
+            owner: self.user.as_ref(),
+            mint: receipt_mint.as_ref(),
+            token_account: receipt_ata.as_ref(),
+            token_program: receipt_token_program.as_ref(),
+        }.issue(
+            self.user.as_ref(),
+            self.config.as_ref(),
+            associated_token_program.as_ref(),
+            self.system_program.as_ref(),
+            signer_seeds,
+        )
+    }
+
+    // Pays `referral_bonus` to both the user and their referrer, once, on the user's first stake
+    fn credit_referral(&mut self)->Result<()>{
+        let (referrer, referrer_account) = match (&self.referrer, &mut self.referrer_account) {
+            (None, None) => return Ok(()),
+            (Some(referrer), Some(referrer_account)) => (referrer.key(), referrer_account),
+            _ => return err!(StakeError::InvalidReferrer),
+        };
+
+        require_keys_neq!(referrer, self.user.key(), StakeError::SelfReferral);
+        require!(!self.user_account.has_staked_before, StakeError::ReferralAlreadyUsed);
+        // Only the referrer stored at initialization, the one `unstake` pays its cut to, earns the bonus
+        require!(self.user_account.referrer == Some(referrer), StakeError::InvalidReferrer);
+
+        require!(
+            UserAccount::is_address_of(&referrer_account.key(), &self.config.key(), &referrer, referrer_account.bump),
+            StakeError::InvalidReferrer
+        );
+
+        let bonus = self.config.referral_bonus as u64;
+        let now = Clock::get()?.unix_timestamp;
+        referrer_account.apply_decay(&self.config, now)?;
+        expire_points(&self.config, referrer_account, now)?;
+        expire_points(&self.config, &mut self.user_account, now)?;
+        let (user_points_before, referrer_points_before) = (self.user_account.points, referrer_account.points);
+
+        self.user_account.points = self.user_account.points
+            .checked_add(bonus)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        referrer_account.points = referrer_account.points
+            .checked_add(bonus)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        self.user_account.restart_expiry_if_changed(user_points_before, now);
+        referrer_account.restart_expiry_if_changed(referrer_points_before, now);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,307 @@
+use anchor_lang::{prelude::*, system_program::{allocate, assign, create_account, transfer, Allocate, Assign, CreateAccount, Transfer}};
+use anchor_spl::{associated_token::get_associated_token_address_with_program_id, metadata::{mpl_token_metadata::types::TokenStandard, MasterEditionAccount, Metadata, MetadataAccount}, token_interface::{Mint, TokenAccount, TokenInterface}};
+
+use crate::{constants::{MAX_BATCH_SIZE, PAUSE_STAKE, STAKE_BATCH_ACCOUNTS}, errors::StakeError, events::NftStaked, nft::{has_verified_creator, NftAccounts}, state::{AllowlistEntry, Blacklist, CollectionConfig, CustodyMode, LockupKind, MintRecord, StakeAccount, StakeConfig, UserAccount, UserPositions}};
+
+#[derive(Accounts)]
+pub struct StakeBatch<'info>{
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    pub collection_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        seeds = [b"collection", config.key().as_ref(), collection_mint.key().as_ref()],
+        bump = collection_config.bump,
+    )]
+    pub collection_config: Account<'info, CollectionConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    // Created here if `initialize` came before positions existed
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"positions", config.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = UserPositions::INIT_SPACE,
+    )]
+    pub user_positions: Account<'info, UserPositions>,
+
+    /// CHECK: `user`'s blacklist PDA, only holds data while the wallet is blacklisted
+    #[account(
+        seeds = [b"blacklist".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub blacklist: UncheckedAccount<'info>,
+
+    /// CHECK: `user`'s allowlist PDA, required while the pool is gated
+    #[account(
+        seeds = [b"allowlist".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub allowlist_entry: Option<UncheckedAccount<'info>>,
+
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub metadata_program: Program<'info, Metadata>,
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info> StakeBatch<'info>{
+
+    // remaining_accounts holds one [mint, mint_ata, metadata, master_edition, stake_account, mint_record] group per mint
+    pub fn stake_batch(&mut self, mints: Vec<Pubkey>, remaining_accounts: &'info [AccountInfo<'info>], bumps: &StakeBatchBumps)->Result<()>{
+        require!(!self.config.is_paused(PAUSE_STAKE), StakeError::ProgramPaused);
+        require!(!Blacklist::is_set(&self.blacklist), StakeError::WalletBlacklisted);
+        AllowlistEntry::require_allowed(&self.config, self.allowlist_entry.as_deref())?;
+        require!(!mints.is_empty() && mints.len() <= MAX_BATCH_SIZE, StakeError::InvalidBatchSize);
+        // Batches don't carry vault accounts, vault pools stake one NFT at a time with `stake`
+        require!(self.config.custody_mode == CustodyMode::FreezeDelegated, StakeError::CustodyModeMismatch);
+        require!(
+            remaining_accounts.len() == mints.len() * STAKE_BATCH_ACCOUNTS,
+            StakeError::InvalidRemainingAccounts
+        );
+
+        let total_staked = self.user_account.amount_staked
+            .checked_add(mints.len() as u8)
+            .ok_or(StakeError::MaxStakeReached)?;
+        require!(total_staked <= self.config.max_stake, StakeError::MaxStakeReached);
+        self.config.add_staked(mints.len() as u32)?;
+
+        let clock = Clock::get()?;
+        self.user_account.apply_decay(&self.config, clock.unix_timestamp)?;
+        // Every NFT in the batch counts against the window, batching doesn't get around the limit
+        self.user_account.record_window_stakes(&self.config, mints.len() as u16, clock.unix_timestamp)?;
+
+        self.user_positions.init_if_new(self.config.key(), self.user.key(), bumps.user_positions);
+
+        for (mint, accounts) in mints.iter().zip(remaining_accounts.chunks(STAKE_BATCH_ACCOUNTS)) {
+            self.stake_one(mint, accounts, clock.unix_timestamp)?;
+            self.user_positions.add(*mint)?;
+        }
+
+        // Every NFT in a batch is from `collection_mint`
+        let set_piece = self.config.set_piece(&self.collection_config.collection_mint);
+        for _ in 0..mints.len() {
+            self.user_account.add_set_piece(&self.config, set_piece, clock.unix_timestamp);
+        }
+        self.user_positions.start_full_set(set_piece, &self.user_account.set_pieces_staked, &self.collection_config, clock.unix_timestamp);
+
+        self.user_account.amount_staked = total_staked;
+        // Batches take no referrer, a first stake made here forgoes the referral bonus
+        self.user_account.record_stake(&mut self.config)?;
+
+        for mint in mints {
+            emit!(NftStaked {
+                config: self.config.key(),
+                user: self.user.key(),
+                mint,
+                staked_at: clock.unix_timestamp,
+                slot: clock.slot,
+                total_staked,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn stake_one(&self, mint_key: &Pubkey, accounts: &'info [AccountInfo<'info>], staked_at: i64)->Result<()>{
+        let [mint_info, mint_ata_info, metadata_info, edition_info, stake_info, mint_record_info] = accounts else {
+            return err!(StakeError::InvalidRemainingAccounts);
+        };
+
+        // Anchor constraints don't run on remaining_accounts, so every account is checked by hand
+        require_keys_eq!(mint_info.key(), *mint_key, StakeError::InvalidRemainingAccounts);
+        require_keys_eq!(*mint_info.owner, self.token_program.key(), StakeError::InvalidRemainingAccounts);
+        let mint = InterfaceAccount::<Mint>::try_from(mint_info)?;
+        require!(mint.decimals == 0 && mint.supply == 1, StakeError::NotAnNft);
+
+        require_keys_eq!(
+            mint_ata_info.key(),
+            get_associated_token_address_with_program_id(&self.user.key(), mint_key, &self.token_program.key()),
+            StakeError::InvalidRemainingAccounts
+        );
+        let mint_ata = InterfaceAccount::<TokenAccount>::try_from(mint_ata_info)?;
+        require!(
+            mint_ata.owner == self.user.key() && mint_ata.mint == *mint_key && mint_ata.amount == 1,
+            StakeError::InvalidRemainingAccounts
+        );
+
+        let metadata_program = self.metadata_program.key();
+
+        let (metadata_key, _) = Pubkey::find_program_address(
+            &[b"metadata", metadata_program.as_ref(), mint_key.as_ref()],
+            &metadata_program,
+        );
+        require_keys_eq!(metadata_info.key(), metadata_key, StakeError::InvalidRemainingAccounts);
+        let metadata = Account::<MetadataAccount>::try_from(metadata_info)?;
+
+        let collection = metadata.collection.as_ref().ok_or(StakeError::InvalidCollection)?;
+        require_keys_eq!(collection.key, self.collection_config.collection_mint, StakeError::InvalidCollection);
+        require!(collection.verified, StakeError::CollectionNotVerified);
+        require!(has_verified_creator(metadata.creators.as_deref(), &self.config.creator), StakeError::UnverifiedNft);
+
+        // pNFTs need a token record per mint, stake them one at a time with `stake`
+        require!(
+            metadata.token_standard != Some(TokenStandard::ProgrammableNonFungible),
+            StakeError::UnsupportedTokenStandard
+        );
+
+        let (edition_key, _) = Pubkey::find_program_address(
+            &[b"metadata", metadata_program.as_ref(), mint_key.as_ref(), b"edition"],
+            &metadata_program,
+        );
+        require_keys_eq!(edition_info.key(), edition_key, StakeError::InvalidRemainingAccounts);
+        let edition = Account::<MasterEditionAccount>::try_from(edition_info)?;
+
+        let config_key = self.config.key();
+        let (stake_key, stake_bump) = Pubkey::find_program_address(
+            &[b"stake", config_key.as_ref(), mint_key.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(stake_info.key(), stake_key, StakeError::InvalidRemainingAccounts);
+        require!(stake_info.data_is_empty(), StakeError::AlreadyStaked);
+
+        self.record_stake(mint_key, mint_record_info, staked_at)?;
+
+        let seeds = &[
+            b"stake",
+            config_key.as_ref(),
+            mint_key.as_ref(),
+            &[stake_bump]
+        ];
+
+        let signer_seeds = &[&seeds[..]];
+
+        create_pda(&self.user, stake_info, StakeAccount::INIT_SPACE, &self.system_program, signer_seeds)?;
+
+        NftAccounts{
+            owner: self.user.as_ref(),
+            delegate: stake_info,
+            mint: mint.as_ref(),
+            token_account: mint_ata.as_ref(),
+            metadata: metadata.as_ref(),
+            edition: edition.as_ref(),
+            token_program: self.token_program.as_ref(),
+            metadata_program: self.metadata_program.as_ref(),
+            system_program: self.system_program.as_ref(),
+            pnft: None,
+            vault: None,
+        }.lock(metadata.token_standard.as_ref(), CustodyMode::FreezeDelegated, signer_seeds)?;
+
+        // Batches take no lockup, every NFT in one is staked Flexible
+        let (lockup_secs, points_multiplier) = self.config.lockup_terms(LockupKind::Flexible, self.collection_config.points_multiplier)?;
+
+        let stake_account = StakeAccount {
+            config: config_key,
+            owner: self.user.key(),
+            mint: *mint_key,
+            staked_at,
+            unlocked_at: self.config.unlocked_at(staked_at, lockup_secs)?,
+            lockup: LockupKind::Flexible,
+            lockup_secs,
+            points_multiplier,
+            custody_mode: CustodyMode::FreezeDelegated,
+            has_receipt: false,
+            position_mint: None,
+            set_piece: self.config.set_piece(&self.collection_config.collection_mint),
+            last_accrued_at: staked_at,
+            accrued_points: 0,
+            opened_at: staked_at,
+            bump: stake_bump,
+        };
+
+        stake_account.try_serialize(&mut &mut stake_info.try_borrow_mut_data()?[..])?;
+
+        Ok(())
+    }
+
+    // `stake`'s init_if_needed mint record, by hand
+    fn record_stake(&self, mint_key: &Pubkey, mint_record_info: &'info AccountInfo<'info>, staked_at: i64)->Result<()>{
+        let config_key = self.config.key();
+        let (record_key, record_bump) = Pubkey::find_program_address(
+            &[b"mint_record", config_key.as_ref(), mint_key.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(mint_record_info.key(), record_key, StakeError::InvalidRemainingAccounts);
+
+        let mut mint_record = if mint_record_info.data_is_empty() {
+            let seeds = &[
+                b"mint_record",
+                config_key.as_ref(),
+                mint_key.as_ref(),
+                &[record_bump]
+            ];
+
+            let signer_seeds = &[&seeds[..]];
+
+            create_pda(&self.user, mint_record_info, MintRecord::INIT_SPACE, &self.system_program, signer_seeds)?;
+
+            MintRecord {
+                config: config_key,
+                mint: *mint_key,
+                times_staked: 0,
+                last_unstaked_at: 0,
+                bump: record_bump,
+            }
+        } else {
+            Account::<MintRecord>::try_from(mint_record_info)?.into_inner()
+        };
+
+        mint_record.record_stake(&self.config, staked_at)?;
+        mint_record.try_serialize(&mut &mut mint_record_info.try_borrow_mut_data()?[..])?;
+
+        Ok(())
+    }
+}
+
+// Anchor's `init` by hand. `create_account` fails once the PDA holds any lamports, which anyone can
+// send it, so an already funded one is topped up to rent exemption, then allocated and assigned
+fn create_pda<'info>(
+    payer: &Signer<'info>,
+    account: &AccountInfo<'info>,
+    space: usize,
+    system_program: &Program<'info, System>,
+    signer_seeds: &[&[&[u8]]],
+)->Result<()>{
+    let rent = Rent::get()?.minimum_balance(space);
+    let lamports = account.lamports();
+
+    if lamports == 0 {
+        let cpi_accounts = CreateAccount{
+            from: payer.to_account_info(),
+            to: account.clone(),
+        };
+        let cpi_ctx = CpiContext::new_with_signer(system_program.to_account_info(), cpi_accounts, signer_seeds);
+        return create_account(cpi_ctx, rent, space as u64, &crate::ID);
+    }
+
+    if lamports < rent {
+        let cpi_accounts = Transfer{
+            from: payer.to_account_info(),
+            to: account.clone(),
+        };
+        transfer(CpiContext::new(system_program.to_account_info(), cpi_accounts), rent - lamports)?;
+    }
+
+    let cpi_ctx = CpiContext::new_with_signer(system_program.to_account_info(), Allocate{ account_to_allocate: account.clone() }, signer_seeds);
+    allocate(cpi_ctx, space as u64)?;
+
+    let cpi_ctx = CpiContext::new_with_signer(system_program.to_account_info(), Assign{ account_to_assign: account.clone() }, signer_seeds);
+    assign(cpi_ctx, &crate::ID)
+}
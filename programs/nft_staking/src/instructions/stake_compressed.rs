@@ -0,0 +1,173 @@
+use anchor_lang::prelude::*;
+
+use crate::{compressed::{asset_id, leaf_has_verified_creator, BubblegumAccounts, CompressedLeaf, LeafCreator}, constants::{BPS_DENOMINATOR, PAUSE_STAKE}, errors::StakeError, events::NftStaked, state::{AllowlistEntry, Blacklist, CustodyMode, LockupKind, MintRecord, StakeAccount, StakeConfig, UserAccount, UserPositions}};
+
+#[derive(Accounts)]
+#[instruction(leaf: CompressedLeaf)]
+pub struct StakeCompressed<'info>{
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    /// CHECK: hashed into the leaf, Bubblegum checks it against the tree
+    pub leaf_delegate: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    // Created here if `initialize` came before positions existed
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"positions", config.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = UserPositions::INIT_SPACE,
+    )]
+    pub user_positions: Account<'info, UserPositions>,
+
+    /// CHECK: `user`'s blacklist PDA, only holds data while the wallet is blacklisted
+    #[account(
+        seeds = [b"blacklist".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub blacklist: UncheckedAccount<'info>,
+
+    /// CHECK: `user`'s allowlist PDA, required while the pool is gated
+    #[account(
+        seeds = [b"allowlist".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub allowlist_entry: Option<UncheckedAccount<'info>>,
+
+    // Keyed by the leaf's asset id where other stakes use the mint
+    #[account(
+        init,
+        payer = user,
+        space = StakeAccount::INIT_SPACE,
+        seeds = [b"stake", config.key().as_ref(), asset_id(&merkle_tree.key(), leaf.nonce).as_ref()],
+        bump,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+
+    // Created on the mint's first stake or unstake since records existed, never closed
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"mint_record", config.key().as_ref(), asset_id(&merkle_tree.key(), leaf.nonce).as_ref()],
+        bump,
+        space = MintRecord::INIT_SPACE,
+    )]
+    pub mint_record: Account<'info, MintRecord>,
+
+    /// CHECK: Bubblegum's tree config PDA of `merkle_tree`, checked by Bubblegum
+    pub tree_config: UncheckedAccount<'info>,
+
+    /// CHECK: checked by the account compression program
+    #[account(mut)]
+    pub merkle_tree: UncheckedAccount<'info>,
+
+    /// CHECK: address checked in `BubblegumAccounts::transfer`
+    pub log_wrapper: UncheckedAccount<'info>,
+
+    /// CHECK: address checked in `BubblegumAccounts::transfer`
+    pub compression_program: UncheckedAccount<'info>,
+
+    /// CHECK: address checked in `BubblegumAccounts::transfer`
+    pub bubblegum_program: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info> StakeCompressed<'info>{
+
+    // remaining_accounts holds the leaf's proof path, minus the nodes the tree's canopy stores.
+    // cNFT creators can be checked against the leaf but its collection can't without the full
+    // metadata, so pools gate them on `config.creator` alone and they earn the base rate, staked Flexible.
+    pub fn stake_compressed(
+        &mut self,
+        leaf: CompressedLeaf,
+        creators: Vec<LeafCreator>,
+        remaining_accounts: &'info [AccountInfo<'info>],
+        bumps: &StakeCompressedBumps,
+    )->Result<()>{
+        require!(!self.config.is_paused(PAUSE_STAKE), StakeError::ProgramPaused);
+        require!(!Blacklist::is_set(&self.blacklist), StakeError::WalletBlacklisted);
+        AllowlistEntry::require_allowed(&self.config, self.allowlist_entry.as_deref())?;
+        require!(self.user_account.amount_staked < self.config.max_stake, StakeError::MaxStakeReached);
+        require!(leaf_has_verified_creator(&leaf, &creators, &self.config.creator), StakeError::UnverifiedNft);
+
+        let clock = Clock::get()?;
+        self.user_account.apply_decay(&self.config, clock.unix_timestamp)?;
+        self.user_account.record_window_stakes(&self.config, 1, clock.unix_timestamp)?;
+        let asset_id = asset_id(&self.merkle_tree.key(), leaf.nonce);
+
+        self.mint_record.init_if_new(self.config.key(), asset_id, bumps.mint_record);
+        self.mint_record.record_stake(&self.config, clock.unix_timestamp)?;
+
+        self.user_positions.init_if_new(self.config.key(), self.user.key(), bumps.user_positions);
+        self.user_positions.add(asset_id)?;
+
+        self.config.add_staked(1)?;
+
+        // Custody of the leaf moves to the stake account, so it can't be sold while staked
+        BubblegumAccounts{
+            tree_config: self.tree_config.as_ref(),
+            merkle_tree: self.merkle_tree.as_ref(),
+            log_wrapper: self.log_wrapper.as_ref(),
+            compression_program: self.compression_program.as_ref(),
+            bubblegum_program: self.bubblegum_program.as_ref(),
+            system_program: self.system_program.as_ref(),
+            proof: remaining_accounts,
+        }.transfer(&leaf, self.user.as_ref(), self.leaf_delegate.as_ref(), self.stake_account.as_ref(), &[])?;
+
+        let (lockup_secs, points_multiplier) = self.config.lockup_terms(LockupKind::Flexible, BPS_DENOMINATOR)?;
+
+        self.stake_account.set_inner(StakeAccount {
+            config: self.config.key(),
+            owner: self.user.key(),
+            mint: asset_id,
+            staked_at: clock.unix_timestamp,
+            unlocked_at: self.config.unlocked_at(clock.unix_timestamp, lockup_secs)?,
+            lockup: LockupKind::Flexible,
+            lockup_secs,
+            points_multiplier,
+            custody_mode: CustodyMode::Compressed,
+            has_receipt: false,
+            position_mint: None,
+            set_piece: None,
+            last_accrued_at: clock.unix_timestamp,
+            accrued_points: 0,
+            opened_at: clock.unix_timestamp,
+            bump: bumps.stake_account,
+        });
+
+        self.user_account.amount_staked = self.user_account.amount_staked
+            .checked_add(1)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        // Like batches, compressed stakes take no referrer
+        self.user_account.record_stake(&mut self.config)?;
+
+        emit!(NftStaked {
+            config: self.config.key(),
+            user: self.user.key(),
+            mint: asset_id,
+            staked_at: clock.unix_timestamp,
+            slot: clock.slot,
+            total_staked: self.user_account.amount_staked,
+        });
+
+        Ok(())
+    }
+}
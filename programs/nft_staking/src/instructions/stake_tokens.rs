@@ -0,0 +1,145 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::{constants::PAUSE_STAKE, errors::StakeError, events::TokensStaked, rewards::expire_points, state::{AllowlistEntry, Blacklist, StakeConfig, TokenStakeAccount, UserAccount}};
+
+#[derive(Accounts)]
+pub struct StakeTokens<'info>{
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        address = config.stake_token_mint.ok_or(StakeError::TokenStakingDisabled)?,
+        mint::token_program = token_program,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = user,
+        token::token_program = token_program,
+    )]
+    pub user_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    /// CHECK: `user`'s blacklist PDA, only holds data while the wallet is blacklisted
+    #[account(
+        seeds = [b"blacklist".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub blacklist: UncheckedAccount<'info>,
+
+    /// CHECK: `user`'s allowlist PDA, required while the pool is gated
+    #[account(
+        seeds = [b"allowlist".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub allowlist_entry: Option<UncheckedAccount<'info>>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = TokenStakeAccount::INIT_SPACE,
+        seeds = [b"token_stake", config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub token_stake_account: Account<'info, TokenStakeAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"token_vault".as_ref(), config.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = config,
+        token::token_program = token_program,
+    )]
+    pub token_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info> StakeTokens<'info> {
+
+    // `amount` is in base units of `config.stake_token_mint`
+    pub fn stake_tokens(&mut self, amount: u64, bumps: &StakeTokensBumps)->Result<()>{
+        require!(!self.config.is_paused(PAUSE_STAKE), StakeError::ProgramPaused);
+        require!(!Blacklist::is_set(&self.blacklist), StakeError::WalletBlacklisted);
+        AllowlistEntry::require_allowed(&self.config, self.allowlist_entry.as_deref())?;
+        require!(amount > 0, StakeError::InvalidAmount);
+
+        let clock = Clock::get()?;
+        self.user_account.apply_decay(&self.config, clock.unix_timestamp)?;
+        expire_points(&self.config, &mut self.user_account, clock.unix_timestamp)?;
+        let points_before = self.user_account.points;
+
+        if self.token_stake_account.owner == Pubkey::default() {
+            self.token_stake_account.set_inner(TokenStakeAccount {
+                config: self.config.key(),
+                owner: self.user.key(),
+                mint: self.mint.key(),
+                decimals: self.mint.decimals,
+                amount: 0,
+                staked_at: clock.unix_timestamp,
+                bump: bumps.token_stake_account,
+            });
+        }
+
+        // Bank what the current amount earned so the top-up only accrues from now on
+        let points = self.token_stake_account.settle(&self.config, clock.unix_timestamp)?;
+        self.user_account.points = self.user_account.points
+            .checked_add(points)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+
+        let total_staked = self.token_stake_account.amount
+            .checked_add(amount)
+            .ok_or(StakeError::MaxStakeReached)?;
+        require!(
+            self.config.max_token_stake == 0 || total_staked <= self.config.max_token_stake,
+            StakeError::MaxStakeReached
+        );
+
+        let cpi_accounts = TransferChecked{
+            from: self.user_ata.to_account_info(),
+            mint: self.mint.to_account_info(),
+            to: self.token_vault.to_account_info(),
+            authority: self.user.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+
+        transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+
+        self.token_stake_account.amount = total_staked;
+        self.token_stake_account.staked_at = clock.unix_timestamp;
+
+        self.user_account.restart_expiry_if_changed(points_before, clock.unix_timestamp);
+
+        emit!(TokensStaked {
+            config: self.config.key(),
+            user: self.user.key(),
+            amount,
+            total_staked,
+            staked_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
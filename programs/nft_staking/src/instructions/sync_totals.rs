@@ -0,0 +1,29 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, state::StakeConfig};
+
+#[derive(Accounts)]
+pub struct SyncTotals<'info>{
+
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+}
+
+impl <'info>SyncTotals<'info> {
+
+    // `total_staked` is counted off-chain from the pool's open stake accounts. Stakes opened
+    // before the counter existed were never added to it, this brings it back in line.
+    pub fn sync_totals(&mut self, total_staked: u32)->Result<()>{
+
+        self.config.total_staked = total_staked;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,133 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_2022::{spl_token_2022::instruction::AuthorityType, Token2022}, token_interface::{mint_to, set_authority, Mint, MintTo, SetAuthority, TokenAccount}};
+
+use crate::{errors::StakeError, events::PositionTokenized, state::{CustodyMode, StakeAccount, StakeConfig, UserAccount, UserPositions}};
+
+#[derive(Accounts)]
+pub struct TokenizePosition<'info>{
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"stake", config.key().as_ref(), stake_account.mint.as_ref()],
+        bump = stake_account.bump,
+        has_one = config @ StakeError::PoolMismatch,
+        constraint = stake_account.owner == user.key() @ StakeError::NotStakeOwner,
+        constraint = stake_account.position_mint.is_none() && !stake_account.has_receipt @ StakeError::AlreadyTokenized,
+        // Only a vault can hand the NFT to whoever holds the receipt, a frozen one stays in `user`'s wallet
+        constraint = stake_account.custody_mode == CustodyMode::VaultTransfer @ StakeError::CustodyModeMismatch,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    // Created on the owner's first stake or unstake since positions existed
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"positions", config.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = UserPositions::INIT_SPACE,
+    )]
+    pub user_positions: Account<'info, UserPositions>,
+
+    // A fresh keypair per tokenization, its metadata pointer names the stake it stands for
+    #[account(
+        init,
+        payer = user,
+        mint::decimals = 0,
+        mint::authority = stake_account,
+        mint::token_program = token_program,
+        extensions::metadata_pointer::authority = stake_account,
+        extensions::metadata_pointer::metadata_address = stake_account,
+    )]
+    pub position_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = user,
+        associated_token::mint = position_mint,
+        associated_token::authority = user,
+        associated_token::token_program = token_program,
+    )]
+    pub position_ata: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Program<'info, Token2022>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info> TokenizePosition<'info> {
+
+    // Mints `user` the stake's 1/1 receipt. The stake leaves their count, whoever holds the
+    // receipt unstakes it and is paid its points
+    pub fn tokenize_position(&mut self, bumps: &TokenizePositionBumps)->Result<()>{
+        let clock = Clock::get()?;
+        self.user_account.apply_decay(&self.config, clock.unix_timestamp)?;
+
+        let config_key = self.config.key();
+        let seeds = &[
+            b"stake",
+            config_key.as_ref(),
+            self.stake_account.mint.as_ref(),
+            &[self.stake_account.bump]
+        ];
+
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = MintTo{
+            mint: self.position_mint.to_account_info(),
+            to: self.position_ata.to_account_info(),
+            authority: self.stake_account.to_account_info(),
+        };
+
+        mint_to(CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, signer_seeds), 1)?;
+
+        // Dropping the mint authority pins the supply at one for good
+        let cpi_accounts = SetAuthority{
+            current_authority: self.stake_account.to_account_info(),
+            account_or_mint: self.position_mint.to_account_info(),
+        };
+
+        set_authority(
+            CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, signer_seeds),
+            AuthorityType::MintTokens,
+            None,
+        )?;
+
+        self.stake_account.position_mint = Some(self.position_mint.key());
+
+        self.user_account.amount_staked = self.user_account.amount_staked
+            .checked_sub(1)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        self.user_account.remove_set_piece(&self.config, self.stake_account.set_piece, clock.unix_timestamp);
+        self.user_positions.init_if_new(config_key, self.user.key(), bumps.user_positions);
+        self.user_positions.remove(&self.stake_account.mint);
+        self.user_positions.end_full_set(self.stake_account.set_piece);
+
+        emit!(PositionTokenized {
+            config: config_key,
+            user: self.user.key(),
+            mint: self.stake_account.mint,
+            position_mint: self.position_mint.key(),
+            tokenized_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
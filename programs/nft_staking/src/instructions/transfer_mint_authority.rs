@@ -0,0 +1,67 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{token_2022::spl_token_2022::instruction::AuthorityType, token_interface::{set_authority, Mint, SetAuthority, TokenInterface}};
+
+use crate::{errors::StakeError, events::MintAuthorityTransferred, state::StakeConfig};
+
+#[derive(Accounts)]
+pub struct TransferMintAuthority<'info>{
+
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    // Only reward mints handed to the config, pools created before the vault still hold theirs
+    #[account(
+        mut,
+        address = config.reward_mint @ StakeError::RewardMintMismatch,
+        mint::authority = config,
+        mint::token_program = token_program,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl <'info>TransferMintAuthority<'info> {
+
+    // Claims only ever pay out of the vault, so they keep working once the config can't mint
+    pub fn transfer_mint_authority(&mut self, new_authority: Pubkey)->Result<()>{
+        require_keys_neq!(new_authority, self.config.key(), StakeError::InvalidMintAuthority);
+
+        let pool_id = self.config.pool_id.to_le_bytes();
+        let seeds = &[
+            b"config".as_ref(),
+            pool_id.as_ref(),
+            &[self.config.bump]
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = SetAuthority{
+            current_authority: self.config.to_account_info(),
+            account_or_mint: self.reward_mint.to_account_info(),
+        };
+
+        set_authority(
+            CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, signer_seeds),
+            AuthorityType::MintTokens,
+            Some(new_authority),
+        )?;
+
+        let clock = Clock::get()?;
+
+        emit!(MintAuthorityTransferred {
+            config: self.config.key(),
+            reward_mint: self.reward_mint.key(),
+            new_authority,
+            transferred_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
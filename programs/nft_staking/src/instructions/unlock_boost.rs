@@ -0,0 +1,94 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::{errors::StakeError, events::BoostUnlocked, state::{StakeConfig, UserAccount}};
+
+#[derive(Accounts)]
+pub struct UnlockBoost<'info>{
+
+    pub user: Signer<'info>,
+
+    #[account(
+        address = config.reward_mint @ StakeError::RewardMintMismatch,
+        mint::token_program = token_program,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = reward_mint,
+        token::authority = user,
+        token::token_program = token_program,
+    )]
+    pub user_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"boost_vault".as_ref(), config.key().as_ref()],
+        bump,
+        token::mint = reward_mint,
+        token::authority = config,
+        token::token_program = token_program,
+    )]
+    pub boost_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+impl <'info> UnlockBoost<'info> {
+
+    // Blacklisted wallets and paused pools can still take their tokens back, the boost itself
+    // already stopped at `boost_expiry`
+    pub fn unlock_boost(&mut self)->Result<()>{
+        let amount = self.user_account.boost_amount;
+        require!(amount > 0, StakeError::NothingToClaim);
+
+        let clock = Clock::get()?;
+        require!(clock.unix_timestamp >= self.user_account.boost_expiry, StakeError::BoostStillLocked);
+
+        let pool_id = self.config.pool_id.to_le_bytes();
+        let seeds = &[
+            b"config".as_ref(),
+            pool_id.as_ref(),
+            &[self.config.bump]
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked{
+            from: self.boost_vault.to_account_info(),
+            mint: self.reward_mint.to_account_info(),
+            to: self.user_ata.to_account_info(),
+            authority: self.config.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, signer_seeds);
+
+        transfer_checked(cpi_ctx, amount, self.reward_mint.decimals)?;
+
+        self.user_account.boost_amount = 0;
+
+        emit!(BoostUnlocked {
+            config: self.config.key(),
+            user: self.user.key(),
+            amount,
+            unlocked_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
-use anchor_lang::prelude::*;
-use anchor_spl::{metadata::{mpl_token_metadata::instructions::{FreezeDelegatedAccountCpi, FreezeDelegatedAccountCpiAccounts, ThawDelegatedAccountCpi, ThawDelegatedAccountCpiAccounts}, MasterEditionAccount, Metadata, MetadataAccount}, token::{approve, revoke, Approve, Mint, Revoke, Token, TokenAccount}};
+use anchor_lang::{prelude::*, solana_program::sysvar};
+use anchor_spl::{associated_token::AssociatedToken, metadata::{mpl_token_metadata::types::TokenStandard, MasterEditionAccount, Metadata, MetadataAccount}, token_2022::Token2022, token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked}};
 
-use crate::{errors::StakeError, state::{StakeAccount, StakeConfig, UserAccount}};
+use crate::{errors::StakeError, nft::{GroupMemberNftAccounts, NftAccounts, PnftAccounts}, events::NftUnstaked, receipt::ReceiptAccounts, rewards::expire_points, state::{Blacklist, FullSet, Leaderboard, MintRecord, StakeAccount, StakeConfig, UserAccount, UserPositions}};
 
 #[derive(Accounts)]
 pub struct Unstake<'info>{
@@ -9,14 +9,30 @@ pub struct Unstake<'info>{
     #[account(mut)]
     pub user: Signer<'info>,
 
-    pub mint: Account<'info, Mint>,
+    pub mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         associated_token::mint = mint,
         associated_token::authority = user,
+        associated_token::token_program = token_program,
     )]
-    pub mint_ata: Account<'info, TokenAccount>,
+    pub mint_ata: InterfaceAccount<'info, TokenAccount>,
+
+    /// Pass to send the NFT on to another token account for it, e.g. a new wallet's ATA
+    #[account(
+        mut,
+        constraint = destination.mint == mint.key() @ StakeError::DestinationMintMismatch,
+    )]
+    pub destination: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"metadata", metadata_program.key().as_ref(), mint.key().as_ref()],
+        seeds::program = metadata_program.key(),
+        bump,
+    )]
+    pub metadata: Option<Account<'info, MetadataAccount>>,
 
     #[account(
         seeds = [
@@ -28,31 +44,138 @@ pub struct Unstake<'info>{
         seeds::program = metadata_program.key(),
         bump,
     )]
-    pub edition: Account<'info, MasterEditionAccount>,
+    pub edition: Option<Account<'info, MasterEditionAccount>>,
+
+    /// Token-2022 group member NFTs frozen in place only, see `stake`
+    pub freeze_authority: Option<UncheckedAccount<'info>>,
 
     #[account(
         mut,
         close = user,
-        seeds = [b"stake", config.key().as_ref(), mint.key().as_ref()],
-        bump,
+        seeds = [b"stake", stake_account.config.as_ref(), mint.key().as_ref()],
+        bump = stake_account.bump,
+        has_one = config @ StakeError::PoolMismatch,
+        // A tokenized position answers to its receipt's holder instead, checked in `burn_receipt`
+        constraint = stake_account.owner == user.key() || stake_account.position_mint.is_some() @ StakeError::NotStakeOwner,
     )]
     pub stake_account: Account<'info, StakeAccount>,
 
+    // Created on the mint's first stake or unstake since records existed, never closed
     #[account(
-        seeds = [b"config"],
+        init_if_needed,
+        payer = user,
+        seeds = [b"mint_record", config.key().as_ref(), mint.key().as_ref()],
+        bump,
+        space = MintRecord::INIT_SPACE,
+    )]
+    pub mint_record: Account<'info, MintRecord>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
         bump = config.bump,
     )]
     pub config: Account<'info, StakeConfig>,
 
     #[account(
         mut,
-        seeds = [b"user", user.key().as_ref()],
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
         bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
     )]
     pub user_account: Account<'info, UserAccount>,
 
+    // Created on the owner's first stake or unstake since positions existed
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"positions", config.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = UserPositions::INIT_SPACE,
+    )]
+    pub user_positions: Account<'info, UserPositions>,
 
-    pub token_program: Program<'info, Token>,
+    /// CHECK: `user`'s blacklist PDA, only holds data while the wallet is blacklisted
+    #[account(
+        seeds = [b"blacklist".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub blacklist: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = reward_mint,
+        associated_token::authority = user,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub rewards_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        address = config.reward_mint @ StakeError::RewardMintMismatch,
+        mint::token_program = reward_token_program,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"reward_vault".as_ref(), config.key().as_ref()],
+        bump = config.reward_vault_bump,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// pNFT only: token record PDA of `mint_ata`
+    #[account(mut)]
+    pub token_record: Option<UncheckedAccount<'info>>,
+
+    /// pNFT only
+    #[account(address = sysvar::instructions::ID)]
+    pub sysvar_instructions: Option<UncheckedAccount<'info>>,
+
+    /// pNFT only, when the NFT has a rule set
+    pub authorization_rules_program: Option<UncheckedAccount<'info>>,
+
+    /// pNFT only, when the NFT has a rule set
+    pub authorization_rules: Option<UncheckedAccount<'info>>,
+
+    /// VaultTransfer stakes only
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = stake_account,
+        associated_token::token_program = token_program,
+    )]
+    pub vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// CHECK: stakes opened with a receipt only, must be `config.receipt_mint`, see `stake`.
+    /// Tokenized positions pass `stake_account.position_mint` instead
+    #[account(mut)]
+    pub receipt_mint: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: stakes opened with a receipt or tokenized only: `user`'s receipt ATA, checked in `ReceiptAccounts`
+    #[account(mut)]
+    pub receipt_ata: Option<UncheckedAccount<'info>>,
+
+    /// Stakes opened with a receipt or tokenized only
+    pub receipt_token_program: Option<Program<'info, Token2022>>,
+
+    /// Pass to rank the user on the pool's leaderboard
+    #[account(
+        mut,
+        seeds = [b"leaderboard".as_ref(), config.key().as_ref()],
+        bump = leaderboard.load()?.bump,
+    )]
+    pub leaderboard: Option<AccountLoader<'info, Leaderboard>>,
+
+    /// The user's stored referrer's user account in this pool, credited `referral_bps` of
+    /// this unstake's points. Leaving it out skips the cut rather than failing.
+    #[account(mut)]
+    pub referrer_account: Option<Account<'info, UserAccount>>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    // The NFT and the reward mint can live under different token programs
+    pub reward_token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
     pub metadata_program: Program<'info, Metadata>,
 }
@@ -60,14 +183,47 @@ pub struct Unstake<'info>{
 
 
 impl <'info> Unstake<'info> {
-    pub fn unstake(&mut self) -> Result<()>{
+    pub fn unstake(&mut self, bumps: &UnstakeBumps) -> Result<()>{
+
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+        self.user_account.apply_decay(&self.config, now)?;
+        expire_points(&self.config, &mut self.user_account, now)?;
+        let points_before = self.user_account.points;
 
-        let time_elapsed = ((Clock::get()?.unix_timestamp - self.stake_account.staked_at) / 86400) as u32;
+        self.stake_account.require_unlocked(now)?;
+        // A pNFT only moves through token metadata, which `send_to_destination` doesn't go through
+        require!(
+            self.destination.is_none() || !matches!(
+                self.metadata.as_ref().and_then(|metadata| metadata.token_standard.as_ref()),
+                Some(TokenStandard::ProgrammableNonFungible)
+            ),
+            StakeError::UnsupportedTokenStandard
+        );
+
+        // A tokenized position already left its original owner's count and set, and was never
+        // part of the holder's
+        let tokenized = self.stake_account.position_mint.is_some();
+        let set_complete_since = if tokenized { 0 } else { self.user_account.set_complete_since };
+        let full_set = if tokenized { FullSet::default() } else { self.user_positions.full_set(self.stake_account.set_piece) };
+
+        // Blacklisted wallets can always take their NFT back, just not its points
+        let points = if Blacklist::is_set(&self.blacklist) {
+            0
+        } else {
+            self.stake_account.settle(&self.config, now)?;
+            self.config.boosted_points(
+                self.stake_account.pending_points_with_set_bonus(&self.config, set_complete_since, full_set, now)?,
+                &self.user_account,
+                self.reward_mint.decimals,
+                now,
+            )?
+        };
 
-        require!(time_elapsed >= self.config.freeze_period, StakeError::FreezePeriodNotPassed);
+        self.burn_receipt()?;
 
         let seeds = &[
-            b"state",
+            b"stake",
             self.config.to_account_info().key.as_ref(),
             self.mint.to_account_info().key.as_ref(),
             &[self.stake_account.bump]
@@ -75,37 +231,230 @@ impl <'info> Unstake<'info> {
 
         let signer_seeds = &[&seeds[..]];
 
-        let delegate = &self.stake_account.to_account_info();
-        let token_account = &self.mint_ata.to_account_info();
-        let edition = &self.edition.to_account_info();
-        let mint = &self.mint.to_account_info();
-        let token_program = &self.token_program.to_account_info();
-        let metadata_program = &self.metadata_program.to_account_info();
-
-        ThawDelegatedAccountCpi::new(
-            metadata_program, 
-            ThawDelegatedAccountCpiAccounts{
-                delegate,
-                token_account,
-                edition,
-                mint,
-                token_program,
-            }
-        ).invoke_signed(signer_seeds)?;
-
-        let cpi_program = self.token_program.to_account_info();
-
-        let cpi_accounts = Revoke{
-            source: self.mint_ata.to_account_info(),
-            authority: self.user.to_account_info()
-        };
+        // The NFT comes back before any state is written, so a failed release reverts the whole unstake
+        // and leaves the stake as it was. Whichever way `stake` locked it, the wrong way fails on the
+        // freeze authority
+        match (&self.metadata, &self.edition) {
+            (Some(metadata), Some(edition)) => NftAccounts{
+                owner: self.user.as_ref(),
+                delegate: self.stake_account.as_ref(),
+                mint: self.mint.as_ref(),
+                token_account: self.mint_ata.as_ref(),
+                metadata: metadata.as_ref(),
+                edition: edition.as_ref(),
+                token_program: self.token_program.as_ref(),
+                metadata_program: self.metadata_program.as_ref(),
+                system_program: self.system_program.as_ref(),
+                pnft: PnftAccounts::from_optional(
+                    self.token_record.as_deref(),
+                    self.sysvar_instructions.as_deref(),
+                    self.authorization_rules_program.as_deref(),
+                    self.authorization_rules.as_deref(),
+                ),
+                vault: self.vault.as_ref().map(|vault| vault.as_ref()),
+            }.unlock(metadata.token_standard.as_ref(), self.stake_account.custody_mode, signer_seeds)?,
+            (None, None) => GroupMemberNftAccounts{
+                owner: self.user.as_ref(),
+                delegate: self.stake_account.as_ref(),
+                mint: self.mint.as_ref(),
+                token_account: self.mint_ata.as_ref(),
+                token_program: self.token_program.as_ref(),
+                freeze_authority: self.freeze_authority.as_deref(),
+                vault: self.vault.as_ref().map(|vault| vault.as_ref()),
+            }.unlock(self.stake_account.custody_mode, signer_seeds)?,
+            _ => return err!(StakeError::MissingMetadataAccounts),
+        }
+
+        self.send_to_destination()?;
+
+        self.mint_record.init_if_new(self.config.key(), self.mint.key(), bumps.mint_record);
+        self.mint_record.record_unstake(now);
+
+        self.user_positions.init_if_new(self.config.key(), self.user.key(), bumps.user_positions);
+        self.user_positions.remove(&self.stake_account.mint);
+
+        let rewards_paid = pay_out_points(
+            &mut self.config,
+            &mut self.user_account,
+            &self.reward_vault,
+            &self.reward_mint,
+            &self.rewards_ata,
+            &self.reward_token_program,
+            points,
+        )?;
+
+        if let Some(leaderboard) = &self.leaderboard {
+            leaderboard.load_mut()?.record(self.user.key(), self.user_account.points_paid);
+        }
 
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+        let referral_points = credit_referrer(&self.config, &self.user_account, self.referrer_account.as_mut(), points, now)?;
 
-        revoke(cpi_ctx)?;
+        // Only this NFT's stake closes, the user's other stakes keep their own timers
+        if !tokenized {
+            self.user_account.amount_staked = self.user_account.amount_staked
+                .checked_sub(1)
+                .ok_or(StakeError::ArithmeticOverflow)?;
+            self.user_account.remove_set_piece(&self.config, self.stake_account.set_piece, now);
+            self.user_positions.end_full_set(self.stake_account.set_piece);
+        }
+        self.config.remove_staked();
 
-        self.user_account.amount_staked -= 1;
+        self.user_account.restart_expiry_if_changed(points_before, now);
+
+        emit!(NftUnstaked {
+            config: self.config.key(),
+            user: self.user.key(),
+            mint: self.mint.key(),
+            unstaked_at: now,
+            slot: clock.slot,
+            duration: now.saturating_sub(self.stake_account.opened_at),
+            points_earned: points,
+            rewards_paid,
+            referral_points,
+        });
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    // The NFT is back in `mint_ata` and unlocked by now, the user signs it on from there
+    fn send_to_destination(&self)->Result<()>{
+        let Some(destination) = &self.destination else {
+            return Ok(());
+        };
+
+        let cpi_accounts = TransferChecked{
+            from: self.mint_ata.to_account_info(),
+            mint: self.mint.to_account_info(),
+            to: destination.to_account_info(),
+            authority: self.user.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new(self.token_program.to_account_info(), cpi_accounts);
+
+        transfer_checked(cpi_ctx, 1, self.mint.decimals)
+    }
+
+    // A stake opened with a receipt or tokenized only closes by burning it, so a wallet still
+    // holding a receipt always holds a live stake
+    fn burn_receipt(&self)->Result<()>{
+        if !self.stake_account.has_receipt && self.stake_account.position_mint.is_none() {
+            return Ok(());
+        }
+
+        let (Some(receipt_mint), Some(receipt_ata), Some(receipt_token_program)) = (
+            &self.receipt_mint,
+            &self.receipt_ata,
+            &self.receipt_token_program,
+        ) else {
+            return match self.stake_account.position_mint {
+                Some(_) => err!(StakeError::ReceiptRequired),
+                None => err!(StakeError::MissingReceipt),
+            };
+        };
+
+        let receipt = ReceiptAccounts{
+            owner: self.user.as_ref(),
+            mint: receipt_mint.as_ref(),
+            token_account: receipt_ata.as_ref(),
+            token_program: receipt_token_program.as_ref(),
+        };
+
+        if let Some(position_mint) = self.stake_account.position_mint {
+            require_keys_eq!(receipt_mint.key(), position_mint, StakeError::InvalidReceipt);
+            return receipt.redeem_position();
+        }
+
+        // Burning one of any other mint the user holds mustn't count
+        require!(self.config.receipt_mint == Some(receipt_mint.key()), StakeError::InvalidReceiptMint);
+        receipt.redeem()
+    }
+}
+
+/// Pays an unstaked NFT's points out of the reward vault, returning the amount paid. If the vault
+/// can't cover them they're banked for a claim once it's topped up instead, so a dry vault never
+/// holds the NFT hostage.
+pub(crate) fn pay_out_points<'info>(
+    config: &mut Account<'info, StakeConfig>,
+    user_account: &mut UserAccount,
+    reward_vault: &InterfaceAccount<'info, TokenAccount>,
+    reward_mint: &InterfaceAccount<'info, Mint>,
+    rewards_ata: &InterfaceAccount<'info, TokenAccount>,
+    reward_token_program: &Interface<'info, TokenInterface>,
+    points: u64,
+)->Result<u64>{
+    // Points past `max_emission` are carried onto the user account, like a short vault's
+    let carried = points.saturating_sub(config.emission_room());
+    user_account.points = user_account.points
+        .checked_add(carried)
+        .ok_or(StakeError::ArithmeticOverflow)?;
+    let points = points - carried;
+
+    let rewards_paid = points
+        .checked_mul(10_u64.checked_pow(reward_mint.decimals as u32).ok_or(StakeError::ArithmeticOverflow)?)
+        .ok_or(StakeError::ArithmeticOverflow)?;
+
+    if rewards_paid > config.available_rewards(reward_vault.amount) {
+        user_account.points = user_account.points
+            .checked_add(points)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        return Ok(0);
+    }
+
+    if rewards_paid > 0 {
+        let pool_id = config.pool_id.to_le_bytes();
+        let seeds = &[
+            b"config".as_ref(),
+            pool_id.as_ref(),
+            &[config.bump]
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked{
+            from: reward_vault.to_account_info(),
+            mint: reward_mint.to_account_info(),
+            to: rewards_ata.to_account_info(),
+            authority: config.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(reward_token_program.to_account_info(), cpi_accounts, signer_seeds);
+
+        transfer_checked(cpi_ctx, rewards_paid, reward_mint.decimals)?;
+
+        config.add_points_distributed(points)?;
+        user_account.record_points_paid(points)?;
+    }
+
+    Ok(rewards_paid)
+}
+
+/// Credits the user's stored referrer `referral_bps` of `points`, returning the cut. Points are
+/// only cut at unstake, where they're earned. Claims pay out points that already were.
+pub(crate) fn credit_referrer(
+    config: &Account<StakeConfig>,
+    user_account: &UserAccount,
+    referrer_account: Option<&mut Account<UserAccount>>,
+    points: u64,
+    now: i64,
+)->Result<u64>{
+    let Some(referrer_account) = referrer_account else {
+        return Ok(0);
+    };
+    let referrer = user_account.referrer.ok_or(StakeError::InvalidReferrer)?;
+
+    require!(
+        UserAccount::is_address_of(&referrer_account.key(), &config.key(), &referrer, referrer_account.bump),
+        StakeError::InvalidReferrer
+    );
+
+    let cut = config.referral_cut(points)?;
+
+    referrer_account.apply_decay(config, now)?;
+    expire_points(config, referrer_account, now)?;
+    let points_before = referrer_account.points;
+    referrer_account.points = referrer_account.points
+        .checked_add(cut)
+        .ok_or(StakeError::ArithmeticOverflow)?;
+    referrer_account.restart_expiry_if_changed(points_before, now);
+
+    Ok(cut)
+}
@@ -0,0 +1,203 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::AssociatedToken, token_interface::{Mint, TokenAccount, TokenInterface}};
+
+use crate::{compressed::{asset_id, BubblegumAccounts, CompressedLeaf}, errors::StakeError, events::NftUnstaked, instructions::{credit_referrer, pay_out_points}, rewards::expire_points, state::{Blacklist, CustodyMode, FullSet, MintRecord, StakeAccount, StakeConfig, UserAccount, UserPositions}};
+
+#[derive(Accounts)]
+pub struct UnstakeCompressed<'info>{
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        close = user,
+        seeds = [b"stake", stake_account.config.as_ref(), stake_account.mint.as_ref()],
+        bump = stake_account.bump,
+        has_one = config @ StakeError::PoolMismatch,
+        constraint = stake_account.owner == user.key() @ StakeError::NotStakeOwner,
+        constraint = stake_account.custody_mode == CustodyMode::Compressed @ StakeError::CustodyModeMismatch,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+
+    // Created on the mint's first stake or unstake since records existed, never closed
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"mint_record", config.key().as_ref(), stake_account.mint.as_ref()],
+        bump,
+        space = MintRecord::INIT_SPACE,
+    )]
+    pub mint_record: Account<'info, MintRecord>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    // Created on the owner's first stake or unstake since positions existed
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"positions", config.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = UserPositions::INIT_SPACE,
+    )]
+    pub user_positions: Account<'info, UserPositions>,
+
+    /// CHECK: `user`'s blacklist PDA, only holds data while the wallet is blacklisted
+    #[account(
+        seeds = [b"blacklist".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub blacklist: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = reward_mint,
+        associated_token::authority = user,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub rewards_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        address = config.reward_mint @ StakeError::RewardMintMismatch,
+        mint::token_program = reward_token_program,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"reward_vault".as_ref(), config.key().as_ref()],
+        bump = config.reward_vault_bump,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: Bubblegum's tree config PDA of `merkle_tree`, checked by Bubblegum
+    pub tree_config: UncheckedAccount<'info>,
+
+    /// CHECK: checked against the stake's asset id in `unstake_compressed`
+    #[account(mut)]
+    pub merkle_tree: UncheckedAccount<'info>,
+
+    /// CHECK: address checked in `BubblegumAccounts::transfer`
+    pub log_wrapper: UncheckedAccount<'info>,
+
+    /// CHECK: address checked in `BubblegumAccounts::transfer`
+    pub compression_program: UncheckedAccount<'info>,
+
+    /// CHECK: address checked in `BubblegumAccounts::transfer`
+    pub bubblegum_program: UncheckedAccount<'info>,
+
+    /// The user's stored referrer's user account in this pool, see `unstake`
+    #[account(mut)]
+    pub referrer_account: Option<Account<'info, UserAccount>>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub reward_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info> UnstakeCompressed<'info> {
+
+    // `leaf` is read after staking, the stake account is now its owner and delegate
+    pub fn unstake_compressed(&mut self, leaf: CompressedLeaf, remaining_accounts: &'info [AccountInfo<'info>], bumps: &UnstakeCompressedBumps)->Result<()>{
+
+        require_keys_eq!(
+            asset_id(&self.merkle_tree.key(), leaf.nonce),
+            self.stake_account.mint,
+            StakeError::AssetMismatch
+        );
+
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+        self.user_account.apply_decay(&self.config, now)?;
+        expire_points(&self.config, &mut self.user_account, now)?;
+        let points_before = self.user_account.points;
+
+        self.stake_account.require_unlocked(now)?;
+
+        // Blacklisted wallets can always take their NFT back, just not its points
+        let points = if Blacklist::is_set(&self.blacklist) {
+            0
+        } else {
+            self.stake_account.settle(&self.config, now)?;
+            self.config.boosted_points(
+                // Compressed stakes are never a set piece
+                self.stake_account.pending_points_with_set_bonus(&self.config, self.user_account.set_complete_since, FullSet::default(), now)?,
+                &self.user_account,
+                self.reward_mint.decimals,
+                now,
+            )?
+        };
+
+        let seeds = &[
+            b"stake",
+            self.config.to_account_info().key.as_ref(),
+            self.stake_account.mint.as_ref(),
+            &[self.stake_account.bump]
+        ];
+
+        let signer_seeds = &[&seeds[..]];
+
+        BubblegumAccounts{
+            tree_config: self.tree_config.as_ref(),
+            merkle_tree: self.merkle_tree.as_ref(),
+            log_wrapper: self.log_wrapper.as_ref(),
+            compression_program: self.compression_program.as_ref(),
+            bubblegum_program: self.bubblegum_program.as_ref(),
+            system_program: self.system_program.as_ref(),
+            proof: remaining_accounts,
+        }.transfer(&leaf, self.stake_account.as_ref(), self.stake_account.as_ref(), self.user.as_ref(), signer_seeds)?;
+
+        self.mint_record.init_if_new(self.config.key(), self.stake_account.mint, bumps.mint_record);
+        self.mint_record.record_unstake(now);
+
+        self.user_positions.init_if_new(self.config.key(), self.user.key(), bumps.user_positions);
+        self.user_positions.remove(&self.stake_account.mint);
+
+        let rewards_paid = pay_out_points(
+            &mut self.config,
+            &mut self.user_account,
+            &self.reward_vault,
+            &self.reward_mint,
+            &self.rewards_ata,
+            &self.reward_token_program,
+            points,
+        )?;
+
+        let referral_points = credit_referrer(&self.config, &self.user_account, self.referrer_account.as_mut(), points, now)?;
+
+        self.user_account.amount_staked = self.user_account.amount_staked
+            .checked_sub(1)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        self.config.remove_staked();
+
+        self.user_account.restart_expiry_if_changed(points_before, now);
+
+        emit!(NftUnstaked {
+            config: self.config.key(),
+            user: self.user.key(),
+            mint: self.stake_account.mint,
+            unstaked_at: now,
+            slot: clock.slot,
+            duration: now.saturating_sub(self.stake_account.opened_at),
+            points_earned: points,
+            rewards_paid,
+            referral_points,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::{get_associated_token_address_with_program_id, AssociatedToken}, metadata::{mpl_token_metadata::types::TokenStandard, MasterEditionAccount, Metadata, MetadataAccount}, token_interface::{Mint, TokenAccount, TokenInterface}};
+
+use crate::{constants::{MAX_BATCH_SIZE, UNSTAKE_BATCH_ACCOUNTS}, errors::StakeError, events::BatchUnstaked, instructions::{credit_referrer, pay_out_points}, nft::NftAccounts, rewards::expire_points, state::{Blacklist, CustodyMode, Leaderboard, MintRecord, StakeAccount, StakeConfig, UserAccount, UserPositions}};
+
+#[derive(Accounts)]
+pub struct UnstakeMany<'info>{
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    // Created on the owner's first stake or unstake since positions existed
+    #[account(
+        init_if_needed,
+        payer = user,
+        seeds = [b"positions", config.key().as_ref(), user.key().as_ref()],
+        bump,
+        space = UserPositions::INIT_SPACE,
+    )]
+    pub user_positions: Account<'info, UserPositions>,
+
+    /// CHECK: `user`'s blacklist PDA, only holds data while the wallet is blacklisted
+    #[account(
+        seeds = [b"blacklist".as_ref(), config.key().as_ref(), user.key().as_ref()],
+        bump,
+    )]
+    pub blacklist: UncheckedAccount<'info>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        associated_token::mint = reward_mint,
+        associated_token::authority = user,
+        associated_token::token_program = reward_token_program,
+    )]
+    pub rewards_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        address = config.reward_mint @ StakeError::RewardMintMismatch,
+        mint::token_program = reward_token_program,
+    )]
+    pub reward_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"reward_vault".as_ref(), config.key().as_ref()],
+        bump = config.reward_vault_bump,
+    )]
+    pub reward_vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Pass to rank the user on the pool's leaderboard
+    #[account(
+        mut,
+        seeds = [b"leaderboard".as_ref(), config.key().as_ref()],
+        bump = leaderboard.load()?.bump,
+    )]
+    pub leaderboard: Option<AccountLoader<'info, Leaderboard>>,
+
+    /// The user's stored referrer's user account in this pool, credited `referral_bps` of
+    /// the batch's points. Leaving it out skips the cut rather than failing.
+    #[account(mut)]
+    pub referrer_account: Option<Account<'info, UserAccount>>,
+
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub token_program: Interface<'info, TokenInterface>,
+    // The NFTs and the reward mint can live under different token programs
+    pub reward_token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub metadata_program: Program<'info, Metadata>,
+}
+
+impl <'info> UnstakeMany<'info> {
+
+    // remaining_accounts holds one [stake_account, mint, mint_ata, metadata, master_edition, mint_record]
+    // group per stake. Each unlocked one is unstaked as `unstake` would, in order, and still-locked
+    // ones are skipped. Only stakes `stake_batch` could have opened fit: frozen in place, not pNFTs,
+    // without a receipt. Returns the number unstaked
+    pub fn unstake_many(&mut self, remaining_accounts: &'info [AccountInfo<'info>], bumps: &UnstakeManyBumps)->Result<u8>{
+        let stakes = remaining_accounts.len() / UNSTAKE_BATCH_ACCOUNTS;
+        require!(stakes > 0 && stakes <= MAX_BATCH_SIZE, StakeError::InvalidBatchSize);
+        require!(
+            remaining_accounts.len() == stakes * UNSTAKE_BATCH_ACCOUNTS,
+            StakeError::InvalidRemainingAccounts
+        );
+
+        let clock = Clock::get()?;
+        let now = clock.unix_timestamp;
+        self.user_account.apply_decay(&self.config, now)?;
+        expire_points(&self.config, &mut self.user_account, now)?;
+        let points_before = self.user_account.points;
+        self.user_positions.init_if_new(self.config.key(), self.user.key(), bumps.user_positions);
+
+        let blacklisted = Blacklist::is_set(&self.blacklist);
+        let mut points = 0_u64;
+        let mut unstaked: Vec<Pubkey> = Vec::with_capacity(stakes);
+        let mut skipped_locked: Vec<Pubkey> = Vec::new();
+
+        for accounts in remaining_accounts.chunks(UNSTAKE_BATCH_ACCOUNTS) {
+            // A stake passed twice would be skipped twice or found already closed
+            require!(
+                !unstaked.contains(accounts[1].key) && !skipped_locked.contains(accounts[1].key),
+                StakeError::InvalidRemainingAccounts
+            );
+
+            match self.unstake_one(accounts, blacklisted, now)? {
+                Some(stake_points) => {
+                    points = points
+                        .checked_add(stake_points)
+                        .ok_or(StakeError::ArithmeticOverflow)?;
+                    unstaked.push(accounts[1].key());
+                }
+                None => skipped_locked.push(accounts[1].key()),
+            }
+        }
+
+        // Every stake's points land in one payout, a dry vault banks them all for a claim
+        let rewards_paid = pay_out_points(
+            &mut self.config,
+            &mut self.user_account,
+            &self.reward_vault,
+            &self.reward_mint,
+            &self.rewards_ata,
+            &self.reward_token_program,
+            points,
+        )?;
+
+        if let Some(leaderboard) = &self.leaderboard {
+            leaderboard.load_mut()?.record(self.user.key(), self.user_account.points_paid);
+        }
+
+        let referral_points = credit_referrer(&self.config, &self.user_account, self.referrer_account.as_mut(), points, now)?;
+
+        self.user_account.restart_expiry_if_changed(points_before, now);
+
+        emit!(BatchUnstaked {
+            config: self.config.key(),
+            user: self.user.key(),
+            mints: unstaked.clone(),
+            skipped_locked,
+            unstaked_at: now,
+            slot: clock.slot,
+            points_earned: points,
+            rewards_paid,
+            referral_points,
+        });
+
+        Ok(unstaked.len() as u8)
+    }
+
+    // The stake's points, or `None` while it's still locked and left as it was
+    fn unstake_one(&mut self, accounts: &'info [AccountInfo<'info>], blacklisted: bool, now: i64)->Result<Option<u64>>{
+        let [stake_info, mint_info, mint_ata_info, metadata_info, edition_info, mint_record_info] = accounts else {
+            return err!(StakeError::InvalidRemainingAccounts);
+        };
+
+        // Anchor constraints don't run on remaining_accounts, so every account is checked by hand,
+        // locked stakes included so a malformed batch fails rather than being reported as skipped
+        let config_key = self.config.key();
+        let mint_key = mint_info.key();
+        let (stake_key, _) = Pubkey::find_program_address(
+            &[b"stake", config_key.as_ref(), mint_key.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(stake_info.key(), stake_key, StakeError::InvalidRemainingAccounts);
+        let mut stake_account = Account::<StakeAccount>::try_from(stake_info)?;
+        require_keys_eq!(stake_account.config, config_key, StakeError::PoolMismatch);
+        require_keys_eq!(stake_account.owner, self.user.key(), StakeError::NotStakeOwner);
+        require!(stake_account.position_mint.is_none(), StakeError::ReceiptRequired);
+        require!(!stake_account.has_receipt, StakeError::MissingReceipt);
+        // Batches don't carry vault accounts, vaulted stakes unstake one at a time with `unstake`
+        require!(stake_account.custody_mode == CustodyMode::FreezeDelegated, StakeError::CustodyModeMismatch);
+
+        require_keys_eq!(*mint_info.owner, self.token_program.key(), StakeError::InvalidRemainingAccounts);
+        let mint = InterfaceAccount::<Mint>::try_from(mint_info)?;
+
+        require_keys_eq!(
+            mint_ata_info.key(),
+            get_associated_token_address_with_program_id(&self.user.key(), &mint_key, &self.token_program.key()),
+            StakeError::InvalidRemainingAccounts
+        );
+        let mint_ata = InterfaceAccount::<TokenAccount>::try_from(mint_ata_info)?;
+
+        let metadata_program = self.metadata_program.key();
+        let (metadata_key, _) = Pubkey::find_program_address(
+            &[b"metadata", metadata_program.as_ref(), mint_key.as_ref()],
+            &metadata_program,
+        );
+        require_keys_eq!(metadata_info.key(), metadata_key, StakeError::InvalidRemainingAccounts);
+        let metadata = Account::<MetadataAccount>::try_from(metadata_info)?;
+
+        // pNFTs need a token record per mint, unstake them one at a time with `unstake`
+        require!(
+            metadata.token_standard != Some(TokenStandard::ProgrammableNonFungible),
+            StakeError::UnsupportedTokenStandard
+        );
+
+        let (edition_key, _) = Pubkey::find_program_address(
+            &[b"metadata", metadata_program.as_ref(), mint_key.as_ref(), b"edition"],
+            &metadata_program,
+        );
+        require_keys_eq!(edition_info.key(), edition_key, StakeError::InvalidRemainingAccounts);
+        let edition = Account::<MasterEditionAccount>::try_from(edition_info)?;
+
+        let (record_key, _) = Pubkey::find_program_address(
+            &[b"mint_record", config_key.as_ref(), mint_key.as_ref()],
+            &crate::ID,
+        );
+        require_keys_eq!(mint_record_info.key(), record_key, StakeError::InvalidRemainingAccounts);
+
+        if !stake_account.is_unlocked(now) {
+            return Ok(None);
+        }
+
+        // Blacklisted wallets can always take their NFTs back, just not their points
+        let points = if blacklisted {
+            0
+        } else {
+            stake_account.settle(&self.config, now)?;
+            self.config.boosted_points(
+                stake_account.pending_points_with_set_bonus(
+                    &self.config,
+                    self.user_account.set_complete_since,
+                    self.user_positions.full_set(stake_account.set_piece),
+                    now,
+                )?,
+                &self.user_account,
+                self.reward_mint.decimals,
+                now,
+            )?
+        };
+
+        let seeds = &[
+            b"stake",
+            config_key.as_ref(),
+            mint_key.as_ref(),
+            &[stake_account.bump]
+        ];
+
+        let signer_seeds = &[&seeds[..]];
+
+        NftAccounts{
+            owner: self.user.as_ref(),
+            delegate: stake_info,
+            mint: mint.as_ref(),
+            token_account: mint_ata.as_ref(),
+            metadata: metadata.as_ref(),
+            edition: edition.as_ref(),
+            token_program: self.token_program.as_ref(),
+            metadata_program: self.metadata_program.as_ref(),
+            system_program: self.system_program.as_ref(),
+            pnft: None,
+            vault: None,
+        }.unlock(metadata.token_standard.as_ref(), CustodyMode::FreezeDelegated, signer_seeds)?;
+
+        // A stake from before mint records existed has none, the mint's next stake creates it
+        if !mint_record_info.data_is_empty() {
+            let mut mint_record = Account::<MintRecord>::try_from(mint_record_info)?;
+            mint_record.record_unstake(now);
+            mint_record.exit(&crate::ID)?;
+        }
+
+        self.user_positions.remove(&mint_key);
+        self.user_account.amount_staked = self.user_account.amount_staked
+            .checked_sub(1)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        self.user_account.remove_set_piece(&self.config, stake_account.set_piece, now);
+        self.user_positions.end_full_set(stake_account.set_piece);
+        self.config.remove_staked();
+
+        stake_account.close(self.user.to_account_info())?;
+
+        Ok(Some(points))
+    }
+}
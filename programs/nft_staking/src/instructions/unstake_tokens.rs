@@ -0,0 +1,122 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{transfer_checked, Mint, TokenAccount, TokenInterface, TransferChecked};
+
+use crate::{errors::StakeError, events::TokensUnstaked, rewards::expire_points, state::{StakeConfig, TokenStakeAccount, UserAccount}};
+
+#[derive(Accounts)]
+pub struct UnstakeTokens<'info>{
+
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        address = token_stake_account.mint,
+        mint::token_program = token_program,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = user,
+        token::token_program = token_program,
+    )]
+    pub user_ata: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+    )]
+    pub config: Account<'info, StakeConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"user", config.key().as_ref(), user.key().as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.is_current() @ StakeError::AccountNeedsMigration,
+    )]
+    pub user_account: Account<'info, UserAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"token_stake", config.key().as_ref(), user.key().as_ref()],
+        bump = token_stake_account.bump,
+        has_one = config @ StakeError::PoolMismatch,
+    )]
+    pub token_stake_account: Account<'info, TokenStakeAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"token_vault".as_ref(), config.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = config,
+        token::token_program = token_program,
+    )]
+    pub token_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+impl <'info> UnstakeTokens<'info> {
+
+    // Token stakes have no freeze period, any part can be withdrawn at any time
+    pub fn unstake_tokens(&mut self, amount: u64)->Result<()>{
+        require!(amount > 0, StakeError::InvalidAmount);
+
+        let clock = Clock::get()?;
+        self.user_account.apply_decay(&self.config, clock.unix_timestamp)?;
+        expire_points(&self.config, &mut self.user_account, clock.unix_timestamp)?;
+        let points_before = self.user_account.points;
+
+        let points_earned = self.token_stake_account.settle(&self.config, clock.unix_timestamp)?;
+        self.user_account.points = self.user_account.points
+            .checked_add(points_earned)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+
+        let total_staked = self.token_stake_account.amount
+            .checked_sub(amount)
+            .ok_or(StakeError::InvalidAmount)?;
+
+        let pool_id = self.config.pool_id.to_le_bytes();
+        let seeds = &[
+            b"config".as_ref(),
+            pool_id.as_ref(),
+            &[self.config.bump]
+        ];
+        let signer_seeds = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked{
+            from: self.token_vault.to_account_info(),
+            mint: self.mint.to_account_info(),
+            to: self.user_ata.to_account_info(),
+            authority: self.config.to_account_info(),
+        };
+
+        let cpi_ctx = CpiContext::new_with_signer(self.token_program.to_account_info(), cpi_accounts, signer_seeds);
+
+        transfer_checked(cpi_ctx, amount, self.mint.decimals)?;
+
+        self.token_stake_account.amount = total_staked;
+
+        // Nothing left to earn on, hand the rent back
+        if total_staked == 0 {
+            self.token_stake_account.close(self.user.to_account_info())?;
+        }
+
+        self.user_account.restart_expiry_if_changed(points_before, clock.unix_timestamp);
+
+        emit!(TokensUnstaked {
+            config: self.config.key(),
+            user: self.user.key(),
+            amount,
+            total_staked,
+            points_earned,
+            unstaked_at: clock.unix_timestamp,
+            slot: clock.slot,
+        });
+
+        Ok(())
+    }
+}
@@ -0,0 +1,263 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::{LOCKUP_KINDS, REWARD_TIERS}, errors::StakeError, state::{CustodyMode, LockupOption, RewardTier, StakeConfig}};
+
+/// Fields left as `None` keep their current value. Every field is live-updatable:
+/// `points_per_stake`, `reward_tiers`, `max_stake_duration`, `min_reward_duration`,
+/// `set_bonus_bps`, the emission schedule and the compound and lock boosts reprice points still
+/// pending on open stakes, `decay_rate_bps_per_day`, `weight_per_day` and `weight_decay_per_day`
+/// reprice time since each user's last update,
+/// `referral_bonus`, `referral_bps`, `treasury`, `fee_bps`, `claim_fee_lamports`, `max_stake`,
+/// `global_max_stake`, `early_unstake_penalty_bps`, `claim_cooldown`, `max_clawback`,
+/// `max_token_stake`, `compound_bonus_bps`, `compound_lock_secs`, `boost_lock_secs`,
+/// `restake_cooldown_secs`, `gated`, `min_claim_points`, `claim_requires_unlocked`,
+/// `epoch_length_secs`, `max_stakes_per_window`, `window_len` and `points_expiry_secs` apply from the next call, and `creator`, `freeze_period_secs`,
+/// `lockup_options` and `custody_mode` only apply to NFTs staked after the change.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct UpdateConfigArgs {
+    pub creator: Option<Pubkey>,
+    pub points_per_stake: Option<u8>,
+    pub referral_bonus: Option<u32>,
+    pub referral_bps: Option<u16>,
+    pub treasury: Option<Pubkey>,
+    pub fee_bps: Option<u16>,
+    pub claim_fee_lamports: Option<u64>,
+    pub max_stake: Option<u8>,
+    pub freeze_period_secs: Option<u64>,
+    pub lockup_options: Option<[LockupOption; LOCKUP_KINDS]>,
+    pub max_stake_duration: Option<u32>,
+    pub global_max_stake: Option<u32>,
+    pub early_unstake_penalty_bps: Option<u16>,
+    pub claim_cooldown: Option<u32>,
+    pub reward_tiers: Option<[RewardTier; REWARD_TIERS]>,
+    pub emission_start: Option<i64>,
+    pub halving_interval_secs: Option<i64>,
+    pub custody_mode: Option<CustodyMode>,
+    pub max_clawback: Option<u64>,
+    pub max_token_stake: Option<u64>,
+    pub compound_boost_bps: Option<u16>,
+    pub max_compound_boost_bps: Option<u16>,
+    pub compound_bonus_bps: Option<u16>,
+    pub compound_lock_secs: Option<u32>,
+    pub lock_boost_bps: Option<u16>,
+    pub max_lock_boost_bps: Option<u16>,
+    pub boost_lock_secs: Option<u32>,
+    pub restake_cooldown_secs: Option<u32>,
+    pub min_reward_duration: Option<u32>,
+    pub set_bonus_bps: Option<u16>,
+    pub decay_rate_bps_per_day: Option<u16>,
+    pub gated: Option<bool>,
+    pub min_claim_points: Option<u64>,
+    pub claim_requires_unlocked: Option<bool>,
+    pub epoch_length_secs: Option<u32>,
+    pub max_stakes_per_window: Option<u16>,
+    pub window_len: Option<u32>,
+    pub points_expiry_secs: Option<i64>,
+    pub weight_per_day: Option<u64>,
+    pub weight_decay_per_day: Option<u64>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info>{
+
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"config".as_ref(), config.pool_id.to_le_bytes().as_ref()],
+        bump = config.bump,
+        has_one = admin @ StakeError::Unauthorized,
+    )]
+    pub config: Account<'info, StakeConfig>,
+}
+
+impl <'info>UpdateConfig<'info> {
+
+    // Stakes snapshot their `unlocked_at` and lockup terms when they open, so changing them only affects new stakes
+    pub fn update_config(&mut self, args: UpdateConfigArgs)->Result<()>{
+
+        if let Some(creator) = args.creator {
+            self.config.creator = creator;
+        }
+
+        if let Some(points_per_stake) = args.points_per_stake {
+            self.config.points_per_stake = points_per_stake;
+        }
+
+        if let Some(referral_bonus) = args.referral_bonus {
+            self.config.referral_bonus = referral_bonus;
+        }
+
+        if let Some(referral_bps) = args.referral_bps {
+            StakeConfig::validate_referral_bps(referral_bps)?;
+            self.config.referral_bps = referral_bps;
+        }
+
+        if let Some(treasury) = args.treasury {
+            self.config.treasury = treasury;
+        }
+
+        if let Some(fee_bps) = args.fee_bps {
+            StakeConfig::validate_fee_bps(fee_bps)?;
+            self.config.fee_bps = fee_bps;
+        }
+
+        if let Some(claim_fee_lamports) = args.claim_fee_lamports {
+            self.config.claim_fee_lamports = claim_fee_lamports;
+        }
+
+        if let Some(max_stake) = args.max_stake {
+            StakeConfig::validate_max_stake(max_stake)?;
+            self.config.max_stake = max_stake;
+        }
+
+        if let Some(freeze_period_secs) = args.freeze_period_secs {
+            StakeConfig::validate_freeze_period_secs(freeze_period_secs)?;
+            self.config.freeze_period_secs = freeze_period_secs;
+        }
+
+        if let Some(lockup_options) = args.lockup_options {
+            StakeConfig::validate_lockup_options(&lockup_options)?;
+            self.config.lockup_options = lockup_options;
+        }
+
+        if let Some(max_stake_duration) = args.max_stake_duration {
+            self.config.max_stake_duration = max_stake_duration;
+        }
+
+        // Lowering it below `total_staked` only blocks new stakes
+        if let Some(global_max_stake) = args.global_max_stake {
+            self.config.global_max_stake = global_max_stake;
+        }
+
+        if let Some(early_unstake_penalty_bps) = args.early_unstake_penalty_bps {
+            StakeConfig::validate_early_unstake_penalty_bps(early_unstake_penalty_bps)?;
+            self.config.early_unstake_penalty_bps = early_unstake_penalty_bps;
+        }
+
+        if let Some(claim_cooldown) = args.claim_cooldown {
+            self.config.claim_cooldown = claim_cooldown;
+        }
+
+        if let Some(reward_tiers) = args.reward_tiers {
+            StakeConfig::validate_reward_tiers(&reward_tiers)?;
+            self.config.reward_tiers = reward_tiers;
+        }
+
+        if let Some(emission_start) = args.emission_start {
+            self.config.emission_start = emission_start;
+        }
+
+        if let Some(halving_interval_secs) = args.halving_interval_secs {
+            StakeConfig::validate_halving_interval_secs(halving_interval_secs)?;
+            self.config.halving_interval_secs = halving_interval_secs;
+        }
+
+        if let Some(custody_mode) = args.custody_mode {
+            StakeConfig::validate_custody_mode(custody_mode)?;
+            self.config.custody_mode = custody_mode;
+        }
+
+        if let Some(max_clawback) = args.max_clawback {
+            self.config.max_clawback = max_clawback;
+        }
+
+        if let Some(max_token_stake) = args.max_token_stake {
+            self.config.max_token_stake = max_token_stake;
+        }
+
+        if let Some(compound_boost_bps) = args.compound_boost_bps {
+            self.config.compound_boost_bps = compound_boost_bps;
+        }
+
+        if let Some(max_compound_boost_bps) = args.max_compound_boost_bps {
+            self.config.max_compound_boost_bps = max_compound_boost_bps;
+        }
+
+        // Balances already locked keep their bonus and `locked_until`
+        if let Some(compound_bonus_bps) = args.compound_bonus_bps {
+            self.config.compound_bonus_bps = compound_bonus_bps;
+        }
+
+        if let Some(compound_lock_secs) = args.compound_lock_secs {
+            self.config.compound_lock_secs = compound_lock_secs;
+        }
+
+        if let Some(lock_boost_bps) = args.lock_boost_bps {
+            self.config.lock_boost_bps = lock_boost_bps;
+        }
+
+        if let Some(max_lock_boost_bps) = args.max_lock_boost_bps {
+            self.config.max_lock_boost_bps = max_lock_boost_bps;
+        }
+
+        // Boosts already locked keep their `boost_expiry`
+        if let Some(boost_lock_secs) = args.boost_lock_secs {
+            self.config.boost_lock_secs = boost_lock_secs;
+        }
+
+        if let Some(restake_cooldown_secs) = args.restake_cooldown_secs {
+            self.config.restake_cooldown_secs = restake_cooldown_secs;
+        }
+
+        if let Some(min_reward_duration) = args.min_reward_duration {
+            self.config.min_reward_duration = min_reward_duration;
+        }
+
+        if let Some(set_bonus_bps) = args.set_bonus_bps {
+            self.config.set_bonus_bps = set_bonus_bps;
+        }
+
+        // Days already past since a user's last update decay at the new rate
+        if let Some(decay_rate_bps_per_day) = args.decay_rate_bps_per_day {
+            StakeConfig::validate_decay_rate_bps_per_day(decay_rate_bps_per_day)?;
+            self.config.decay_rate_bps_per_day = decay_rate_bps_per_day;
+        }
+
+        // Entries stay in place, gating again later lets the same wallets back in
+        if let Some(gated) = args.gated {
+            self.config.gated = gated;
+        }
+
+        if let Some(min_claim_points) = args.min_claim_points {
+            self.config.min_claim_points = min_claim_points;
+        }
+
+        if let Some(claim_requires_unlocked) = args.claim_requires_unlocked {
+            self.config.claim_requires_unlocked = claim_requires_unlocked;
+        }
+
+        // Epochs are recounted from `EpochHistory.started_at`, snapshots resume once the new count
+        // passes `current_epoch`
+        if let Some(epoch_length_secs) = args.epoch_length_secs {
+            self.config.epoch_length_secs = epoch_length_secs;
+        }
+
+        // Stakes already counted stay against the window they opened in
+        if let Some(max_stakes_per_window) = args.max_stakes_per_window {
+            self.config.max_stakes_per_window = max_stakes_per_window;
+        }
+
+        if let Some(window_len) = args.window_len {
+            self.config.window_len = window_len;
+        }
+
+        // Measured from each user's `points_updated_at`, so a shorter expiry can forfeit balances at once
+        if let Some(points_expiry_secs) = args.points_expiry_secs {
+            StakeConfig::validate_points_expiry_secs(points_expiry_secs)?;
+            self.config.points_expiry_secs = points_expiry_secs;
+        }
+
+        if let Some(weight_per_day) = args.weight_per_day {
+            self.config.weight_per_day = weight_per_day;
+        }
+
+        if let Some(weight_decay_per_day) = args.weight_decay_per_day {
+            self.config.weight_decay_per_day = weight_decay_per_day;
+        }
+
+        self.config.validate_consistency()?;
+
+        Ok(())
+    }
+}
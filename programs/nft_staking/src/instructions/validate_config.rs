@@ -0,0 +1,20 @@
+use anchor_lang::prelude::*;
+
+use crate::instructions::InitializeConfigArgs;
+
+#[derive(Accounts)]
+pub struct ValidateConfig {}
+
+impl ValidateConfig {
+
+    // Read-only, meant to be simulated before initialize_config. Runs the same checks it does and
+    // returns the StakeError code the first failing one would reject `args` with, `None` if
+    // initialize_config would accept them
+    pub fn validate_config(&self, args: &InitializeConfigArgs)->Result<Option<u32>>{
+        match args.to_config().validate() {
+            Ok(()) => Ok(None),
+            Err(Error::AnchorError(error)) => Ok(Some(error.error_code_number)),
+            Err(error) => Err(error),
+        }
+    }
+}
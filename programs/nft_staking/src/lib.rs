@@ -1,10 +1,18 @@
 use anchor_lang::prelude::*;
 
+mod compressed;
+mod constants;
 mod errors;
+mod events;
+mod merkle;
+mod nft;
+mod receipt;
+mod rewards;
 mod state;
-use state::*;
 mod instructions;
+use compressed::{CompressedLeaf, LeafCreator};
 use instructions::*;
+use state::{LockupKind, RarityProof};
 
 declare_id!("519ZqTPoznvEMEh4rcxEL47JhG8yj3RiH5oE9SWhizj1");
 
@@ -12,28 +20,297 @@ declare_id!("519ZqTPoznvEMEh4rcxEL47JhG8yj3RiH5oE9SWhizj1");
 pub mod nft_staking {
     use super::*;
 
-    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
-        ctx.accounts.initialize_user_account(&ctx.bumps)?;
+    pub fn initialize(ctx: Context<Initialize>, referrer: Option<Pubkey>) -> Result<()> {
+        ctx.accounts.initialize_user_account(referrer, &ctx.bumps)?;
         Ok(())
     }
 
-    pub fn initialize_config(ctx: Context<InitializeConfig>,points_per_stake: u8, max_stake: u8, freeze_period: u32) -> Result<()> {
-        ctx.accounts.initialize_config(points_per_stake, max_stake, freeze_period, &ctx.bumps)?;
+    pub fn initialize_config(ctx: Context<InitializeConfig>, args: InitializeConfigArgs) -> Result<()> {
+        ctx.accounts.initialize_config(args, &ctx.bumps)?;
         Ok(())
     }
 
-    pub fn stake(ctx: Context<Stake>) -> Result<()> {
-        ctx.accounts.stake(&ctx.bumps)?;
+    pub fn update_config(ctx: Context<UpdateConfig>, args: UpdateConfigArgs) -> Result<()> {
+        ctx.accounts.update_config(args)?;
+        Ok(())
+    }
+
+    pub fn init_collection_config(ctx: Context<InitCollectionConfig>, points_multiplier: u16) -> Result<()> {
+        ctx.accounts.init_collection_config(points_multiplier, &ctx.bumps)?;
+        Ok(())
+    }
+
+    pub fn init_leaderboard(ctx: Context<InitLeaderboard>) -> Result<()> {
+        ctx.accounts.init_leaderboard(&ctx.bumps)?;
+        Ok(())
+    }
+
+    pub fn resize_leaderboard(ctx: Context<ResizeLeaderboard>, capacity: u32) -> Result<()> {
+        ctx.accounts.resize_leaderboard(capacity)?;
+        Ok(())
+    }
+
+    pub fn set_rarity_root(ctx: Context<SetRarityRoot>, root: [u8; 32]) -> Result<()> {
+        ctx.accounts.set_rarity_root(root, &ctx.bumps)?;
+        Ok(())
+    }
+
+    pub fn set_pause(ctx: Context<SetPause>, pause_flags: u8) -> Result<()> {
+        ctx.accounts.set_pause(pause_flags)?;
+        Ok(())
+    }
+
+    pub fn sync_totals(ctx: Context<SyncTotals>, total_staked: u32) -> Result<()> {
+        ctx.accounts.sync_totals(total_staked)?;
+        Ok(())
+    }
+
+    pub fn propose_admin(ctx: Context<ProposeAdmin>, new_admin: Pubkey) -> Result<()> {
+        ctx.accounts.propose_admin(new_admin)?;
+        Ok(())
+    }
+
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        ctx.accounts.accept_admin()?;
+        Ok(())
+    }
+
+    pub fn cancel_admin_transfer(ctx: Context<CancelAdminTransfer>) -> Result<()> {
+        ctx.accounts.cancel_admin_transfer()?;
+        Ok(())
+    }
+
+    pub fn adjust_points(ctx: Context<AdjustPoints>, delta: i64, reason_code: u8) -> Result<()> {
+        ctx.accounts.adjust_points(delta, reason_code)?;
+        Ok(())
+    }
+
+    pub fn add_to_blacklist(ctx: Context<AddToBlacklist>) -> Result<()> {
+        ctx.accounts.add_to_blacklist(&ctx.bumps)?;
+        Ok(())
+    }
+
+    pub fn remove_from_blacklist(ctx: Context<RemoveFromBlacklist>) -> Result<()> {
+        ctx.accounts.remove_from_blacklist()?;
+        Ok(())
+    }
+
+    pub fn allow_wallet(ctx: Context<AllowWallet>) -> Result<()> {
+        ctx.accounts.allow_wallet(&ctx.bumps)?;
+        Ok(())
+    }
+
+    pub fn disallow_wallet(ctx: Context<DisallowWallet>) -> Result<()> {
+        ctx.accounts.disallow_wallet()?;
+        Ok(())
+    }
+
+    pub fn distribute_snapshot(ctx: Context<DistributeSnapshot>) -> Result<()> {
+        ctx.accounts.distribute_snapshot()?;
+        Ok(())
+    }
+
+    pub fn stake(ctx: Context<Stake>, lockup: LockupKind, rarity: Option<RarityProof>) -> Result<()> {
+        ctx.accounts.stake(lockup, rarity, &ctx.bumps)?;
+        Ok(())
+    }
+
+    pub fn stake_batch<'info>(ctx: Context<'_, '_, 'info, 'info, StakeBatch<'info>>, mints: Vec<Pubkey>) -> Result<()> {
+        ctx.accounts.stake_batch(mints, ctx.remaining_accounts, &ctx.bumps)?;
         Ok(())
     }
 
     pub fn unstake(ctx: Context<Unstake>) -> Result<()> {
-        ctx.accounts.unstake()?;
+        ctx.accounts.unstake(&ctx.bumps)?;
+        Ok(())
+    }
+
+    pub fn emergency_unstake(ctx: Context<EmergencyUnstake>) -> Result<()> {
+        ctx.accounts.emergency_unstake(&ctx.bumps)?;
+        Ok(())
+    }
+
+    pub fn force_unstake(ctx: Context<ForceUnstake>) -> Result<()> {
+        ctx.accounts.force_unstake(&ctx.bumps)?;
+        Ok(())
+    }
+
+    pub fn admin_unstake(ctx: Context<AdminUnstake>) -> Result<()> {
+        ctx.accounts.admin_unstake(&ctx.bumps)?;
+        Ok(())
+    }
+
+    pub fn close_stake_account(ctx: Context<CloseStakeAccount>) -> Result<()> {
+        ctx.accounts.close_stake_account(&ctx.bumps)?;
+        Ok(())
+    }
+
+    pub fn stake_compressed<'info>(
+        ctx: Context<'_, '_, 'info, 'info, StakeCompressed<'info>>,
+        leaf: CompressedLeaf,
+        creators: Vec<LeafCreator>,
+    ) -> Result<()> {
+        ctx.accounts.stake_compressed(leaf, creators, ctx.remaining_accounts, &ctx.bumps)?;
+        Ok(())
+    }
+
+    pub fn unstake_compressed<'info>(
+        ctx: Context<'_, '_, 'info, 'info, UnstakeCompressed<'info>>,
+        leaf: CompressedLeaf,
+    ) -> Result<()> {
+        ctx.accounts.unstake_compressed(leaf, ctx.remaining_accounts, &ctx.bumps)?;
+        Ok(())
+    }
+
+    pub fn stake_tokens(ctx: Context<StakeTokens>, amount: u64) -> Result<()> {
+        ctx.accounts.stake_tokens(amount, &ctx.bumps)?;
+        Ok(())
+    }
+
+    pub fn unstake_tokens(ctx: Context<UnstakeTokens>, amount: u64) -> Result<()> {
+        ctx.accounts.unstake_tokens(amount)?;
+        Ok(())
+    }
+
+    pub fn claim<'info>(ctx: Context<'_, '_, 'info, 'info, Claim<'info>>) -> Result<()> {
+        ctx.accounts.claim(ctx.remaining_accounts, &ctx.bumps)?;
         Ok(())
     }
 
-    pub fn claim(ctx: Context<Claim>) -> Result<()> {
-        ctx.accounts.claim()?;
+    pub fn claim_many<'info>(ctx: Context<'_, '_, 'info, 'info, ClaimMany<'info>>) -> Result<()> {
+        ctx.accounts.claim_many(ctx.remaining_accounts)?;
+        Ok(())
+    }
+
+    pub fn set_claim_delegate(ctx: Context<SetClaimDelegate>, delegate: Option<Pubkey>) -> Result<()> {
+        ctx.accounts.set_claim_delegate(delegate)?;
+        Ok(())
+    }
+
+    pub fn revoke_claim_delegate(ctx: Context<RevokeClaimDelegate>) -> Result<()> {
+        ctx.accounts.revoke_claim_delegate()?;
+        Ok(())
+    }
+
+    pub fn compound(ctx: Context<Compound>) -> Result<()> {
+        ctx.accounts.compound()?;
+        Ok(())
+    }
+
+    pub fn compound_locked(ctx: Context<CompoundLocked>) -> Result<()> {
+        ctx.accounts.compound_locked()?;
+        Ok(())
+    }
+
+    pub fn claim_locked(ctx: Context<ClaimLocked>) -> Result<()> {
+        ctx.accounts.claim_locked()?;
+        Ok(())
+    }
+
+    pub fn lock_boost(ctx: Context<LockBoost>, amount: u64) -> Result<()> {
+        ctx.accounts.lock_boost(amount)?;
+        Ok(())
+    }
+
+    pub fn unlock_boost(ctx: Context<UnlockBoost>) -> Result<()> {
+        ctx.accounts.unlock_boost()?;
+        Ok(())
+    }
+
+    pub fn fund_vault(ctx: Context<FundVault>, amount: u64) -> Result<()> {
+        ctx.accounts.fund_vault(amount)?;
+        Ok(())
+    }
+
+    pub fn fund_snapshot(ctx: Context<FundSnapshot>, amount: u64) -> Result<()> {
+        ctx.accounts.fund_snapshot(amount)?;
+        Ok(())
+    }
+
+    pub fn claim_snapshot<'info>(ctx: Context<'_, '_, 'info, 'info, ClaimSnapshot<'info>>) -> Result<()> {
+        ctx.accounts.claim_snapshot(ctx.remaining_accounts)?;
+        Ok(())
+    }
+
+    pub fn close_user(ctx: Context<CloseUser>) -> Result<()> {
+        ctx.accounts.close_user()?;
+        Ok(())
+    }
+
+    pub fn migrate_user(ctx: Context<MigrateUser>) -> Result<()> {
+        ctx.accounts.migrate_user()?;
+        Ok(())
+    }
+
+    // `pool_id` only derives the config address, its layout can't be decoded to read it
+    pub fn migrate_config(ctx: Context<MigrateConfig>, _pool_id: u64) -> Result<()> {
+        ctx.accounts.migrate_config()?;
+        Ok(())
+    }
+
+    pub fn create_redemption(ctx: Context<CreateRedemption>, redemption_id: u64, cost_points: u32, max_redemptions: u32) -> Result<()> {
+        ctx.accounts.create_redemption(redemption_id, cost_points, max_redemptions, &ctx.bumps)?;
+        Ok(())
+    }
+
+    pub fn redeem(ctx: Context<Redeem>) -> Result<()> {
+        ctx.accounts.redeem(&ctx.bumps)?;
+        Ok(())
+    }
+
+    pub fn tokenize_position(ctx: Context<TokenizePosition>) -> Result<()> {
+        ctx.accounts.tokenize_position(&ctx.bumps)?;
+        Ok(())
+    }
+
+    pub fn detokenize_position(ctx: Context<DetokenizePosition>) -> Result<()> {
+        ctx.accounts.detokenize_position(&ctx.bumps)?;
+        Ok(())
+    }
+
+    pub fn get_pending_rewards<'info>(ctx: Context<'_, '_, 'info, 'info, GetPendingRewards<'info>>) -> Result<PendingRewards> {
+        ctx.accounts.get_pending_rewards(ctx.remaining_accounts)
+    }
+
+    pub fn get_stake_status(ctx: Context<GetStakeStatus>) -> Result<StakeStatus> {
+        ctx.accounts.get_stake_status()
+    }
+
+    pub fn harvest(ctx: Context<Harvest>, restart_lock: bool, lockup: Option<LockupKind>) -> Result<()> {
+        ctx.accounts.harvest(restart_lock, lockup)?;
+        Ok(())
+    }
+
+    pub fn init_epoch_history(ctx: Context<InitEpochHistory>) -> Result<()> {
+        ctx.accounts.init_epoch_history(&ctx.bumps)?;
+        Ok(())
+    }
+
+    pub fn snapshot_epoch(ctx: Context<SnapshotEpoch>) -> Result<()> {
+        ctx.accounts.snapshot_epoch()?;
+        Ok(())
+    }
+
+    pub fn set_full_set_bonus(ctx: Context<SetFullSetBonus>, collection_size: u16, full_set_bonus_bps: u16) -> Result<()> {
+        ctx.accounts.set_full_set_bonus(collection_size, full_set_bonus_bps)?;
+        Ok(())
+    }
+
+    pub fn unstake_many<'info>(ctx: Context<'_, '_, 'info, 'info, UnstakeMany<'info>>) -> Result<u8> {
+        ctx.accounts.unstake_many(ctx.remaining_accounts, &ctx.bumps)
+    }
+
+    pub fn transfer_mint_authority(ctx: Context<TransferMintAuthority>, new_authority: Pubkey) -> Result<()> {
+        ctx.accounts.transfer_mint_authority(new_authority)?;
+        Ok(())
+    }
+
+    pub fn validate_config(ctx: Context<ValidateConfig>, args: InitializeConfigArgs) -> Result<Option<u32>> {
+        ctx.accounts.validate_config(&args)
+    }
+
+    pub fn crank_accrue(ctx: Context<CrankAccrue>) -> Result<()> {
+        ctx.accounts.crank_accrue()?;
         Ok(())
     }
 
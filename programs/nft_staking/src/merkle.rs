@@ -0,0 +1,67 @@
+use anchor_lang::solana_program::hash::hashv;
+
+// Leaves and inner nodes hash with different prefixes, so a node can't be passed off as a leaf
+const LEAF_PREFIX: &[u8] = &[0];
+const NODE_PREFIX: &[u8] = &[1];
+
+/// Hash of a leaf made of `data`.
+pub fn hash_leaf(data: &[&[u8]]) -> [u8; 32] {
+    let mut slices = Vec::with_capacity(data.len() + 1);
+    slices.push(LEAF_PREFIX);
+    slices.extend_from_slice(data);
+    hashv(&slices).to_bytes()
+}
+
+/// Hash of the node above `a` and `b`. Pairs are sorted first, so proofs carry no left/right bits.
+pub fn hash_node(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    hashv(&[NODE_PREFIX, left, right]).to_bytes()
+}
+
+/// Whether `proof` leads from `leaf` up to `root`.
+pub fn verify(proof: &[[u8; 32]], root: &[u8; 32], leaf: [u8; 32]) -> bool {
+    proof.iter().fold(leaf, |node, sibling| hash_node(&node, sibling)) == *root
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A four leaf tree, returned with its root
+    fn tree() -> ([[u8; 32]; 4], [u8; 32]) {
+        let leaves = [b"a", b"b", b"c", b"d"].map(|data| hash_leaf(&[data]));
+        let root = hash_node(&hash_node(&leaves[0], &leaves[1]), &hash_node(&leaves[2], &leaves[3]));
+        (leaves, root)
+    }
+
+    #[test]
+    fn verifies_every_leaf() {
+        let (leaves, root) = tree();
+
+        assert!(verify(&[leaves[1], hash_node(&leaves[2], &leaves[3])], &root, leaves[0]));
+        assert!(verify(&[leaves[0], hash_node(&leaves[2], &leaves[3])], &root, leaves[1]));
+        assert!(verify(&[leaves[3], hash_node(&leaves[0], &leaves[1])], &root, leaves[2]));
+        assert!(verify(&[leaves[2], hash_node(&leaves[0], &leaves[1])], &root, leaves[3]));
+    }
+
+    #[test]
+    fn rejects_bad_proofs() {
+        let (leaves, root) = tree();
+        let proof = [leaves[1], hash_node(&leaves[2], &leaves[3])];
+
+        assert!(!verify(&proof, &root, hash_leaf(&[b"e"])));
+        assert!(!verify(&proof[..1], &root, leaves[0]));
+        assert!(!verify(&[leaves[2], proof[1]], &root, leaves[0]));
+        assert!(!verify(&[], &root, leaves[0]));
+    }
+
+    #[test]
+    fn inner_nodes_are_not_leaves() {
+        let (leaves, root) = tree();
+        let node = hash_node(&leaves[0], &leaves[1]);
+
+        // The node itself proves up to the root, but only as a raw hash, not as leaf data
+        assert!(verify(&[hash_node(&leaves[2], &leaves[3])], &root, node));
+        assert!(!verify(&[hash_node(&leaves[2], &leaves[3])], &root, hash_leaf(&[&leaves[0], &leaves[1]])));
+    }
+}
@@ -0,0 +1,618 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{token_2022::spl_token_2022::{extension::{BaseStateWithExtensions, StateWithExtensions}, state::{Account as TokenAccountState, AccountState, Mint}}, token_interface::{freeze_account, thaw_account, FreezeAccount, ThawAccount}};
+use anchor_spl::{metadata::mpl_token_metadata::{instructions::{DelegateUtilityV1Cpi, DelegateUtilityV1CpiAccounts, DelegateUtilityV1InstructionArgs, FreezeDelegatedAccountCpi, FreezeDelegatedAccountCpiAccounts, LockV1Cpi, LockV1CpiAccounts, LockV1InstructionArgs, RevokeUtilityV1Cpi, RevokeUtilityV1CpiAccounts, ThawDelegatedAccountCpi, ThawDelegatedAccountCpiAccounts, UnlockV1Cpi, UnlockV1CpiAccounts, UnlockV1InstructionArgs}, types::{Creator, TokenStandard}}, token_interface::{approve, close_account, revoke, transfer_checked, Approve, CloseAccount, Revoke, TransferChecked}};
+
+use spl_token_group_interface::state::TokenGroupMember;
+
+use crate::{constants::FREEZE_AUTHORITY_SEED, errors::StakeError, state::CustodyMode};
+
+/// Extra accounts token metadata needs to lock and unlock a programmable NFT.
+pub struct PnftAccounts<'a, 'info> {
+    pub token_record: &'a AccountInfo<'info>,
+    pub sysvar_instructions: &'a AccountInfo<'info>,
+    pub authorization_rules_program: Option<&'a AccountInfo<'info>>,
+    pub authorization_rules: Option<&'a AccountInfo<'info>>,
+}
+
+impl<'a, 'info> PnftAccounts<'a, 'info> {
+    /// Assembles the pNFT accounts from an instruction's optional accounts, `None` when they weren't passed.
+    pub fn from_optional(
+        token_record: Option<&'a AccountInfo<'info>>,
+        sysvar_instructions: Option<&'a AccountInfo<'info>>,
+        authorization_rules_program: Option<&'a AccountInfo<'info>>,
+        authorization_rules: Option<&'a AccountInfo<'info>>,
+    ) -> Option<Self> {
+        Some(Self {
+            token_record: token_record?,
+            sysvar_instructions: sysvar_instructions?,
+            authorization_rules_program,
+            authorization_rules,
+        })
+    }
+}
+
+/// Everything needed to lock a staked NFT, either in its owner's wallet with the stake account as
+/// delegate or in a `vault` ATA owned by the stake account.
+pub struct NftAccounts<'a, 'info> {
+    pub owner: &'a AccountInfo<'info>,
+    pub delegate: &'a AccountInfo<'info>,
+    pub mint: &'a AccountInfo<'info>,
+    pub token_account: &'a AccountInfo<'info>,
+    pub metadata: &'a AccountInfo<'info>,
+    pub edition: &'a AccountInfo<'info>,
+    pub token_program: &'a AccountInfo<'info>,
+    pub metadata_program: &'a AccountInfo<'info>,
+    pub system_program: &'a AccountInfo<'info>,
+    pub pnft: Option<PnftAccounts<'a, 'info>>,
+    pub vault: Option<&'a AccountInfo<'info>>,
+}
+
+enum LockKind {
+    Legacy,
+    Programmable,
+}
+
+fn lock_kind(token_standard: Option<&TokenStandard>) -> Result<LockKind> {
+    match token_standard {
+        // Metadata created before token standards existed is a plain NFT
+        None | Some(TokenStandard::NonFungible) => Ok(LockKind::Legacy),
+        Some(TokenStandard::ProgrammableNonFungible) => Ok(LockKind::Programmable),
+        Some(_) => err!(StakeError::UnsupportedTokenStandard),
+    }
+}
+
+/// Whether `creator` is listed in `creators` and has verified it, counterfeits can copy the
+/// list but can't sign for someone else's address.
+pub fn has_verified_creator(creators: Option<&[Creator]>, creator: &Pubkey) -> bool {
+    creators.is_some_and(|creators| creators.iter().any(|c| c.address == *creator && c.verified))
+}
+
+// The vault must be passed exactly when the stake uses it
+fn vault_for<'a, 'info>(custody_mode: CustodyMode, vault: Option<&'a AccountInfo<'info>>) -> Result<Option<&'a AccountInfo<'info>>> {
+    match (custody_mode, vault) {
+        (CustodyMode::FreezeDelegated, None) => Ok(None),
+        (CustodyMode::VaultTransfer, Some(vault)) => Ok(Some(vault)),
+        _ => err!(StakeError::CustodyModeMismatch),
+    }
+}
+
+/// The token group a Token-2022 NFT with no Metaplex metadata belongs to. Only the group's
+/// update authority can add a member, so the mint's own member extension can be trusted.
+pub fn token_group_of(mint: &AccountInfo) -> Result<Pubkey> {
+    require_keys_eq!(*mint.owner, anchor_spl::token_2022::ID, StakeError::NotGroupMember);
+    token_group_from_data(&mint.key(), &mint.try_borrow_data()?)
+}
+
+fn token_group_from_data(mint_key: &Pubkey, data: &[u8]) -> Result<Pubkey> {
+    let mint = StateWithExtensions::<Mint>::unpack(data)?;
+
+    // Without a master edition nothing else pins the supply, so it has to be fixed at one
+    require!(
+        mint.base.decimals == 0 && mint.base.supply == 1 && mint.base.mint_authority.is_none(),
+        StakeError::UnsupportedTokenStandard
+    );
+
+    let member = mint.get_extension::<TokenGroupMember>().map_err(|_| error!(StakeError::NotGroupMember))?;
+    require_keys_eq!(member.mint, *mint_key, StakeError::NotGroupMember);
+
+    Ok(member.group)
+}
+
+/// Errors if `token_account` is still frozen. A thaw that returns without thawing would otherwise
+/// let the stake close over an NFT nothing can thaw anymore.
+fn require_thawed(token_account: &AccountInfo) -> Result<()> {
+    require!(!is_frozen(&token_account.try_borrow_data()?)?, StakeError::NftStillFrozen);
+    Ok(())
+}
+
+fn is_frozen(data: &[u8]) -> Result<bool> {
+    let account = StateWithExtensions::<TokenAccountState>::unpack(data)?;
+    Ok(account.base.state == AccountState::Frozen)
+}
+
+/// Errors if `token_account` is already frozen or delegated to anyone but `delegate`, meaning
+/// another protocol holds it and locking would only fail deep in a CPI. pNFTs sit frozen by token
+/// metadata at rest, so only their delegate counts.
+fn require_unencumbered(token_account: &AccountInfo, delegate: &Pubkey, programmable: bool) -> Result<()> {
+    check_unencumbered(&token_account.try_borrow_data()?, delegate, programmable)
+}
+
+fn check_unencumbered(data: &[u8], delegate: &Pubkey, programmable: bool) -> Result<()> {
+    let account = StateWithExtensions::<TokenAccountState>::unpack(data)?;
+    require!(programmable || account.base.state != AccountState::Frozen, StakeError::TokenAccountFrozen);
+    require!(
+        !matches!(Option::<Pubkey>::from(account.base.delegate), Some(key) if key != *delegate),
+        StakeError::ExistingDelegate
+    );
+    Ok(())
+}
+
+fn has_delegate(data: &[u8]) -> Result<bool> {
+    let account = StateWithExtensions::<TokenAccountState>::unpack(data)?;
+    Ok(account.base.delegate.is_some())
+}
+
+/// Revokes whatever delegate `token_account` still has, `owner` signs. Skipped when there's none
+/// left, so an already-clear account never stands between the user and their thawed NFT.
+fn revoke_delegate<'info>(token_account: &AccountInfo<'info>, owner: &AccountInfo<'info>, token_program: &AccountInfo<'info>) -> Result<()> {
+    if !has_delegate(&token_account.try_borrow_data()?)? {
+        return Ok(());
+    }
+
+    let cpi_accounts = Revoke{
+        source: token_account.clone(),
+        authority: owner.clone(),
+    };
+
+    revoke(CpiContext::new(token_program.clone(), cpi_accounts))
+}
+
+/// Token-2022 NFTs whose collection is a token group rather than Metaplex metadata. Freezing
+/// needs the mint's freeze authority to be the program's `freeze_authority` PDA, vault pools
+/// take any.
+pub struct GroupMemberNftAccounts<'a, 'info> {
+    pub owner: &'a AccountInfo<'info>,
+    pub delegate: &'a AccountInfo<'info>,
+    pub mint: &'a AccountInfo<'info>,
+    pub token_account: &'a AccountInfo<'info>,
+    pub token_program: &'a AccountInfo<'info>,
+    pub freeze_authority: Option<&'a AccountInfo<'info>>,
+    pub vault: Option<&'a AccountInfo<'info>>,
+}
+
+impl<'a, 'info> GroupMemberNftAccounts<'a, 'info> {
+
+    // Returns the PDA with its bump once it's checked to be the mint's freeze authority
+    fn freeze_authority(&self) -> Result<(&'a AccountInfo<'info>, u8)> {
+        let freeze_authority = self.freeze_authority.ok_or(StakeError::InvalidFreezeAuthority)?;
+        let (key, bump) = Pubkey::find_program_address(&[FREEZE_AUTHORITY_SEED], &crate::ID);
+        require_keys_eq!(freeze_authority.key(), key, StakeError::InvalidFreezeAuthority);
+
+        let data = self.mint.try_borrow_data()?;
+        let mint = StateWithExtensions::<Mint>::unpack(&data)?;
+        require!(mint.base.freeze_authority == Some(key).into(), StakeError::InvalidFreezeAuthority);
+
+        Ok((freeze_authority, bump))
+    }
+
+    /// Vault mode: moves the NFT into the vault. Otherwise delegates it to `delegate` and freezes
+    /// it in place.
+    pub fn lock(&self, custody_mode: CustodyMode) -> Result<()> {
+        require_unencumbered(self.token_account, self.delegate.key, false)?;
+
+        if let Some(vault) = vault_for(custody_mode, self.vault)? {
+            let cpi_accounts = TransferChecked{
+                from: self.token_account.clone(),
+                mint: self.mint.clone(),
+                to: vault.clone(),
+                authority: self.owner.clone(),
+            };
+
+            let cpi_ctx = CpiContext::new(self.token_program.clone(), cpi_accounts);
+
+            return transfer_checked(cpi_ctx, 1, 0);
+        }
+
+        let (freeze_authority, bump) = self.freeze_authority()?;
+
+        let cpi_accounts = Approve{
+            to: self.token_account.clone(),
+            delegate: self.delegate.clone(),
+            authority: self.owner.clone(),
+        };
+
+        let cpi_ctx = CpiContext::new(self.token_program.clone(), cpi_accounts);
+
+        approve(cpi_ctx, 1)?;
+
+        let cpi_accounts = FreezeAccount{
+            account: self.token_account.clone(),
+            mint: self.mint.clone(),
+            authority: freeze_authority.clone(),
+        };
+
+        let seeds: &[&[u8]] = &[FREEZE_AUTHORITY_SEED, &[bump]];
+        let signer_seeds = &[seeds];
+        let cpi_ctx = CpiContext::new_with_signer(self.token_program.clone(), cpi_accounts, signer_seeds);
+
+        freeze_account(cpi_ctx)
+    }
+
+    /// Reverses `lock`. `signer_seeds` are the delegate PDA's seeds, which own the vault.
+    pub fn unlock(&self, custody_mode: CustodyMode, signer_seeds: &[&[&[u8]]]) -> Result<()> {
+        self.release(custody_mode, signer_seeds, true)
+    }
+
+    /// `unlock` without the owner's signature, the approval to the delegate is left for the
+    /// owner to revoke.
+    pub fn force_unlock(&self, custody_mode: CustodyMode, signer_seeds: &[&[&[u8]]]) -> Result<()> {
+        self.release(custody_mode, signer_seeds, false)
+    }
+
+    fn release(&self, custody_mode: CustodyMode, signer_seeds: &[&[&[u8]]], revoke: bool) -> Result<()> {
+        if let Some(vault) = vault_for(custody_mode, self.vault)? {
+            let cpi_accounts = TransferChecked{
+                from: vault.clone(),
+                mint: self.mint.clone(),
+                to: self.token_account.clone(),
+                authority: self.delegate.clone(),
+            };
+
+            let cpi_ctx = CpiContext::new_with_signer(self.token_program.clone(), cpi_accounts, signer_seeds);
+
+            transfer_checked(cpi_ctx, 1, 0)?;
+
+            let cpi_accounts = CloseAccount{
+                account: vault.clone(),
+                destination: self.owner.clone(),
+                authority: self.delegate.clone(),
+            };
+
+            let cpi_ctx = CpiContext::new_with_signer(self.token_program.clone(), cpi_accounts, signer_seeds);
+
+            return close_account(cpi_ctx);
+        }
+
+        let (freeze_authority, bump) = self.freeze_authority()?;
+
+        let cpi_accounts = ThawAccount{
+            account: self.token_account.clone(),
+            mint: self.mint.clone(),
+            authority: freeze_authority.clone(),
+        };
+
+        let seeds: &[&[u8]] = &[FREEZE_AUTHORITY_SEED, &[bump]];
+        let freeze_signer_seeds = &[seeds];
+        let cpi_ctx = CpiContext::new_with_signer(self.token_program.clone(), cpi_accounts, freeze_signer_seeds);
+
+        thaw_account(cpi_ctx)?;
+        require_thawed(self.token_account)?;
+
+        if !revoke {
+            return Ok(());
+        }
+        revoke_delegate(self.token_account, self.owner, self.token_program)
+    }
+}
+
+impl<'a, 'info> NftAccounts<'a, 'info> {
+
+    fn pnft(&self) -> Result<&PnftAccounts<'a, 'info>> {
+        self.pnft.as_ref().ok_or(error!(StakeError::MissingPnftAccounts))
+    }
+
+    fn vault(&self, custody_mode: CustodyMode) -> Result<Option<&'a AccountInfo<'info>>> {
+        vault_for(custody_mode, self.vault)
+    }
+
+    /// Vault mode: moves the NFT into the vault. Otherwise delegates it to `delegate` and
+    /// freezes (legacy) or locks (pNFT) it. `signer_seeds` are the delegate PDA's seeds.
+    pub fn lock(&self, token_standard: Option<&TokenStandard>, custody_mode: CustodyMode, signer_seeds: &[&[&[u8]]]) -> Result<()> {
+        let kind = lock_kind(token_standard)?;
+        require_unencumbered(self.token_account, self.delegate.key, matches!(kind, LockKind::Programmable))?;
+
+        if let Some(vault) = self.vault(custody_mode)? {
+            // pNFTs can only move through token metadata's TransferV1
+            require!(matches!(kind, LockKind::Legacy), StakeError::UnsupportedTokenStandard);
+
+            let cpi_accounts = TransferChecked{
+                from: self.token_account.clone(),
+                mint: self.mint.clone(),
+                to: vault.clone(),
+                authority: self.owner.clone(),
+            };
+
+            let cpi_ctx = CpiContext::new(self.token_program.clone(), cpi_accounts);
+
+            // NFTs have no decimals
+            return transfer_checked(cpi_ctx, 1, 0);
+        }
+
+        match kind {
+            LockKind::Legacy => {
+                let cpi_accounts = Approve{
+                    to: self.token_account.clone(),
+                    delegate: self.delegate.clone(),
+                    authority: self.owner.clone(),
+                };
+
+                let cpi_ctx = CpiContext::new(self.token_program.clone(), cpi_accounts);
+
+                approve(cpi_ctx, 1)?;
+
+                FreezeDelegatedAccountCpi::new(
+                    self.metadata_program,
+                    FreezeDelegatedAccountCpiAccounts{
+                        delegate: self.delegate,
+                        token_account: self.token_account,
+                        edition: self.edition,
+                        mint: self.mint,
+                        token_program: self.token_program,
+                    }
+                ).invoke_signed(signer_seeds)?;
+            }
+            LockKind::Programmable => {
+                let pnft = self.pnft()?;
+
+                DelegateUtilityV1Cpi::new(
+                    self.metadata_program,
+                    DelegateUtilityV1CpiAccounts{
+                        delegate_record: None,
+                        delegate: self.delegate,
+                        metadata: self.metadata,
+                        master_edition: Some(self.edition),
+                        token_record: Some(pnft.token_record),
+                        mint: self.mint,
+                        token: self.token_account,
+                        authority: self.owner,
+                        payer: self.owner,
+                        system_program: self.system_program,
+                        sysvar_instructions: pnft.sysvar_instructions,
+                        spl_token_program: Some(self.token_program),
+                        authorization_rules_program: pnft.authorization_rules_program,
+                        authorization_rules: pnft.authorization_rules,
+                    },
+                    DelegateUtilityV1InstructionArgs{ amount: 1, authorization_data: None },
+                ).invoke()?;
+
+                LockV1Cpi::new(
+                    self.metadata_program,
+                    LockV1CpiAccounts{
+                        authority: self.delegate,
+                        token_owner: Some(self.owner),
+                        token: self.token_account,
+                        mint: self.mint,
+                        metadata: self.metadata,
+                        edition: Some(self.edition),
+                        token_record: Some(pnft.token_record),
+                        payer: self.owner,
+                        system_program: self.system_program,
+                        sysvar_instructions: pnft.sysvar_instructions,
+                        spl_token_program: Some(self.token_program),
+                        authorization_rules_program: pnft.authorization_rules_program,
+                        authorization_rules: pnft.authorization_rules,
+                    },
+                    LockV1InstructionArgs{ authorization_data: None },
+                ).invoke_signed(signer_seeds)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reverses `lock`: moves the NFT back out of the vault and closes it to the owner, or
+    /// thaws (legacy) / unlocks (pNFT) the NFT and revokes the delegate.
+    pub fn unlock(&self, token_standard: Option<&TokenStandard>, custody_mode: CustodyMode, signer_seeds: &[&[&[u8]]]) -> Result<()> {
+        self.release(lock_kind(token_standard)?, custody_mode, signer_seeds, true)
+    }
+
+    /// `unlock` without the owner's signature, the approval to the delegate is left for the owner
+    /// to revoke. Token metadata needs the owner to sign a pNFT's unlock, so those are refused.
+    pub fn force_unlock(&self, token_standard: Option<&TokenStandard>, custody_mode: CustodyMode, signer_seeds: &[&[&[u8]]]) -> Result<()> {
+        let kind = lock_kind(token_standard)?;
+        require_unencumbered(self.token_account, self.delegate.key, matches!(kind, LockKind::Programmable))?;
+        require!(matches!(kind, LockKind::Legacy), StakeError::UnsupportedTokenStandard);
+        self.release(kind, custody_mode, signer_seeds, false)
+    }
+
+    fn release(&self, kind: LockKind, custody_mode: CustodyMode, signer_seeds: &[&[&[u8]]], revoke: bool) -> Result<()> {
+        if let Some(vault) = self.vault(custody_mode)? {
+            let cpi_accounts = TransferChecked{
+                from: vault.clone(),
+                mint: self.mint.clone(),
+                to: self.token_account.clone(),
+                authority: self.delegate.clone(),
+            };
+
+            let cpi_ctx = CpiContext::new_with_signer(self.token_program.clone(), cpi_accounts, signer_seeds);
+
+            transfer_checked(cpi_ctx, 1, 0)?;
+
+            let cpi_accounts = CloseAccount{
+                account: vault.clone(),
+                destination: self.owner.clone(),
+                authority: self.delegate.clone(),
+            };
+
+            let cpi_ctx = CpiContext::new_with_signer(self.token_program.clone(), cpi_accounts, signer_seeds);
+
+            return close_account(cpi_ctx);
+        }
+
+        match kind {
+            LockKind::Legacy => {
+                ThawDelegatedAccountCpi::new(
+                    self.metadata_program,
+                    ThawDelegatedAccountCpiAccounts{
+                        delegate: self.delegate,
+                        token_account: self.token_account,
+                        edition: self.edition,
+                        mint: self.mint,
+                        token_program: self.token_program,
+                    }
+                ).invoke_signed(signer_seeds)?;
+                require_thawed(self.token_account)?;
+
+                if revoke {
+                    revoke_delegate(self.token_account, self.owner, self.token_program)?;
+                }
+            }
+            LockKind::Programmable => {
+                let pnft = self.pnft()?;
+
+                UnlockV1Cpi::new(
+                    self.metadata_program,
+                    UnlockV1CpiAccounts{
+                        authority: self.delegate,
+                        token_owner: Some(self.owner),
+                        token: self.token_account,
+                        mint: self.mint,
+                        metadata: self.metadata,
+                        edition: Some(self.edition),
+                        token_record: Some(pnft.token_record),
+                        payer: self.owner,
+                        system_program: self.system_program,
+                        sysvar_instructions: pnft.sysvar_instructions,
+                        spl_token_program: Some(self.token_program),
+                        authorization_rules_program: pnft.authorization_rules_program,
+                        authorization_rules: pnft.authorization_rules,
+                    },
+                    UnlockV1InstructionArgs{ authorization_data: None },
+                ).invoke_signed(signer_seeds)?;
+
+                RevokeUtilityV1Cpi::new(
+                    self.metadata_program,
+                    RevokeUtilityV1CpiAccounts{
+                        delegate_record: None,
+                        delegate: self.delegate,
+                        metadata: self.metadata,
+                        master_edition: Some(self.edition),
+                        token_record: Some(pnft.token_record),
+                        mint: self.mint,
+                        token: self.token_account,
+                        authority: self.owner,
+                        payer: self.owner,
+                        system_program: self.system_program,
+                        sysvar_instructions: pnft.sysvar_instructions,
+                        spl_token_program: Some(self.token_program),
+                        authorization_rules_program: pnft.authorization_rules_program,
+                        authorization_rules: pnft.authorization_rules,
+                    },
+                ).invoke()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verified_creator_must_match_and_be_verified() {
+        let creator = Pubkey::new_unique();
+        let other = Pubkey::new_unique();
+        let entry = |address, verified| Creator { address, verified, share: 50 };
+
+        assert!(has_verified_creator(Some(&[entry(other, true), entry(creator, true)]), &creator));
+        assert!(!has_verified_creator(Some(&[entry(creator, false)]), &creator));
+        assert!(!has_verified_creator(Some(&[entry(other, true)]), &creator));
+        assert!(!has_verified_creator(Some(&[]), &creator));
+        assert!(!has_verified_creator(None, &creator));
+    }
+
+    #[test]
+    fn lock_kind_by_token_standard() {
+        assert!(matches!(lock_kind(None), Ok(LockKind::Legacy)));
+        assert!(matches!(lock_kind(Some(&TokenStandard::NonFungible)), Ok(LockKind::Legacy)));
+        assert!(matches!(lock_kind(Some(&TokenStandard::ProgrammableNonFungible)), Ok(LockKind::Programmable)));
+
+        for standard in [
+            TokenStandard::Fungible,
+            TokenStandard::FungibleAsset,
+            TokenStandard::NonFungibleEdition,
+            TokenStandard::ProgrammableNonFungibleEdition,
+        ] {
+            assert_eq!(lock_kind(Some(&standard)).err(), Some(StakeError::UnsupportedTokenStandard.into()));
+        }
+    }
+
+    #[test]
+    fn is_frozen_reads_the_account_state() {
+        use anchor_spl::token_2022::spl_token_2022::solana_program::program_pack::Pack;
+
+        for (state, frozen) in [(AccountState::Initialized, false), (AccountState::Frozen, true)] {
+            let mut data = vec![0; TokenAccountState::LEN];
+            TokenAccountState { amount: 1, state, ..TokenAccountState::default() }.pack_into_slice(&mut data);
+            assert_eq!(is_frozen(&data).unwrap(), frozen);
+        }
+
+        assert!(is_frozen(&[0; 10]).is_err());
+    }
+
+    #[test]
+    fn has_delegate_reads_the_delegate() {
+        use anchor_spl::token_2022::spl_token_2022::solana_program::{program_option::COption, program_pack::Pack};
+
+        for (delegate, delegated) in [(COption::None, false), (COption::Some(Pubkey::new_unique()), true)] {
+            let mut data = vec![0; TokenAccountState::LEN];
+            TokenAccountState { amount: 1, delegate, state: AccountState::Initialized, ..TokenAccountState::default() }.pack_into_slice(&mut data);
+            assert_eq!(has_delegate(&data).unwrap(), delegated);
+        }
+    }
+
+    #[test]
+    fn frozen_or_foreign_delegated_accounts_cant_be_locked() {
+        use anchor_spl::token_2022::spl_token_2022::solana_program::{program_option::COption, program_pack::Pack};
+
+        let stake = Pubkey::new_unique();
+        let account = |delegate, state| {
+            let mut data = vec![0; TokenAccountState::LEN];
+            TokenAccountState { amount: 1, delegate, state, ..TokenAccountState::default() }.pack_into_slice(&mut data);
+            data
+        };
+
+        let check = |delegate, state, programmable| check_unencumbered(&account(delegate, state), &stake, programmable).err();
+
+        assert_eq!(check(COption::None, AccountState::Initialized, false), None);
+        assert_eq!(check(COption::Some(stake), AccountState::Initialized, false), None);
+        assert_eq!(check(COption::Some(Pubkey::new_unique()), AccountState::Initialized, false), Some(error!(StakeError::ExistingDelegate)));
+        assert_eq!(check(COption::None, AccountState::Frozen, false), Some(error!(StakeError::TokenAccountFrozen)));
+
+        // pNFTs at rest are frozen by token metadata, only a foreign delegate counts
+        assert_eq!(check(COption::None, AccountState::Frozen, true), None);
+        assert_eq!(check(COption::Some(Pubkey::new_unique()), AccountState::Frozen, true), Some(error!(StakeError::ExistingDelegate)));
+    }
+
+    // A Token-2022 mint carrying `group`'s (member mint, group) in its member extension, if any
+    fn group_member_mint(group: Option<(Pubkey, Pubkey)>, supply: u64, decimals: u8) -> Vec<u8> {
+        use anchor_spl::token_2022::spl_token_2022::extension::{BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut};
+
+        let extensions: &[ExtensionType] = if group.is_some() { &[ExtensionType::TokenGroupMember] } else { &[] };
+        let mut data = vec![0; ExtensionType::try_calculate_account_len::<Mint>(extensions).unwrap()];
+        let mut mint = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        mint.base = Mint { supply, decimals, is_initialized: true, ..Mint::default() };
+        mint.pack_base();
+        mint.init_account_type().unwrap();
+        if let Some((member_mint, group)) = group {
+            let member = mint.init_extension::<TokenGroupMember>(false).unwrap();
+            member.mint = member_mint;
+            member.group = group;
+        }
+        data
+    }
+
+    #[test]
+    fn token_group_comes_from_the_member_extension() {
+        let mint = Pubkey::new_unique();
+        let group = Pubkey::new_unique();
+
+        assert_eq!(token_group_from_data(&mint, &group_member_mint(Some((mint, group)), 1, 0)).unwrap(), group);
+
+        // A member extension copied from another mint doesn't count
+        let other = Pubkey::new_unique();
+        assert_eq!(
+            token_group_from_data(&mint, &group_member_mint(Some((other, group)), 1, 0)).err(),
+            Some(StakeError::NotGroupMember.into())
+        );
+        assert_eq!(
+            token_group_from_data(&mint, &group_member_mint(None, 1, 0)).err(),
+            Some(StakeError::NotGroupMember.into())
+        );
+    }
+
+    #[test]
+    fn group_member_must_be_a_one_of_one() {
+        let mint = Pubkey::new_unique();
+        let group = Some((mint, Pubkey::new_unique()));
+
+        for (supply, decimals) in [(2, 0), (1, 6), (0, 0)] {
+            assert_eq!(
+                token_group_from_data(&mint, &group_member_mint(group, supply, decimals)).err(),
+                Some(StakeError::UnsupportedTokenStandard.into())
+            );
+        }
+    }
+}
@@ -0,0 +1,191 @@
+use anchor_lang::prelude::*;
+use anchor_spl::{associated_token::{create_idempotent, get_associated_token_address_with_program_id, Create}, token_2022::spl_token_2022::{extension::{non_transferable::NonTransferable, BaseStateWithExtensions, StateWithExtensions}, state::{Account as TokenAccountState, Mint}}, token_2022::Token2022, token_interface::{burn, mint_to, Burn, MintTo}};
+
+use crate::{errors::StakeError, state::{StakeAccount, StakeConfig}};
+
+/// A pool's soulbound receipt mint and `owner`'s receipt ATA. The mint is a Token-2022 mint with
+/// the NonTransferable extension, no decimals and the config PDA as mint authority, so a receipt
+/// can only leave `owner`'s wallet by being burned.
+pub struct ReceiptAccounts<'a, 'info> {
+    pub owner: &'a AccountInfo<'info>,
+    pub mint: &'a AccountInfo<'info>,
+    pub token_account: &'a AccountInfo<'info>,
+    pub token_program: &'a AccountInfo<'info>,
+}
+
+impl<'a, 'info> ReceiptAccounts<'a, 'info> {
+
+    /// Mints one receipt to `owner`, creating their receipt ATA if it doesn't exist yet.
+    /// `config` signs as mint authority through `signer_seeds`.
+    pub fn issue(
+        &self,
+        payer: &'a AccountInfo<'info>,
+        config: &'a AccountInfo<'info>,
+        associated_token_program: &'a AccountInfo<'info>,
+        system_program: &'a AccountInfo<'info>,
+        signer_seeds: &[&[&[u8]]],
+    ) -> Result<()> {
+        self.check_accounts()?;
+        require!(is_soulbound(&self.mint.try_borrow_data()?)?, StakeError::InvalidReceiptMint);
+
+        create_idempotent(CpiContext::new(associated_token_program.clone(), Create {
+            payer: payer.clone(),
+            associated_token: self.token_account.clone(),
+            authority: self.owner.clone(),
+            mint: self.mint.clone(),
+            system_program: system_program.clone(),
+            token_program: self.token_program.clone(),
+        }))?;
+
+        let cpi_accounts = MintTo {
+            mint: self.mint.clone(),
+            to: self.token_account.clone(),
+            authority: config.clone(),
+        };
+
+        mint_to(CpiContext::new_with_signer(self.token_program.clone(), cpi_accounts, signer_seeds), 1)
+    }
+
+    /// Burns one of `owner`'s receipts, `owner` signs. Fails with MissingReceipt if they no
+    /// longer hold one.
+    pub fn redeem(&self) -> Result<()> {
+        self.check_accounts()?;
+        require!(
+            !self.token_account.data_is_empty() && receipt_balance(&self.token_account.try_borrow_data()?)? > 0,
+            StakeError::MissingReceipt
+        );
+
+        let cpi_accounts = Burn {
+            mint: self.mint.clone(),
+            from: self.token_account.clone(),
+            authority: self.owner.clone(),
+        };
+
+        burn(CpiContext::new(self.token_program.clone(), cpi_accounts), 1)
+    }
+
+    /// `redeem`, doing nothing if `owner` no longer holds a receipt. A soulbound receipt only
+    /// leaves their wallet by being burned, so a missing one was already burned by them.
+    pub fn redeem_if_held(&self) -> Result<()> {
+        self.check_accounts()?;
+        if self.token_account.data_is_empty() || receipt_balance(&self.token_account.try_borrow_data()?)? == 0 {
+            return Ok(());
+        }
+        self.redeem()
+    }
+
+    /// Burns `owner`'s tokenized position receipt, `owner` signs. Fails with ReceiptRequired unless
+    /// they hold it and InvalidReceipt unless its supply is exactly one.
+    pub fn redeem_position(&self) -> Result<()> {
+        require_keys_eq!(self.token_program.key(), anchor_spl::token_2022::ID, StakeError::InvalidReceipt);
+        require!(mint_supply(&self.mint.try_borrow_data()?)? == 1, StakeError::InvalidReceipt);
+        require_keys_eq!(
+            self.token_account.key(),
+            get_associated_token_address_with_program_id(self.owner.key, self.mint.key, self.token_program.key),
+            StakeError::ReceiptRequired
+        );
+        require!(
+            !self.token_account.data_is_empty() && receipt_balance(&self.token_account.try_borrow_data()?)? == 1,
+            StakeError::ReceiptRequired
+        );
+
+        let cpi_accounts = Burn {
+            mint: self.mint.clone(),
+            from: self.token_account.clone(),
+            authority: self.owner.clone(),
+        };
+
+        burn(CpiContext::new(self.token_program.clone(), cpi_accounts), 1)
+    }
+
+    fn check_accounts(&self) -> Result<()> {
+        require_keys_eq!(self.token_program.key(), anchor_spl::token_2022::ID, StakeError::InvalidReceiptMint);
+        require_keys_eq!(
+            self.token_account.key(),
+            get_associated_token_address_with_program_id(self.owner.key, self.mint.key, self.token_program.key),
+            StakeError::MissingReceipt
+        );
+        Ok(())
+    }
+}
+
+/// Burns the receipt `stake_account` was issued if its owner, who signs, still holds it. For the
+/// ways a stake closes other than `unstake`, so none leaves a receipt behind without blocking
+/// the NFT's way out. Stakes issued one must pass the receipt accounts.
+pub(crate) fn burn_held_receipt<'info>(
+    config: &StakeConfig,
+    stake_account: &StakeAccount,
+    owner: &AccountInfo<'info>,
+    receipt_mint: Option<&UncheckedAccount<'info>>,
+    receipt_ata: Option<&UncheckedAccount<'info>>,
+    receipt_token_program: Option<&Program<'info, Token2022>>,
+) -> Result<()> {
+    if !stake_account.has_receipt {
+        return Ok(());
+    }
+
+    let (Some(receipt_mint), Some(receipt_ata), Some(receipt_token_program)) = (receipt_mint, receipt_ata, receipt_token_program) else {
+        return err!(StakeError::MissingReceipt);
+    };
+    // Burning one of any other mint the owner holds mustn't count
+    require!(config.receipt_mint == Some(receipt_mint.key()), StakeError::InvalidReceiptMint);
+
+    ReceiptAccounts{
+        owner,
+        mint: receipt_mint.as_ref(),
+        token_account: receipt_ata.as_ref(),
+        token_program: receipt_token_program.as_ref(),
+    }.redeem_if_held()
+}
+
+/// Whether `data` is a Token-2022 mint receipts can be issued from: non-transferable, no decimals.
+fn is_soulbound(data: &[u8]) -> Result<bool> {
+    let mint = StateWithExtensions::<Mint>::unpack(data)?;
+    Ok(mint.base.decimals == 0 && mint.get_extension::<NonTransferable>().is_ok())
+}
+
+fn mint_supply(data: &[u8]) -> Result<u64> {
+    Ok(StateWithExtensions::<Mint>::unpack(data)?.base.supply)
+}
+
+fn receipt_balance(data: &[u8]) -> Result<u64> {
+    Ok(StateWithExtensions::<TokenAccountState>::unpack(data)?.base.amount)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_spl::token_2022::spl_token_2022::extension::{BaseStateWithExtensionsMut, ExtensionType, StateWithExtensionsMut};
+
+    fn receipt_mint(non_transferable: bool, decimals: u8) -> Vec<u8> {
+        receipt_mint_with_supply(non_transferable, decimals, 0)
+    }
+
+    fn receipt_mint_with_supply(non_transferable: bool, decimals: u8, supply: u64) -> Vec<u8> {
+        let extensions: &[ExtensionType] = if non_transferable { &[ExtensionType::NonTransferable] } else { &[] };
+        let mut data = vec![0; ExtensionType::try_calculate_account_len::<Mint>(extensions).unwrap()];
+        let mut mint = StateWithExtensionsMut::<Mint>::unpack_uninitialized(&mut data).unwrap();
+        mint.base = Mint { decimals, supply, is_initialized: true, ..Mint::default() };
+        mint.pack_base();
+        mint.init_account_type().unwrap();
+        if non_transferable {
+            mint.init_extension::<NonTransferable>(false).unwrap();
+        }
+        data
+    }
+
+    #[test]
+    fn receipts_need_a_non_transferable_whole_token_mint() {
+        assert!(is_soulbound(&receipt_mint(true, 0)).unwrap());
+        assert!(!is_soulbound(&receipt_mint(false, 0)).unwrap());
+        assert!(!is_soulbound(&receipt_mint(true, 6)).unwrap());
+        assert!(is_soulbound(&[0; 10]).is_err());
+    }
+
+    #[test]
+    fn position_supply_reads_the_mint() {
+        assert_eq!(mint_supply(&receipt_mint_with_supply(false, 0, 1)).unwrap(), 1);
+        assert_eq!(mint_supply(&receipt_mint_with_supply(false, 0, 2)).unwrap(), 2);
+        assert!(mint_supply(&[0; 10]).is_err());
+    }
+}
@@ -0,0 +1,480 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::{BPS_DENOMINATOR, MAX_HALVINGS, SECONDS_PER_DAY}, errors::StakeError, events::PointsExpired, state::{StakeConfig, UserAccount}};
+
+/// `points_per_stake` after the halvings that have happened by `ts`, at most `MAX_HALVINGS` of
+/// them and never halved below 1.
+pub fn emission_rate(config: &StakeConfig, ts: i64) -> u64 {
+    let base = config.points_per_stake as u64;
+    (base >> halvings_at(config, ts)).max(base.min(1))
+}
+
+fn halvings_at(config: &StakeConfig, ts: i64) -> i64 {
+    if config.halving_interval_secs == 0 {
+        return 0;
+    }
+    (ts.saturating_sub(config.emission_start).max(0) / config.halving_interval_secs).min(MAX_HALVINGS)
+}
+
+// When the rate in force at `ts` next changes, `None` once it can't drop any further
+fn next_halving(config: &StakeConfig, ts: i64) -> Option<i64> {
+    if config.halving_interval_secs == 0 || halvings_at(config, ts) >= MAX_HALVINGS || emission_rate(config, ts) <= 1 {
+        return None;
+    }
+    halvings_at(config, ts)
+        .checked_add(1)?
+        .checked_mul(config.halving_interval_secs)?
+        .checked_add(config.emission_start)
+}
+
+// Multiplier of the tier `day` falls in, and the day the next tier starts
+fn tier_at(config: &StakeConfig, day: u32) -> (u64, Option<u32>) {
+    let mut multiplier_bps = BPS_DENOMINATOR as u64;
+    for tier in config.reward_tiers.iter().filter(|tier| tier.min_days > 0) {
+        if day < tier.min_days as u32 {
+            return (multiplier_bps, Some(tier.min_days as u32));
+        }
+        multiplier_bps = tier.multiplier_bps as u64;
+    }
+    (multiplier_bps, None)
+}
+
+/// Points for the first `days` of a stake opened at `staked_at`, `accrue_segment` over all of
+/// them rounded down once at the end.
+pub fn points_for_days(config: &StakeConfig, staked_at: i64, days: u32) -> Result<u64> {
+    Ok(accrue_segment(config, staked_at, 0, days)? / BPS_DENOMINATOR as u64)
+}
+
+/// What days `from_day` up to `to_day` of a stake opened at `staked_at` earn, in bps of a point so
+/// segments settled one after another sum to exactly what settling them at once would. Each day
+/// pays the emission rate in force when it starts times the multiplier of the reward tier it falls
+/// in, so the sum walks segments split at every tier and halving boundary. There are at most
+/// `REWARD_TIERS` + `MAX_HALVINGS` of those, a year of days costs the same as one.
+pub fn accrue_segment(config: &StakeConfig, staked_at: i64, from_day: u32, to_day: u32) -> Result<u64> {
+    let mut points_bps: u64 = 0;
+    let mut day = from_day;
+
+    while day < to_day {
+        let day_start = staked_at
+            .checked_add(day as i64 * SECONDS_PER_DAY)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        let rate = emission_rate(config, day_start);
+        let (multiplier_bps, next_tier) = tier_at(config, day);
+
+        let mut end = next_tier.map_or(to_day, |next_tier| next_tier.min(to_day));
+        if let Some(halving) = next_halving(config, day_start) {
+            // The first day starting at or after the halving pays the lower rate
+            let halving_day = halving
+                .checked_sub(staked_at)
+                .and_then(|offset| offset.checked_add(SECONDS_PER_DAY - 1))
+                .ok_or(StakeError::ArithmeticOverflow)?
+                / SECONDS_PER_DAY;
+            end = end.min(u32::try_from(halving_day).unwrap_or(u32::MAX));
+        }
+
+        points_bps = rate
+            .checked_mul((end - day) as u64)
+            .and_then(|segment| segment.checked_mul(multiplier_bps))
+            .and_then(|segment| points_bps.checked_add(segment))
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        day = end;
+    }
+
+    Ok(points_bps)
+}
+
+/// Whole days from `staked_at` to `now`, zero if the clock is behind `staked_at`.
+pub fn days_between(staked_at: i64, now: i64) -> Result<u32> {
+    let days = now
+        .checked_sub(staked_at)
+        .ok_or(StakeError::ArithmeticOverflow)?
+        .max(0) / SECONDS_PER_DAY;
+    u32::try_from(days).map_err(|_| error!(StakeError::ArithmeticOverflow))
+}
+
+/// Points an NFT staked at `staked_at` has earned by `now` at the collection's base 1x, capped at
+/// `max_stake_duration`, settled in one go. `StakeAccount::accrue` reaches the same total a
+/// segment at a time.
+pub fn calculate_pending(staked_at: i64, now: i64, config: &StakeConfig) -> Result<u64> {
+    points_for_days(config, staked_at, config.accrual_days(days_between(staked_at, now)?))
+}
+
+/// Multiplier of the reward tier a stake is in after `days`, in bps.
+pub fn tier_multiplier_bps(config: &StakeConfig, days: u32) -> u16 {
+    tier_at(config, days).0 as u16
+}
+
+/// `points` after `days` of `decay_rate_bps_per_day` decay, compounding daily and rounded down.
+pub fn decayed_points(points: u64, decay_rate_bps_per_day: u16, days: u32) -> u64 {
+    // Fixed point with 18 decimals, squared factors stay well inside u128
+    const ONE: u128 = 1_000_000_000_000_000_000;
+    let kept_bps = BPS_DENOMINATOR.saturating_sub(decay_rate_bps_per_day) as u128;
+
+    let mut factor = ONE;
+    let mut base = ONE * kept_bps / BPS_DENOMINATOR as u128;
+    let mut days = days;
+    while days > 0 && factor > 0 {
+        if days & 1 == 1 {
+            factor = factor * base / ONE;
+        }
+        base = base * base / ONE;
+        days >>= 1;
+    }
+
+    (points as u128 * factor / ONE) as u64
+}
+
+/// Whether points last credited or spent at `updated_at` have outlived `expiry_secs` by `now`.
+/// They're still live exactly `expiry_secs` later, and never expire before their first update.
+pub fn points_expired(updated_at: i64, expiry_secs: i64, now: i64) -> bool {
+    expiry_secs > 0 && updated_at > 0 && now.saturating_sub(updated_at) > expiry_secs
+}
+
+/// Forfeits the user's stale points through `UserAccount::apply_expiry`, emitting PointsExpired
+/// if there were any, and returns the points lost. Every instruction that credits or spends
+/// `points` calls it first, so each path expires them the same way, then
+/// `UserAccount::restart_expiry_if_changed` once it has.
+pub fn expire_points(config: &Account<StakeConfig>, user_account: &mut UserAccount, now: i64) -> Result<u64> {
+    let last_updated_at = user_account.points_updated_at;
+    let forfeited = user_account.apply_expiry(config, now);
+
+    if forfeited > 0 {
+        emit!(PointsExpired {
+            config: config.key(),
+            user: user_account.owner,
+            points_forfeited: forfeited,
+            last_updated_at,
+            expired_at: now,
+            slot: Clock::get()?.slot,
+        });
+    }
+
+    Ok(forfeited)
+}
+
+/// Points `amount` base units of the staked token earn over `days`, `points_per_stake` a whole
+/// token a day. Token stakes earn a flat rate, reward tiers and halvings only apply to NFTs.
+pub fn token_points_for_days(config: &StakeConfig, amount: u64, decimals: u8, days: u32) -> Result<u64> {
+    let unit = 10_u128.checked_pow(decimals as u32).ok_or(StakeError::ArithmeticOverflow)?;
+    let points = (amount as u128)
+        .checked_mul(config.points_per_stake as u128)
+        .and_then(|points| points.checked_mul(days as u128))
+        .ok_or(StakeError::ArithmeticOverflow)?
+        / unit;
+    u64::try_from(points).map_err(|_| error!(StakeError::ArithmeticOverflow))
+}
+
+/// `weight` after `elapsed_secs` more of settlement: it grows `weight_per_day` per staked NFT a
+/// day while `amount_staked` is non-zero, and drains `weight_decay_per_day` a day down to zero
+/// while it isn't. Both run per second, rounded down, and saturate rather than fail.
+pub fn governance_weight_after(weight: u64, amount_staked: u8, weight_per_day: u64, weight_decay_per_day: u64, elapsed_secs: i64) -> u64 {
+    let elapsed = elapsed_secs.max(0) as u128;
+    if amount_staked > 0 {
+        let earned = (weight_per_day as u128)
+            .saturating_mul(amount_staked as u128)
+            .saturating_mul(elapsed)
+            / SECONDS_PER_DAY as u128;
+        u64::try_from(earned).unwrap_or(u64::MAX).saturating_add(weight)
+    } else {
+        let lost = (weight_decay_per_day as u128).saturating_mul(elapsed) / SECONDS_PER_DAY as u128;
+        u64::try_from(lost).map_or(0, |lost| weight.saturating_sub(lost))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::RewardTier;
+
+    // 1x for the first 30 days, 1.5x for days 31-90, 2x beyond
+    fn tiered_config() -> StakeConfig {
+        StakeConfig {
+            points_per_stake: 10,
+            reward_tiers: [
+                RewardTier { min_days: 30, multiplier_bps: 15_000 },
+                RewardTier { min_days: 90, multiplier_bps: 20_000 },
+                RewardTier::default(),
+                RewardTier::default(),
+            ],
+            ..Default::default()
+        }
+    }
+
+    // 80 a day, halving every 10 days from t = 0
+    fn halving_config() -> StakeConfig {
+        StakeConfig {
+            points_per_stake: 80,
+            halving_interval_secs: 10 * SECONDS_PER_DAY,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn calculate_pending_counts_whole_days_up_to_the_cap() {
+        let config = StakeConfig { max_stake_duration: 60, ..tiered_config() };
+
+        assert_eq!(calculate_pending(1_000, 1_000 + 10 * SECONDS_PER_DAY - 1, &config).unwrap(), 90);
+        assert_eq!(calculate_pending(1_000, 1_000 + 10 * SECONDS_PER_DAY, &config).unwrap(), 100);
+        assert_eq!(calculate_pending(1_000, 0, &config).unwrap(), 0);
+        assert_eq!(calculate_pending(0, 365 * SECONDS_PER_DAY, &config).unwrap(), points_for_days(&config, 0, 60).unwrap());
+    }
+
+    #[test]
+    fn tier_multiplier_by_days() {
+        let config = tiered_config();
+
+        assert_eq!(tier_multiplier_bps(&config, 0), BPS_DENOMINATOR);
+        assert_eq!(tier_multiplier_bps(&config, 29), BPS_DENOMINATOR);
+        assert_eq!(tier_multiplier_bps(&config, 30), 15_000);
+        assert_eq!(tier_multiplier_bps(&config, 365), 20_000);
+    }
+
+    #[test]
+    fn points_for_days_walks_tiers() {
+        let config = tiered_config();
+
+        assert_eq!(points_for_days(&config, 0, 0).unwrap(), 0);
+        assert_eq!(points_for_days(&config, 0, 10).unwrap(), 100);
+        assert_eq!(points_for_days(&config, 0, 30).unwrap(), 300);
+        assert_eq!(points_for_days(&config, 0, 45).unwrap(), 300 + 225);
+        assert_eq!(points_for_days(&config, 0, 120).unwrap(), 300 + 900 + 600);
+    }
+
+    #[test]
+    fn points_for_days_rounds_down_once() {
+        let config = StakeConfig {
+            points_per_stake: 1,
+            reward_tiers: [
+                RewardTier { min_days: 1, multiplier_bps: 15_000 },
+                RewardTier::default(),
+                RewardTier::default(),
+                RewardTier::default(),
+            ],
+            ..Default::default()
+        };
+
+        // 1 + 1.5 + 1.5 + 1.5 = 5.5, not 1 + 1 + 1 + 1 rounded per day
+        assert_eq!(points_for_days(&config, 0, 4).unwrap(), 5);
+    }
+
+    #[test]
+    fn points_for_days_without_tiers_is_flat() {
+        let config = StakeConfig { points_per_stake: 10, ..Default::default() };
+
+        assert_eq!(points_for_days(&config, 0, 180).unwrap(), 1_800);
+    }
+
+    #[test]
+    fn no_halving_inside_the_stake() {
+        let config = halving_config();
+
+        assert_eq!(points_for_days(&config, 0, 10).unwrap(), 800);
+        assert_eq!(points_for_days(&config, 10 * SECONDS_PER_DAY, 10).unwrap(), 400);
+    }
+
+    #[test]
+    fn one_halving_inside_the_stake() {
+        let config = halving_config();
+
+        // Days 5-9 at 80, days 10-14 at 40
+        assert_eq!(points_for_days(&config, 5 * SECONDS_PER_DAY, 10).unwrap(), 5 * 80 + 5 * 40);
+    }
+
+    #[test]
+    fn three_halvings_inside_the_stake() {
+        let config = halving_config();
+
+        assert_eq!(points_for_days(&config, 0, 40).unwrap(), 10 * (80 + 40 + 20 + 10));
+        // Starting mid-epoch splits the first and last segments
+        assert_eq!(
+            points_for_days(&config, 5 * SECONDS_PER_DAY, 30).unwrap(),
+            5 * 80 + 10 * 40 + 10 * 20 + 5 * 10
+        );
+    }
+
+    #[test]
+    fn halving_mid_day_applies_from_the_next_day() {
+        let config = StakeConfig {
+            points_per_stake: 80,
+            halving_interval_secs: 3 * SECONDS_PER_DAY / 2,
+            ..Default::default()
+        };
+
+        // Days 0 and 1 start before the halving at day 1.5, day 2 starts after
+        assert_eq!(points_for_days(&config, 0, 3).unwrap(), 80 + 80 + 40);
+    }
+
+    #[test]
+    fn halvings_compose_with_tiers() {
+        let config = StakeConfig {
+            halving_interval_secs: 20 * SECONDS_PER_DAY,
+            ..tiered_config()
+        };
+
+        // Days 0-19 at 10, 20-29 at 5, then the 1.5x tier on 5 for days 30-39 and on 10 >> 2 = 2 from day 40
+        assert_eq!(
+            points_for_days(&config, 0, 45).unwrap(),
+            (20 * 10 * 10_000 + 10 * 5 * 10_000 + 10 * 5 * 15_000 + 5 * 2 * 15_000) / 10_000
+        );
+    }
+
+    #[test]
+    fn two_years_of_tiers_and_halvings_settle_in_one_walk() {
+        let config = StakeConfig {
+            halving_interval_secs: 90 * SECONDS_PER_DAY,
+            ..tiered_config()
+        };
+
+        // 30 days at 10 x1, 60 at 10 x1.5, then x2 on 5 for 90 days, 2 for 90 and the floor of 1
+        // for the last 460
+        assert_eq!(
+            points_for_days(&config, 0, 730).unwrap(),
+            30 * 10 + 60 * 15 + 90 * 5 * 2 + 90 * 2 * 2 + 460 * 2
+        );
+    }
+
+    #[test]
+    fn segments_sum_to_the_whole_walk() {
+        let config = StakeConfig {
+            halving_interval_secs: 90 * SECONDS_PER_DAY,
+            ..tiered_config()
+        };
+        let whole = accrue_segment(&config, 0, 0, 730).unwrap();
+
+        // Split inside a tier, on a tier boundary, on a halving and on the last day
+        for split in [7, 30, 90, 181, 729] {
+            let parts = accrue_segment(&config, 0, 0, split).unwrap() + accrue_segment(&config, 0, split, 730).unwrap();
+            assert_eq!(parts, whole);
+        }
+        assert_eq!(whole / BPS_DENOMINATOR as u64, points_for_days(&config, 0, 730).unwrap());
+        assert_eq!(accrue_segment(&config, 0, 400, 400).unwrap(), 0);
+    }
+
+    #[test]
+    fn emission_rate_floors_at_one() {
+        let config = StakeConfig {
+            points_per_stake: 3,
+            halving_interval_secs: SECONDS_PER_DAY,
+            ..Default::default()
+        };
+
+        assert_eq!(points_for_days(&config, 0, 5).unwrap(), 3 + 1 + 1 + 1 + 1);
+        assert_eq!(emission_rate(&config, i64::MAX), 1);
+        assert_eq!(emission_rate(&StakeConfig { points_per_stake: 0, ..config }, i64::MAX), 0);
+    }
+
+    #[test]
+    fn emission_rate_halves_exactly_at_each_interval() {
+        let config = StakeConfig { emission_start: 1_000, ..halving_config() };
+        let interval = config.halving_interval_secs;
+
+        assert_eq!(emission_rate(&config, 0), 80);
+        for halvings in 1..=4 {
+            let boundary = 1_000 + halvings * interval;
+            assert_eq!(emission_rate(&config, boundary - 1), 80 >> (halvings - 1));
+            assert_eq!(emission_rate(&config, boundary), 80 >> halvings);
+        }
+    }
+
+    #[test]
+    fn halvings_stop_at_the_cap() {
+        let config = StakeConfig { points_per_stake: u8::MAX, ..halving_config() };
+        let interval = config.halving_interval_secs;
+
+        assert_eq!(emission_rate(&config, MAX_HALVINGS * interval - 1), 255 >> (MAX_HALVINGS - 1));
+        assert_eq!(emission_rate(&config, MAX_HALVINGS * interval), 255 >> MAX_HALVINGS);
+        assert_eq!(emission_rate(&config, i64::MAX), 255 >> MAX_HALVINGS);
+        assert_eq!(next_halving(&config, MAX_HALVINGS * interval), None);
+
+        // Days past the cap keep paying the floor rate
+        let capped_at = (MAX_HALVINGS * 10) as u32;
+        assert_eq!(
+            points_for_days(&config, 0, capped_at + 10).unwrap() - points_for_days(&config, 0, capped_at).unwrap(),
+            10 * (255 >> MAX_HALVINGS) as u64
+        );
+    }
+
+    #[test]
+    fn token_points_scale_with_amount_and_time() {
+        let config = StakeConfig { points_per_stake: 10, ..Default::default() };
+
+        assert_eq!(token_points_for_days(&config, 1_000_000, 6, 1).unwrap(), 10);
+        assert_eq!(token_points_for_days(&config, 2_500_000, 6, 3).unwrap(), 75);
+        // Dust below a whole token-day rounds down
+        assert_eq!(token_points_for_days(&config, 99_999, 6, 1).unwrap(), 0);
+        assert_eq!(token_points_for_days(&config, 0, 6, 1_000).unwrap(), 0);
+    }
+
+    #[test]
+    fn token_points_overflow() {
+        let config = StakeConfig { points_per_stake: u8::MAX, ..Default::default() };
+
+        // u64::MAX * 255 * u32::MAX fits a u128 but not the u64 result
+        assert_eq!(
+            token_points_for_days(&config, u64::MAX, 0, u32::MAX).unwrap_err(),
+            error!(StakeError::ArithmeticOverflow)
+        );
+        assert!(token_points_for_days(&config, u64::MAX, 18, u32::MAX).is_ok());
+    }
+
+    #[test]
+    fn no_halvings_before_emission_start() {
+        let config = StakeConfig { emission_start: 100 * SECONDS_PER_DAY, ..halving_config() };
+
+        assert_eq!(points_for_days(&config, 0, 110).unwrap(), 110 * 80);
+        assert_eq!(points_for_days(&config, 0, 120).unwrap(), 110 * 80 + 10 * 40);
+    }
+
+    #[test]
+    fn points_decay_compounding_daily() {
+        assert_eq!(decayed_points(10_000, 100, 0), 10_000);
+        assert_eq!(decayed_points(10_000, 100, 1), 9_900);
+        // 0.99^2 = 0.9801
+        assert_eq!(decayed_points(10_000, 100, 2), 9_801);
+        // 0.5^10 = 1/1024
+        assert_eq!(decayed_points(1 << 20, 5_000, 10), 1 << 10);
+        assert_eq!(decayed_points(u64::MAX, 0, u32::MAX), u64::MAX);
+        assert_eq!(decayed_points(u64::MAX, BPS_DENOMINATOR, 1), 0);
+        assert_eq!(decayed_points(u64::MAX, 1, u32::MAX), 0);
+    }
+
+    #[test]
+    fn points_expire_only_past_the_window() {
+        assert!(!points_expired(1_000, 100, 1_100));
+        assert!(points_expired(1_000, 100, 1_101));
+        // Off, or before the first update
+        assert!(!points_expired(1_000, 0, i64::MAX));
+        assert!(!points_expired(0, 100, i64::MAX));
+        assert!(!points_expired(1_000, 100, 0));
+    }
+
+    #[test]
+    fn governance_weight_grows_staked_and_drains_idle() {
+        const DAY: i64 = SECONDS_PER_DAY;
+
+        // Two NFTs staked for a day and a half
+        let weight = governance_weight_after(0, 2, 100, 40, DAY + DAY / 2);
+        assert_eq!(weight, 300);
+        // Unstaked, two days idle
+        let weight = governance_weight_after(weight, 0, 100, 40, 2 * DAY);
+        assert_eq!(weight, 220);
+        // Restaked, growth picks up from what was left
+        let weight = governance_weight_after(weight, 1, 100, 40, DAY);
+        assert_eq!(weight, 320);
+        // Staked time never decays, idle time never grows
+        assert_eq!(governance_weight_after(weight, 1, 100, 1_000, DAY), 420);
+        assert_eq!(governance_weight_after(weight, 0, 1_000, 40, DAY), 280);
+        // Partial days round down
+        assert_eq!(governance_weight_after(0, 1, 100, 40, DAY / 3), 33);
+    }
+
+    #[test]
+    fn governance_weight_floors_at_zero_and_saturates() {
+        assert_eq!(governance_weight_after(50, 0, 100, 40, 30 * SECONDS_PER_DAY), 0);
+        assert_eq!(governance_weight_after(50, 0, 100, u64::MAX, i64::MAX), 0);
+        assert_eq!(governance_weight_after(50, 0, 100, 0, i64::MAX), 50);
+        assert_eq!(governance_weight_after(u64::MAX - 1, 255, u64::MAX, 0, i64::MAX), u64::MAX);
+        // Clock drift backwards changes nothing
+        assert_eq!(governance_weight_after(50, 3, 100, 40, -SECONDS_PER_DAY), 50);
+    }
+}
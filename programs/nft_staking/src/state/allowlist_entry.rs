@@ -0,0 +1,60 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, state::StakeConfig};
+
+/// Lets `wallet` stake in a `gated` pool. Entries outlive the gate, ungating a pool ignores them
+/// rather than closing them.
+#[account]
+pub struct AllowlistEntry{
+    pub config: Pubkey,
+    pub wallet: Pubkey,
+    pub added_at: i64,
+    pub bump: u8,
+}
+
+impl Space for AllowlistEntry {
+    const INIT_SPACE: usize = 8 + 32 + 32 + 8 + 1;
+}
+
+impl AllowlistEntry {
+
+    /// Checks a wallet may stake in `config`. `entry`, when passed, is already checked to be the
+    /// wallet's allowlist PDA, and only `allow_wallet` can allocate it.
+    pub fn require_allowed(config: &StakeConfig, entry: Option<&AccountInfo>) -> Result<()> {
+        if config.gated {
+            require!(
+                entry.is_some_and(|info| info.owner == &crate::ID && !info.data_is_empty()),
+                StakeError::NotAllowlisted
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn require_allowed(gated: bool, entry: Option<(&Pubkey, &mut [u8])>) -> Result<()> {
+        let config = StakeConfig { gated, ..Default::default() };
+        let key = Pubkey::new_unique();
+        let mut lamports = 1_000_000;
+        let info = entry.map(|(owner, data)| AccountInfo::new(&key, false, false, &mut lamports, data, owner, false, 0));
+        AllowlistEntry::require_allowed(&config, info.as_ref())
+    }
+
+    #[test]
+    fn only_gated_pools_need_an_allocated_entry() {
+        assert!(require_allowed(true, Some((&crate::ID, &mut [0; AllowlistEntry::INIT_SPACE]))).is_ok());
+        assert_eq!(require_allowed(true, None).err(), Some(error!(StakeError::NotAllowlisted)));
+        // Lamports sent to the PDA leave it an empty system account
+        assert_eq!(
+            require_allowed(true, Some((&anchor_lang::system_program::ID, &mut []))).err(),
+            Some(error!(StakeError::NotAllowlisted))
+        );
+
+        assert!(require_allowed(false, None).is_ok());
+        assert!(require_allowed(false, Some((&anchor_lang::system_program::ID, &mut []))).is_ok());
+    }
+}
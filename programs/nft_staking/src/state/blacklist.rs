@@ -0,0 +1,43 @@
+use anchor_lang::prelude::*;
+
+/// Marks `wallet` as flagged in a pool. While it exists the wallet can't stake or claim, and its
+/// unstakes pay no points.
+#[account]
+pub struct Blacklist{
+    pub config: Pubkey,
+    pub wallet: Pubkey,
+    pub added_at: i64,
+    pub bump: u8,
+}
+
+impl Space for Blacklist {
+    const INIT_SPACE: usize = 8 + 32 + 32 + 8 + 1;
+}
+
+impl Blacklist {
+
+    /// Whether `info`, already checked to be a wallet's blacklist PDA, holds a `Blacklist`. Only
+    /// `add_to_blacklist` can allocate the PDA, so lamports sent to it don't count.
+    pub fn is_set(info: &AccountInfo) -> bool {
+        info.owner == &crate::ID && !info.data_is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_set(owner: &Pubkey, data: &mut [u8]) -> bool {
+        let key = Pubkey::new_unique();
+        let mut lamports = 1_000_000;
+        Blacklist::is_set(&AccountInfo::new(&key, false, false, &mut lamports, data, owner, false, 0))
+    }
+
+    #[test]
+    fn only_an_allocated_program_account_blacklists() {
+        assert!(is_set(&crate::ID, &mut [0; Blacklist::INIT_SPACE]));
+        // Lamports sent to the PDA leave it an empty system account
+        assert!(!is_set(&anchor_lang::system_program::ID, &mut []));
+        assert!(!is_set(&anchor_lang::system_program::ID, &mut [0; Blacklist::INIT_SPACE]));
+    }
+}
@@ -0,0 +1,76 @@
+use anchor_lang::prelude::*;
+
+use crate::errors::StakeError;
+
+/// A collection a pool accepts and what its NFTs earn. Configs from before the full-set bonus are
+/// `V1_SPACE` and don't decode until `set_full_set_bonus` migrates them.
+#[account]
+pub struct CollectionConfig{
+    pub collection_mint: Pubkey,
+    pub points_multiplier: u16,
+    pub bump: u8,
+    // Fields from here on come after the bump so `try_from_legacy` can zero them on older layouts
+    // Distinct mints in the collection, a user with this many staked holds the full set. 0 turns
+    // the full-set bonus off
+    pub collection_size: u16,
+    // Bonus on the points a full set's pieces earn while it's held
+    pub full_set_bonus_bps: u16,
+}
+
+impl Space for CollectionConfig {
+    const INIT_SPACE: usize = 8 + 32 + 2 + 1 + 2 + 2;
+}
+
+impl CollectionConfig {
+
+    /// Size of configs from before `collection_size`.
+    pub const V1_SPACE: usize = Self::INIT_SPACE - 2 - 2;
+
+    /// Decodes a config still in the `V1_SPACE` layout, with the full-set bonus off.
+    pub fn try_from_legacy(data: &[u8]) -> Result<Self> {
+        require!(data.len() == Self::V1_SPACE, StakeError::ConfigAlreadyMigrated);
+        require!(data[..8] == *Self::DISCRIMINATOR, ErrorCode::AccountDiscriminatorMismatch);
+
+        let mut data = data.to_vec();
+        data.resize(Self::INIT_SPACE, 0);
+        Self::try_deserialize(&mut &data[..])
+    }
+
+    /// Whether `pieces_staked` distinct mints of this collection make up its full set.
+    pub fn is_full_set(&self, pieces_staked: u8) -> bool {
+        self.collection_size > 0 && pieces_staked as u16 >= self.collection_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn legacy_configs_migrate_without_a_full_set() {
+        let current = CollectionConfig {
+            collection_mint: Pubkey::new_unique(),
+            points_multiplier: 15_000,
+            bump: 254,
+            collection_size: 9,
+            full_set_bonus_bps: 9,
+        };
+        let mut data = Vec::new();
+        current.try_serialize(&mut data).unwrap();
+
+        let config = CollectionConfig::try_from_legacy(&data[..CollectionConfig::V1_SPACE]).unwrap();
+
+        assert_eq!((config.collection_mint, config.points_multiplier, config.bump), (current.collection_mint, 15_000, 254));
+        assert_eq!((config.collection_size, config.full_set_bonus_bps), (0, 0));
+        assert!(!config.is_full_set(u8::MAX));
+        assert_eq!(CollectionConfig::try_from_legacy(&data).err(), Some(error!(StakeError::ConfigAlreadyMigrated)));
+    }
+
+    #[test]
+    fn full_sets_need_every_piece() {
+        let config = CollectionConfig { collection_mint: Pubkey::default(), points_multiplier: 0, bump: 0, collection_size: 3, full_set_bonus_bps: 5_000 };
+
+        assert!(!config.is_full_set(2));
+        assert!(config.is_full_set(3));
+    }
+}
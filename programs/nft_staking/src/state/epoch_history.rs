@@ -0,0 +1,96 @@
+use anchor_lang::prelude::*;
+
+use crate::constants::EPOCH_HISTORY_SIZE;
+
+#[zero_copy]
+#[derive(Default, PartialEq, Eq, Debug)]
+pub struct EpochSnapshot {
+    pub epoch: u64,
+    // When `snapshot_epoch` ran, not the epoch's boundary
+    pub timestamp: i64,
+    // `StakeConfig.total_staked` at `timestamp`
+    pub total_staked: u32,
+    pub padding: [u8; 4],
+}
+
+/// A pool's `total_staked` over its last `EPOCH_HISTORY_SIZE` epochs, for reporting only. Epoch
+/// `n` sits in slot `n % EPOCH_HISTORY_SIZE`, so a slot can hold an older epoch when snapshots
+/// were skipped. Zero-copy like the `Leaderboard`.
+#[account(zero_copy)]
+pub struct EpochHistory{
+    pub config: Pubkey,
+    // Epochs are `StakeConfig.epoch_length_secs` windows counted from here, epoch 0 is unrecorded
+    pub started_at: i64,
+    pub entries: [EpochSnapshot; EPOCH_HISTORY_SIZE],
+    pub bump: u8,
+    pub padding: [u8; 7],
+}
+
+impl Space for EpochHistory {
+    const INIT_SPACE: usize = 8 + 32 + 8 + (8 + 8 + 4 + 4) * EPOCH_HISTORY_SIZE + 1 + 7;
+}
+
+impl EpochHistory {
+
+    /// The epoch `now` falls in with `epoch_length_secs` long epochs, or `None` while they're off.
+    pub fn epoch_at(&self, epoch_length_secs: u32, now: i64) -> Option<u64> {
+        if epoch_length_secs == 0 {
+            return None;
+        }
+        Some(now.saturating_sub(self.started_at).max(0) as u64 / epoch_length_secs as u64)
+    }
+
+    /// Writes `snapshot` to its epoch's slot, over the one `EPOCH_HISTORY_SIZE` epochs before it.
+    pub fn record(&mut self, snapshot: EpochSnapshot) {
+        self.entries[(snapshot.epoch % EPOCH_HISTORY_SIZE as u64) as usize] = snapshot;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history() -> EpochHistory {
+        EpochHistory {
+            config: Pubkey::default(),
+            started_at: 1_000,
+            entries: [EpochSnapshot::default(); EPOCH_HISTORY_SIZE],
+            bump: 0,
+            padding: [0; 7],
+        }
+    }
+
+    fn snapshot(epoch: u64) -> EpochSnapshot {
+        EpochSnapshot { epoch, timestamp: epoch as i64 * 10, total_staked: epoch as u32, padding: [0; 4] }
+    }
+
+    #[test]
+    fn epochs_count_from_the_start() {
+        let history = history();
+
+        assert_eq!(history.epoch_at(100, 1_000), Some(0));
+        assert_eq!(history.epoch_at(100, 1_099), Some(0));
+        assert_eq!(history.epoch_at(100, 1_100), Some(1));
+        assert_eq!(history.epoch_at(100, 1_350), Some(3));
+        // Clock drift before the start stays in epoch 0
+        assert_eq!(history.epoch_at(100, 900), Some(0));
+        assert_eq!(history.epoch_at(0, 1_350), None);
+    }
+
+    #[test]
+    fn wraps_around_after_a_full_history() {
+        let mut history = history();
+        for epoch in 1..=EPOCH_HISTORY_SIZE as u64 {
+            history.record(snapshot(epoch));
+        }
+        assert_eq!(history.entries[1], snapshot(1));
+        assert_eq!(history.entries[0], snapshot(EPOCH_HISTORY_SIZE as u64));
+
+        let next = EPOCH_HISTORY_SIZE as u64 + 1;
+        history.record(snapshot(next));
+
+        assert_eq!(history.entries[1], snapshot(next));
+        assert_eq!(history.entries[2], snapshot(2));
+        assert!(history.entries.iter().all(|entry| entry.epoch >= 2));
+    }
+}
@@ -0,0 +1,151 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::LEADERBOARD_SIZE, errors::StakeError};
+
+#[zero_copy]
+#[derive(Default, PartialEq, Eq, Debug)]
+pub struct LeaderboardEntry {
+    pub user: Pubkey,
+    // The user's `UserAccount.points_paid` when last ranked
+    pub points: u64,
+}
+
+/// A pool's top `capacity` users by points paid out, highest first. Unused slots are zeroed and
+/// sort last. Zero-copy, deserializing every slot on each claim would eat the compute budget.
+#[account(zero_copy)]
+pub struct Leaderboard{
+    pub config: Pubkey,
+    pub entries: [LeaderboardEntry; LEADERBOARD_SIZE],
+    // Slots that rank, at most `LEADERBOARD_SIZE`
+    pub capacity: u32,
+    pub bump: u8,
+    pub padding: [u8; 3],
+}
+
+impl Space for Leaderboard {
+    const INIT_SPACE: usize = 8 + 32 + (32 + 8) * LEADERBOARD_SIZE + 4 + 1 + 3;
+}
+
+impl Leaderboard {
+
+    /// Ranks `user` at `points`, moving their entry up if they're already on the board or
+    /// evicting the lowest entry if `points` beats it. Returns whether `user` is on the board.
+    pub fn record(&mut self, user: Pubkey, points: u64) -> bool {
+        let entries = &mut self.entries[..self.capacity as usize];
+        let Some(last) = entries.len().checked_sub(1) else {
+            return false;
+        };
+        let index = match entries.iter().position(|entry| entry.user == user && entry.points > 0) {
+            // Points paid only grow, an entry never has to move down
+            Some(index) => index,
+            None if points > entries[last].points => last,
+            None => return false,
+        };
+        entries[index] = LeaderboardEntry { user, points };
+
+        let mut index = index;
+        while index > 0 && entries[index - 1].points < points {
+            entries.swap(index - 1, index);
+            index -= 1;
+        }
+        true
+    }
+
+    /// Ranks the top `capacity` users from now on, dropping anyone ranked below it. Fails with
+    /// LeaderboardFull past the account's `LEADERBOARD_SIZE` slots.
+    pub fn resize(&mut self, capacity: u32) -> Result<()> {
+        require!(capacity as usize <= LEADERBOARD_SIZE, StakeError::LeaderboardFull);
+        self.entries[capacity as usize..].fill(LeaderboardEntry::default());
+        self.capacity = capacity;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leaderboard() -> Leaderboard {
+        Leaderboard {
+            config: Pubkey::default(),
+            entries: [LeaderboardEntry::default(); LEADERBOARD_SIZE],
+            capacity: LEADERBOARD_SIZE as u32,
+            bump: 0,
+            padding: [0; 3],
+        }
+    }
+
+    fn ranked(leaderboard: &Leaderboard) -> Vec<u64> {
+        leaderboard.entries.iter().map(|entry| entry.points).filter(|points| *points > 0).collect()
+    }
+
+    #[test]
+    fn inserts_in_order() {
+        let mut leaderboard = leaderboard();
+
+        for points in [30, 10, 20, 40] {
+            assert!(leaderboard.record(Pubkey::new_unique(), points));
+        }
+        assert_eq!(ranked(&leaderboard), [40, 30, 20, 10]);
+
+        // Nothing paid out yet doesn't rank
+        assert!(!leaderboard.record(Pubkey::new_unique(), 0));
+    }
+
+    #[test]
+    fn ranked_users_move_up_without_duplicates() {
+        let mut leaderboard = leaderboard();
+        let user = Pubkey::new_unique();
+
+        leaderboard.record(Pubkey::new_unique(), 50);
+        leaderboard.record(user, 10);
+        leaderboard.record(Pubkey::new_unique(), 30);
+
+        assert!(leaderboard.record(user, 60));
+        assert_eq!(ranked(&leaderboard), [60, 50, 30]);
+        assert_eq!(leaderboard.entries[0].user, user);
+        assert_eq!(leaderboard.entries.iter().filter(|entry| entry.user == user).count(), 1);
+    }
+
+    #[test]
+    fn full_boards_evict_the_lowest_entry() {
+        let mut leaderboard = leaderboard();
+        for points in 1..=LEADERBOARD_SIZE as u64 {
+            leaderboard.record(Pubkey::new_unique(), points * 10);
+        }
+
+        // Ties with the lowest entry don't evict it
+        assert!(!leaderboard.record(Pubkey::new_unique(), 10));
+
+        let user = Pubkey::new_unique();
+        assert!(leaderboard.record(user, 15));
+        assert_eq!(leaderboard.entries[LEADERBOARD_SIZE - 1], LeaderboardEntry { user, points: 15 });
+        assert_eq!(leaderboard.entries[LEADERBOARD_SIZE - 2].points, 20);
+        assert!(!ranked(&leaderboard).contains(&10));
+    }
+
+    #[test]
+    fn resizing_drops_entries_past_the_capacity() {
+        let mut leaderboard = leaderboard();
+        for points in [40, 30, 20, 10] {
+            leaderboard.record(Pubkey::new_unique(), points);
+        }
+
+        leaderboard.resize(2).unwrap();
+        assert_eq!(ranked(&leaderboard), [40, 30]);
+        assert!(!leaderboard.record(Pubkey::new_unique(), 25));
+        assert!(leaderboard.record(Pubkey::new_unique(), 35));
+        assert_eq!(ranked(&leaderboard), [40, 35]);
+
+        // Growing back only opens slots, nothing dropped comes back
+        leaderboard.resize(3).unwrap();
+        assert!(leaderboard.record(Pubkey::new_unique(), 5));
+        assert_eq!(ranked(&leaderboard), [40, 35, 5]);
+
+        leaderboard.resize(0).unwrap();
+        assert!(!leaderboard.record(Pubkey::new_unique(), 50));
+
+        assert!(leaderboard.resize(LEADERBOARD_SIZE as u32 + 1).is_err());
+        assert_eq!(leaderboard.capacity, 0);
+    }
+}
@@ -0,0 +1,95 @@
+use anchor_lang::prelude::*;
+
+use crate::{errors::StakeError, state::StakeConfig};
+
+/// Stake history of one mint in a pool. Unlike its `StakeAccount` it's never closed, so it
+/// remembers the last unstake for `StakeConfig.restake_cooldown_secs`.
+#[account]
+pub struct MintRecord{
+    pub config: Pubkey,
+    pub mint: Pubkey,
+    pub times_staked: u16,
+    // 0 until the mint's first unstake since the record was created
+    pub last_unstaked_at: i64,
+    pub bump: u8,
+}
+
+impl Space for MintRecord {
+    const INIT_SPACE: usize = 8 + 32 + 32 + 2 + 8 + 1;
+}
+
+impl MintRecord {
+
+    /// Fills in a record `init_if_needed` just created, a no-op on existing ones.
+    pub fn init_if_new(&mut self, config: Pubkey, mint: Pubkey, bump: u8) {
+        if self.mint == Pubkey::default() {
+            self.config = config;
+            self.mint = mint;
+            self.bump = bump;
+        }
+    }
+
+    /// Counts a new stake of the mint, erroring while `config.restake_cooldown_secs` hasn't
+    /// passed since its last unstake.
+    pub fn record_stake(&mut self, config: &StakeConfig, now: i64) -> Result<()> {
+        let cooldown_ends_at = self.last_unstaked_at
+            .checked_add(config.restake_cooldown_secs as i64)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+
+        // A zero cooldown means restakes are never held back
+        require!(
+            self.last_unstaked_at == 0 || now >= cooldown_ends_at,
+            StakeError::RestakeCooldownActive
+        );
+
+        // Saturates rather than failing, a counter shouldn't lock a mint out of staking
+        self.times_staked = self.times_staked.saturating_add(1);
+        Ok(())
+    }
+
+    pub fn record_unstake(&mut self, now: i64) {
+        self.last_unstaked_at = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record() -> MintRecord {
+        MintRecord { config: Pubkey::default(), mint: Pubkey::default(), times_staked: 0, last_unstaked_at: 0, bump: 0 }
+    }
+
+    #[test]
+    fn restakes_wait_out_the_cooldown() {
+        let config = StakeConfig { restake_cooldown_secs: 100, ..Default::default() };
+        let mut record = record();
+
+        record.record_stake(&config, 1_000).unwrap();
+        record.record_unstake(2_000);
+
+        assert_eq!(record.record_stake(&config, 2_099).unwrap_err(), error!(StakeError::RestakeCooldownActive));
+        record.record_stake(&config, 2_100).unwrap();
+        assert_eq!(record.times_staked, 2);
+
+        // A zero cooldown turns the check off
+        record.record_unstake(3_000);
+        record.record_stake(&StakeConfig::default(), 3_000).unwrap();
+        assert_eq!(record.times_staked, 3);
+    }
+
+    #[test]
+    fn init_if_new_keeps_existing_records() {
+        let mut record = record();
+        let (config, mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+
+        record.init_if_new(config, mint, 254);
+        record.init_if_new(Pubkey::new_unique(), Pubkey::new_unique(), 1);
+
+        assert_eq!((record.config, record.mint, record.bump), (config, mint, 254));
+
+        record.times_staked = u16::MAX;
+        record.record_stake(&StakeConfig::default(), 0).unwrap();
+        assert_eq!(record.times_staked, u16::MAX);
+    }
+}
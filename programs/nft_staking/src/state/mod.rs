@@ -5,4 +5,34 @@ pub mod stake_config;
 pub use stake_config::*;
 
 pub mod stake_account;
-pub use stake_account::*;
\ No newline at end of file
+pub use stake_account::*;
+
+pub mod collection_config;
+pub use collection_config::*;
+
+pub mod redemption;
+pub use redemption::*;
+
+pub mod token_stake_account;
+pub use token_stake_account::*;
+
+pub mod blacklist;
+pub use blacklist::*;
+
+pub mod allowlist_entry;
+pub use allowlist_entry::*;
+
+pub mod mint_record;
+pub use mint_record::*;
+
+pub mod user_positions;
+pub use user_positions::*;
+
+pub mod leaderboard;
+pub use leaderboard::*;
+
+pub mod rarity_map;
+pub use rarity_map::*;
+
+pub mod epoch_history;
+pub use epoch_history::*;
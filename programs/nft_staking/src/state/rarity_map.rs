@@ -0,0 +1,70 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::BPS_DENOMINATOR, errors::StakeError, merkle};
+
+/// A stake's claim to a rarity multiplier: `multiplier_bps` for the NFT being staked and the
+/// merkle proof of it against `RarityMap.root`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct RarityProof {
+    pub multiplier_bps: u16,
+    pub proof: Vec<[u8; 32]>,
+}
+
+/// Root of a merkle tree of `(mint, multiplier_bps)` leaves, kept by the admin with
+/// set_rarity_root. Mints not in the tree stake at 1x.
+#[account]
+pub struct RarityMap{
+    pub config: Pubkey,
+    pub root: [u8; 32],
+    pub bump: u8,
+}
+
+impl Space for RarityMap {
+    const INIT_SPACE: usize = 8 + 32 + 32 + 1;
+}
+
+impl RarityMap {
+
+    pub fn leaf(mint: &Pubkey, multiplier_bps: u16) -> [u8; 32] {
+        merkle::hash_leaf(&[mint.as_ref(), &multiplier_bps.to_le_bytes()])
+    }
+
+    /// The rarity multiplier `proof` establishes for `mint`, 1x without one. A proof that doesn't
+    /// verify fails with InvalidRarityProof rather than falling back to 1x.
+    pub fn multiplier_bps(rarity_map: Option<&RarityMap>, mint: &Pubkey, proof: Option<&RarityProof>) -> Result<u16> {
+        let Some(proof) = proof else {
+            return Ok(BPS_DENOMINATOR);
+        };
+        let rarity_map = rarity_map.ok_or(StakeError::InvalidRarityProof)?;
+        require!(
+            merkle::verify(&proof.proof, &rarity_map.root, Self::leaf(mint, proof.multiplier_bps)),
+            StakeError::InvalidRarityProof
+        );
+        Ok(proof.multiplier_bps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn proofs_set_the_multiplier() {
+        let rare = Pubkey::new_unique();
+        let common = Pubkey::new_unique();
+        let rare_leaf = RarityMap::leaf(&rare, 25_000);
+        let common_leaf = RarityMap::leaf(&common, 10_000);
+        let map = RarityMap { config: Pubkey::default(), root: merkle::hash_node(&rare_leaf, &common_leaf), bump: 0 };
+
+        let proof = RarityProof { multiplier_bps: 25_000, proof: vec![common_leaf] };
+        assert_eq!(RarityMap::multiplier_bps(Some(&map), &rare, Some(&proof)).unwrap(), 25_000);
+        assert_eq!(RarityMap::multiplier_bps(Some(&map), &rare, None).unwrap(), BPS_DENOMINATOR);
+        assert_eq!(RarityMap::multiplier_bps(None, &rare, None).unwrap(), BPS_DENOMINATOR);
+
+        // Claiming a higher multiplier, or another mint's, doesn't verify
+        let inflated = RarityProof { multiplier_bps: 50_000, proof: vec![common_leaf] };
+        assert_eq!(RarityMap::multiplier_bps(Some(&map), &rare, Some(&inflated)).unwrap_err(), error!(StakeError::InvalidRarityProof));
+        assert_eq!(RarityMap::multiplier_bps(Some(&map), &common, Some(&proof)).unwrap_err(), error!(StakeError::InvalidRarityProof));
+        assert_eq!(RarityMap::multiplier_bps(None, &rare, Some(&proof)).unwrap_err(), error!(StakeError::InvalidRarityProof));
+    }
+}
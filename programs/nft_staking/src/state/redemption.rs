@@ -0,0 +1,28 @@
+use anchor_lang::prelude::*;
+
+/// A perk users buy with points, e.g. a whitelist spot. Each user can redeem it once.
+#[account]
+pub struct Redemption{
+    pub config: Pubkey,
+    pub redemption_id: u64,
+    pub cost_points: u32,
+    pub max_redemptions: u32,
+    pub redeemed: u32,
+    pub bump: u8,
+}
+
+impl Space for Redemption {
+    const INIT_SPACE: usize = 8 + 32 + 8 + 4 + 4 + 4 + 1;
+}
+
+#[account]
+pub struct RedemptionReceipt{
+    pub redemption: Pubkey,
+    pub user: Pubkey,
+    pub redeemed_at: i64,
+    pub bump: u8,
+}
+
+impl Space for RedemptionReceipt {
+    const INIT_SPACE: usize = 8 + 32 + 32 + 8 + 1;
+}
@@ -1,13 +1,503 @@
 use anchor_lang::prelude::*;
 
+use crate::{constants::{BPS_DENOMINATOR, MAX_ACCRUAL_DAYS, SECONDS_PER_DAY}, errors::StakeError, rewards::{accrue_segment, calculate_pending, days_between}, state::{CustodyMode, FullSet, LockupKind, StakeConfig}};
+
+/// One per staked NFT, at `[b"stake", config, mint]`. The PDA itself holds the NFT: it's the
+/// freeze delegate, the vault ATA's owner or the cNFT leaf's owner, so stakes aren't folded into
+/// one per-user account. Its rent goes back to the owner when the stake closes.
 #[account]
 pub struct StakeAccount{
+    // The pool this NFT is staked in
+    pub config: Pubkey,
     pub owner: Pubkey,
+    // The NFT's mint, or the Bubblegum asset id of a `stake_compressed` stake
     pub mint: Pubkey,
+    // The points checkpoint: accrual counts from here, and `harvest` moves it past the whole days
+    // it realized so they can't be realized again. Closing the stake realizes the rest
     pub staked_at: i64,
+    // When unstake accepts this stake, past both the pool's freeze period and the lockup
+    pub unlocked_at: i64,
+    // Lockup picked at stake time and its freeze, already counted in `unlocked_at`
+    pub lockup: LockupKind,
+    pub lockup_secs: u32,
+    // The collection's multiplier scaled by the lockup's
+    pub points_multiplier: u16,
+    // Snapshot of `StakeConfig.custody_mode` so unstake knows where the NFT is
+    pub custody_mode: CustodyMode,
+    // Whether `stake` minted the owner a receipt from `StakeConfig.receipt_mint`, burned when the stake closes
+    pub has_receipt: bool,
+    // The transferable receipt `tokenize_position` minted, its holder controls the stake instead of `owner`
+    pub position_mint: Option<Pubkey>,
+    // The NFT's slot in `StakeConfig.set_collections`, if its collection is part of the set
+    pub set_piece: Option<u8>,
+    // Start of the first day not yet folded into `accrued_points`, `staked_at` until then
+    pub last_accrued_at: i64,
+    // What the days from `staked_at` to `last_accrued_at` earned at the config in force when they
+    // were settled, in bps of a point before `points_multiplier`
+    pub accrued_points: u64,
+    // When the NFT was staked, which `harvest` never moves. Snapshots and `min_reward_duration`
+    // go by it rather than by the checkpoint
+    pub opened_at: i64,
     pub bump: u8,
 }
 
 impl Space for StakeAccount {
-    const INIT_SPACE: usize = 8 + 32 + 32 + 8 + 1;
-}
\ No newline at end of file
+    const INIT_SPACE: usize = 8 + 32 + 32 + 32 + 8 + 8 + 1 + 4 + 2 + 1 + 1 + (1 + 32) + (1 + 1) + 8 + 8 + 8 + 1;
+}
+
+impl StakeAccount {
+
+    /// Whole days this NFT has been staked as of `now`, zero if the clock is behind `staked_at`.
+    pub fn days_staked(&self, now: i64) -> Result<u32> {
+        days_between(self.staked_at, now)
+    }
+
+    /// Seconds until `unstake` accepts this stake, zero from `unlocked_at` on.
+    pub fn seconds_until_unlock(&self, now: i64) -> i64 {
+        self.unlocked_at.saturating_sub(now).max(0)
+    }
+
+    pub fn is_unlocked(&self, now: i64) -> bool {
+        now >= self.unlocked_at
+    }
+
+    /// Fails with FreezePeriodNotPassed before `unlocked_at`, logging the stake's timing first so
+    /// the failed transaction shows how long is left. `get_stake_status` returns the same countdown.
+    pub fn require_unlocked(&self, now: i64) -> Result<()> {
+        if !self.is_unlocked(now) {
+            msg!(
+                "Stake of {} is frozen: staked_at {}, unlocked_at {}, now {}, {} seconds remaining",
+                self.mint,
+                self.staked_at,
+                self.unlocked_at,
+                now,
+                self.seconds_until_unlock(now)
+            );
+            return err!(StakeError::FreezePeriodNotPassed);
+        }
+        Ok(())
+    }
+
+    /// Folds up to `MAX_ACCRUAL_DAYS` more whole days, up to `max_stake_duration`, into
+    /// `accrued_points` and returns how many it settled. The part of a day left over keeps
+    /// accruing. The stake is caught up once `is_accrued` holds.
+    pub fn accrue(&mut self, config: &StakeConfig, now: i64) -> Result<u32> {
+        let from_day = self.accrued_days()?;
+        let to_day = self.accrual_target(config, now)?.min(from_day.saturating_add(MAX_ACCRUAL_DAYS));
+        if to_day <= from_day {
+            return Ok(0);
+        }
+
+        self.accrued_points = accrue_segment(config, self.staked_at, from_day, to_day)?
+            .checked_add(self.accrued_points)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        self.last_accrued_at = self.staked_at
+            .checked_add(to_day as i64 * SECONDS_PER_DAY)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        Ok(to_day - from_day)
+    }
+
+    /// Whether every whole day up to `now` is already in `accrued_points`.
+    pub fn is_accrued(&self, config: &StakeConfig, now: i64) -> Result<bool> {
+        Ok(self.accrual_target(config, now)? <= self.accrued_days()?)
+    }
+
+    /// `accrue`, failing with AccrualTooLong when one call can't catch the stake up. Every
+    /// instruction that realizes or reprices the stake's points settles it first.
+    pub fn settle(&mut self, config: &StakeConfig, now: i64) -> Result<()> {
+        self.accrue(config, now)?;
+        require!(self.is_accrued(config, now)?, StakeError::AccrualTooLong);
+        Ok(())
+    }
+
+    /// Moves the checkpoint past the whole days up to `now`, for harvests that just realized
+    /// them. The part of a day since keeps accruing toward the next.
+    pub fn advance_checkpoint(&mut self, now: i64) -> Result<()> {
+        self.staked_at = self.staked_at
+            .checked_add(self.days_staked(now)? as i64 * SECONDS_PER_DAY)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        self.last_accrued_at = self.staked_at;
+        self.accrued_points = 0;
+        Ok(())
+    }
+
+    /// Reopens the freeze period from `now` under `lockup`, rescaling `points_multiplier` to its
+    /// multiplier. Never unlocks the stake sooner than it would have.
+    pub fn relock(&mut self, config: &StakeConfig, lockup: LockupKind, now: i64) -> Result<()> {
+        let (lockup_secs, points_multiplier) = config.relock_terms(self.lockup, lockup, self.points_multiplier)?;
+
+        self.unlocked_at = config.unlocked_at(now, lockup_secs)?.max(self.unlocked_at);
+        self.lockup = lockup;
+        self.lockup_secs = lockup_secs;
+        self.points_multiplier = points_multiplier;
+        Ok(())
+    }
+
+    /// Whether this stake was open when a snapshot was taken at `snapshot_taken_at`.
+    pub fn in_snapshot(&self, snapshot_taken_at: i64) -> bool {
+        self.opened_at < snapshot_taken_at
+    }
+
+    /// Points earned by this stake so far under the config's emission schedule and tiered daily
+    /// rates, up to its `max_stake_duration`, scaled by the collection's `points_multiplier` (in bps).
+    /// Zero until the stake has lasted `min_reward_duration` since `opened_at`.
+    pub fn pending_points(&self, config: &StakeConfig, now: i64) -> Result<u64> {
+        if now.saturating_sub(self.opened_at) < config.min_reward_duration as i64 {
+            return Ok(0);
+        }
+        self.earned_points(config, now)
+    }
+
+    /// `config.set_bonus_bps` of the points this stake earned since the user's set was completed
+    /// at `set_complete_since`, 0 while it isn't. Breaking the set ends the window for every stake.
+    pub fn set_bonus_points(&self, config: &StakeConfig, set_complete_since: i64, now: i64) -> Result<u64> {
+        self.window_bonus_points(config, set_complete_since, config.set_bonus_bps, now)
+    }
+
+    /// `full_set.bonus_bps` of the points this stake earned since the full set of its collection
+    /// was completed, 0 while it isn't held. As with the set bonus, breaking it ends the window.
+    pub fn full_set_bonus_points(&self, config: &StakeConfig, full_set: FullSet, now: i64) -> Result<u64> {
+        self.window_bonus_points(config, full_set.since, full_set.bonus_bps, now)
+    }
+
+    /// `pending_points` plus the user's `set_bonus_points` and `full_set_bonus_points` on them.
+    pub fn pending_points_with_set_bonus(&self, config: &StakeConfig, set_complete_since: i64, full_set: FullSet, now: i64) -> Result<u64> {
+        let points = self.pending_points(config, now)?
+            .checked_add(self.set_bonus_points(config, set_complete_since, now)?)
+            .ok_or(StakeError::ArithmeticOverflow)?
+            .checked_add(self.full_set_bonus_points(config, full_set, now)?)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        Ok(points)
+    }
+
+    fn window_bonus_points(&self, config: &StakeConfig, since: i64, bonus_bps: u16, now: i64) -> Result<u64> {
+        if since == 0 || bonus_bps == 0 || self.pending_points(config, now)? == 0 {
+            return Ok(0);
+        }
+
+        let window_start = since.max(self.staked_at);
+        let points = self.earned_points(config, now)?
+            .saturating_sub(self.earned_points(config, window_start)?)
+            .checked_mul(bonus_bps as u64)
+            .ok_or(StakeError::ArithmeticOverflow)?
+            / BPS_DENOMINATOR as u64;
+        Ok(points)
+    }
+
+    // `accrued_points` plus the days since `last_accrued_at`, at the current config and without
+    // the `accrue` cap. A `now` inside the accrued days, only ever a bonus window's start, reprices
+    // the days before it at the current config instead
+    fn earned_points(&self, config: &StakeConfig, now: i64) -> Result<u64> {
+        let from_day = self.accrued_days()?;
+        let to_day = self.accrual_target(config, now)?;
+        let points = if to_day < from_day {
+            calculate_pending(self.staked_at, now, config)?
+        } else {
+            accrue_segment(config, self.staked_at, from_day, to_day)?
+                .checked_add(self.accrued_points)
+                .ok_or(StakeError::ArithmeticOverflow)?
+                / BPS_DENOMINATOR as u64
+        };
+        let points = points
+            .checked_mul(self.points_multiplier as u64)
+            .ok_or(StakeError::ArithmeticOverflow)?
+            / BPS_DENOMINATOR as u64;
+        Ok(points)
+    }
+
+    fn accrued_days(&self) -> Result<u32> {
+        days_between(self.staked_at, self.last_accrued_at)
+    }
+
+    // Whole days `now` pays for, as `calculate_pending` counts them
+    fn accrual_target(&self, config: &StakeConfig, now: i64) -> Result<u32> {
+        Ok(config.accrual_days(self.days_staked(now)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{constants::SECONDS_PER_DAY, state::{LockupOption, RewardTier}};
+
+    fn config(points_per_stake: u8) -> StakeConfig {
+        StakeConfig { points_per_stake, ..Default::default() }
+    }
+
+    fn stake_at(staked_at: i64) -> StakeAccount {
+        StakeAccount { config: Pubkey::default(), owner: Pubkey::default(), mint: Pubkey::default(), staked_at, unlocked_at: staked_at, lockup: LockupKind::Flexible, lockup_secs: 0, points_multiplier: BPS_DENOMINATOR, custody_mode: CustodyMode::FreezeDelegated, has_receipt: false, position_mint: None, set_piece: None, last_accrued_at: staked_at, accrued_points: 0, opened_at: staked_at, bump: 0 }
+    }
+
+    #[test]
+    fn pending_points_at_freeze_boundary() {
+        let stake = stake_at(1_000);
+        let freeze_period = 7;
+
+        assert_eq!(stake.pending_points(&config(10), 1_000 + freeze_period * SECONDS_PER_DAY).unwrap(), 70);
+        assert_eq!(stake.pending_points(&config(10), 1_000 + freeze_period * SECONDS_PER_DAY - 1).unwrap(), 60);
+    }
+
+    #[test]
+    fn pending_points_are_zero_before_min_reward_duration() {
+        let stake = stake_at(0);
+        let gated = StakeConfig { min_reward_duration: 3 * SECONDS_PER_DAY as u32, ..config(10) };
+
+        assert_eq!(stake.pending_points(&gated, 3 * SECONDS_PER_DAY - 1).unwrap(), 0);
+        // Once reached, the days before it count too
+        assert_eq!(stake.pending_points(&gated, 3 * SECONDS_PER_DAY).unwrap(), 30);
+        assert_eq!(stake.pending_points(&config(10), 3 * SECONDS_PER_DAY - 1).unwrap(), 20);
+    }
+
+    #[test]
+    fn set_bonus_only_covers_the_complete_window() {
+        let stake = stake_at(0);
+        let boosted = StakeConfig { set_bonus_bps: 5_000, ..config(10) };
+        let day = SECONDS_PER_DAY;
+
+        // Set completed on day 4 of a 10 day stake, half of the last 6 days' 60 points
+        assert_eq!(stake.set_bonus_points(&boosted, 4 * day, 10 * day).unwrap(), 30);
+        // A set completed before the stake only counts from the stake on
+        assert_eq!(stake.set_bonus_points(&boosted, 1, 10 * day).unwrap(), 50);
+        assert_eq!(stake.set_bonus_points(&boosted, 0, 10 * day).unwrap(), 0);
+        assert_eq!(stake.set_bonus_points(&config(10), 4 * day, 10 * day).unwrap(), 0);
+    }
+
+    #[test]
+    fn full_set_bonus_stacks_on_the_set_bonus() {
+        let stake = stake_at(0);
+        let boosted = StakeConfig { set_bonus_bps: 5_000, ..config(10) };
+        let day = SECONDS_PER_DAY;
+        let full_set = FullSet { since: 6 * day, bonus_bps: 10_000 };
+
+        // 100 base, 30 set bonus from day 4, 40 full-set bonus from day 6
+        assert_eq!(stake.full_set_bonus_points(&boosted, full_set, 10 * day).unwrap(), 40);
+        assert_eq!(stake.pending_points_with_set_bonus(&boosted, 4 * day, full_set, 10 * day).unwrap(), 170);
+        assert_eq!(stake.pending_points_with_set_bonus(&boosted, 0, FullSet::default(), 10 * day).unwrap(), 100);
+    }
+
+    #[test]
+    fn pending_points_after_many_days() {
+        let stake = stake_at(0);
+
+        assert_eq!(stake.pending_points(&config(10), 180 * SECONDS_PER_DAY).unwrap(), 1_800);
+        assert_eq!(stake.pending_points(&config(10), -SECONDS_PER_DAY).unwrap(), 0);
+    }
+
+    #[test]
+    fn pending_points_with_collection_multiplier() {
+        let mut stake = stake_at(0);
+        stake.points_multiplier = 15_000;
+
+        assert_eq!(stake.pending_points(&config(10), 30 * SECONDS_PER_DAY).unwrap(), 450);
+    }
+
+    #[test]
+    fn pending_points_past_u32() {
+        let stake = stake_at(0);
+
+        // Would have wrapped the old u32 points
+        assert_eq!(stake.pending_points(&config(u8::MAX), 20_000_000 * SECONDS_PER_DAY).unwrap(), 5_100_000_000);
+    }
+
+    #[test]
+    fn pending_points_at_every_maximum_fit_a_u64() {
+        let mut stake = stake_at(0);
+        stake.points_multiplier = u16::MAX;
+        let config = StakeConfig {
+            points_per_stake: u8::MAX,
+            reward_tiers: [
+                RewardTier { min_days: 1, multiplier_bps: u16::MAX },
+                RewardTier::default(),
+                RewardTier::default(),
+                RewardTier::default(),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(stake.pending_points(&config, u32::MAX as i64 * SECONDS_PER_DAY).unwrap(), 47_037_761_856_295);
+    }
+
+    #[test]
+    fn pending_points_stop_at_max_stake_duration() {
+        let stake = stake_at(0);
+        let config = StakeConfig { points_per_stake: 10, max_stake_duration: 30, ..Default::default() };
+
+        assert_eq!(stake.pending_points(&config, 20 * SECONDS_PER_DAY).unwrap(), 200);
+        // Staked 100 days, paid for 30
+        assert_eq!(stake.pending_points(&config, 100 * SECONDS_PER_DAY).unwrap(), 300);
+    }
+
+    #[test]
+    fn cranks_settle_two_years_like_one_walk() {
+        let config = StakeConfig {
+            points_per_stake: 10,
+            halving_interval_secs: 90 * SECONDS_PER_DAY,
+            reward_tiers: [
+                RewardTier { min_days: 30, multiplier_bps: 15_000 },
+                RewardTier { min_days: 90, multiplier_bps: 20_000 },
+                RewardTier::default(),
+                RewardTier::default(),
+            ],
+            ..Default::default()
+        };
+        let two_years = 730 * SECONDS_PER_DAY + SECONDS_PER_DAY / 2;
+        let mut stake = stake_at(0);
+        stake.points_multiplier = 15_000;
+        // Tiers and halvings as in `rewards`' two year walk, at the collection's 1.5x
+        let expected = (30 * 10 + 60 * 15 + 90 * 5 * 2 + 90 * 2 * 2 + 460 * 2) * 3 / 2;
+        assert_eq!(stake.pending_points(&config, two_years).unwrap(), expected);
+
+        // Too long for one settlement, so the first crank takes a year and leaves the rest
+        assert_eq!(stake.clone().settle(&config, two_years).unwrap_err(), error!(StakeError::AccrualTooLong));
+        assert_eq!(stake.accrue(&config, two_years).unwrap(), MAX_ACCRUAL_DAYS);
+        assert!(!stake.is_accrued(&config, two_years).unwrap());
+        assert_eq!(stake.pending_points(&config, two_years).unwrap(), expected);
+
+        // Cranks at odd times in between change nothing about the total
+        assert_eq!(stake.accrue(&config, 500 * SECONDS_PER_DAY + 17).unwrap(), 500 - MAX_ACCRUAL_DAYS);
+        stake.settle(&config, two_years).unwrap();
+        assert_eq!(stake.last_accrued_at, 730 * SECONDS_PER_DAY);
+        assert_eq!(stake.accrue(&config, two_years).unwrap(), 0);
+        assert_eq!(stake.pending_points(&config, two_years).unwrap(), expected);
+    }
+
+    #[test]
+    fn accrued_days_keep_the_config_they_were_settled_at() {
+        let mut stake = stake_at(0);
+        let day = SECONDS_PER_DAY;
+
+        stake.settle(&config(10), 10 * day).unwrap();
+        // Days 10-19 at the new rate, the first ten stay at the old one
+        assert_eq!(stake.pending_points(&config(20), 20 * day).unwrap(), 100 + 200);
+        // A harvest starts over
+        stake.advance_checkpoint(20 * day).unwrap();
+        assert_eq!((stake.staked_at, stake.last_accrued_at, stake.accrued_points), (20 * day, 20 * day, 0));
+        assert_eq!(stake.pending_points(&config(20), 21 * day).unwrap(), 20);
+    }
+
+    // What `harvest` does to the stake, banking nothing and leaving it be when it has nothing to bank
+    fn harvest(stake: &mut StakeAccount, config: &StakeConfig, now: i64) -> u64 {
+        stake.settle(config, now).unwrap();
+        let points = stake.pending_points(config, now).unwrap();
+        if points > 0 {
+            stake.advance_checkpoint(now).unwrap();
+        }
+        points
+    }
+
+    #[test]
+    fn harvest_then_unstake_banks_what_a_straight_unstake_would() {
+        let day = SECONDS_PER_DAY;
+        // A flat rate, tiers count again from a harvest by design
+        let config = StakeConfig { min_reward_duration: 2 * day as u32, ..config(10) };
+        let unstaked_at = 9 * day + day / 2;
+        let straight = stake_at(0).pending_points(&config, unstaked_at).unwrap();
+        assert_eq!(straight, 90);
+
+        // Before `min_reward_duration`, mid-day, twice, twice at once, and in the last partial day
+        for harvests in [
+            vec![day + day / 3],
+            vec![4 * day + day / 2],
+            vec![2 * day + 1, 6 * day + day / 4],
+            vec![4 * day + day / 2, 4 * day + day / 2],
+            vec![9 * day + day / 4],
+        ] {
+            let mut stake = stake_at(0);
+            let banked: u64 = harvests.iter().map(|&now| harvest(&mut stake, &config, now)).sum();
+
+            stake.settle(&config, unstaked_at).unwrap();
+            assert_eq!(banked + stake.pending_points(&config, unstaked_at).unwrap(), straight);
+        }
+    }
+
+    #[test]
+    fn relocking_restarts_the_freeze_but_never_shortens_it() {
+        let day = SECONDS_PER_DAY;
+        let config = StakeConfig {
+            freeze_period_secs: day as u64,
+            lockup_options: [
+                LockupOption { freeze_secs: 0, multiplier_bps: 10_000 },
+                LockupOption { freeze_secs: 30 * day as u32, multiplier_bps: 15_000 },
+                LockupOption::default(),
+            ],
+            ..config(10)
+        };
+        let mut stake = StakeAccount { unlocked_at: day, ..stake_at(0) };
+
+        // Into the 30 day lockup from a harvest on day 2
+        stake.relock(&config, LockupKind::ThirtyDay, 2 * day).unwrap();
+        assert_eq!((stake.lockup, stake.unlocked_at, stake.points_multiplier), (LockupKind::ThirtyDay, 32 * day, 15_000));
+
+        // Back to flexible keeps the later unlock time
+        stake.relock(&config, LockupKind::Flexible, 3 * day).unwrap();
+        assert_eq!((stake.lockup, stake.unlocked_at, stake.points_multiplier), (LockupKind::Flexible, 32 * day, 10_000));
+
+        // Without a restart, `harvest` leaves the lock alone
+        let mut unlocked = StakeAccount { unlocked_at: day, ..stake_at(0) };
+        harvest(&mut unlocked, &config, 2 * day);
+        assert_eq!(unlocked.unlocked_at, day);
+    }
+
+    #[test]
+    fn a_stake_harvested_after_a_snapshot_still_claims_it() {
+        let day = SECONDS_PER_DAY;
+        let mut stake = stake_at(day);
+
+        stake.advance_checkpoint(4 * day + 5).unwrap();
+        assert_eq!((stake.staked_at, stake.opened_at), (4 * day, day));
+
+        // Still in a snapshot taken before the harvest, never in one taken before the stake
+        assert!(stake.in_snapshot(2 * day));
+        assert!(!stake.in_snapshot(day));
+    }
+
+    #[test]
+    fn seconds_until_unlock_counts_down_to_zero() {
+        let mut stake = stake_at(1_000);
+        stake.unlocked_at = 1_000 + 2 * SECONDS_PER_DAY;
+
+        assert_eq!(stake.seconds_until_unlock(1_000), 2 * SECONDS_PER_DAY);
+        assert_eq!(stake.seconds_until_unlock(1_000 + 2 * SECONDS_PER_DAY - 1), 1);
+        assert_eq!(stake.seconds_until_unlock(1_000 + 2 * SECONDS_PER_DAY), 0);
+        assert_eq!(stake.seconds_until_unlock(i64::MAX), 0);
+
+        assert_eq!(stake.require_unlocked(1_000 + 2 * SECONDS_PER_DAY - 1).unwrap_err(), error!(StakeError::FreezePeriodNotPassed));
+        stake.require_unlocked(1_000 + 2 * SECONDS_PER_DAY).unwrap();
+    }
+
+    #[test]
+    fn unlocks_at_exactly_unlocked_at() {
+        let mut stake = stake_at(0);
+        stake.unlocked_at = 90;
+
+        assert!(!stake.is_unlocked(89));
+        assert_eq!(stake.seconds_until_unlock(89), 1);
+        assert!(stake.is_unlocked(90));
+        assert!(stake.is_unlocked(91));
+    }
+
+    #[test]
+    fn days_staked_overflow() {
+        let stake = stake_at(i64::MIN);
+
+        assert_eq!(stake.days_staked(i64::MAX).unwrap_err(), error!(StakeError::ArithmeticOverflow));
+    }
+
+    #[test]
+    fn pending_points_at_max_rate_and_tiers() {
+        let stake = stake_at(0);
+        let config = StakeConfig {
+            points_per_stake: u8::MAX,
+            reward_tiers: [
+                RewardTier { min_days: 1, multiplier_bps: u16::MAX },
+                RewardTier::default(),
+                RewardTier::default(),
+                RewardTier::default(),
+            ],
+            ..Default::default()
+        };
+
+        // 255 * 6.5535 a day, past u32::MAX after ~2.57M days
+        assert_eq!(stake.pending_points(&config, 3_000_000 * SECONDS_PER_DAY).unwrap(), 5_013_426_083);
+    }
+}
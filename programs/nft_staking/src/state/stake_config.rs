@@ -1,14 +1,1072 @@
 use anchor_lang::prelude::*;
 
+use crate::{constants::{BPS_DENOMINATOR, EMERGENCY_UNSTAKE_DISABLED, LOCKUP_KINDS, MAX_FREEZE_PERIOD_SECS, MAX_POSITIONS, PAUSE_CLAIM, REWARD_TIERS, SECONDS_PER_DAY, SET_COLLECTIONS}, errors::StakeError, state::UserAccount};
+
+/// From `min_days` onward a stake earns `points_per_stake` scaled by `multiplier_bps` instead of
+/// the previous tier's multiplier. Days before the first tier earn 1x. A tier with `min_days == 0` is unused.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct RewardTier {
+    pub min_days: u16,
+    pub multiplier_bps: u16,
+}
+
+/// Lockup a staker picks in `stake`, indexes `StakeConfig.lockup_options`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum LockupKind {
+    #[default]
+    Flexible,
+    ThirtyDay,
+    NinetyDay,
+}
+
+/// How long a lockup holds a stake on top of the pool's `freeze_period_secs` and the multiplier its
+/// points earn. A slot with `multiplier_bps == 0` isn't offered.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct LockupOption {
+    pub freeze_secs: u32,
+    pub multiplier_bps: u16,
+}
+
+/// Where a staked NFT is held: frozen in the owner's wallet with the stake account as delegate,
+/// or moved into a vault ATA owned by the stake account.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum CustodyMode {
+    #[default]
+    FreezeDelegated,
+    VaultTransfer,
+    // Per stake only, set by stake_compressed: the cNFT leaf is transferred to the stake account
+    Compressed,
+}
+
 #[account]
+#[derive(Default)]
 pub struct StakeConfig{
+    pub pool_id: u64,
+    pub admin: Pubkey,
+    // Proposed by the admin, becomes the admin once it signs accept_admin
+    pub pending_admin: Option<Pubkey>,
+    // Must appear verified in a staked NFT's metadata creators
+    pub creator: Pubkey,
+    // Rewards are paid out of `reward_vault`, the program holds no authority over this mint
+    pub reward_mint: Pubkey,
+    // Owner of the `reward_mint` ATA claims pay `fee_bps` on top of what the user is owed, and
+    // receiver of `claim_fee_lamports`
+    pub treasury: Pubkey,
+    pub fee_bps: u16,
+    // SOL the claimer pays `treasury` per claim, 0 turns it off
+    pub claim_fee_lamports: u64,
     pub points_per_stake: u8,
+    // Points credited to both sides when a user's first stake names a referrer
+    pub referral_bonus: u32,
+    // Cut of a referred user's unstake points credited to their `UserAccount.referrer`
+    pub referral_bps: u16,
     pub max_stake: u8,
-    pub freeze_period: u32,
-    pub rewards_bump: u8,
+    // How long a stake is held before unstake accepts it
+    pub freeze_period_secs: u64,
+    // One slot per `LockupKind`, in order
+    pub lockup_options: [LockupOption; LOCKUP_KINDS],
+    // Days after which a stake stops earning, 0 means no cap
+    pub max_stake_duration: u32,
+    // Cap on NFTs staked across all users, 0 means unlimited
+    pub global_max_stake: u32,
+    pub total_staked: u32,
+    // Users who have staked at least once and not closed their user account
+    pub total_users: u32,
+    // Points paid out as reward tokens by claim and unstake
+    pub total_points_distributed: u64,
+    // Cap on `total_points_distributed`, fixed at init. 0 leaves emissions uncapped
+    pub max_emission: u64,
+    pub early_unstake_penalty_bps: u16,
+    pub claim_cooldown: u32,
+    pub reward_tiers: [RewardTier; REWARD_TIERS],
+    // `points_per_stake` halves every `halving_interval_secs` from `emission_start`, 0 never halves
+    pub emission_start: i64,
+    pub halving_interval_secs: i64,
+    // Fungible token staked with stake_tokens, `None` turns token staking off
+    pub stake_token_mint: Option<Pubkey>,
+    // Per-user token stake cap in base units, 0 means unlimited
+    pub max_token_stake: u64,
+    // Soulbound Token-2022 mint `stake` issues a receipt from per NFT staked, `None` issues none.
+    // Batch and compressed stakes don't get one
+    pub receipt_mint: Option<Pubkey>,
+    // Points multiplier boost per whole reward token compounded, 0 turns compounding off
+    pub compound_boost_bps: u16,
+    // Cap on a user's total compound boost
+    pub max_compound_boost_bps: u16,
+    // Bonus on points moved into `UserAccount.locked_points` by compound_locked, 0 turns it off
+    pub compound_bonus_bps: u16,
+    // How long compound_locked holds points before claim_locked pays them out
+    pub compound_lock_secs: u32,
+    // Points multiplier boost per whole reward token locked with lock_boost, 0 turns boosting off
+    pub lock_boost_bps: u16,
+    // Cap on a user's total lock boost
+    pub max_lock_boost_bps: u16,
+    // How long lock_boost holds the tokens, the boost lasts as long
+    pub boost_lock_secs: u32,
+    // How long after an unstake the same mint can't be staked again, 0 turns it off
+    pub restake_cooldown_secs: u32,
+    // Seconds an NFT must stay staked before it earns anything, unlike `freeze_period_secs` it doesn't
+    // hold the NFT. 0 turns it off
+    pub min_reward_duration: u32,
+    // Collections making up a full set, fixed at init. `Pubkey::default()` slots are unused
+    pub set_collections: [Pubkey; SET_COLLECTIONS],
+    // Boost on points earned while a user has an NFT of every set collection staked
+    pub set_bonus_bps: u16,
+    // Share of a user's unclaimed `points` lost per whole day they go untouched, 0 turns it off
+    pub decay_rate_bps_per_day: u16,
+    // Largest adjust_points clawback allowed while claims aren't paused
+    pub max_clawback: u64,
+    // Reward token base units set aside with fund_snapshot, paid out by the next distribute_snapshot
+    pub snapshot_pool: u64,
+    // Id of the latest snapshot, 0 before the first
+    pub snapshot_id: u64,
+    pub snapshot_taken_at: i64,
+    // NFTs staked when the latest snapshot was taken, claims are a share of it
+    pub snapshot_total_weight: u32,
+    pub snapshot_amount: u64,
+    // What's left of `snapshot_amount`, rolled into the next snapshot
+    pub snapshot_unclaimed: u64,
+    // Bitset of the `PAUSE_*` constants
+    pub pause_flags: u8,
+    // Applies to new stakes, each stake keeps the mode it was opened with
+    pub custody_mode: CustodyMode,
+    pub reward_vault_bump: u8,
     pub bump: u8,
+    // Fields from here on come after the bumps so `try_from_legacy` can zero them on older layouts
+    // Staking needs an `AllowlistEntry` while set
+    pub gated: bool,
+    // Least `points` a claim accepts, 0 turns it off
+    pub min_claim_points: u64,
+    // Claims fail while any of the user's listed stakes is before its `unlocked_at`
+    pub claim_requires_unlocked: bool,
+    // Length of an `EpochHistory` epoch, 0 turns `snapshot_epoch` off
+    pub epoch_length_secs: u32,
+    // Latest epoch `snapshot_epoch` recorded
+    pub current_epoch: u64,
+    // Stakes a wallet may open per `window_len` seconds, 0 turns the limit off
+    pub max_stakes_per_window: u16,
+    pub window_len: u32,
+    // Unclaimed `points` untouched for longer than this are forfeited whole, 0 lets them live forever
+    pub points_expiry_secs: i64,
+    // `UserAccount.governance_weight` gained per staked NFT per day, accrued by the second
+    pub weight_per_day: u64,
+    // Weight lost per day while a user has nothing staked, floored at 0
+    pub weight_decay_per_day: u64,
 }
 
 impl Space for StakeConfig {
-    const INIT_SPACE: usize = 8 + 1 + 1 + 4 + 1 + 1;
-}
\ No newline at end of file
+    const INIT_SPACE: usize = 8 + 8 + 32 + (1 + 32) + 32 + 32 + 32 + 2 + 8 + 1 + 4 + 2 + 1 + 8 + (4 + 2) * LOCKUP_KINDS + 4 + 4 + 4 + 4 + 8 + 8 + 2 + 4 + (2 + 2) * REWARD_TIERS + 8 + 8 + (1 + 32) + 8 + (1 + 32) + 2 + 2 + 2 + 4 + 2 + 2 + 4 + 4 + 4 + 32 * SET_COLLECTIONS + 2 + 2 + 8 + 8 + 8 + 8 + 4 + 8 + 8 + 1 + 1 + 1 + 1 + 1 + 8 + 1 + 4 + 8 + 2 + 4 + 8 + 8 + 8;
+}
+
+impl StakeConfig {
+
+    /// Size of configs from before `freeze_period_secs`, see `migrate_config`.
+    pub const V1_SPACE: usize = Self::V2_SPACE - 8 + 4;
+
+    /// Size of configs from before the fields after the bumps, `gated` on.
+    pub const V2_SPACE: usize = Self::V3_SPACE - 1 - 8 - 1 - 4 - 8 - 2 - 4 - 8 - 8 - 8;
+
+    /// Size of configs from before `collection_mint` was dropped, whitelisting is per `CollectionConfig`.
+    pub const V3_SPACE: usize = Self::INIT_SPACE + 32;
+
+    /// Size of the original config at `[b"config"]`, from before pools: `points_per_stake`,
+    /// `max_stake`, a `u32` freeze period in days and the two bumps.
+    pub const BASELINE_SPACE: usize = 8 + 1 + 1 + 4 + 1 + 1;
+
+    /// Decodes the terms of a config in the `BASELINE_SPACE` layout, converting its freeze period
+    /// to seconds. Every other field is left default for `initialize_config` to fill in.
+    pub fn try_from_baseline(data: &[u8]) -> Result<Self> {
+        require!(data.len() == Self::BASELINE_SPACE, StakeError::ConfigAlreadyMigrated);
+        require!(data[..8] == *Self::DISCRIMINATOR, ErrorCode::AccountDiscriminatorMismatch);
+
+        let days = u32::from_le_bytes(data[10..14].try_into().unwrap());
+
+        Ok(Self {
+            points_per_stake: data[8],
+            max_stake: data[9],
+            freeze_period_secs: days as u64 * SECONDS_PER_DAY as u64,
+            ..Default::default()
+        })
+    }
+
+    /// Decodes a config still in the `V1_SPACE` layout, whose `freeze_period` was a `u32` of days
+    /// where `freeze_period_secs` now sits, converting it to seconds, or in the `V2_SPACE` or
+    /// `V3_SPACE` one, dropping the `collection_mint` all three hold. The first two come out with
+    /// every field after the bumps zeroed, so ungated.
+    pub fn try_from_legacy(data: &[u8]) -> Result<Self> {
+        require!(
+            data.len() == Self::V1_SPACE || data.len() == Self::V2_SPACE || data.len() == Self::V3_SPACE,
+            StakeError::ConfigAlreadyMigrated
+        );
+        require!(data[..8] == *Self::DISCRIMINATOR, ErrorCode::AccountDiscriminatorMismatch);
+
+        let mut data = data.to_vec();
+
+        // `pending_admin` is the only option ahead of the fields touched here, a `None` shifts them up 32 bytes
+        let pending_admin_len = if data[8 + 8 + 32] == 0 { 1 } else { 1 + 32 };
+
+        if data.len() == Self::V1_SPACE {
+            let at = 8 + 8 + 32 + pending_admin_len + 32 + 32 + 32 + 32 + 2 + 8 + 1 + 4 + 2 + 1;
+            let days = u32::from_le_bytes(data[at..at + 4].try_into().unwrap());
+            data.splice(at..at + 4, (days as u64 * SECONDS_PER_DAY as u64).to_le_bytes());
+        }
+
+        // `collection_mint`, right after `pending_admin`
+        let at = 8 + 8 + 32 + pending_admin_len;
+        data.drain(at..at + 32);
+
+        // Everything after the bumps decodes as zero
+        data.resize(Self::INIT_SPACE, 0);
+        Self::try_deserialize(&mut &data[..])
+    }
+
+    pub fn is_paused(&self, flag: u8) -> bool {
+        self.pause_flags & flag != 0
+    }
+
+    /// Counts `count` new stakes against `global_max_stake`.
+    pub fn add_staked(&mut self, count: u32) -> Result<()> {
+        let total_staked = self.total_staked
+            .checked_add(count)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        require!(
+            self.global_max_stake == 0 || total_staked <= self.global_max_stake,
+            StakeError::GlobalMaxStakeReached
+        );
+        self.total_staked = total_staked;
+        Ok(())
+    }
+
+    /// Uncounts an unstaked NFT. Stakes opened before the counter existed were never counted, so
+    /// this bottoms out at 0 rather than failing their unstake, `sync_totals` corrects the drift.
+    pub fn remove_staked(&mut self) {
+        self.total_staked = self.total_staked.saturating_sub(1);
+    }
+
+    /// Counts a user's first stake in this pool.
+    pub fn add_user(&mut self) -> Result<()> {
+        self.total_users = self.total_users
+            .checked_add(1)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    /// Uncounts a closed user account, bottoming out at 0 like `remove_staked` since users who
+    /// staked before the counter existed were never counted.
+    pub fn remove_user(&mut self) {
+        self.total_users = self.total_users.saturating_sub(1);
+    }
+
+    pub fn add_points_distributed(&mut self, points: u64) -> Result<()> {
+        self.total_points_distributed = self.total_points_distributed
+            .checked_add(points)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    /// Points that can still be paid out before `total_points_distributed` reaches `max_emission`.
+    pub fn emission_room(&self) -> u64 {
+        if self.max_emission == 0 {
+            return u64::MAX;
+        }
+        self.max_emission.saturating_sub(self.total_points_distributed)
+    }
+
+    /// Part of `vault_balance` free for points payouts, snapshot funds are held back for their claims.
+    pub fn available_rewards(&self, vault_balance: u64) -> u64 {
+        vault_balance
+            .saturating_sub(self.snapshot_pool)
+            .saturating_sub(self.snapshot_unclaimed)
+    }
+
+    /// Whole points a claim can pay out of `vault_balance`, `treasury_fee` included.
+    pub fn affordable_points(&self, vault_balance: u64, decimals: u8) -> Result<u64> {
+        let unit = 10_u128.checked_pow(decimals as u32).ok_or(StakeError::ArithmeticOverflow)?;
+        let points = self.available_rewards(vault_balance) as u128 * BPS_DENOMINATOR as u128
+            / (unit * (BPS_DENOMINATOR as u128 + self.fee_bps as u128));
+        Ok(points as u64)
+    }
+
+    /// Sets aside `amount` of the reward vault for the next snapshot.
+    pub fn fund_snapshot(&mut self, amount: u64) -> Result<()> {
+        require!(amount > 0, StakeError::InvalidAmount);
+        self.snapshot_pool = self.snapshot_pool
+            .checked_add(amount)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    /// Starts a new snapshot paying out the funded pool, plus whatever the previous one left
+    /// unclaimed, pro rata to the NFTs staked right now.
+    pub fn take_snapshot(&mut self, now: i64) -> Result<()> {
+        require!(self.snapshot_pool > 0 && self.total_staked > 0, StakeError::NothingToDistribute);
+
+        self.snapshot_id = self.snapshot_id
+            .checked_add(1)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        self.snapshot_amount = self.snapshot_pool
+            .checked_add(self.snapshot_unclaimed)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        self.snapshot_unclaimed = self.snapshot_amount;
+        self.snapshot_pool = 0;
+        self.snapshot_total_weight = self.total_staked;
+        self.snapshot_taken_at = now;
+        Ok(())
+    }
+
+    /// The latest snapshot's payout for `weight` NFTs staked when it was taken, and takes it out
+    /// of `snapshot_unclaimed`.
+    pub fn claim_snapshot_share(&mut self, weight: u32) -> Result<u64> {
+        require!(self.snapshot_total_weight > 0, StakeError::NothingToClaim);
+
+        // Stakes uncounted by `total_staked` can push a claim past the total, it's capped at it
+        let weight = weight.min(self.snapshot_total_weight);
+        let share = (self.snapshot_amount as u128)
+            .checked_mul(weight as u128)
+            .ok_or(StakeError::ArithmeticOverflow)?
+            / self.snapshot_total_weight as u128;
+        // Shares round down so the sum of all claims never exceeds the amount
+        let share = (share as u64).min(self.snapshot_unclaimed);
+
+        self.snapshot_unclaimed -= share;
+        Ok(share)
+    }
+
+    /// `days` staked clamped to `max_stake_duration`, points stop accruing past the cap.
+    pub fn accrual_days(&self, days: u32) -> u32 {
+        match self.max_stake_duration {
+            0 => days,
+            max_stake_duration => days.min(max_stake_duration),
+        }
+    }
+
+    /// Runs every field check `initialize_config` makes, then `validate_consistency`.
+    pub fn validate(&self) -> Result<()> {
+        Self::validate_max_stake(self.max_stake)?;
+        Self::validate_freeze_period_secs(self.freeze_period_secs)?;
+        Self::validate_lockup_options(&self.lockup_options)?;
+        Self::validate_early_unstake_penalty_bps(self.early_unstake_penalty_bps)?;
+        Self::validate_reward_tiers(&self.reward_tiers)?;
+        Self::validate_referral_bps(self.referral_bps)?;
+        Self::validate_fee_bps(self.fee_bps)?;
+        Self::validate_halving_interval_secs(self.halving_interval_secs)?;
+        Self::validate_custody_mode(self.custody_mode)?;
+        Self::validate_set_collections(&self.set_collections)?;
+        Self::validate_decay_rate_bps_per_day(self.decay_rate_bps_per_day)?;
+        Self::validate_points_expiry_secs(self.points_expiry_secs)?;
+        self.validate_consistency()
+    }
+
+    /// Checks the fields that are each valid alone but not together. `update_config` runs it
+    /// after applying every change, so fields moved together are checked as a whole.
+    pub fn validate_consistency(&self) -> Result<()> {
+        require!(self.points_per_stake > 0, StakeError::InvalidPointsPerStake);
+
+        // A freeze or lockup outlasting accrual would hold NFTs for days they earn nothing on
+        if self.max_stake_duration > 0 {
+            let max_stake_secs = self.max_stake_duration as u64 * SECONDS_PER_DAY as u64;
+            let longest_lockup = self.lockup_options
+                .iter()
+                .filter(|option| option.multiplier_bps > 0)
+                .map(|option| option.freeze_secs as u64)
+                .max()
+                .unwrap_or(0);
+            require!(
+                self.freeze_period_secs.max(longest_lockup) <= max_stake_secs,
+                StakeError::FreezeExceedsStakeDuration
+            );
+        }
+
+        require!(self.max_stakes_per_window == 0 || self.window_len > 0, StakeError::InvalidStakeWindow);
+
+        // A zero cap would make the boost a no-op the pool still advertises
+        require!(
+            (self.compound_boost_bps == 0 || self.max_compound_boost_bps > 0)
+                && (self.lock_boost_bps == 0 || self.max_lock_boost_bps > 0),
+            StakeError::InvalidBoostCap
+        );
+        Ok(())
+    }
+
+    pub fn validate_max_stake(max_stake: u8) -> Result<()> {
+        require!(max_stake > 0, StakeError::InvalidMaxStake);
+        // Every stake is listed in the owner's `UserPositions`
+        require!(max_stake as usize <= MAX_POSITIONS, StakeError::MaxStakeExceedsCapacity);
+        Ok(())
+    }
+
+    pub fn validate_freeze_period_secs(freeze_period_secs: u64) -> Result<()> {
+        require!(freeze_period_secs <= MAX_FREEZE_PERIOD_SECS, StakeError::InvalidFreezePeriod);
+        Ok(())
+    }
+
+    /// Flexible must be offered with no freeze, and each offered lockup must hold at least as long
+    /// and pay at least as much as the shorter ones.
+    pub fn validate_lockup_options(lockup_options: &[LockupOption; LOCKUP_KINDS]) -> Result<()> {
+        let flexible = lockup_options[LockupKind::Flexible as usize];
+        require!(flexible.freeze_secs == 0 && flexible.multiplier_bps > 0, StakeError::InvalidLockupOptions);
+
+        let mut previous = flexible;
+        for option in lockup_options.iter().skip(1).filter(|option| option.multiplier_bps > 0) {
+            require!(
+                option.freeze_secs >= previous.freeze_secs && option.multiplier_bps >= previous.multiplier_bps,
+                StakeError::InvalidLockupOptions
+            );
+            previous = *option;
+        }
+        Ok(())
+    }
+
+    /// Freeze seconds and points multiplier of a new stake under `lockup`, the collection's
+    /// `points_multiplier` scaled by the lockup's.
+    pub fn lockup_terms(&self, lockup: LockupKind, points_multiplier: u16) -> Result<(u32, u16)> {
+        let option = self.lockup_options[lockup as usize];
+        require!(option.multiplier_bps > 0, StakeError::InvalidLockupKind);
+
+        let multiplier = (points_multiplier as u64)
+            .checked_mul(option.multiplier_bps as u64)
+            .ok_or(StakeError::ArithmeticOverflow)?
+            / BPS_DENOMINATOR as u64;
+        let multiplier = u16::try_from(multiplier).map_err(|_| error!(StakeError::ArithmeticOverflow))?;
+
+        Ok((option.freeze_secs, multiplier))
+    }
+
+    /// `lockup_terms` for a stake moving from `from` to `to`, its current `points_multiplier`
+    /// rescaled by the ratio of their multipliers under the pool's current lockup options.
+    pub fn relock_terms(&self, from: LockupKind, to: LockupKind, points_multiplier: u16) -> Result<(u32, u16)> {
+        let from_bps = self.lockup_options[from as usize].multiplier_bps;
+        require!(from_bps > 0, StakeError::InvalidLockupKind);
+
+        let base_multiplier = (points_multiplier as u64)
+            .checked_mul(BPS_DENOMINATOR as u64)
+            .ok_or(StakeError::ArithmeticOverflow)?
+            / from_bps as u64;
+        let base_multiplier = u16::try_from(base_multiplier).map_err(|_| error!(StakeError::ArithmeticOverflow))?;
+
+        self.lockup_terms(to, base_multiplier)
+    }
+
+    /// When a stake opened at `staked_at` under a lockup freezing it `lockup_secs` can be
+    /// unstaked, whichever of the freeze period and the lockup ends later.
+    pub fn unlocked_at(&self, staked_at: i64, lockup_secs: u32) -> Result<i64> {
+        let freeze_secs = i64::try_from(self.freeze_period_secs)
+            .map_err(|_| StakeError::ArithmeticOverflow)?
+            .max(lockup_secs as i64);
+        let unlocked_at = staked_at
+            .checked_add(freeze_secs)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        Ok(unlocked_at)
+    }
+
+    pub fn validate_early_unstake_penalty_bps(early_unstake_penalty_bps: u16) -> Result<()> {
+        require!(
+            early_unstake_penalty_bps <= BPS_DENOMINATOR || early_unstake_penalty_bps == EMERGENCY_UNSTAKE_DISABLED,
+            StakeError::InvalidPenaltyBps
+        );
+        Ok(())
+    }
+
+    pub fn validate_halving_interval_secs(halving_interval_secs: i64) -> Result<()> {
+        require!(halving_interval_secs >= 0, StakeError::InvalidEmissionSchedule);
+        Ok(())
+    }
+
+    pub fn validate_points_expiry_secs(points_expiry_secs: i64) -> Result<()> {
+        require!(points_expiry_secs >= 0, StakeError::InvalidPointsExpiry);
+        Ok(())
+    }
+
+    pub fn validate_set_collections(set_collections: &[Pubkey; SET_COLLECTIONS]) -> Result<()> {
+        for (index, collection) in set_collections.iter().enumerate().filter(|(_, collection)| **collection != Pubkey::default()) {
+            require!(!set_collections[..index].contains(collection), StakeError::InvalidSetCollections);
+        }
+        Ok(())
+    }
+
+    /// `collection`'s slot in `set_collections`, `None` if it isn't part of the set.
+    pub fn set_piece(&self, collection: &Pubkey) -> Option<u8> {
+        self.set_collections
+            .iter()
+            .position(|set_collection| set_collection == collection && *collection != Pubkey::default())
+            .map(|index| index as u8)
+    }
+
+    pub fn validate_custody_mode(custody_mode: CustodyMode) -> Result<()> {
+        // Compressed is picked per stake by stake_compressed, a pool can't default to it
+        require!(custody_mode != CustodyMode::Compressed, StakeError::CustodyModeMismatch);
+        Ok(())
+    }
+
+    pub fn validate_referral_bps(referral_bps: u16) -> Result<()> {
+        require!(referral_bps <= BPS_DENOMINATOR, StakeError::InvalidReferralBps);
+        Ok(())
+    }
+
+    pub fn validate_decay_rate_bps_per_day(decay_rate_bps_per_day: u16) -> Result<()> {
+        require!(decay_rate_bps_per_day <= BPS_DENOMINATOR, StakeError::InvalidDecayRate);
+        Ok(())
+    }
+
+    pub fn validate_fee_bps(fee_bps: u16) -> Result<()> {
+        require!(fee_bps <= BPS_DENOMINATOR, StakeError::InvalidFeeBps);
+        Ok(())
+    }
+
+    pub fn validate_reward_tiers(reward_tiers: &[RewardTier; REWARD_TIERS]) -> Result<()> {
+        let mut previous = 0;
+        for tier in reward_tiers.iter().filter(|tier| tier.min_days > 0) {
+            require!(tier.min_days > previous, StakeError::InvalidRewardTiers);
+            previous = tier.min_days;
+        }
+        Ok(())
+    }
+
+    /// `points` after an admin adjustment of `delta`, floored at 0. Clawbacks over `max_clawback`
+    /// need claims paused first, bounding what a compromised admin key can burn in one go.
+    pub fn adjusted_points(&self, points: u64, delta: i64) -> Result<u64> {
+        if delta >= 0 {
+            return points
+                .checked_add(delta as u64)
+                .ok_or(error!(StakeError::ArithmeticOverflow));
+        }
+        require!(
+            delta.unsigned_abs() <= self.max_clawback || self.is_paused(PAUSE_CLAIM),
+            StakeError::AdjustmentTooLarge
+        );
+        Ok(points.saturating_sub(delta.unsigned_abs()))
+    }
+
+    /// Points credited to a referrer when the user they referred earns `points`.
+    pub fn referral_cut(&self, points: u64) -> Result<u64> {
+        let cut = points
+            .checked_mul(self.referral_bps as u64)
+            .ok_or(StakeError::ArithmeticOverflow)?
+            / BPS_DENOMINATOR as u64;
+        Ok(cut)
+    }
+
+    /// Reward tokens sent to the treasury when a claim pays a user `amount`, rounded down. Paid
+    /// on top of `amount`, never out of it.
+    pub fn treasury_fee(&self, amount: u64) -> Result<u64> {
+        let fee = (amount as u128 * self.fee_bps as u128 / BPS_DENOMINATOR as u128)
+            .try_into()
+            .map_err(|_| StakeError::ArithmeticOverflow)?;
+        Ok(fee)
+    }
+
+    /// `points` boosted by `compound_boost_bps` for every whole reward token a user has
+    /// compounded, up to `max_compound_boost_bps`.
+    pub fn compounded_points(&self, points: u64, staked_reward_balance: u64, decimals: u8) -> Result<u64> {
+        let tokens = staked_reward_balance / 10_u64.checked_pow(decimals as u32).ok_or(StakeError::ArithmeticOverflow)?;
+        let boost_bps = tokens
+            .saturating_mul(self.compound_boost_bps as u64)
+            .min(self.max_compound_boost_bps as u64);
+        let boosted = points
+            .checked_mul(BPS_DENOMINATOR as u64 + boost_bps)
+            .ok_or(StakeError::ArithmeticOverflow)?
+            / BPS_DENOMINATOR as u64;
+        Ok(boosted)
+    }
+
+    /// `compounded_points` for `user`, boosted on top by `lock_boost_bps` for every whole reward
+    /// token they have locked with lock_boost, up to `max_lock_boost_bps`. The lock boost stops
+    /// counting once `boost_expiry` has passed, whether or not the tokens were unlocked yet.
+    pub fn boosted_points(&self, points: u64, user: &UserAccount, decimals: u8, now: i64) -> Result<u64> {
+        let points = self.compounded_points(points, user.staked_reward_balance, decimals)?;
+        if now >= user.boost_expiry {
+            return Ok(points);
+        }
+
+        let tokens = user.boost_amount / 10_u64.checked_pow(decimals as u32).ok_or(StakeError::ArithmeticOverflow)?;
+        let boost_bps = tokens
+            .saturating_mul(self.lock_boost_bps as u64)
+            .min(self.max_lock_boost_bps as u64);
+        let boosted = points
+            .checked_mul(BPS_DENOMINATOR as u64 + boost_bps)
+            .ok_or(StakeError::ArithmeticOverflow)?
+            / BPS_DENOMINATOR as u64;
+        Ok(boosted)
+    }
+
+    /// `points` plus `compound_bonus_bps` of them, what compound_locked locks up.
+    pub fn compound_bonus(&self, points: u64) -> Result<u64> {
+        let bonus = points
+            .checked_mul(self.compound_bonus_bps as u64)
+            .ok_or(StakeError::ArithmeticOverflow)?
+            / BPS_DENOMINATOR as u64;
+        points
+            .checked_add(bonus)
+            .ok_or(error!(StakeError::ArithmeticOverflow))
+    }
+
+    /// Points burned from `points` when a user unstakes early.
+    pub fn early_unstake_penalty(&self, points: u64) -> Result<u64> {
+        let penalty = points
+            .checked_mul(self.early_unstake_penalty_bps as u64)
+            .ok_or(StakeError::ArithmeticOverflow)?
+            / BPS_DENOMINATOR as u64;
+        Ok(penalty)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{PAUSE_CLAIM, PAUSE_STAKE, SECONDS_PER_DAY};
+
+    fn config_with_penalty(early_unstake_penalty_bps: u16) -> StakeConfig {
+        StakeConfig { early_unstake_penalty_bps, ..Default::default() }
+    }
+
+    // 1x for the first 30 days, 1.5x for days 31-90, 2x beyond
+    fn tiered_config() -> StakeConfig {
+        StakeConfig {
+            points_per_stake: 10,
+            reward_tiers: [
+                RewardTier { min_days: 30, multiplier_bps: 15_000 },
+                RewardTier { min_days: 90, multiplier_bps: 20_000 },
+                RewardTier::default(),
+                RewardTier::default(),
+            ],
+            ..Default::default()
+        }
+    }
+
+    // `config` serialized in the v3 layout, with a `collection_mint` after `pending_admin`
+    fn with_collection_mint(config: &StakeConfig) -> Vec<u8> {
+        let mut data = Vec::new();
+        config.try_serialize(&mut data).unwrap();
+        let at = 8 + 8 + 32 + config.pending_admin.try_to_vec().unwrap().len();
+        data.splice(at..at, Pubkey::new_unique().to_bytes());
+        data
+    }
+
+    #[test]
+    fn reward_tiers_must_ascend() {
+        assert!(StakeConfig::validate_reward_tiers(&tiered_config().reward_tiers).is_ok());
+        assert!(StakeConfig::validate_reward_tiers(&[
+            RewardTier { min_days: 90, multiplier_bps: 20_000 },
+            RewardTier { min_days: 30, multiplier_bps: 15_000 },
+            RewardTier::default(),
+            RewardTier::default(),
+        ]).is_err());
+    }
+
+    #[test]
+    fn accrual_days_caps_at_max_stake_duration() {
+        let uncapped = StakeConfig::default();
+        let capped = StakeConfig { max_stake_duration: 60, ..Default::default() };
+
+        assert_eq!(uncapped.accrual_days(1_000), 1_000);
+        assert_eq!(capped.accrual_days(59), 59);
+        assert_eq!(capped.accrual_days(60), 60);
+        assert_eq!(capped.accrual_days(1_000), 60);
+    }
+
+    #[test]
+    fn user_and_points_counters() {
+        let mut config = StakeConfig::default();
+
+        config.add_user().unwrap();
+        config.remove_user();
+        config.remove_user();
+        assert_eq!(config.total_users, 0);
+
+        config.add_points_distributed(u64::MAX).unwrap();
+        assert_eq!(config.add_points_distributed(1).unwrap_err(), error!(StakeError::ArithmeticOverflow));
+    }
+
+    #[test]
+    fn global_max_stake_caps_total_staked() {
+        let mut config = StakeConfig { global_max_stake: 3, total_staked: 1, ..Default::default() };
+
+        config.add_staked(2).unwrap();
+        assert_eq!(config.total_staked, 3);
+        assert_eq!(config.add_staked(1).unwrap_err(), error!(StakeError::GlobalMaxStakeReached));
+        assert_eq!(config.total_staked, 3);
+
+        config.remove_staked();
+        config.add_staked(1).unwrap();
+
+        let mut unlimited = StakeConfig { total_staked: u32::MAX - 1, ..Default::default() };
+        unlimited.add_staked(1).unwrap();
+        assert_eq!(unlimited.add_staked(1).unwrap_err(), error!(StakeError::ArithmeticOverflow));
+    }
+
+    #[test]
+    fn snapshots_split_the_pool_by_weight_and_roll_over_leftovers() {
+        let mut config = StakeConfig { total_staked: 3, ..Default::default() };
+
+        assert_eq!(config.take_snapshot(10).unwrap_err(), error!(StakeError::NothingToDistribute));
+        config.fund_snapshot(1_000).unwrap();
+        assert_eq!(config.fund_snapshot(0).unwrap_err(), error!(StakeError::InvalidAmount));
+        assert_eq!(config.available_rewards(1_500), 500);
+
+        config.take_snapshot(10).unwrap();
+        assert_eq!((config.snapshot_id, config.snapshot_total_weight, config.snapshot_pool), (1, 3, 0));
+
+        // Shares round down, and unstakes after the snapshot don't grow anyone's share
+        config.remove_staked();
+        assert_eq!(config.claim_snapshot_share(2).unwrap(), 666);
+        assert_eq!(config.snapshot_unclaimed, 334);
+        assert_eq!(config.available_rewards(834), 500);
+
+        config.fund_snapshot(66).unwrap();
+        config.take_snapshot(20).unwrap();
+        assert_eq!((config.snapshot_id, config.snapshot_amount, config.snapshot_total_weight), (2, 400, 2));
+        assert_eq!(config.claim_snapshot_share(u32::MAX).unwrap(), 400);
+        assert_eq!(config.snapshot_unclaimed, 0);
+    }
+
+    #[test]
+    fn remove_staked_tolerates_uncounted_legacy_stakes() {
+        let mut config = StakeConfig::default();
+
+        config.remove_staked();
+        assert_eq!(config.total_staked, 0);
+    }
+
+    #[test]
+    fn referral_cut_rounds_down_and_validates() {
+        let config = StakeConfig { referral_bps: 1_000, ..Default::default() };
+
+        assert_eq!(config.referral_cut(1_999).unwrap(), 199);
+        assert_eq!(StakeConfig::default().referral_cut(1_000).unwrap(), 0);
+        assert!(StakeConfig::validate_referral_bps(BPS_DENOMINATOR).is_ok());
+        assert!(StakeConfig::validate_referral_bps(BPS_DENOMINATOR + 1).is_err());
+    }
+
+    #[test]
+    fn emission_room_counts_down_to_the_cap() {
+        let mut config = StakeConfig::default();
+        assert_eq!(config.emission_room(), u64::MAX);
+
+        config.max_emission = 100;
+        config.add_points_distributed(70).unwrap();
+        assert_eq!(config.emission_room(), 30);
+        config.add_points_distributed(30).unwrap();
+        assert_eq!(config.emission_room(), 0);
+    }
+
+    #[test]
+    fn set_collections_map_to_their_slot() {
+        let collection = Pubkey::new_unique();
+        let mut config = StakeConfig::default();
+        config.set_collections[3] = collection;
+
+        assert_eq!(config.set_piece(&collection), Some(3));
+        assert_eq!(config.set_piece(&Pubkey::new_unique()), None);
+        assert_eq!(config.set_piece(&Pubkey::default()), None);
+
+        assert!(StakeConfig::validate_set_collections(&config.set_collections).is_ok());
+        config.set_collections[4] = collection;
+        assert!(StakeConfig::validate_set_collections(&config.set_collections).is_err());
+    }
+
+    #[test]
+    fn affordable_points_leave_room_for_the_fee_and_snapshots() {
+        let config = StakeConfig { fee_bps: 250, ..Default::default() };
+
+        assert_eq!(StakeConfig::default().affordable_points(3_999_999, 6).unwrap(), 3);
+        // 4 points cost 4.1 tokens with the fee
+        assert_eq!(config.affordable_points(4_099_999, 6).unwrap(), 3);
+        assert_eq!(config.affordable_points(4_100_000, 6).unwrap(), 4);
+        assert_eq!(config.treasury_fee(4_000_000).unwrap() + 4_000_000, 4_100_000);
+
+        let snapshot = StakeConfig { snapshot_pool: 1_000_000, snapshot_unclaimed: 1_000_000, ..Default::default() };
+        assert_eq!(snapshot.affordable_points(3_000_000, 6).unwrap(), 1);
+        assert_eq!(snapshot.affordable_points(1_000_000, 6).unwrap(), 0);
+        assert_eq!(StakeConfig::default().affordable_points(u64::MAX, 0).unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn treasury_fee_rounds_down_and_validates() {
+        let config = StakeConfig { fee_bps: 250, ..Default::default() };
+
+        assert_eq!(config.treasury_fee(1_000).unwrap(), 25);
+        assert_eq!(config.treasury_fee(39).unwrap(), 0);
+        assert_eq!(config.treasury_fee(u64::MAX).unwrap(), u64::MAX / 40);
+        assert_eq!(StakeConfig::default().treasury_fee(1_000).unwrap(), 0);
+        assert!(StakeConfig::validate_fee_bps(BPS_DENOMINATOR).is_ok());
+        assert!(StakeConfig::validate_fee_bps(BPS_DENOMINATOR + 1).is_err());
+    }
+
+    #[test]
+    fn compounded_points_boost_per_whole_token_up_to_the_cap() {
+        let config = StakeConfig { compound_boost_bps: 100, max_compound_boost_bps: 2_500, ..Default::default() };
+
+        assert_eq!(config.compounded_points(1_000, 0, 6).unwrap(), 1_000);
+        // Partial tokens don't count
+        assert_eq!(config.compounded_points(1_000, 1_999_999, 6).unwrap(), 1_010);
+        assert_eq!(config.compounded_points(1_000, 10_000_000, 6).unwrap(), 1_100);
+        assert_eq!(config.compounded_points(1_000, u64::MAX, 6).unwrap(), 1_250);
+        assert_eq!(StakeConfig::default().compounded_points(1_000, u64::MAX, 0).unwrap(), 1_000);
+        assert_eq!(config.compounded_points(u64::MAX, 0, 6).unwrap_err(), error!(StakeError::ArithmeticOverflow));
+    }
+
+    #[test]
+    fn lock_boost_stacks_on_the_compound_boost_until_expiry() {
+        let config = StakeConfig { compound_boost_bps: 100, max_compound_boost_bps: 2_500, lock_boost_bps: 500, max_lock_boost_bps: 5_000, ..Default::default() };
+        let mut user = UserAccount::new(Pubkey::new_unique(), None, 0);
+
+        assert_eq!(config.boosted_points(1_000, &user, 6, 0).unwrap(), 1_000);
+
+        user.boost_amount = 4_500_000;
+        user.boost_expiry = 100;
+        // 4 whole tokens locked, partial ones don't count
+        assert_eq!(config.boosted_points(1_000, &user, 6, 99).unwrap(), 1_200);
+        assert_eq!(config.boosted_points(1_000, &user, 6, 100).unwrap(), 1_000);
+
+        user.staked_reward_balance = 10_000_000;
+        assert_eq!(config.boosted_points(1_000, &user, 6, 0).unwrap(), 1_320);
+        user.boost_amount = u64::MAX;
+        assert_eq!(config.boosted_points(1_000, &user, 6, 0).unwrap(), 1_650);
+        assert_eq!(StakeConfig::default().boosted_points(1_000, &user, 6, 0).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn adjusted_points_grants_and_claws_back() {
+        let config = StakeConfig { max_clawback: 100, ..Default::default() };
+
+        assert_eq!(config.adjusted_points(10, 5).unwrap(), 15);
+        assert_eq!(config.adjusted_points(u64::MAX, 1).unwrap_err(), error!(StakeError::ArithmeticOverflow));
+        assert_eq!(config.adjusted_points(150, -100).unwrap(), 50);
+        // Floors at 0 instead of failing
+        assert_eq!(config.adjusted_points(50, -100).unwrap(), 0);
+    }
+
+    #[test]
+    fn large_clawbacks_need_claims_paused() {
+        let mut config = StakeConfig { max_clawback: 100, ..Default::default() };
+
+        assert_eq!(config.adjusted_points(1_000, -101).unwrap_err(), error!(StakeError::AdjustmentTooLarge));
+        assert_eq!(config.adjusted_points(1_000, i64::MIN).unwrap_err(), error!(StakeError::AdjustmentTooLarge));
+
+        config.pause_flags = PAUSE_CLAIM;
+        assert_eq!(config.adjusted_points(1_000, -101).unwrap(), 899);
+        assert_eq!(config.adjusted_points(1_000, i64::MIN).unwrap(), 0);
+    }
+
+    #[test]
+    fn early_unstake_penalty_rounds_down() {
+        assert_eq!(config_with_penalty(2_500).early_unstake_penalty(999).unwrap(), 249);
+    }
+
+    #[test]
+    fn early_unstake_penalty_past_u32() {
+        let points = u32::MAX as u64 * 4;
+
+        assert_eq!(config_with_penalty(5_000).early_unstake_penalty(points).unwrap(), u32::MAX as u64 * 2);
+        assert_eq!(
+            config_with_penalty(BPS_DENOMINATOR).early_unstake_penalty(u64::MAX).unwrap_err(),
+            error!(StakeError::ArithmeticOverflow)
+        );
+    }
+
+    #[test]
+    fn full_penalty_zeroes_out_points() {
+        let points = 1_234;
+        let penalty = config_with_penalty(BPS_DENOMINATOR).early_unstake_penalty(points).unwrap();
+
+        assert_eq!(points.saturating_sub(penalty), 0);
+    }
+
+    #[test]
+    fn max_stake_and_freeze_period_validation() {
+        assert!(StakeConfig::validate_max_stake(1).is_ok());
+        assert!(StakeConfig::validate_max_stake(0).is_err());
+        assert!(StakeConfig::validate_max_stake(MAX_POSITIONS as u8).is_ok());
+        assert_eq!(
+            StakeConfig::validate_max_stake(MAX_POSITIONS as u8 + 1).err(),
+            Some(error!(StakeError::MaxStakeExceedsCapacity))
+        );
+        assert!(StakeConfig::validate_freeze_period_secs(0).is_ok());
+        assert!(StakeConfig::validate_freeze_period_secs(MAX_FREEZE_PERIOD_SECS).is_ok());
+        assert!(StakeConfig::validate_freeze_period_secs(MAX_FREEZE_PERIOD_SECS + 1).is_err());
+    }
+
+    fn lockup(freeze_days: u32, multiplier_bps: u16) -> LockupOption {
+        LockupOption { freeze_secs: freeze_days * SECONDS_PER_DAY as u32, multiplier_bps }
+    }
+
+    #[test]
+    fn lockup_options_validation() {
+        assert!(StakeConfig::validate_lockup_options(&[lockup(0, 5_000), lockup(30, 10_000), lockup(90, 20_000)]).is_ok());
+        // Longer lockups can be left out, Flexible can't
+        assert!(StakeConfig::validate_lockup_options(&[lockup(0, 10_000), lockup(0, 0), lockup(90, 20_000)]).is_ok());
+        assert!(StakeConfig::validate_lockup_options(&[lockup(0, 0), lockup(30, 10_000), lockup(90, 20_000)]).is_err());
+        assert!(StakeConfig::validate_lockup_options(&[lockup(1, 10_000), lockup(30, 10_000), lockup(90, 20_000)]).is_err());
+        // Ninety pays less than thirty, or holds for less
+        assert!(StakeConfig::validate_lockup_options(&[lockup(0, 10_000), lockup(30, 15_000), lockup(90, 12_000)]).is_err());
+        assert!(StakeConfig::validate_lockup_options(&[lockup(0, 10_000), lockup(30, 15_000), lockup(20, 20_000)]).is_err());
+    }
+
+    #[test]
+    fn lockup_terms_scale_the_collection_multiplier() {
+        let config = StakeConfig {
+            lockup_options: [lockup(0, 5_000), lockup(30, 15_000), LockupOption::default()],
+            ..Default::default()
+        };
+
+        assert_eq!(config.lockup_terms(LockupKind::Flexible, 10_000).unwrap(), (0, 5_000));
+        assert_eq!(config.lockup_terms(LockupKind::ThirtyDay, 12_000).unwrap(), (30 * SECONDS_PER_DAY as u32, 18_000));
+        assert_eq!(config.lockup_terms(LockupKind::NinetyDay, 10_000).unwrap_err(), error!(StakeError::InvalidLockupKind));
+        assert_eq!(config.lockup_terms(LockupKind::ThirtyDay, u16::MAX).unwrap_err(), error!(StakeError::ArithmeticOverflow));
+    }
+
+    #[test]
+    fn relock_terms_swap_the_lockup_multiplier() {
+        let config = StakeConfig {
+            lockup_options: [lockup(0, 5_000), lockup(30, 15_000), LockupOption::default()],
+            ..Default::default()
+        };
+
+        // A 12_000 collection multiplier staked Flexible, moved to ThirtyDay and back
+        assert_eq!(config.relock_terms(LockupKind::Flexible, LockupKind::ThirtyDay, 6_000).unwrap(), (30 * SECONDS_PER_DAY as u32, 18_000));
+        assert_eq!(config.relock_terms(LockupKind::ThirtyDay, LockupKind::Flexible, 18_000).unwrap(), (0, 6_000));
+        assert_eq!(config.relock_terms(LockupKind::Flexible, LockupKind::Flexible, 6_000).unwrap(), (0, 6_000));
+        assert_eq!(config.relock_terms(LockupKind::NinetyDay, LockupKind::Flexible, 6_000).unwrap_err(), error!(StakeError::InvalidLockupKind));
+        assert_eq!(config.relock_terms(LockupKind::Flexible, LockupKind::NinetyDay, 6_000).unwrap_err(), error!(StakeError::InvalidLockupKind));
+    }
+
+    #[test]
+    fn unlocked_at_is_the_later_of_the_freeze_period_and_the_lockup() {
+        let config = StakeConfig { freeze_period_secs: 2 * SECONDS_PER_DAY as u64, ..Default::default() };
+
+        assert_eq!(config.unlocked_at(1_000, 0).unwrap(), 1_000 + 2 * SECONDS_PER_DAY);
+        assert_eq!(config.unlocked_at(1_000, 30 * SECONDS_PER_DAY as u32).unwrap(), 1_000 + 30 * SECONDS_PER_DAY);
+        // A lockup shorter than the freeze period doesn't cut it short
+        assert_eq!(config.unlocked_at(1_000, 1).unwrap(), 1_000 + 2 * SECONDS_PER_DAY);
+
+        let seconds = StakeConfig { freeze_period_secs: 90, ..Default::default() };
+        assert_eq!(seconds.unlocked_at(1_000, 0).unwrap(), 1_090);
+        assert_eq!(StakeConfig::default().unlocked_at(1_000, 0).unwrap(), 1_000);
+        assert!(seconds.unlocked_at(i64::MAX, 0).is_err());
+    }
+
+    #[test]
+    fn legacy_configs_convert_the_freeze_period_to_seconds() {
+        for pending_admin in [None, Some(Pubkey::new_unique())] {
+            let current = StakeConfig { pool_id: 3, pending_admin, max_stake: 5, freeze_period_secs: 7, points_per_stake: 10, bump: 254, ..Default::default() };
+            let mut data = with_collection_mint(&current);
+
+            // Back to the v1 layout: `freeze_period_secs` as a u32 of days
+            let at = 8 + 8 + 32 + pending_admin.try_to_vec().unwrap().len() + 32 * 4 + 2 + 8 + 1 + 4 + 2 + 1;
+            data.splice(at..at + 8, 7_u32.to_le_bytes());
+            data.resize(StakeConfig::V1_SPACE, 0);
+
+            let config = StakeConfig::try_from_legacy(&data).unwrap();
+
+            assert_eq!(config.freeze_period_secs, 7 * SECONDS_PER_DAY as u64);
+            assert_eq!((config.pool_id, config.pending_admin, config.max_stake), (3, pending_admin, 5));
+            assert_eq!((config.points_per_stake, config.bump), (10, 254));
+            assert!(!config.gated);
+        }
+
+        let mut current = Vec::new();
+        StakeConfig::default().try_serialize(&mut current).unwrap();
+        current.resize(StakeConfig::INIT_SPACE, 0);
+        assert_eq!(StakeConfig::try_from_legacy(&current).err(), Some(error!(StakeError::ConfigAlreadyMigrated)));
+    }
+
+    #[test]
+    fn the_baseline_config_converts_the_freeze_period_to_seconds() {
+        let mut data = StakeConfig::DISCRIMINATOR.to_vec();
+        data.extend([10, 5]);
+        data.extend(7_u32.to_le_bytes());
+        data.extend([254, 253]);
+
+        let config = StakeConfig::try_from_baseline(&data).unwrap();
+
+        assert_eq!(config.freeze_period_secs, 7 * 86_400);
+        assert_eq!((config.points_per_stake, config.max_stake), (10, 5));
+        assert_eq!((config.pool_id, config.bump, config.admin), (0, 0, Pubkey::default()));
+
+        data.push(0);
+        assert_eq!(StakeConfig::try_from_baseline(&data).err(), Some(error!(StakeError::ConfigAlreadyMigrated)));
+
+        data.truncate(StakeConfig::BASELINE_SPACE);
+        data[0] ^= 1;
+        assert_eq!(StakeConfig::try_from_baseline(&data).err(), Some(error!(ErrorCode::AccountDiscriminatorMismatch)));
+    }
+
+    #[test]
+    fn configs_from_before_gated_migrate_ungated() {
+        // Every option set, so the v2 account is full to its last bump
+        let some = Some(Pubkey::new_unique());
+        let current = StakeConfig { pending_admin: some, stake_token_mint: some, receipt_mint: some, freeze_period_secs: 7, bump: 254, ..Default::default() };
+        let mut data = with_collection_mint(&current);
+        data.truncate(StakeConfig::V2_SPACE);
+
+        let config = StakeConfig::try_from_legacy(&data).unwrap();
+
+        assert_eq!((config.freeze_period_secs, config.receipt_mint, config.bump), (7, some, 254));
+        assert!(!config.gated);
+        assert_eq!(config.min_claim_points, 0);
+        assert!(!config.claim_requires_unlocked);
+        assert_eq!((config.epoch_length_secs, config.current_epoch), (0, 0));
+        assert_eq!((config.max_stakes_per_window, config.window_len), (0, 0));
+        assert_eq!(config.points_expiry_secs, 0);
+        assert_eq!((config.weight_per_day, config.weight_decay_per_day), (0, 0));
+    }
+
+    #[test]
+    fn configs_with_a_collection_mint_drop_it_and_keep_the_rest() {
+        for pending_admin in [None, Some(Pubkey::new_unique())] {
+            let current = StakeConfig { pending_admin, creator: Pubkey::new_unique(), gated: true, weight_decay_per_day: 9, bump: 254, ..Default::default() };
+            let mut data = with_collection_mint(&current);
+            data.resize(StakeConfig::V3_SPACE, 0);
+
+            // Fields after the bumps survive, unlike in the v1 and v2 layouts
+            let mut migrated = Vec::new();
+            StakeConfig::try_from_legacy(&data).unwrap().try_serialize(&mut migrated).unwrap();
+            let mut expected = Vec::new();
+            current.try_serialize(&mut expected).unwrap();
+            assert_eq!(migrated, expected);
+        }
+    }
+
+    #[test]
+    fn inconsistent_fields_are_rejected_together() {
+        let valid = StakeConfig {
+            points_per_stake: 10,
+            max_stake_duration: 30,
+            freeze_period_secs: 30 * SECONDS_PER_DAY as u64,
+            lockup_options: [lockup(0, 10_000), lockup(30, 15_000), LockupOption::default()],
+            ..Default::default()
+        };
+        valid.validate_consistency().unwrap();
+
+        for (config, error) in [
+            (StakeConfig { points_per_stake: 0, ..valid }, StakeError::InvalidPointsPerStake),
+            (StakeConfig { freeze_period_secs: 30 * SECONDS_PER_DAY as u64 + 1, ..valid }, StakeError::FreezeExceedsStakeDuration),
+            (StakeConfig { lockup_options: [lockup(0, 10_000), lockup(31, 15_000), LockupOption::default()], ..valid }, StakeError::FreezeExceedsStakeDuration),
+            (StakeConfig { max_stakes_per_window: 2, ..valid }, StakeError::InvalidStakeWindow),
+            (StakeConfig { compound_boost_bps: 100, ..valid }, StakeError::InvalidBoostCap),
+            (StakeConfig { lock_boost_bps: 100, ..valid }, StakeError::InvalidBoostCap),
+        ] {
+            assert_eq!(config.validate_consistency().unwrap_err(), error!(error));
+        }
+
+        // Without a max duration stakes accrue for as long as they're held
+        StakeConfig { max_stake_duration: 0, freeze_period_secs: u64::MAX, ..valid }.validate_consistency().unwrap();
+        // Options that aren't offered don't count
+        StakeConfig { lockup_options: [lockup(0, 10_000), lockup(30, 15_000), lockup(90, 0)], ..valid }.validate_consistency().unwrap();
+    }
+
+    #[test]
+    fn pause_flags_are_independent() {
+        let config = StakeConfig { pause_flags: PAUSE_CLAIM, ..Default::default() };
+
+        assert!(config.is_paused(PAUSE_CLAIM));
+        assert!(!config.is_paused(PAUSE_STAKE));
+    }
+
+    #[test]
+    fn penalty_bps_validation() {
+        assert!(StakeConfig::validate_early_unstake_penalty_bps(BPS_DENOMINATOR).is_ok());
+        assert!(StakeConfig::validate_early_unstake_penalty_bps(EMERGENCY_UNSTAKE_DISABLED).is_ok());
+        assert!(StakeConfig::validate_early_unstake_penalty_bps(BPS_DENOMINATOR + 1).is_err());
+    }
+}
@@ -1,12 +1,1014 @@
 use anchor_lang::prelude::*;
 
-#[account]
+use crate::{constants::{SECONDS_PER_DAY, SET_COLLECTIONS}, errors::StakeError, rewards::{days_between, decayed_points, governance_weight_after, points_expired}, state::StakeConfig};
+
+// `#[account]` spelled out so `try_deserialize` can tell an account still in an older layout,
+// which fails with AccountNeedsMigration, from one that's corrupt
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
 pub struct UserAccount{
-    pub points: u32,
+    pub points: u64,
     pub amount_staked: u8,
+    pub last_claim_ts: i64,
+    // Set on the first stake, a referral bonus is only paid out before it
+    pub has_staked_before: bool,
+    // Earns `StakeConfig.referral_bps` of this user's unstake points, fixed at initialization
+    pub referrer: Option<Pubkey>,
+    // Reward token base units compounded back in, boosts `StakeConfig.compounded_points`
+    pub staked_reward_balance: u64,
+    // May sign `claim` for this user, rewards still go to the user's ATA
+    pub claim_delegate: Option<Pubkey>,
+    // Latest snapshot paid out by claim_snapshot
+    pub last_snapshot_id: u64,
+    // Points compounded with compound_locked, bonus included, claimable from `locked_until`
+    pub locked_points: u64,
+    pub locked_until: i64,
+    // Points paid out as reward tokens by claim, claim_locked and unstake, ranks the leaderboard
+    pub points_paid: u64,
+    // `UserAccount::VERSION` once created or migrated, instructions reject any other
+    pub version: u8,
+    // The wallet this account belongs to, since version 2
+    pub owner: Pubkey,
+    // NFTs staked per `StakeConfig.set_collections` slot
+    pub set_pieces_staked: [u8; SET_COLLECTIONS],
+    // When the user last came to hold a full set, 0 while they don't
+    pub set_complete_since: i64,
+    // Start of the day `points` last decayed from, 0 before they're first touched
+    pub last_points_update_ts: i64,
+    // Reward token base units lock_boost holds in the boost vault, 0 once unlock_boost returns them
+    pub boost_amount: u64,
+    // When the lock boost stops counting and unlock_boost can return the tokens
+    pub boost_expiry: i64,
+    // Stakes counted against `StakeConfig.max_stakes_per_window` since `window_start_ts`
+    pub stakes_in_window: u16,
+    pub window_start_ts: i64,
+    // When `points` were last credited or spent, `StakeConfig.points_expiry_secs` counts from here
+    pub points_updated_at: i64,
+    // Voting weight, grows while NFTs are staked and decays while none are, never spent
+    pub governance_weight: u64,
+    // When `governance_weight` was last settled, 0 before it's first touched
+    pub weight_updated_at: i64,
+    // Room for new fields so growing the layout doesn't need a realloc, zeroed until used
+    pub reserved: [u8; 1],
     pub bump: u8
 }
 
+impl Discriminator for UserAccount {
+    const DISCRIMINATOR: &'static [u8] = &[211, 33, 136, 16, 186, 110, 242, 127];
+}
+
+impl Owner for UserAccount {
+    fn owner() -> Pubkey {
+        crate::ID
+    }
+}
+
+impl AccountSerialize for UserAccount {
+    fn try_serialize<W: std::io::Write>(&self, writer: &mut W) -> Result<()> {
+        if writer.write_all(Self::DISCRIMINATOR).is_err() || AnchorSerialize::serialize(self, writer).is_err() {
+            return err!(ErrorCode::AccountDidNotSerialize);
+        }
+        Ok(())
+    }
+}
+
+impl AccountDeserialize for UserAccount {
+    fn try_deserialize(buf: &mut &[u8]) -> Result<Self> {
+        if buf.len() < Self::DISCRIMINATOR.len() {
+            return err!(ErrorCode::AccountDiscriminatorNotFound);
+        }
+        if buf[..Self::DISCRIMINATOR.len()] != *Self::DISCRIMINATOR {
+            return Err(error!(ErrorCode::AccountDiscriminatorMismatch).with_account_name("UserAccount"));
+        }
+        // Every older layout is shorter than the current one, `migrate_user` grows it
+        if buf.len() < Self::INIT_SPACE {
+            return err!(StakeError::AccountNeedsMigration);
+        }
+        Self::try_deserialize_unchecked(buf)
+    }
+
+    fn try_deserialize_unchecked(buf: &mut &[u8]) -> Result<Self> {
+        let mut data: &[u8] = &buf[Self::DISCRIMINATOR.len()..];
+        AnchorDeserialize::deserialize(&mut data).map_err(|_| error!(ErrorCode::AccountDidNotDeserialize))
+    }
+}
+
 impl Space for UserAccount {
-    const INIT_SPACE: usize = 8 + 4 + 1 + 1;
-}
\ No newline at end of file
+    const INIT_SPACE: usize = 8 + 8 + 1 + 8 + 1 + (1 + 32) + 8 + (1 + 32) + 8 + 8 + 8 + 8 + 1 + 32 + SET_COLLECTIONS + 8 + 8 + 8 + 8 + 2 + 8 + 8 + 8 + 8 + 1 + 1;
+}
+
+impl UserAccount {
+    /// Layout version written by `initialize`, `stake` and `migrate_user`. Accounts from before
+    /// it, v0, are the `V*_SPACE` layouts below. Versions 1 and 2 are `V10_SPACE`, version 1
+    /// without `owner`, version 3 is `V11_SPACE` and version 4 `V12_SPACE`.
+    pub const VERSION: u8 = 5;
+
+    /// Size of accounts created while `points` was a `u32`, see `migrate_user`.
+    pub const V1_SPACE: usize = 8 + 4 + 1 + 8 + 1;
+    /// Size of accounts created before `has_staked_before`.
+    pub const V2_SPACE: usize = 8 + 8 + 1 + 8 + 1;
+    /// Size of accounts created before `referrer`.
+    pub const V3_SPACE: usize = 8 + 8 + 1 + 8 + 1 + 1;
+    /// Size of accounts created before `staked_reward_balance`.
+    pub const V4_SPACE: usize = 8 + 8 + 1 + 8 + 1 + (1 + 32) + 1;
+    /// Size of accounts created before `claim_delegate`.
+    pub const V5_SPACE: usize = 8 + 8 + 1 + 8 + 1 + (1 + 32) + 8 + 1;
+    /// Size of accounts created before `last_snapshot_id`.
+    pub const V6_SPACE: usize = 8 + 8 + 1 + 8 + 1 + (1 + 32) + 8 + (1 + 32) + 1;
+    /// Size of accounts created before `locked_points`.
+    pub const V7_SPACE: usize = 8 + 8 + 1 + 8 + 1 + (1 + 32) + 8 + (1 + 32) + 8 + 1;
+    /// Size of accounts created before `points_paid`.
+    pub const V8_SPACE: usize = 8 + 8 + 1 + 8 + 1 + (1 + 32) + 8 + (1 + 32) + 8 + 8 + 8 + 1;
+    /// Size of accounts created before `version`.
+    pub const V9_SPACE: usize = 8 + 8 + 1 + 8 + 1 + (1 + 32) + 8 + (1 + 32) + 8 + 8 + 8 + 8 + 1;
+    /// Size of version 1 and 2 accounts, before `boost_amount`.
+    pub const V10_SPACE: usize = Self::V11_SPACE - 8 - 8;
+    /// Size of version 3 accounts, before `points_updated_at`.
+    pub const V11_SPACE: usize = Self::V12_SPACE - 8;
+    /// Size of version 4 accounts, before `governance_weight`.
+    pub const V12_SPACE: usize = Self::INIT_SPACE - 8 - 8;
+
+    /// A fresh account for `owner`, who `referrer` referred.
+    pub fn new(owner: Pubkey, referrer: Option<Pubkey>, bump: u8) -> Self {
+        Self {
+            points: 0,
+            amount_staked: 0,
+            last_claim_ts: 0,
+            has_staked_before: false,
+            referrer,
+            staked_reward_balance: 0,
+            claim_delegate: None,
+            last_snapshot_id: 0,
+            locked_points: 0,
+            locked_until: 0,
+            points_paid: 0,
+            version: Self::VERSION,
+            owner,
+            set_pieces_staked: [0; SET_COLLECTIONS],
+            set_complete_since: 0,
+            last_points_update_ts: 0,
+            boost_amount: 0,
+            boost_expiry: 0,
+            stakes_in_window: 0,
+            window_start_ts: 0,
+            points_updated_at: 0,
+            governance_weight: 0,
+            weight_updated_at: 0,
+            reserved: [0; 1],
+            bump,
+        }
+    }
+
+    pub fn is_current(&self) -> bool {
+        self.version == Self::VERSION
+    }
+
+    /// Whether this is an account `init_if_needed` just created, still zeroed.
+    pub fn is_new(&self) -> bool {
+        self.version == 0
+    }
+
+    /// Sets `has_staked_before`, counting the user in `config.total_users` on their first stake.
+    pub fn record_stake(&mut self, config: &mut StakeConfig) -> Result<()> {
+        if !self.has_staked_before {
+            config.add_user()?;
+            self.has_staked_before = true;
+        }
+        Ok(())
+    }
+
+    /// Counts `count` new stakes against `config.max_stakes_per_window`, opening a new window once
+    /// `config.window_len` has passed since the current one started. Fails with StakeRateLimited
+    /// past the limit.
+    pub fn record_window_stakes(&mut self, config: &StakeConfig, count: u16, now: i64) -> Result<()> {
+        // A zero limit leaves staking unlimited
+        if config.max_stakes_per_window == 0 {
+            return Ok(());
+        }
+
+        if now.saturating_sub(self.window_start_ts) >= config.window_len as i64 {
+            self.window_start_ts = now;
+            self.stakes_in_window = 0;
+        }
+
+        let stakes_in_window = self.stakes_in_window
+            .checked_add(count)
+            .ok_or(StakeError::StakeRateLimited)?;
+        require!(stakes_in_window <= config.max_stakes_per_window, StakeError::StakeRateLimited);
+        self.stakes_in_window = stakes_in_window;
+        Ok(())
+    }
+
+    /// Errors until `config.claim_cooldown` has passed since the last claim or compound.
+    pub fn require_claim_cooldown_passed(&self, config: &StakeConfig, now: i64) -> Result<()> {
+        let next_claim_ts = self.last_claim_ts
+            .checked_add(config.claim_cooldown as i64)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+
+        // A zero cooldown means claims are never rate limited
+        require!(now >= next_claim_ts, StakeError::ClaimCooldownActive);
+        Ok(())
+    }
+
+    pub fn record_points_paid(&mut self, points: u64) -> Result<()> {
+        self.points_paid = self.points_paid
+            .checked_add(points)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    /// Decays `points` for the whole days since `last_points_update_ts`, returning the points lost.
+    /// The part of a day left over carries to the next call, so calling it twice in a slot
+    /// changes nothing. Call before adding points so new ones don't decay for days already past,
+    /// and before changing `amount_staked`, since it settles `governance_weight` too.
+    pub fn apply_decay(&mut self, config: &StakeConfig, now: i64) -> Result<u64> {
+        self.settle_governance_weight(config, now);
+
+        if self.last_points_update_ts == 0 || config.decay_rate_bps_per_day == 0 {
+            self.last_points_update_ts = now;
+            return Ok(0);
+        }
+
+        let days = days_between(self.last_points_update_ts, now)?;
+        if days == 0 {
+            return Ok(0);
+        }
+
+        let points = self.decayed_points(config, now)?;
+        let lost = self.points
+            .checked_sub(points)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        self.points = points;
+        self.last_points_update_ts = self.last_points_update_ts
+            .checked_add(days as i64 * SECONDS_PER_DAY)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        Ok(lost)
+    }
+
+    /// Forfeits all of `points` once `config.points_expiry_secs` has passed since `points_updated_at`,
+    /// returning the points lost and restarting the window at `now` if there were any. Call before
+    /// crediting or spending points, so a credit to an expired balance starts it over rather than
+    /// reviving it.
+    pub fn apply_expiry(&mut self, config: &StakeConfig, now: i64) -> u64 {
+        let forfeited = if points_expired(self.points_updated_at, config.points_expiry_secs, now) {
+            self.points
+        } else {
+            0
+        };
+        if forfeited > 0 {
+            self.points = 0;
+            self.points_updated_at = now;
+        }
+        forfeited
+    }
+
+    /// Restarts the expiry window at `now` if a credit or spend moved `points` off `before`, the
+    /// balance `apply_expiry` left. Touches that move nothing leave the window running.
+    pub fn restart_expiry_if_changed(&mut self, before: u64, now: i64) {
+        if self.points != before {
+            self.points_updated_at = now;
+        }
+    }
+
+    /// Brings `governance_weight` up to `now` for the `amount_staked` held since `weight_updated_at`.
+    /// The first settlement only starts the clock. Changes only with time staked or idle, so
+    /// claims and redemptions settling it along with points leave the weight as it was.
+    pub fn settle_governance_weight(&mut self, config: &StakeConfig, now: i64) {
+        self.governance_weight = self.governance_weight_at(config, now);
+        self.weight_updated_at = now;
+    }
+
+    /// `governance_weight` as `settle_governance_weight` would leave it at `now`, for views.
+    pub fn governance_weight_at(&self, config: &StakeConfig, now: i64) -> u64 {
+        if self.weight_updated_at == 0 {
+            return self.governance_weight;
+        }
+        governance_weight_after(
+            self.governance_weight,
+            self.amount_staked,
+            config.weight_per_day,
+            config.weight_decay_per_day,
+            now.saturating_sub(self.weight_updated_at),
+        )
+    }
+
+    /// `points` as `apply_decay` would leave them at `now`, for views.
+    pub fn decayed_points(&self, config: &StakeConfig, now: i64) -> Result<u64> {
+        if self.last_points_update_ts == 0 {
+            return Ok(self.points);
+        }
+        let days = days_between(self.last_points_update_ts, now)?;
+        Ok(decayed_points(self.points, config.decay_rate_bps_per_day, days))
+    }
+
+    /// `points` as `apply_decay` and `apply_expiry` would leave them at `now`, for views.
+    pub fn live_points(&self, config: &StakeConfig, now: i64) -> Result<u64> {
+        if points_expired(self.points_updated_at, config.points_expiry_secs, now) {
+            return Ok(0);
+        }
+        self.decayed_points(config, now)
+    }
+
+    /// Counts a staked NFT of set slot `piece`, starting `set_complete_since` if it completes the set.
+    pub fn add_set_piece(&mut self, config: &StakeConfig, piece: Option<u8>, now: i64) {
+        if let Some(piece) = piece {
+            self.set_pieces_staked[piece as usize] = self.set_pieces_staked[piece as usize].saturating_add(1);
+            self.update_set_complete_since(config, now);
+        }
+    }
+
+    /// Uncounts an unstaked NFT of set slot `piece`, clearing `set_complete_since` if it breaks the set.
+    pub fn remove_set_piece(&mut self, config: &StakeConfig, piece: Option<u8>, now: i64) {
+        if let Some(piece) = piece {
+            self.set_pieces_staked[piece as usize] = self.set_pieces_staked[piece as usize].saturating_sub(1);
+            self.update_set_complete_since(config, now);
+        }
+    }
+
+    fn update_set_complete_since(&mut self, config: &StakeConfig, now: i64) {
+        let mut slots = config.set_collections
+            .iter()
+            .zip(self.set_pieces_staked)
+            .filter(|(collection, _)| **collection != Pubkey::default())
+            .peekable();
+        let complete = slots.peek().is_some() && slots.all(|(_, staked)| staked > 0);
+
+        if !complete {
+            self.set_complete_since = 0;
+        } else if self.set_complete_since == 0 {
+            self.set_complete_since = now;
+        }
+    }
+
+    /// Moves all of `points` into `locked_points` with `config.compound_bonus_bps` on top, returning
+    /// the points locked. Locks don't stack into buckets, every compound pushes `locked_until` out to
+    /// `compound_lock_secs` from `now` for the whole balance.
+    pub fn lock_points(&mut self, config: &StakeConfig, now: i64) -> Result<u64> {
+        let locked = config.compound_bonus(self.points)?;
+        let locked_until = now
+            .checked_add(config.compound_lock_secs as i64)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+
+        self.locked_points = self.locked_points
+            .checked_add(locked)
+            .ok_or(StakeError::ArithmeticOverflow)?;
+        // A shorter lock set since the last compound doesn't cut the running one short
+        self.locked_until = self.locked_until.max(locked_until);
+        self.points = 0;
+        Ok(locked)
+    }
+
+    /// Empties `locked_points` once `locked_until` has passed, returning the points to pay out.
+    pub fn unlock_points(&mut self, now: i64) -> Result<u64> {
+        require!(self.locked_points > 0, StakeError::NothingToClaim);
+        require!(now >= self.locked_until, StakeError::CompoundStillLocked);
+
+        let unlocked = self.locked_points;
+        self.locked_points = 0;
+        Ok(unlocked)
+    }
+
+    /// Whether `key` is `wallet`'s user account in `config`, for user accounts passed without a
+    /// seeds constraint since they don't store their wallet.
+    pub fn is_address_of(key: &Pubkey, config: &Pubkey, wallet: &Pubkey, bump: u8) -> bool {
+        Pubkey::create_program_address(&[b"user", config.as_ref(), wallet.as_ref(), &[bump]], &crate::ID)
+            .is_ok_and(|address| address == *key)
+    }
+
+    /// Decodes an account still in one of the legacy layouts or an older version of the current
+    /// one. `owner` is left for the caller to fill in.
+    pub fn try_from_legacy(data: &[u8]) -> Result<Self> {
+        if [Self::V10_SPACE, Self::V11_SPACE, Self::V12_SPACE].contains(&data.len()) {
+            require!(data[..8] == *Self::DISCRIMINATOR, ErrorCode::AccountDiscriminatorMismatch);
+
+            // Version 4 is the current layout without the governance weight fields, version 3 also
+            // lacks `points_updated_at` and versions 1 and 2 the boost fields, which go in ahead of
+            // the 11 bytes then reserved, now the stake window and `reserved`. A `None` option
+            // shifts the fields after it up, leaving 32 bytes of zero padding at the end
+            let option_len = |tag: u8| if tag == 0 { 1 } else { 1 + 32 };
+            let referrer_len = option_len(data[26]);
+            let claim_delegate_len = option_len(data[26 + referrer_len + 8]);
+            let end = data.len() - (2 * (1 + 32) - referrer_len - claim_delegate_len);
+            let before_boost = data.len() == Self::V10_SPACE;
+            let before_expiry = data.len() != Self::V12_SPACE;
+
+            let mut data = data[..end].to_vec();
+            if before_boost {
+                let boost_at = end - 11 - 1;
+                data.splice(boost_at..boost_at, [0; 8 + 8]);
+            }
+            // Every older version starts its expiry window on its next credit or spend
+            if before_expiry {
+                let expiry_at = data.len() - 1 - 1;
+                data.splice(expiry_at..expiry_at, [0; 8]);
+            }
+            // and its governance weight from zero on its next settlement
+            let weight_at = data.len() - 1 - 1;
+            data.splice(weight_at..weight_at, [0; 8 + 8]);
+            let account = Self::try_deserialize_unchecked(&mut &data[..])?;
+            return Ok(Self { version: Self::VERSION, ..account });
+        }
+
+        let points_len = match data.len() {
+            Self::V1_SPACE => 4,
+            Self::V2_SPACE | Self::V3_SPACE | Self::V4_SPACE | Self::V5_SPACE | Self::V6_SPACE | Self::V7_SPACE | Self::V8_SPACE | Self::V9_SPACE => 8,
+            _ => return err!(StakeError::UserAlreadyMigrated),
+        };
+        require!(data[..8] == *Self::DISCRIMINATOR, ErrorCode::AccountDiscriminatorMismatch);
+
+        let (points, rest) = data[8..].split_at(points_len);
+        let mut points_bytes = [0; 8];
+        points_bytes[..points_len].copy_from_slice(points);
+
+        let amount_staked = rest[0];
+        let last_claim_ts = i64::from_le_bytes(rest[1..9].try_into().unwrap());
+
+        // Whether an older account ever staked is lost, so it doesn't get a first-stake referral
+        let (has_staked_before, mut rest) = match data.len() {
+            Self::V1_SPACE | Self::V2_SPACE => (true, &rest[9..]),
+            _ => (rest[9] != 0, &rest[10..]),
+        };
+
+        // A `None` referrer shifts the fields after it up, leaving zero padding at the end
+        let referrer = match data.len() {
+            Self::V4_SPACE | Self::V5_SPACE | Self::V6_SPACE | Self::V7_SPACE | Self::V8_SPACE | Self::V9_SPACE => Option::<Pubkey>::deserialize(&mut rest)?,
+            _ => None,
+        };
+        let staked_reward_balance = match data.len() {
+            Self::V5_SPACE | Self::V6_SPACE | Self::V7_SPACE | Self::V8_SPACE | Self::V9_SPACE => u64::deserialize(&mut rest)?,
+            _ => 0,
+        };
+        let claim_delegate = match data.len() {
+            Self::V6_SPACE | Self::V7_SPACE | Self::V8_SPACE | Self::V9_SPACE => Option::<Pubkey>::deserialize(&mut rest)?,
+            _ => None,
+        };
+        let last_snapshot_id = match data.len() {
+            Self::V7_SPACE | Self::V8_SPACE | Self::V9_SPACE => u64::deserialize(&mut rest)?,
+            _ => 0,
+        };
+        let (locked_points, locked_until) = match data.len() {
+            Self::V8_SPACE | Self::V9_SPACE => (u64::deserialize(&mut rest)?, i64::deserialize(&mut rest)?),
+            _ => (0, 0),
+        };
+        let points_paid = match data.len() {
+            Self::V9_SPACE => u64::deserialize(&mut rest)?,
+            _ => 0,
+        };
+
+        Ok(Self {
+            points: u64::from_le_bytes(points_bytes),
+            amount_staked,
+            last_claim_ts,
+            has_staked_before,
+            referrer,
+            staked_reward_balance,
+            claim_delegate,
+            last_snapshot_id,
+            locked_points,
+            locked_until,
+            points_paid,
+            version: Self::VERSION,
+            owner: Pubkey::default(),
+            set_pieces_staked: [0; SET_COLLECTIONS],
+            set_complete_since: 0,
+            last_points_update_ts: 0,
+            boost_amount: 0,
+            boost_expiry: 0,
+            stakes_in_window: 0,
+            window_start_ts: 0,
+            points_updated_at: 0,
+            governance_weight: 0,
+            weight_updated_at: 0,
+            reserved: [0; 1],
+            bump: rest[0],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn legacy(points: &[u8], amount_staked: u8, last_claim_ts: i64, has_staked_before: Option<bool>, bump: u8) -> Vec<u8> {
+        let mut data = UserAccount::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(points);
+        data.push(amount_staked);
+        data.extend_from_slice(&last_claim_ts.to_le_bytes());
+        data.extend(has_staked_before.map(u8::from));
+        data.push(bump);
+        data
+    }
+
+    #[test]
+    fn legacy_layouts_round_trip() {
+        for (data, points, has_staked_before) in [
+            (legacy(&u32::MAX.to_le_bytes(), 3, -42, None, 254), u32::MAX as u64, true),
+            (legacy(&u64::MAX.to_le_bytes(), 3, -42, None, 254), u64::MAX, true),
+            (legacy(&u64::MAX.to_le_bytes(), 3, -42, Some(false), 254), u64::MAX, false),
+        ] {
+            let user = UserAccount::try_from_legacy(&data).unwrap();
+
+            assert_eq!(user.points, points);
+            assert_eq!(user.amount_staked, 3);
+            assert_eq!(user.last_claim_ts, -42);
+            assert_eq!(user.has_staked_before, has_staked_before);
+            assert_eq!(user.referrer, None);
+            assert_eq!(user.bump, 254);
+
+            // Serialized Option<Pubkey> is shorter when None, the realloc keeps room for Some
+            let mut migrated = Vec::new();
+            user.try_serialize(&mut migrated).unwrap();
+            assert_eq!(migrated.len(), UserAccount::INIT_SPACE - 64);
+        }
+    }
+
+    #[test]
+    fn v4_layout_keeps_the_referrer() {
+        let referrer = Pubkey::new_unique();
+
+        for stored in [Some(referrer), None] {
+            let mut data = legacy(&7_u64.to_le_bytes(), 1, 5, Some(true), 253);
+            let bump = data.pop().unwrap();
+            data.extend(stored.try_to_vec().unwrap());
+            data.push(bump);
+            data.resize(UserAccount::V4_SPACE, 0);
+
+            let user = UserAccount::try_from_legacy(&data).unwrap();
+
+            assert_eq!(user.points, 7);
+            assert!(user.has_staked_before);
+            assert_eq!(user.referrer, stored);
+            assert_eq!(user.staked_reward_balance, 0);
+            assert_eq!(user.bump, 253);
+        }
+    }
+
+    #[test]
+    fn record_stake_counts_first_stakes_only() {
+        let mut config = StakeConfig::default();
+        let mut user = UserAccount::try_from_legacy(&legacy(&0_u64.to_le_bytes(), 0, 0, Some(false), 0)).unwrap();
+
+        user.record_stake(&mut config).unwrap();
+        user.record_stake(&mut config).unwrap();
+
+        assert!(user.has_staked_before);
+        assert_eq!(config.total_users, 1);
+    }
+
+    #[test]
+    fn v5_layout_keeps_the_compounded_balance() {
+        let referrer = Pubkey::new_unique();
+
+        for stored in [Some(referrer), None] {
+            let mut data = legacy(&7_u64.to_le_bytes(), 1, 5, Some(false), 253);
+            let bump = data.pop().unwrap();
+            data.extend(stored.try_to_vec().unwrap());
+            data.extend(9_000_u64.to_le_bytes());
+            data.push(bump);
+            data.resize(UserAccount::V5_SPACE, 0);
+
+            let user = UserAccount::try_from_legacy(&data).unwrap();
+
+            assert!(!user.has_staked_before);
+            assert_eq!(user.referrer, stored);
+            assert_eq!(user.staked_reward_balance, 9_000);
+            assert_eq!(user.claim_delegate, None);
+            assert_eq!(user.bump, 253);
+        }
+    }
+
+    #[test]
+    fn v6_layout_keeps_the_claim_delegate() {
+        let claim_delegate = Pubkey::new_unique();
+
+        for (referrer, stored) in [(None, Some(claim_delegate)), (Some(Pubkey::new_unique()), None)] {
+            let mut data = legacy(&7_u64.to_le_bytes(), 1, 5, Some(true), 253);
+            let bump = data.pop().unwrap();
+            data.extend(referrer.try_to_vec().unwrap());
+            data.extend(9_000_u64.to_le_bytes());
+            data.extend(stored.try_to_vec().unwrap());
+            data.push(bump);
+            data.resize(UserAccount::V6_SPACE, 0);
+
+            let user = UserAccount::try_from_legacy(&data).unwrap();
+
+            assert_eq!(user.referrer, referrer);
+            assert_eq!(user.staked_reward_balance, 9_000);
+            assert_eq!(user.claim_delegate, stored);
+            assert_eq!(user.last_snapshot_id, 0);
+            assert_eq!(user.bump, 253);
+        }
+    }
+
+    #[test]
+    fn v7_layout_keeps_the_last_snapshot_id() {
+        let mut data = legacy(&7_u64.to_le_bytes(), 1, 5, Some(true), 253);
+        let bump = data.pop().unwrap();
+        data.extend(None::<Pubkey>.try_to_vec().unwrap());
+        data.extend(9_000_u64.to_le_bytes());
+        data.extend(None::<Pubkey>.try_to_vec().unwrap());
+        data.extend(3_u64.to_le_bytes());
+        data.push(bump);
+        data.resize(UserAccount::V7_SPACE, 0);
+
+        let user = UserAccount::try_from_legacy(&data).unwrap();
+
+        assert_eq!(user.staked_reward_balance, 9_000);
+        assert_eq!(user.last_snapshot_id, 3);
+        assert_eq!(user.locked_points, 0);
+        assert_eq!(user.bump, 253);
+    }
+
+    #[test]
+    fn v8_layout_keeps_the_locked_points() {
+        let mut data = legacy(&7_u64.to_le_bytes(), 1, 5, Some(true), 253);
+        let bump = data.pop().unwrap();
+        data.extend(None::<Pubkey>.try_to_vec().unwrap());
+        data.extend(9_000_u64.to_le_bytes());
+        data.extend(None::<Pubkey>.try_to_vec().unwrap());
+        data.extend(3_u64.to_le_bytes());
+        data.extend(1_200_u64.to_le_bytes());
+        data.extend(110_i64.to_le_bytes());
+        data.push(bump);
+        data.resize(UserAccount::V8_SPACE, 0);
+
+        let user = UserAccount::try_from_legacy(&data).unwrap();
+
+        assert_eq!(user.last_snapshot_id, 3);
+        assert_eq!((user.locked_points, user.locked_until), (1_200, 110));
+        assert_eq!(user.points_paid, 0);
+        assert_eq!(user.bump, 253);
+    }
+
+    #[test]
+    fn v9_layout_migrates_into_the_current_version() {
+        let mut data = legacy(&7_u64.to_le_bytes(), 2, 5, Some(true), 253);
+        let bump = data.pop().unwrap();
+        data.extend(None::<Pubkey>.try_to_vec().unwrap());
+        data.extend(9_000_u64.to_le_bytes());
+        data.extend(None::<Pubkey>.try_to_vec().unwrap());
+        data.extend(3_u64.to_le_bytes());
+        data.extend(1_200_u64.to_le_bytes());
+        data.extend(110_i64.to_le_bytes());
+        data.extend(40_u64.to_le_bytes());
+        data.push(bump);
+        data.resize(UserAccount::V9_SPACE, 0);
+
+        let user = UserAccount::try_from_legacy(&data).unwrap();
+
+        assert_eq!((user.points, user.amount_staked), (7, 2));
+        assert_eq!(user.points_paid, 40);
+        assert!(user.is_current());
+        assert_eq!((user.stakes_in_window, user.window_start_ts, user.reserved), (0, 0, [0; 1]));
+        assert_eq!(user.bump, 253);
+
+        // `migrate_user` reallocs to the current size before writing it back
+        let mut migrated = Vec::new();
+        user.try_serialize(&mut migrated).unwrap();
+        migrated.resize(UserAccount::INIT_SPACE, 0);
+        let decoded = UserAccount::try_deserialize(&mut migrated.as_slice()).unwrap();
+        assert_eq!((decoded.points, decoded.amount_staked, decoded.bump), (7, 2, 253));
+        assert!(decoded.is_current());
+    }
+
+    // The current layout serialized without the governance weight fields
+    fn serialized_before_weight(user: &UserAccount) -> Vec<u8> {
+        let mut data = Vec::new();
+        user.try_serialize(&mut data).unwrap();
+        let weight_at = data.len() - 1 - 1;
+        data.drain(weight_at - 8 - 8..weight_at);
+        data
+    }
+
+    // The current layout serialized without those or `points_updated_at`
+    fn serialized_before_expiry(user: &UserAccount) -> Vec<u8> {
+        let mut data = serialized_before_weight(user);
+        let expiry_at = data.len() - 1 - 1;
+        data.drain(expiry_at - 8..expiry_at);
+        data
+    }
+
+    // A version 4 account
+    fn v12(user: &UserAccount) -> Vec<u8> {
+        let mut data = serialized_before_weight(user);
+        data.resize(UserAccount::V12_SPACE, 0);
+        data
+    }
+
+    // A version 3 account
+    fn v11(user: &UserAccount) -> Vec<u8> {
+        let mut data = serialized_before_expiry(user);
+        data.resize(UserAccount::V11_SPACE, 0);
+        data
+    }
+
+    // A version 1 or 2 account: the version 3 layout without the boost fields
+    fn v10(user: &UserAccount) -> Vec<u8> {
+        let mut data = serialized_before_expiry(user);
+        let boost_at = data.len() - (2 + 8 + 1) - 1;
+        data.drain(boost_at - 8 - 8..boost_at);
+        data.resize(UserAccount::V10_SPACE, 0);
+        data
+    }
+
+    #[test]
+    fn older_layouts_need_migrating_rather_than_failing_to_decode() {
+        let user = UserAccount::new(Pubkey::new_unique(), Some(Pubkey::new_unique()), 253);
+
+        for legacy in [v10(&user), v11(&user), v12(&user)] {
+            assert_eq!(UserAccount::try_deserialize(&mut legacy.as_slice()).err(), Some(error!(StakeError::AccountNeedsMigration)));
+        }
+        let mut v0 = UserAccount::DISCRIMINATOR.to_vec();
+        v0.resize(UserAccount::V1_SPACE, 0);
+        assert_eq!(UserAccount::try_deserialize(&mut v0.as_slice()).err(), Some(error!(StakeError::AccountNeedsMigration)));
+
+        // Full size but undecodable is still reported as corrupt
+        let mut corrupt = UserAccount::DISCRIMINATOR.to_vec();
+        corrupt.resize(UserAccount::INIT_SPACE, 0xff);
+        assert_eq!(UserAccount::try_deserialize(&mut corrupt.as_slice()).err(), Some(error!(ErrorCode::AccountDidNotDeserialize)));
+
+        let mut current = Vec::new();
+        user.try_serialize(&mut current).unwrap();
+        current.resize(UserAccount::INIT_SPACE, 0);
+        assert_eq!(UserAccount::try_deserialize(&mut current.as_slice()).unwrap().referrer, user.referrer);
+    }
+
+    #[test]
+    fn version_1_accounts_upgrade_in_place() {
+        // Version 1 had 64 reserved bytes where `owner` and the remaining reserve now sit
+        let mut v1 = UserAccount { points: 7, amount_staked: 2, version: 1, ..UserAccount::new(Pubkey::default(), None, 253) };
+        v1.points_paid = 40;
+
+        let user = UserAccount::try_from_legacy(&v10(&v1)).unwrap();
+
+        assert!(user.is_current());
+        assert_eq!((user.points, user.amount_staked, user.points_paid, user.bump), (7, 2, 40, 253));
+        assert_eq!(user.owner, Pubkey::default());
+    }
+
+    #[test]
+    fn version_2_accounts_make_room_for_the_boost() {
+        let owner = Pubkey::new_unique();
+
+        for (referrer, claim_delegate) in [(None, None), (Some(Pubkey::new_unique()), None), (None, Some(Pubkey::new_unique())), (Some(Pubkey::new_unique()), Some(Pubkey::new_unique()))] {
+            let mut v2 = UserAccount { version: 2, claim_delegate, ..UserAccount::new(owner, referrer, 253) };
+            v2.last_points_update_ts = 1_234;
+            v2.set_pieces_staked = [1, 0, 2, 0, 0];
+
+            let user = UserAccount::try_from_legacy(&v10(&v2)).unwrap();
+
+            assert!(user.is_current());
+            assert_eq!((user.owner, user.referrer, user.claim_delegate), (owner, referrer, claim_delegate));
+            assert_eq!((user.set_pieces_staked, user.last_points_update_ts), ([1, 0, 2, 0, 0], 1_234));
+            assert_eq!((user.boost_amount, user.boost_expiry, user.bump), (0, 0, 253));
+        }
+
+        let mut current = Vec::new();
+        UserAccount::new(owner, None, 253).try_serialize(&mut current).unwrap();
+        current.resize(UserAccount::INIT_SPACE, 0);
+        assert!(UserAccount::try_from_legacy(&current).is_err());
+    }
+
+    #[test]
+    fn version_3_accounts_make_room_for_expiry() {
+        let owner = Pubkey::new_unique();
+
+        for referrer in [None, Some(Pubkey::new_unique())] {
+            let mut v3 = UserAccount { version: 3, points: 9, ..UserAccount::new(owner, referrer, 253) };
+            (v3.boost_amount, v3.window_start_ts) = (5, 77);
+
+            let user = UserAccount::try_from_legacy(&v11(&v3)).unwrap();
+
+            assert!(user.is_current());
+            assert_eq!((user.owner, user.referrer, user.points), (owner, referrer, 9));
+            assert_eq!((user.boost_amount, user.window_start_ts, user.points_updated_at, user.bump), (5, 77, 0, 253));
+        }
+    }
+
+    #[test]
+    fn version_4_accounts_make_room_for_governance_weight() {
+        let owner = Pubkey::new_unique();
+
+        for claim_delegate in [None, Some(Pubkey::new_unique())] {
+            let mut v4 = UserAccount { version: 4, amount_staked: 3, claim_delegate, ..UserAccount::new(owner, None, 253) };
+            (v4.window_start_ts, v4.points_updated_at) = (77, 1_500);
+
+            let user = UserAccount::try_from_legacy(&v12(&v4)).unwrap();
+
+            assert!(user.is_current());
+            assert_eq!((user.owner, user.claim_delegate, user.amount_staked), (owner, claim_delegate, 3));
+            assert_eq!((user.window_start_ts, user.points_updated_at, user.bump), (77, 1_500, 253));
+            assert_eq!((user.governance_weight, user.weight_updated_at), (0, 0));
+        }
+    }
+
+    #[test]
+    fn governance_weight_settles_apart_from_points() {
+        let config = StakeConfig { weight_per_day: 10, weight_decay_per_day: 4, decay_rate_bps_per_day: 5_000, ..Default::default() };
+        let mut user = UserAccount::new(Pubkey::new_unique(), None, 0);
+        user.amount_staked = 2;
+
+        // The first settlement only starts the clock
+        user.apply_decay(&config, 1_000).unwrap();
+        assert_eq!((user.governance_weight, user.weight_updated_at), (0, 1_000));
+
+        let day = SECONDS_PER_DAY;
+        assert_eq!(user.governance_weight_at(&config, 1_000 + day), 20);
+        user.points = 100;
+        user.apply_decay(&config, 1_000 + day).unwrap();
+        assert_eq!((user.governance_weight, user.points), (20, 50));
+
+        // Spending points leaves the weight alone
+        user.points = 0;
+        user.settle_governance_weight(&config, 1_000 + day);
+        assert_eq!(user.governance_weight, 20);
+
+        user.amount_staked = 0;
+        assert_eq!(user.governance_weight_at(&config, 1_000 + 3 * day), 12);
+        assert_eq!(user.governance_weight_at(&config, 1_000 + 30 * day), 0);
+    }
+
+    #[test]
+    fn points_expire_all_at_once() {
+        let config = StakeConfig { points_expiry_secs: 100, ..Default::default() };
+        let mut user = UserAccount::new(Pubkey::new_unique(), None, 0);
+
+        // The first credit starts the window
+        assert_eq!(user.apply_expiry(&config, 1_000), 0);
+        user.points = 1_000;
+        user.restart_expiry_if_changed(0, 1_000);
+        assert_eq!(user.live_points(&config, 1_100).unwrap(), 1_000);
+
+        // Exactly at the boundary a claim still sees every point
+        assert_eq!(user.apply_expiry(&config, 1_100), 0);
+        assert_eq!(user.points, 1_000);
+
+        // A touch that credits and spends nothing doesn't keep the balance alive
+        user.restart_expiry_if_changed(1_000, 1_150);
+        assert_eq!(user.points_updated_at, 1_000);
+
+        assert_eq!(user.live_points(&config, 1_201).unwrap(), 0);
+        assert_eq!(user.apply_expiry(&config, 1_201), 1_000);
+        assert_eq!((user.points, user.points_updated_at), (0, 1_201));
+
+        // A credit after expiry starts a fresh window
+        assert_eq!(user.apply_expiry(&config, 1_301), 0);
+        user.points = 50;
+        user.restart_expiry_if_changed(0, 1_301);
+        assert_eq!((user.points, user.points_updated_at), (50, 1_301));
+        assert_eq!(user.apply_expiry(&config, 1_401), 0);
+        assert_eq!(user.live_points(&config, 1_402).unwrap(), 0);
+
+        assert_eq!(user.apply_expiry(&StakeConfig::default(), i64::MAX), 0);
+    }
+
+    #[test]
+    fn decay_applies_once_per_whole_day() {
+        let config = StakeConfig { decay_rate_bps_per_day: 1_000, ..Default::default() };
+        let day = SECONDS_PER_DAY;
+        let mut user = UserAccount::new(Pubkey::new_unique(), None, 0);
+        user.points = 1_000;
+
+        // The first touch only starts the clock
+        assert_eq!(user.apply_decay(&config, day).unwrap(), 0);
+        assert_eq!(user.points, 1_000);
+
+        assert_eq!(user.apply_decay(&config, 2 * day + day / 2).unwrap(), 100);
+        assert_eq!(user.points, 900);
+        // Same slot, and the half day left over, change nothing yet
+        assert_eq!(user.apply_decay(&config, 2 * day + day / 2).unwrap(), 0);
+        assert_eq!(user.decayed_points(&config, 3 * day - 1).unwrap(), 900);
+        assert_eq!(user.apply_decay(&config, 3 * day).unwrap(), 90);
+        assert_eq!(user.points, 810);
+
+        // A decay rate turned off leaves points alone from then on
+        user.apply_decay(&StakeConfig::default(), 10 * day).unwrap();
+        assert_eq!(user.points, 810);
+        assert_eq!(user.apply_decay(&config, 10 * day).unwrap(), 0);
+    }
+
+    #[test]
+    fn set_completes_with_one_piece_per_collection() {
+        let mut config = StakeConfig::default();
+        config.set_collections[0] = Pubkey::new_unique();
+        config.set_collections[2] = Pubkey::new_unique();
+        let mut user = UserAccount::new(Pubkey::new_unique(), None, 0);
+
+        user.add_set_piece(&config, Some(0), 10);
+        user.add_set_piece(&config, Some(0), 20);
+        user.add_set_piece(&config, None, 30);
+        assert_eq!(user.set_complete_since, 0);
+
+        user.add_set_piece(&config, Some(2), 40);
+        assert_eq!(user.set_complete_since, 40);
+
+        // A duplicate piece coming off leaves the set whole
+        user.remove_set_piece(&config, Some(0), 50);
+        assert_eq!(user.set_complete_since, 40);
+        user.remove_set_piece(&config, Some(0), 60);
+        assert_eq!(user.set_complete_since, 0);
+
+        user.add_set_piece(&config, Some(0), 70);
+        assert_eq!(user.set_complete_since, 70);
+
+        // Stakes from before the set was configured don't underflow it
+        user.remove_set_piece(&config, Some(0), 80);
+        user.remove_set_piece(&config, Some(0), 90);
+        assert_eq!(user.set_pieces_staked[0], 0);
+
+        // Without set collections there's no set to complete
+        user.add_set_piece(&StakeConfig::default(), Some(1), 100);
+        assert_eq!(user.set_complete_since, 0);
+    }
+
+    #[test]
+    fn stake_windows_reset_lazily() {
+        let config = StakeConfig { max_stakes_per_window: 3, window_len: 100, ..Default::default() };
+        let mut user = UserAccount::new(Pubkey::default(), None, 0);
+
+        user.record_window_stakes(&config, 2, 1_000).unwrap();
+        user.record_window_stakes(&config, 1, 1_050).unwrap();
+        assert_eq!(user.record_window_stakes(&config, 1, 1_099).err(), Some(error!(StakeError::StakeRateLimited)));
+        assert_eq!((user.stakes_in_window, user.window_start_ts), (3, 1_000));
+
+        // The window runs from its first stake, not the latest one
+        user.record_window_stakes(&config, 1, 1_100).unwrap();
+        assert_eq!((user.stakes_in_window, user.window_start_ts), (1, 1_100));
+        assert!(user.record_window_stakes(&config, 3, 1_150).is_err());
+
+        let unlimited = StakeConfig::default();
+        user.record_window_stakes(&unlimited, u16::MAX, 1_150).unwrap();
+        assert_eq!(user.stakes_in_window, 1);
+    }
+
+    #[test]
+    fn is_current_checks_the_version() {
+        let mut user = UserAccount::try_from_legacy(&legacy(&0_u64.to_le_bytes(), 0, 0, Some(true), 0)).unwrap();
+        assert!(user.is_current());
+
+        user.version = 0;
+        assert!(!user.is_current());
+        user.version = UserAccount::VERSION + 1;
+        assert!(!user.is_current());
+    }
+
+    #[test]
+    fn locked_points_earn_the_bonus_and_share_one_lock() {
+        let config = StakeConfig { compound_bonus_bps: 2_000, compound_lock_secs: 100, ..Default::default() };
+        let mut user = UserAccount::try_from_legacy(&legacy(&0_u64.to_le_bytes(), 0, 0, Some(true), 0)).unwrap();
+
+        user.points = 1_000;
+        assert_eq!(user.lock_points(&config, 10).unwrap(), 1_200);
+        assert_eq!((user.points, user.locked_points, user.locked_until), (0, 1_200, 110));
+
+        user.points = 5;
+        assert_eq!(user.lock_points(&config, 50).unwrap(), 6);
+        assert_eq!((user.locked_points, user.locked_until), (1_206, 150));
+
+        // A shorter lock configured since doesn't release earlier points sooner
+        user.points = 1;
+        user.lock_points(&StakeConfig { compound_lock_secs: 0, ..config }, 60).unwrap();
+        assert_eq!(user.locked_until, 150);
+
+        assert_eq!(user.unlock_points(149).err(), Some(error!(StakeError::CompoundStillLocked)));
+        assert_eq!(user.unlock_points(150).unwrap(), 1_207);
+        assert_eq!(user.unlock_points(150).err(), Some(error!(StakeError::NothingToClaim)));
+
+        user.points = u64::MAX;
+        assert_eq!(user.lock_points(&config, 0).err(), Some(error!(StakeError::ArithmeticOverflow)));
+    }
+
+    #[test]
+    fn is_address_of_matches_the_user_pda() {
+        let config = Pubkey::new_unique();
+        let wallet = Pubkey::new_unique();
+        let (key, bump) = Pubkey::find_program_address(&[b"user", config.as_ref(), wallet.as_ref()], &crate::ID);
+
+        assert!(UserAccount::is_address_of(&key, &config, &wallet, bump));
+        assert!(!UserAccount::is_address_of(&key, &config, &Pubkey::new_unique(), bump));
+        assert!(!UserAccount::is_address_of(&key, &Pubkey::new_unique(), &wallet, bump));
+    }
+
+    #[test]
+    fn migrated_points_grow_past_u32() {
+        let mut user = UserAccount::try_from_legacy(&legacy(&u32::MAX.to_le_bytes(), 0, 0, None, 0)).unwrap();
+
+        user.points = user.points.checked_add(1).unwrap();
+        assert_eq!(user.points, u32::MAX as u64 + 1);
+    }
+
+    #[test]
+    fn rejects_migrated_or_foreign_accounts() {
+        let current = UserAccount {
+            claim_delegate: Some(Pubkey::new_unique()),
+            ..UserAccount::new(Pubkey::new_unique(), Some(Pubkey::new_unique()), 0)
+        };
+        let mut migrated = Vec::new();
+        current.try_serialize(&mut migrated).unwrap();
+        assert_eq!(migrated.len(), UserAccount::INIT_SPACE);
+        assert_eq!(UserAccount::try_from_legacy(&migrated).err(), Some(error!(StakeError::UserAlreadyMigrated)));
+
+        let mut foreign = legacy(&0_u32.to_le_bytes(), 0, 0, None, 0);
+        foreign[0] ^= 1;
+        assert!(UserAccount::try_from_legacy(&foreign).is_err());
+    }
+}
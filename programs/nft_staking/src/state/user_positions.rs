@@ -0,0 +1,145 @@
+use anchor_lang::prelude::*;
+
+use crate::{constants::{MAX_POSITIONS, SET_COLLECTIONS}, errors::StakeError, state::CollectionConfig};
+
+/// A full set of one `StakeConfig.set_collections` slot's collection, held since `since` and
+/// earning `bonus_bps` on its pieces. Zeroed while the set isn't held.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct FullSet {
+    pub since: i64,
+    // `CollectionConfig.full_set_bonus_bps` when the set was completed
+    pub bonus_bps: u16,
+}
+
+/// Mints `owner` has staked in a pool, at `[b"positions", config, owner]`, so clients can list
+/// them without `getProgramAccounts`. Compressed stakes are listed by asset id. Created with the
+/// user account, or on the first stake or unstake since positions existed, so stakes opened
+/// before that aren't listed.
+#[account]
+pub struct UserPositions{
+    pub config: Pubkey,
+    pub owner: Pubkey,
+    // Unordered, unstakes swap-remove
+    pub mints: Vec<Pubkey>,
+    // Per `StakeConfig.set_collections` slot, only those collections' pieces are counted
+    pub full_sets: [FullSet; SET_COLLECTIONS],
+    pub bump: u8,
+}
+
+impl Space for UserPositions {
+    const INIT_SPACE: usize = 8 + 32 + 32 + 4 + 32 * MAX_POSITIONS + (8 + 2) * SET_COLLECTIONS + 1;
+}
+
+impl UserPositions {
+
+    /// Fills in positions `init_if_needed` just created, a no-op on existing ones.
+    pub fn init_if_new(&mut self, config: Pubkey, owner: Pubkey, bump: u8) {
+        if self.owner == Pubkey::default() {
+            self.config = config;
+            self.owner = owner;
+            self.bump = bump;
+        }
+    }
+
+    pub fn add(&mut self, mint: Pubkey) -> Result<()> {
+        require!(self.mints.len() < MAX_POSITIONS, StakeError::MaxStakeExceedsCapacity);
+        self.mints.push(mint);
+        Ok(())
+    }
+
+    /// A no-op for mints staked before the positions were created.
+    pub fn remove(&mut self, mint: &Pubkey) {
+        if let Some(index) = self.mints.iter().position(|listed| listed == mint) {
+            self.mints.swap_remove(index);
+        }
+    }
+
+    /// The full set of set slot `piece`'s collection, zeroed for NFTs outside the set.
+    pub fn full_set(&self, piece: Option<u8>) -> FullSet {
+        piece.map_or(FullSet::default(), |piece| self.full_sets[piece as usize])
+    }
+
+    /// Starts the full set of `piece`'s collection once `pieces_staked` hold all of it. A set
+    /// already held keeps the `since` and bonus it was completed with.
+    pub fn start_full_set(&mut self, piece: Option<u8>, pieces_staked: &[u8; SET_COLLECTIONS], collection: &CollectionConfig, now: i64) {
+        let Some(piece) = piece else {
+            return;
+        };
+        let full_set = &mut self.full_sets[piece as usize];
+        if full_set.since == 0 && collection.full_set_bonus_bps > 0 && collection.is_full_set(pieces_staked[piece as usize]) {
+            *full_set = FullSet { since: now, bonus_bps: collection.full_set_bonus_bps };
+        }
+    }
+
+    /// Ends the full set of `piece`'s collection when one of its pieces leaves, none of them earn
+    /// the bonus from here on.
+    pub fn end_full_set(&mut self, piece: Option<u8>) {
+        if let Some(piece) = piece {
+            self.full_sets[piece as usize] = FullSet::default();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions() -> UserPositions {
+        UserPositions { config: Pubkey::default(), owner: Pubkey::default(), mints: Vec::new(), full_sets: [FullSet::default(); SET_COLLECTIONS], bump: 0 }
+    }
+
+    #[test]
+    fn removal_swaps_in_the_last_mint() {
+        let mut positions = positions();
+        let [first, middle, last] = [Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique()];
+        for mint in [first, middle, last] {
+            positions.add(mint).unwrap();
+        }
+
+        positions.remove(&middle);
+        assert_eq!(positions.mints, vec![first, last]);
+
+        // Unlisted mints leave it as is
+        positions.remove(&middle);
+        assert_eq!(positions.mints, vec![first, last]);
+    }
+
+    #[test]
+    fn a_full_list_rejects_more_mints() {
+        let mut positions = positions();
+        for _ in 0..MAX_POSITIONS {
+            positions.add(Pubkey::new_unique()).unwrap();
+        }
+
+        assert_eq!(positions.add(Pubkey::new_unique()).err(), Some(error!(StakeError::MaxStakeExceedsCapacity)));
+    }
+
+    #[test]
+    fn full_sets_start_on_the_last_piece_and_end_on_any_removal() {
+        let mut positions = positions();
+        let collection = CollectionConfig { collection_mint: Pubkey::default(), points_multiplier: 10_000, bump: 0, collection_size: 3, full_set_bonus_bps: 2_000 };
+
+        positions.start_full_set(Some(1), &[0, 2, 0, 0, 0], &collection, 100);
+        assert_eq!(positions.full_set(Some(1)), FullSet::default());
+
+        positions.start_full_set(Some(1), &[0, 3, 0, 0, 0], &collection, 200);
+        assert_eq!(positions.full_set(Some(1)), FullSet { since: 200, bonus_bps: 2_000 });
+        // Already held, a later bonus change doesn't move it
+        let changed = CollectionConfig { full_set_bonus_bps: 9_000, ..collection };
+        positions.start_full_set(Some(1), &[0, 3, 0, 0, 0], &changed, 300);
+        assert_eq!(positions.full_set(Some(1)).since, 200);
+        assert_eq!(positions.full_set(None), FullSet::default());
+
+        positions.end_full_set(Some(1));
+        assert_eq!(positions.full_set(Some(1)), FullSet::default());
+    }
+
+    #[test]
+    fn a_full_list_fits_its_space() {
+        let full_sets = [FullSet { since: 1, bonus_bps: 1 }; SET_COLLECTIONS];
+        let positions = UserPositions { mints: vec![Pubkey::new_unique(); MAX_POSITIONS], full_sets, ..positions() };
+        let mut data = Vec::new();
+        positions.try_serialize(&mut data).unwrap();
+        assert_eq!(data.len(), UserPositions::INIT_SPACE);
+    }
+}
@@ -1,16 +1,5819 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { NftStaking } from "../target/types/nft_staking";
+import { createUmi } from "@metaplex-foundation/umi-bundle-defaults";
+import {
+  createNft,
+  createProgrammableNft,
+  findMasterEditionPda,
+  findMetadataPda,
+  findTokenRecordPda,
+  mplTokenMetadata,
+  MPL_TOKEN_METADATA_PROGRAM_ID,
+  verifyCollectionV1,
+  verifySizedCollectionItem,
+} from "@metaplex-foundation/mpl-token-metadata";
+import {
+  createTree,
+  fetchMerkleTree,
+  findLeafAssetIdPda,
+  findTreeConfigPda,
+  getCurrentRoot,
+  hashMetadataCreators,
+  hashMetadataData,
+  MetadataArgsArgs,
+  mintV1,
+  MPL_BUBBLEGUM_PROGRAM_ID,
+  SPL_ACCOUNT_COMPRESSION_PROGRAM_ID,
+  SPL_NOOP_PROGRAM_ID,
+  TokenProgramVersion,
+  TokenStandard as BubblegumTokenStandard,
+} from "@metaplex-foundation/mpl-bubblegum";
+import {
+  generateSigner,
+  keypairIdentity,
+  none,
+  percentAmount,
+  publicKey,
+  some,
+} from "@metaplex-foundation/umi";
+import {
+  approve,
+  ASSOCIATED_TOKEN_PROGRAM_ID,
+  AuthorityType,
+  burn,
+  createAssociatedTokenAccountIdempotent,
+  createInitializeGroupMemberPointerInstruction,
+  createInitializeGroupPointerInstruction,
+  createInitializeMintInstruction,
+  createInitializeNonTransferableMintInstruction,
+  createMint,
+  ExtensionType,
+  freezeAccount,
+  getAccount,
+  getAssociatedTokenAddressSync,
+  getMint,
+  getMintLen,
+  getOrCreateAssociatedTokenAccount,
+  mintTo,
+  setAuthority,
+  TOKEN_2022_PROGRAM_ID,
+  TOKEN_PROGRAM_ID,
+  tokenGroupInitializeGroupWithRentTransfer,
+  tokenGroupMemberInitializeWithRentTransfer,
+  transferChecked,
+} from "@solana/spl-token";
+import { expect } from "chai";
+import { createHash } from "crypto";
 
 describe("nft_staking", () => {
   // Configure the client to use the local cluster.
-  anchor.setProvider(anchor.AnchorProvider.env());
+  const provider = anchor.AnchorProvider.env();
+  anchor.setProvider(provider);
 
   const program = anchor.workspace.nftStaking as Program<NftStaking>;
+  const admin = provider.wallet as anchor.Wallet;
+
+  const metadataProgram = new anchor.web3.PublicKey(
+    MPL_TOKEN_METADATA_PROGRAM_ID
+  );
+
+  const umi = createUmi(provider.connection.rpcEndpoint).use(
+    mplTokenMetadata()
+  );
+  umi.use(
+    keypairIdentity(umi.eddsa.createKeypairFromSecretKey(admin.payer.secretKey))
+  );
+
+  const collectionMint = generateSigner(umi);
+
+  const configPda = (poolId: number) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("config"), new anchor.BN(poolId).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    )[0];
+
+  const rewardVaultPda = (pool: anchor.web3.PublicKey) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("reward_vault"), pool.toBuffer()],
+      program.programId
+    )[0];
+
+  // Most of the suite runs against pool 0.
+  const config = configPda(0);
+  // Created in `before` with the admin as mint authority, every pool pays out of its own vault.
+  let rewardMint: anchor.web3.PublicKey;
+  let adminRewardsAta: anchor.web3.PublicKey;
+
+  const userAccountPda = (
+    user: anchor.web3.PublicKey,
+    pool: anchor.web3.PublicKey = config
+  ) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("user"), pool.toBuffer(), user.toBuffer()],
+      program.programId
+    )[0];
+
+  const stakeAccountPda = (
+    mint: anchor.web3.PublicKey,
+    pool: anchor.web3.PublicKey = config
+  ) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("stake"), pool.toBuffer(), mint.toBuffer()],
+      program.programId
+    )[0];
+
+  const mintRecordPda = (
+    mint: anchor.web3.PublicKey,
+    pool: anchor.web3.PublicKey = config
+  ) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("mint_record"), pool.toBuffer(), mint.toBuffer()],
+      program.programId
+    )[0];
+
+  const leaderboardPda = (pool: anchor.web3.PublicKey = config) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("leaderboard"), pool.toBuffer()],
+      program.programId
+    )[0];
+
+  const epochHistoryPda = (pool: anchor.web3.PublicKey = config) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("epoch_history"), pool.toBuffer()],
+      program.programId
+    )[0];
+
+  const rarityMapPda = (pool: anchor.web3.PublicKey = config) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("rarity"), pool.toBuffer()],
+      program.programId
+    )[0];
+
+  const freezeAuthorityPda = anchor.web3.PublicKey.findProgramAddressSync(
+    [Buffer.from("freeze_authority")],
+    program.programId
+  )[0];
+
+  const collectionConfigPda = (
+    collection: anchor.web3.PublicKey,
+    pool: anchor.web3.PublicKey = config
+  ) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("collection"), pool.toBuffer(), collection.toBuffer()],
+      program.programId
+    )[0];
+
+  // Mints a 1/1 NFT to `owner` inside `collection` (the test collection by default).
+  const mintCollectionNft = async (
+    owner: anchor.web3.PublicKey,
+    {
+      collection = collectionMint,
+      verify = true,
+      programmable = false,
+      tokenProgram = TOKEN_PROGRAM_ID,
+      creatorVerified = true,
+    } = {}
+  ) => {
+    const mint = generateSigner(umi);
+    await (programmable ? createProgrammableNft : createNft)(umi, {
+      mint,
+      name: "Staked NFT",
+      uri: "",
+      sellerFeeBasisPoints: percentAmount(0),
+      collection: { key: collection.publicKey, verified: false },
+      tokenOwner: publicKey(owner),
+      splTokenProgram: publicKey(tokenProgram),
+      // Lists the admin as creator without its verification
+      ...(creatorVerified
+        ? {}
+        : {
+            creators: [
+              { address: umi.identity.publicKey, verified: false, share: 100 },
+            ],
+          }),
+    }).sendAndConfirm(umi);
+    if (verify && programmable) {
+      // The legacy verify instructions don't accept pNFTs
+      await verifyCollectionV1(umi, {
+        metadata: findMetadataPda(umi, { mint: mint.publicKey }),
+        collectionMint: collection.publicKey,
+        authority: umi.identity,
+      }).sendAndConfirm(umi);
+    } else if (verify) {
+      await verifySizedCollectionItem(umi, {
+        metadata: findMetadataPda(umi, { mint: mint.publicKey }),
+        collectionAuthority: umi.identity,
+        collectionMint: collection.publicKey,
+        collection: findMetadataPda(umi, { mint: collection.publicKey }),
+        collectionMasterEditionAccount: findMasterEditionPda(umi, {
+          mint: collection.publicKey,
+        }),
+      }).sendAndConfirm(umi);
+    }
+    return new anchor.web3.PublicKey(mint.publicKey);
+  };
+
+  const createCollection = async () => {
+    const collection = generateSigner(umi);
+    await createNft(umi, {
+      mint: collection,
+      name: "Collection",
+      uri: "",
+      sellerFeeBasisPoints: percentAmount(0),
+      isCollection: true,
+    }).sendAndConfirm(umi);
+    return collection;
+  };
+
+  // How a staked NFT was minted, legacy SPL Token non-programmable by default,
+  // the pool it's staked in and whether that pool holds it in a vault. `group` is the
+  // token group of a Token-2022 NFT minted without Metaplex metadata. `lockup` is picked at
+  // stake time, Flexible when unset.
+  type NftOptions = {
+    programmable?: boolean;
+    tokenProgram?: anchor.web3.PublicKey;
+    pool?: anchor.web3.PublicKey;
+    vault?: boolean;
+    group?: anchor.web3.PublicKey;
+    lockup?: anchor.IdlTypes<NftStaking>["lockupKind"];
+    // The pool's receipt mint, when it issues stake receipts
+    receipt?: anchor.web3.PublicKey;
+    // The Metaplex collection the NFT was minted into, when it isn't the test collection
+    collection?: anchor.web3.PublicKey;
+    // Proof of a rare NFT's multiplier against the pool's rarity map
+    rarity?: anchor.IdlTypes<NftStaking>["rarityProof"];
+  };
+
+  const nftAta = (
+    mint: anchor.web3.PublicKey,
+    owner: anchor.web3.PublicKey,
+    { tokenProgram = TOKEN_PROGRAM_ID }: NftOptions = {}
+  ) => getAssociatedTokenAddressSync(mint, owner, true, tokenProgram);
+
+  // The accounts every stake/unstake variant needs for one NFT.
+  const nftAccounts = (
+    mint: anchor.web3.PublicKey,
+    owner: anchor.web3.PublicKey,
+    options: NftOptions = {}
+  ) => ({
+    mint,
+    mintAta: nftAta(mint, owner, options),
+    stakeAccount: stakeAccountPda(mint, options.pool),
+    tokenProgram: options.tokenProgram ?? TOKEN_PROGRAM_ID,
+    ...pnftAccounts(mint, owner, options),
+    ...(options.group
+      ? {
+          metadata: null,
+          masterEdition: null,
+          edition: null,
+          freezeAuthority: options.vault ? null : freezeAuthorityPda,
+        }
+      : {}),
+    ...(options.vault
+      ? {
+          vault: nftAta(mint, stakeAccountPda(mint, options.pool), options),
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        }
+      : {}),
+    ...(options.receipt
+      ? {
+          receiptMint: options.receipt,
+          receiptAta: getAssociatedTokenAddressSync(
+            options.receipt,
+            owner,
+            false,
+            TOKEN_2022_PROGRAM_ID
+          ),
+          receiptTokenProgram: TOKEN_2022_PROGRAM_ID,
+          associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
+        }
+      : {}),
+  });
+
+  // The extra accounts token metadata needs to lock and unlock a pNFT.
+  const pnftAccounts = (
+    mint: anchor.web3.PublicKey,
+    owner: anchor.web3.PublicKey,
+    options: NftOptions
+  ) =>
+    options.programmable
+      ? {
+          tokenRecord: new anchor.web3.PublicKey(
+            findTokenRecordPda(umi, {
+              mint: publicKey(mint),
+              token: publicKey(nftAta(mint, owner, options)),
+            })[0]
+          ),
+          sysvarInstructions: anchor.web3.SYSVAR_INSTRUCTIONS_PUBKEY,
+        }
+      : {};
+
+  // `referral` names the referrer on a first stake, its user account defaults to the one in the pool.
+  const stake = (
+    mint: anchor.web3.PublicKey,
+    user: anchor.web3.Keypair = admin.payer,
+    options: NftOptions = {},
+    referral: {
+      referrer: anchor.web3.PublicKey;
+      referrerAccount?: anchor.web3.PublicKey;
+    } = null
+  ) => {
+    const collection =
+      options.group ??
+      options.collection ??
+      new anchor.web3.PublicKey(collectionMint.publicKey);
+    return program.methods
+      .stake(options.lockup ?? { flexible: {} }, options.rarity ?? null)
+      .accountsPartial({
+        user: user.publicKey,
+        collectionMint: collection,
+        config: options.pool ?? config,
+        collectionConfig: collectionConfigPda(collection, options.pool),
+        // Only rare NFTs pass it, keep the client from deriving it
+        rarityMap: options.rarity ? rarityMapPda(options.pool ?? config) : null,
+        userAccount: userAccountPda(user.publicKey, options.pool),
+        metadataProgram,
+        ...nftAccounts(mint, user.publicKey, options),
+        ...(referral
+          ? {
+              referrer: referral.referrer,
+              referrerAccount:
+                referral.referrerAccount ??
+                userAccountPda(referral.referrer, options.pool),
+            }
+          : {}),
+      })
+      .signers([user])
+      .rpc();
+  };
+
+  // `dropAccounts` trims the tail of remaining_accounts to build malformed batches.
+  const stakeBatch = (
+    mints: anchor.web3.PublicKey[],
+    user: anchor.web3.Keypair = admin.payer,
+    dropAccounts = 0
+  ) => {
+    const account = (pubkey: anchor.web3.PublicKey, isWritable = false) => ({
+      pubkey,
+      isWritable,
+      isSigner: false,
+    });
+    const remainingAccounts = mints.flatMap((mint) => [
+      account(mint),
+      account(getAssociatedTokenAddressSync(mint, user.publicKey), true),
+      account(
+        new anchor.web3.PublicKey(
+          findMetadataPda(umi, { mint: publicKey(mint) })[0]
+        )
+      ),
+      account(
+        new anchor.web3.PublicKey(
+          findMasterEditionPda(umi, { mint: publicKey(mint) })[0]
+        )
+      ),
+      account(stakeAccountPda(mint), true),
+      account(mintRecordPda(mint), true),
+    ]);
+    return program.methods
+      .stakeBatch(mints)
+      .accountsPartial({
+        user: user.publicKey,
+        collectionMint: new anchor.web3.PublicKey(collectionMint.publicKey),
+        config,
+        collectionConfig: collectionConfigPda(
+          new anchor.web3.PublicKey(collectionMint.publicKey)
+        ),
+        userAccount: userAccountPda(user.publicKey),
+        tokenProgram: TOKEN_PROGRAM_ID,
+        metadataProgram,
+      })
+      .remainingAccounts(
+        remainingAccounts.slice(0, remainingAccounts.length - dropAccounts)
+      )
+      .preInstructions([
+        anchor.web3.ComputeBudgetProgram.setComputeUnitLimit({
+          units: 1_000_000,
+        }),
+      ])
+      .signers([user]);
+  };
+
+  const unstake = (
+    mint: anchor.web3.PublicKey,
+    user: anchor.web3.Keypair = admin.payer,
+    options: NftOptions = {},
+    referrerAccount: anchor.web3.PublicKey = null,
+    leaderboard: anchor.web3.PublicKey = null
+  ) =>
+    program.methods
+      .unstake()
+      .accountsPartial({
+        user: user.publicKey,
+        config: options.pool ?? config,
+        userAccount: userAccountPda(user.publicKey, options.pool),
+        rewardsAta: getAssociatedTokenAddressSync(rewardMint, user.publicKey),
+        rewardMint,
+        rewardVault: rewardVaultPda(options.pool ?? config),
+        rewardTokenProgram: TOKEN_PROGRAM_ID,
+        metadataProgram,
+        leaderboard,
+        ...nftAccounts(mint, user.publicKey, options),
+        ...(referrerAccount ? { referrerAccount } : {}),
+      })
+      .signers([user])
+      .rpc();
+
+  const emergencyUnstake = (
+    mint: anchor.web3.PublicKey,
+    user: anchor.web3.Keypair = admin.payer,
+    options: NftOptions = {}
+  ) =>
+    program.methods
+      .emergencyUnstake()
+      .accountsPartial({
+        user: user.publicKey,
+        config: options.pool ?? config,
+        userAccount: userAccountPda(user.publicKey, options.pool),
+        metadataProgram,
+        ...nftAccounts(mint, user.publicKey, options),
+      })
+      .signers([user])
+      .rpc();
+
+  // `authority` is the user themselves or their claim delegate
+  const claim = (
+    user: anchor.web3.Keypair = admin.payer,
+    authority: anchor.web3.Keypair = user,
+    treasuryAta: anchor.web3.PublicKey = null,
+    leaderboard: anchor.web3.PublicKey = null,
+    treasury: anchor.web3.PublicKey = null
+  ) =>
+    program.methods
+      .claim()
+      .accountsPartial({
+        authority: authority.publicKey,
+        user: user.publicKey,
+        rewardsAta: getAssociatedTokenAddressSync(rewardMint, user.publicKey),
+        config,
+        userAccount: userAccountPda(user.publicKey),
+        // Only token stakers have one, keep the client from deriving it
+        tokenStakeAccount: null,
+        rewardMint,
+        rewardVault: rewardVaultPda(config),
+        treasuryAta,
+        treasury,
+        leaderboard,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([authority])
+      .rpc();
+
+  const fundVault = (
+    amount: number,
+    pool: anchor.web3.PublicKey = config
+  ) =>
+    program.methods
+      .fundVault(new anchor.BN(amount))
+      .accountsPartial({
+        funder: admin.publicKey,
+        funderAta: adminRewardsAta,
+        config: pool,
+        rewardMint,
+        rewardVault: rewardVaultPda(pool),
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .rpc();
+
+  // Every update_config field is optional, unset ones keep their value.
+  const updateConfig = (
+    overrides: Partial<anchor.IdlTypes<NftStaking>["updateConfigArgs"]>,
+    pool: anchor.web3.PublicKey = config
+  ) =>
+    program.methods
+      .updateConfig({
+        creator: null,
+        pointsPerStake: null,
+        referralBonus: null,
+        referralBps: null,
+        treasury: null,
+        feeBps: null,
+        claimFeeLamports: null,
+        maxStake: null,
+        freezePeriodSecs: null,
+        lockupOptions: null,
+        maxStakeDuration: null,
+        globalMaxStake: null,
+        earlyUnstakePenaltyBps: null,
+        claimCooldown: null,
+        rewardTiers: null,
+        emissionStart: null,
+        halvingIntervalSecs: null,
+        custodyMode: null,
+        maxClawback: null,
+        maxTokenStake: null,
+        compoundBoostBps: null,
+        maxCompoundBoostBps: null,
+        compoundBonusBps: null,
+        compoundLockSecs: null,
+        lockBoostBps: null,
+        maxLockBoostBps: null,
+        boostLockSecs: null,
+        restakeCooldownSecs: null,
+        minRewardDuration: null,
+        setBonusBps: null,
+        decayRateBpsPerDay: null,
+        gated: null,
+        minClaimPoints: null,
+        claimRequiresUnlocked: null,
+        epochLengthSecs: null,
+        maxStakesPerWindow: null,
+        windowLen: null,
+        pointsExpirySecs: null,
+        weightPerDay: null,
+        weightDecayPerDay: null,
+        ...overrides,
+      })
+      .accountsPartial({ config: pool });
+
+  // A zero freeze period lets the suite unstake without warping the clock.
+  const DAY = 86_400;
+
+  // Flexible at 1x so the rest of the suite earns the base rate, 30 and 90 day lockups pay more.
+  const lockupOptions = (thirtyDayBps = 15_000, ninetyDayBps = 20_000) => [
+    { freezeSecs: 0, multiplierBps: 10_000 },
+    { freezeSecs: 30 * DAY, multiplierBps: thirtyDayBps },
+    { freezeSecs: 90 * DAY, multiplierBps: ninetyDayBps },
+  ];
+
+  // The suite deploys the program, so the provider wallet is its upgrade authority.
+  const programData = anchor.web3.PublicKey.findProgramAddressSync(
+    [program.programId.toBuffer()],
+    anchor.web3.BPF_LOADER_UPGRADEABLE_PROGRAM_ID
+  )[0];
+
+  const initializeConfigArgs = (
+    poolId: number,
+    overrides: Partial<anchor.IdlTypes<NftStaking>["initializeConfigArgs"]> = {}
+  ) => ({
+    poolId: new anchor.BN(poolId),
+    // Every test NFT is minted by the admin, which token metadata verifies as its creator
+    creator: admin.publicKey,
+    pointsPerStake: 10,
+    referralBonus: 0,
+    referralBps: 0,
+    treasury: admin.publicKey,
+    feeBps: 0,
+    claimFeeLamports: new anchor.BN(0),
+    maxStake: 5,
+    freezePeriodSecs: new anchor.BN(0),
+    lockupOptions: lockupOptions(),
+    maxStakeDuration: 0,
+    globalMaxStake: 0,
+    maxEmission: new anchor.BN(0),
+    earlyUnstakePenaltyBps: 500,
+    claimCooldown: 0,
+    rewardTiers: [
+      { minDays: 30, multiplierBps: 15_000 },
+      { minDays: 90, multiplierBps: 20_000 },
+      { minDays: 0, multiplierBps: 0 },
+      { minDays: 0, multiplierBps: 0 },
+    ],
+    emissionStart: new anchor.BN(0),
+    halvingIntervalSecs: new anchor.BN(0),
+    custodyMode: { freezeDelegated: {} },
+    maxClawback: new anchor.BN(0),
+    stakeTokenMint: null,
+    maxTokenStake: new anchor.BN(0),
+    receiptMint: null,
+    compoundBoostBps: 0,
+    maxCompoundBoostBps: 0,
+    compoundBonusBps: 0,
+    compoundLockSecs: 0,
+    lockBoostBps: 0,
+    maxLockBoostBps: 0,
+    boostLockSecs: 0,
+    restakeCooldownSecs: 0,
+    minRewardDuration: 0,
+    setCollections: Array(5).fill(anchor.web3.PublicKey.default),
+    setBonusBps: 0,
+    decayRateBpsPerDay: 0,
+    gated: false,
+    minClaimPoints: new anchor.BN(0),
+    claimRequiresUnlocked: false,
+    epochLengthSecs: 0,
+    maxStakesPerWindow: 0,
+    windowLen: 0,
+    pointsExpirySecs: new anchor.BN(0),
+    weightPerDay: new anchor.BN(0),
+    weightDecayPerDay: new anchor.BN(0),
+    ...overrides,
+  });
+
+  // Pools default to the suite's reward mint, each still pays out of its own vault.
+  const initializeConfig = (
+    poolId: number,
+    overrides: Partial<anchor.IdlTypes<NftStaking>["initializeConfigArgs"]> = {},
+    poolRewardMint: anchor.web3.PublicKey = rewardMint,
+    signer: anchor.web3.Keypair = admin.payer
+  ) =>
+    program.methods
+      .initializeConfig(initializeConfigArgs(poolId, overrides))
+      .accountsPartial({
+        admin: signer.publicKey,
+        programData,
+        config: configPda(poolId),
+        collectionMint: new anchor.web3.PublicKey(collectionMint.publicKey),
+        rewardMint: poolRewardMint,
+        rewardVault: rewardVaultPda(configPda(poolId)),
+        // Localnet has no baseline config to carry over
+        legacyConfig: null,
+        tokenProgram: TOKEN_PROGRAM_ID,
+      })
+      .signers([signer])
+      .rpc();
+
+  const setPause = (pauseFlags: number) =>
+    program.methods.setPause(pauseFlags).accountsPartial({ config });
+
+  const fundedKeypair = async () => {
+    const keypair = anchor.web3.Keypair.generate();
+    const sig = await provider.connection.requestAirdrop(
+      keypair.publicKey,
+      2 * anchor.web3.LAMPORTS_PER_SOL
+    );
+    await provider.connection.confirmTransaction(sig);
+    return keypair;
+  };
+
+  const eventsOf = async (signature: string) => {
+    await provider.connection.confirmTransaction(signature, "confirmed");
+    const tx = await provider.connection.getTransaction(signature, {
+      commitment: "confirmed",
+      maxSupportedTransactionVersion: 0,
+    });
+    const parser = new anchor.EventParser(program.programId, program.coder);
+    return [...parser.parseLogs(tx.meta.logMessages)];
+  };
+
+  const expectError = async (tx: Promise<unknown>, code: string) => {
+    try {
+      await tx;
+      expect.fail(`expected ${code}`);
+    } catch (err) {
+      expect((err as anchor.AnchorError).error?.errorCode?.code).to.equal(
+        code
+      );
+    }
+  };
+
+  before(async () => {
+    await createNft(umi, {
+      mint: collectionMint,
+      name: "Collection",
+      uri: "",
+      sellerFeeBasisPoints: percentAmount(0),
+      isCollection: true,
+    }).sendAndConfirm(umi);
+
+    rewardMint = await createMint(
+      provider.connection,
+      admin.payer,
+      admin.publicKey,
+      null,
+      6
+    );
+    adminRewardsAta = (
+      await getOrCreateAssociatedTokenAccount(
+        provider.connection,
+        admin.payer,
+        rewardMint,
+        admin.publicKey
+      )
+    ).address;
+    await mintTo(
+      provider.connection,
+      admin.payer,
+      rewardMint,
+      adminRewardsAta,
+      admin.payer,
+      1_000_000_000_000
+    );
+
+    await initializeConfig(0);
+    await fundVault(100_000_000_000);
+  });
 
   it("Is initialized!", async () => {
-    // Add your test here.
-    const tx = await program.methods.initialize().rpc();
+    const tx = await program.methods
+      .initialize(null)
+      .accountsPartial({ config })
+      .rpc();
     console.log("Your transaction signature", tx);
   });
+
+  it("Rejects a claim with no points", async () => {
+    const mint = await mintCollectionNft(admin.publicKey);
+    await stake(mint);
+    await unstake(mint);
+
+    // Unstaking on the same day accrues nothing, so both claims are empty.
+    await expectError(claim(), "NothingToClaim");
+    await expectError(claim(), "NothingToClaim");
+  });
+
+  it("Pays banked points once across back-to-back claims and an unstake", async () => {
+    const user = await fundedKeypair();
+    await program.methods
+      .initialize(null)
+      .accountsPartial({ user: user.publicKey, config })
+      .signers([user])
+      .rpc();
+    const mint = await mintCollectionNft(user.publicKey);
+    await stake(mint, user);
+    await program.methods
+      .adjustPoints(new anchor.BN(5), 0)
+      .accountsPartial({
+        user: user.publicKey,
+        config,
+        userAccount: userAccountPda(user.publicKey),
+        leaderboard: null,
+      })
+      .rpc();
+
+    const [claimed] = (await eventsOf(await claim(user))).filter(
+      (e) => e.name === "rewardsClaimed"
+    );
+    expect(claimed.data.pointsBurned.toNumber()).to.equal(5);
+    expect(claimed.data.remainingPoints.toNumber()).to.equal(0);
+    await expectError(claim(user), "NothingToClaim");
+
+    // The stake's own points are only realized as it closes, on the same day that's nothing
+    await unstake(mint, user);
+    await expectError(claim(user), "NothingToClaim");
+  });
+
+  it("Leaves a claim to the balance when a harvest has nothing to bank", async () => {
+    const user = await fundedKeypair();
+    await program.methods
+      .initialize(null)
+      .accountsPartial({ user: user.publicKey, config })
+      .signers([user])
+      .rpc();
+    const mint = await mintCollectionNft(user.publicKey);
+    await stake(mint, user);
+    const harvest = () =>
+      program.methods
+        .harvest(false, null)
+        .accountsPartial({
+          user: user.publicKey,
+          stakeAccount: stakeAccountPda(mint),
+          config,
+          userAccount: userAccountPda(user.publicKey),
+          rewardMint,
+          referrerAccount: null,
+        })
+        .signers([user])
+        .rpc();
+    await program.methods
+      .adjustPoints(new anchor.BN(5), 0)
+      .accountsPartial({
+        user: user.publicKey,
+        config,
+        userAccount: userAccountPda(user.publicKey),
+        leaderboard: null,
+      })
+      .rpc();
+
+    // Less than a day in, the harvest fails rather than moving the stake's checkpoint. That
+    // harvested days are realized once is covered by the unit tests, localnet can't warp a day
+    const { stakedAt } = await program.account.stakeAccount.fetch(stakeAccountPda(mint));
+    await expectError(harvest(), "NothingToClaim");
+    const stakeAccount = await program.account.stakeAccount.fetch(stakeAccountPda(mint));
+    expect(stakeAccount.stakedAt.toNumber()).to.equal(stakedAt.toNumber());
+
+    const [claimed] = (await eventsOf(await claim(user))).filter(
+      (e) => e.name === "rewardsClaimed"
+    );
+    expect(claimed.data.pointsBurned.toNumber()).to.equal(5);
+    await expectError(harvest(), "NothingToClaim");
+    await expectError(claim(user), "NothingToClaim");
+
+    await unstake(mint, user);
+  });
+
+  it("Holds claims back until min_claim_points is reached", async () => {
+    const user = await fundedKeypair();
+    await program.methods
+      .initialize(null)
+      .accountsPartial({ user: user.publicKey, config })
+      .signers([user])
+      .rpc();
+    const grant = (points: number) =>
+      program.methods
+        .adjustPoints(new anchor.BN(points), 0)
+        .accountsPartial({
+          user: user.publicKey,
+          config,
+          userAccount: userAccountPda(user.publicKey),
+          leaderboard: null,
+        })
+        .rpc();
+
+    await updateConfig({ minClaimPoints: new anchor.BN(10) }).rpc();
+    try {
+      await grant(9);
+      await expectError(claim(user), "BelowMinClaim");
+
+      await grant(1);
+      const [claimed] = (await eventsOf(await claim(user))).filter(
+        (e) => e.name === "rewardsClaimed"
+      );
+      expect(claimed.data.pointsBurned.toNumber()).to.equal(10);
+    } finally {
+      await updateConfig({ minClaimPoints: new anchor.BN(0) }).rpc();
+    }
+  });
+
+  it("Lets the admin update the config", async () => {
+    await updateConfig({ pointsPerStake: 20 }).rpc();
+
+    const account = await program.account.stakeConfig.fetch(config);
+    expect(account.pointsPerStake).to.equal(20);
+    expect(account.maxStake).to.equal(5);
+
+    await updateConfig({ pointsPerStake: 10 }).rpc();
+  });
+
+  // Accrual past the cap needs whole days to pass, the clamp itself is unit tested in the program.
+  it("Caps stake accrual with max_stake_duration, 0 turning the cap off", async () => {
+    expect(
+      (await program.account.stakeConfig.fetch(config)).maxStakeDuration
+    ).to.equal(0);
+
+    await updateConfig({ maxStakeDuration: 90 }).rpc();
+    expect(
+      (await program.account.stakeConfig.fetch(config)).maxStakeDuration
+    ).to.equal(90);
+
+    await updateConfig({ maxStakeDuration: 0 }).rpc();
+  });
+
+  it("Rejects config updates from a non-admin", async () => {
+    const intruder = await fundedKeypair();
+
+    await expectError(
+      updateConfig({ pointsPerStake: 255 })
+        .accountsPartial({ admin: intruder.publicKey, config })
+        .signers([intruder])
+        .rpc(),
+      "Unauthorized"
+    );
+  });
+
+  it("Rejects out of range config updates", async () => {
+    await expectError(updateConfig({ maxStake: 0 }).rpc(), "InvalidMaxStake");
+    await expectError(
+      updateConfig({ freezePeriodSecs: new anchor.BN(365 * DAY + 1) }).rpc(),
+      "InvalidFreezePeriod"
+    );
+    await expectError(
+      updateConfig({
+        rewardTiers: [
+          { minDays: 90, multiplierBps: 20_000 },
+          { minDays: 30, multiplierBps: 15_000 },
+          { minDays: 0, multiplierBps: 0 },
+          { minDays: 0, multiplierBps: 0 },
+        ],
+      }).rpc(),
+      "InvalidRewardTiers"
+    );
+    await expectError(
+      updateConfig({ halvingIntervalSecs: new anchor.BN(-1) }).rpc(),
+      "InvalidEmissionSchedule"
+    );
+  });
+
+  it("Unstakes under the freeze period snapshotted at stake time", async () => {
+    const mint = await mintCollectionNft(admin.publicKey);
+    await stake(mint);
+
+    await updateConfig({ freezePeriodSecs: new anchor.BN(7 * DAY) }).rpc();
+    await unstake(mint);
+
+    await updateConfig({ freezePeriodSecs: new anchor.BN(0) }).rpc();
+  });
+
+  it("Emergency unstakes before the freeze period ends", async () => {
+    await updateConfig({ freezePeriodSecs: new anchor.BN(7 * DAY) }).rpc();
+    const mint = await mintCollectionNft(admin.publicKey);
+    await stake(mint);
+
+    await expectError(unstake(mint), "FreezePeriodNotPassed");
+    await emergencyUnstake(mint);
+
+    await updateConfig({ freezePeriodSecs: new anchor.BN(0) }).rpc();
+  });
+
+  it("Logs how long a frozen stake has left when unstake rejects it", async () => {
+    await updateConfig({ freezePeriodSecs: new anchor.BN(7 * DAY) }).rpc();
+    const mint = await mintCollectionNft(admin.publicKey);
+    await stake(mint);
+    await updateConfig({ freezePeriodSecs: new anchor.BN(0) }).rpc();
+    const stakeAccount = await program.account.stakeAccount.fetch(stakeAccountPda(mint));
+
+    try {
+      await unstake(mint);
+      expect.fail("expected FreezePeriodNotPassed");
+    } catch (err) {
+      expect((err as anchor.AnchorError).error?.errorCode?.code).to.equal("FreezePeriodNotPassed");
+      const line = (err as anchor.AnchorError).logs.find((log) => log.includes("is frozen"));
+      expect(line).to.include(mint.toBase58());
+      expect(line).to.include(`staked_at ${stakeAccount.stakedAt.toString()}`);
+      expect(line).to.include(`unlocked_at ${stakeAccount.unlockedAt.toString()}`);
+      const remaining = Number(/(\d+) seconds remaining/.exec(line)[1]);
+      expect(remaining).to.be.within(1, 7 * DAY);
+    }
+
+    await emergencyUnstake(mint);
+  });
+
+  it("Reports the countdown to unstaking with get_stake_status", async () => {
+    const stakeStatus = (mint: anchor.web3.PublicKey) =>
+      program.methods
+        .getStakeStatus()
+        .accountsPartial({ config, stakeAccount: stakeAccountPda(mint) })
+        .view();
+
+    await updateConfig({ freezePeriodSecs: new anchor.BN(7 * DAY) }).rpc();
+    const frozen = await mintCollectionNft(admin.publicKey);
+    await stake(frozen);
+    await updateConfig({ freezePeriodSecs: new anchor.BN(0) }).rpc();
+
+    const stakeAccount = await program.account.stakeAccount.fetch(
+      stakeAccountPda(frozen)
+    );
+    const status = await stakeStatus(frozen);
+    expect(status.stakedAt.toNumber()).to.equal(stakeAccount.stakedAt.toNumber());
+    expect(status.unlockedAt.toNumber()).to.equal(
+      stakeAccount.stakedAt.toNumber() + 7 * DAY
+    );
+    expect(status.secondsUntilUnlock.toNumber()).to.be.within(1, 7 * DAY);
+    expect(status.points.toNumber()).to.equal(0);
+    await emergencyUnstake(frozen);
+
+    // Nothing left to wait for once the freeze has passed
+    const free = await mintCollectionNft(admin.publicKey);
+    await stake(free);
+    expect((await stakeStatus(free)).secondsUntilUnlock.toNumber()).to.equal(0);
+    await unstake(free);
+  });
+
+  it("Holds stakes to the second until unlocked_at", async () => {
+    await updateConfig({ freezePeriodSecs: new anchor.BN(2) }).rpc();
+    const mint = await mintCollectionNft(admin.publicKey);
+    await stake(mint);
+    await updateConfig({ freezePeriodSecs: new anchor.BN(0) }).rpc();
+
+    const stakeAccount = await program.account.stakeAccount.fetch(stakeAccountPda(mint));
+    expect(stakeAccount.unlockedAt.toNumber()).to.equal(stakeAccount.stakedAt.toNumber() + 2);
+    await expectError(unstake(mint), "FreezePeriodNotPassed");
+
+    // The boundary itself is covered by the unit tests, localnet only moves forward in real time
+    await new Promise((resolve) => setTimeout(resolve, 3_000));
+    await unstake(mint);
+  });
+
+  it("Only migrates configs still holding the freeze period in days", async () => {
+    await expectError(
+      program.methods.migrateConfig(new anchor.BN(0)).accountsPartial({ config }).rpc(),
+      "ConfigAlreadyMigrated"
+    );
+    const account = await program.account.stakeConfig.fetch(config);
+    expect(account.freezePeriodSecs.toNumber()).to.equal(0);
+  });
+
+  it("Rejects emergency unstakes when the penalty is the disabled sentinel", async () => {
+    const mint = await mintCollectionNft(admin.publicKey);
+    await stake(mint);
+
+    await updateConfig({ earlyUnstakePenaltyBps: 65535 }).rpc();
+    await expectError(emergencyUnstake(mint), "EmergencyUnstakeDisabled");
+
+    await updateConfig({ earlyUnstakePenaltyBps: 500 }).rpc();
+    await unstake(mint);
+  });
+
+  describe("stake_batch", () => {
+    const mints: anchor.web3.PublicKey[] = [];
+
+    before(async () => {
+      for (let i = 0; i < 6; i++) {
+        mints.push(await mintCollectionNft(admin.publicKey));
+      }
+    });
+
+    it("Stakes a full batch", async () => {
+      const before = await program.account.userAccount.fetch(
+        userAccountPda(admin.publicKey)
+      );
+      await stakeBatch(mints.slice(0, 3)).rpc();
+
+      const after = await program.account.userAccount.fetch(
+        userAccountPda(admin.publicKey)
+      );
+      expect(after.amountStaked).to.equal(before.amountStaked + 3);
+    });
+
+    it("Stakes nothing when the batch crosses max_stake", async () => {
+      await expectError(stakeBatch(mints.slice(3, 6)).rpc(), "MaxStakeReached");
+
+      const stakeAccount = await provider.connection.getAccountInfo(
+        stakeAccountPda(mints[3])
+      );
+      expect(stakeAccount).to.be.null;
+    });
+
+    it("Rejects malformed remaining accounts", async () => {
+      await expectError(
+        stakeBatch([mints[3]], admin.payer, 1).rpc(),
+        "InvalidRemainingAccounts"
+      );
+    });
+
+    it("Stakes a mint whose stake and mint record PDAs were sent lamports beforehand", async () => {
+      const user = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+      const mint = await mintCollectionNft(user.publicKey);
+      const tip = (toPubkey: anchor.web3.PublicKey) =>
+        anchor.web3.SystemProgram.transfer({ fromPubkey: admin.publicKey, toPubkey, lamports: 1 });
+      await provider.sendAndConfirm(
+        new anchor.web3.Transaction().add(tip(stakeAccountPda(mint)), tip(mintRecordPda(mint)))
+      );
+
+      await stakeBatch([mint], user).rpc();
+
+      const stakeAccount = await program.account.stakeAccount.fetch(stakeAccountPda(mint));
+      expect(stakeAccount.owner.toBase58()).to.equal(user.publicKey.toBase58());
+      const mintRecord = await program.account.mintRecord.fetch(mintRecordPda(mint));
+      expect(mintRecord.timesStaked).to.equal(1);
+      await unstake(mint, user);
+    });
+
+    after(async () => {
+      for (const mint of mints.slice(0, 3)) {
+        await unstake(mint);
+      }
+    });
+  });
+  it("Flags emergency unstakes made after the freeze period", async () => {
+    const mint = await mintCollectionNft(admin.publicKey);
+    await stake(mint);
+
+    const [event] = (await eventsOf(await emergencyUnstake(mint))).filter(
+      (e) => e.name === "emergencyUnstaked"
+    );
+    expect(event.data.freezePeriodPassed).to.be.true;
+    expect(event.data.pointsPenalty.toNumber()).to.equal(0);
+  });
+  it("Restakes the same mint after unstaking and refunds the rent", async () => {
+    const mint = await mintCollectionNft(admin.publicKey);
+    await stake(mint);
+
+    const stakeRent = (
+      await provider.connection.getAccountInfo(stakeAccountPda(mint))
+    ).lamports;
+    const balanceBefore = await provider.connection.getBalance(admin.publicKey);
+    const sig = await unstake(mint);
+    await provider.connection.confirmTransaction(sig, "confirmed");
+    const balanceAfter = await provider.connection.getBalance(
+      admin.publicKey,
+      "confirmed"
+    );
+
+    // The refund covers the stake account rent minus the tx fee.
+    expect(balanceAfter - balanceBefore).to.equal(stakeRent - 5_000);
+    expect(await provider.connection.getAccountInfo(stakeAccountPda(mint))).to
+      .be.null;
+
+    await stake(mint);
+    await unstake(mint);
+  });
+
+  it("Rejects staking an already staked NFT", async () => {
+    const mint = await mintCollectionNft(admin.publicKey);
+    await stake(mint);
+    const { stakedAt } = await program.account.stakeAccount.fetch(
+      stakeAccountPda(mint)
+    );
+
+    await expectError(stake(mint), "AlreadyStaked");
+    await expectError(stakeBatch([mint]).rpc(), "AlreadyStaked");
+
+    // The live stake keeps its original terms
+    const stakeAccount = await program.account.stakeAccount.fetch(
+      stakeAccountPda(mint)
+    );
+    expect(stakeAccount.stakedAt.toNumber()).to.equal(stakedAt.toNumber());
+
+    await unstake(mint);
+  });
+
+  it("Refuses to close a stake account while the NFT is frozen", async () => {
+    const mint = await mintCollectionNft(admin.publicKey);
+    await stake(mint);
+
+    await expectError(
+      program.methods
+        .closeStakeAccount()
+        .accountsPartial({
+          user: admin.publicKey,
+          mint,
+          mintAta: getAssociatedTokenAddressSync(mint, admin.publicKey),
+          stakeAccount: stakeAccountPda(mint),
+          config,
+          userAccount: userAccountPda(admin.publicKey),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc(),
+      "NftStillFrozen"
+    );
+
+    await unstake(mint);
+  });
+  describe("pause flags", () => {
+    const PAUSE_STAKE = 1 << 0;
+    const PAUSE_CLAIM = 1 << 1;
+
+    it("Blocks stakes and claims while paused but still allows unstaking", async () => {
+      const stakedMint = await mintCollectionNft(admin.publicKey);
+      const pausedMint = await mintCollectionNft(admin.publicKey);
+      await stake(stakedMint);
+
+      const [event] = (
+        await eventsOf(await setPause(PAUSE_STAKE | PAUSE_CLAIM).rpc())
+      ).filter((e) => e.name === "pauseToggled");
+      expect(event.data.pauseFlags).to.equal(PAUSE_STAKE | PAUSE_CLAIM);
+
+      await expectError(stake(pausedMint), "ProgramPaused");
+      await expectError(claim(), "ProgramPaused");
+      await unstake(stakedMint);
+
+      await setPause(0).rpc();
+      await stake(pausedMint);
+      await expectError(claim(), "NothingToClaim");
+      await unstake(pausedMint);
+    });
+
+    it("Pauses claims independently of stakes", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+
+      await setPause(PAUSE_CLAIM).rpc();
+      await stake(mint);
+      await expectError(claim(), "ProgramPaused");
+      await emergencyUnstake(mint);
+
+      await setPause(0).rpc();
+    });
+
+    it("Rejects pause toggles from a non-admin and unknown flags", async () => {
+      const intruder = await fundedKeypair();
+
+      await expectError(
+        program.methods
+          .setPause(PAUSE_STAKE)
+          .accountsPartial({ admin: intruder.publicKey, config })
+          .signers([intruder])
+          .rpc(),
+        "Unauthorized"
+      );
+      await expectError(
+        setPause(1 << 2).rpc(),
+        "InvalidPauseFlags"
+      );
+    });
+  });
+  it("Rejects NFTs from a different collection", async () => {
+    const lookalike = await createCollection();
+    const mint = await mintCollectionNft(admin.publicKey, {
+      collection: lookalike,
+    });
+
+    await expectError(stake(mint), "InvalidCollection");
+  });
+
+  it("Rejects NFTs with an unverified collection", async () => {
+    const mint = await mintCollectionNft(admin.publicKey, { verify: false });
+
+    await expectError(stake(mint), "CollectionNotVerified");
+  });
+  it("Rejects NFTs listing the creator without its verification", async () => {
+    const mint = await mintCollectionNft(admin.publicKey, {
+      creatorVerified: false,
+    });
+
+    await expectError(stake(mint), "UnverifiedNft");
+    await expectError(stakeBatch([mint]).rpc(), "UnverifiedNft");
+  });
+  it("Rejects NFTs from a creator other than the configured one", async () => {
+    const mint = await mintCollectionNft(admin.publicKey);
+    await updateConfig({
+      creator: anchor.web3.Keypair.generate().publicKey,
+    }).rpc();
+
+    try {
+      await expectError(stake(mint), "UnverifiedNft");
+    } finally {
+      await updateConfig({ creator: admin.publicKey }).rpc();
+    }
+  });
+  describe("programmable NFTs", () => {
+    it("Stakes and unstakes a pNFT", async () => {
+      const mint = await mintCollectionNft(admin.publicKey, {
+        programmable: true,
+      });
+
+      await stake(mint, admin.payer, { programmable: true });
+      await unstake(mint, admin.payer, { programmable: true });
+
+      const ata = await provider.connection.getParsedAccountInfo(
+        getAssociatedTokenAddressSync(mint, admin.publicKey)
+      );
+      // pNFT token accounts stay frozen, unlocking only clears the delegate
+      const info = (ata.value.data as anchor.web3.ParsedAccountData).parsed
+        .info;
+      expect(info.delegate).to.be.undefined;
+    });
+
+    it("Emergency unstakes a pNFT", async () => {
+      const mint = await mintCollectionNft(admin.publicKey, {
+        programmable: true,
+      });
+
+      await stake(mint, admin.payer, { programmable: true });
+      await emergencyUnstake(mint, admin.payer, { programmable: true });
+    });
+
+    it("Rejects a pNFT stake without its token record", async () => {
+      const mint = await mintCollectionNft(admin.publicKey, {
+        programmable: true,
+      });
+
+      await expectError(stake(mint), "MissingPnftAccounts");
+    });
+
+    it("Rejects pNFTs in stake_batch", async () => {
+      const mint = await mintCollectionNft(admin.publicKey, {
+        programmable: true,
+      });
+
+      await expectError(stakeBatch([mint]).rpc(), "UnsupportedTokenStandard");
+    });
+  });
+  it("Pays an NFT's rewards out of the vault on unstake without a separate claim", async () => {
+    const user = await fundedKeypair();
+    await program.methods
+      .initialize(null)
+      .accountsPartial({ user: user.publicKey, config })
+      .signers([user])
+      .rpc();
+    const mint = await mintCollectionNft(user.publicKey);
+    await stake(mint, user);
+
+    const sig = await unstake(mint, user);
+
+    const [event] = (await eventsOf(sig)).filter(
+      (e) => e.name === "nftUnstaked"
+    );
+    const balance = await provider.connection.getTokenAccountBalance(
+      getAssociatedTokenAddressSync(rewardMint, user.publicKey)
+    );
+    // Localnet can't warp past whole days, so this pins the paid amount to the event
+    // rather than to a fixed number of points.
+    expect(balance.value.amount).to.equal(event.data.rewardsPaid.toString());
+    expect(event.data.rewardsPaid.toString()).to.equal(
+      event.data.pointsEarned.muln(1_000_000).toString()
+    );
+
+    const account = await program.account.userAccount.fetch(
+      userAccountPda(user.publicKey)
+    );
+    expect(account.points.toNumber()).to.equal(0);
+  });
+  it("Funds the reward vault from any token account of the reward mint", async () => {
+    const vault = rewardVaultPda(config);
+    const before = await provider.connection.getTokenAccountBalance(vault);
+
+    const [event] = (await eventsOf(await fundVault(5_000_000))).filter(
+      (e) => e.name === "rewardVaultFunded"
+    );
+
+    const after = await provider.connection.getTokenAccountBalance(vault);
+    expect(
+      new anchor.BN(after.value.amount).sub(new anchor.BN(before.value.amount)).toNumber()
+    ).to.equal(5_000_000);
+    expect(event.data.vaultBalance.toString()).to.equal(after.value.amount);
+
+    // The program has no authority over the reward mint, supply stays with its owner
+    const mintInfo = await provider.connection.getParsedAccountInfo(rewardMint);
+    expect(
+      (mintInfo.value.data as anchor.web3.ParsedAccountData).parsed.info.mintAuthority
+    ).to.equal(admin.publicKey.toBase58());
+  });
+  // Running the vault dry on unstake needs accrued points, which localnet can't produce without
+  // warping the clock, so the banking fallback is only covered for claims, under "short reward vault".
+  it("Emits stake and unstake events tagged with the pool config", async () => {
+    const mint = await mintCollectionNft(admin.publicKey);
+
+    const [staked] = (await eventsOf(await stake(mint))).filter(
+      (e) => e.name === "nftStaked"
+    );
+    expect(staked.data.config.toBase58()).to.equal(config.toBase58());
+    expect(staked.data.user.toBase58()).to.equal(admin.publicKey.toBase58());
+    expect(staked.data.mint.toBase58()).to.equal(mint.toBase58());
+
+    const [unstaked] = (await eventsOf(await unstake(mint))).filter(
+      (e) => e.name === "nftUnstaked"
+    );
+    expect(unstaked.data.config.toBase58()).to.equal(config.toBase58());
+    expect(unstaked.data.mint.toBase58()).to.equal(mint.toBase58());
+    expect(unstaked.data.duration.toNumber()).to.equal(
+      unstaked.data.unstakedAt.sub(staked.data.stakedAt).toNumber()
+    );
+    expect(unstaked.data.pointsEarned.toNumber()).to.equal(0);
+  });
+  // The suite's reward mint is created under SPL Token in `before`, and there is one config per
+  // program, so a Token-2022 reward mint can't be exercised alongside it here.
+  it("Stakes and unstakes a Token-2022 NFT", async () => {
+    const options = { tokenProgram: TOKEN_2022_PROGRAM_ID };
+    const mint = await mintCollectionNft(admin.publicKey, options);
+
+    await stake(mint, admin.payer, options);
+
+    const ata = nftAta(mint, admin.publicKey, options);
+    const frozen = await provider.connection.getParsedAccountInfo(ata);
+    expect(
+      (frozen.value.data as anchor.web3.ParsedAccountData).parsed.info.state
+    ).to.equal("frozen");
+
+    await unstake(mint, admin.payer, options);
+
+    const thawed = await provider.connection.getParsedAccountInfo(ata);
+    expect(
+      (thawed.value.data as anchor.web3.ParsedAccountData).parsed.info.state
+    ).to.equal("initialized");
+  });
+  it("Closes a user account only once nothing is staked", async () => {
+    const user = await fundedKeypair();
+    const userAccount = userAccountPda(user.publicKey);
+    await program.methods
+      .initialize(null)
+      .accountsPartial({ user: user.publicKey, config })
+      .signers([user])
+      .rpc();
+    const mint = await mintCollectionNft(user.publicKey);
+    await stake(mint, user);
+
+    const closeUser = () =>
+      program.methods
+        .closeUser()
+        .accountsPartial({ user: user.publicKey, config, userAccount })
+        .signers([user])
+        .rpc();
+
+    await expectError(closeUser(), "OutstandingStakeOrRewards");
+
+    await unstake(mint, user);
+    const rent = await provider.connection.getBalance(userAccount);
+    const before = await provider.connection.getBalance(user.publicKey);
+    await closeUser();
+
+    expect(await provider.connection.getAccountInfo(userAccount)).to.be.null;
+    expect(await provider.connection.getBalance(user.publicKey)).to.be.greaterThan(
+      before + rent - 10_000
+    );
+  });
+  it("Only migrates user accounts still in the legacy layout", async () => {
+    const user = await fundedKeypair();
+    const userAccount = userAccountPda(user.publicKey);
+    await program.methods
+      .initialize(null)
+      .accountsPartial({ user: user.publicKey, config })
+      .signers([user])
+      .rpc();
+
+    // New accounts are created with u64 points, there's nothing to migrate
+    await expectError(
+      program.methods
+        .migrateUser()
+        .accountsPartial({ user: user.publicKey, config, userAccount })
+        .signers([user])
+        .rpc(),
+      "UserAlreadyMigrated"
+    );
+    const account = await program.account.userAccount.fetch(userAccount);
+    expect(account.points.toNumber()).to.equal(0);
+    expect(account.version).to.equal(5);
+    expect(account.owner.toBase58()).to.equal(user.publicKey.toBase58());
+  });
+  describe("pools", () => {
+    const pool = configPda(1);
+
+    before(async () => {
+      await initializeConfig(1, { pointsPerStake: 30 });
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ config: pool })
+        .rpc();
+    });
+
+    it("Keeps stakes in two pools apart", async () => {
+      const first = await mintCollectionNft(admin.publicKey);
+      const second = await mintCollectionNft(admin.publicKey);
+
+      await stake(first);
+      await stake(second, admin.payer, { pool });
+
+      const poolConfig = await program.account.stakeConfig.fetch(pool);
+      expect(poolConfig.poolId.toNumber()).to.equal(1);
+      expect(poolConfig.pointsPerStake).to.equal(30);
+
+      const user = await program.account.userAccount.fetch(
+        userAccountPda(admin.publicKey, pool)
+      );
+      expect(user.amountStaked).to.equal(1);
+      expect(user.points.toNumber()).to.equal(0);
+
+      await unstake(first);
+      await unstake(second, admin.payer, { pool });
+    });
+
+    it("Rejects unstaking through another pool's config", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+      await stake(mint, admin.payer, { pool });
+
+      await expectError(
+        program.methods
+          .unstake()
+          .accountsPartial({
+            user: admin.publicKey,
+            config,
+            userAccount: userAccountPda(admin.publicKey),
+            rewardsAta: getAssociatedTokenAddressSync(
+              rewardMint,
+              admin.publicKey
+            ),
+            rewardMint,
+            rewardVault: rewardVaultPda(config),
+            rewardTokenProgram: TOKEN_PROGRAM_ID,
+            metadataProgram,
+            leaderboard: null,
+            ...nftAccounts(mint, admin.publicKey, { pool }),
+          })
+          .rpc(),
+        "PoolMismatch"
+      );
+
+      await unstake(mint, admin.payer, { pool });
+    });
+
+    it("Pays each pool out in its own reward mint", async () => {
+      const otherPool = configPda(5);
+      const otherMint = await createMint(
+        provider.connection,
+        admin.payer,
+        admin.publicKey,
+        null,
+        0
+      );
+      const otherAta = (
+        await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          admin.payer,
+          otherMint,
+          admin.publicKey
+        )
+      ).address;
+      await mintTo(provider.connection, admin.payer, otherMint, otherAta, admin.payer, 1_000);
+
+      await initializeConfig(5, {}, otherMint);
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ config: otherPool })
+        .rpc();
+      await program.methods
+        .fundVault(new anchor.BN(1_000))
+        .accountsPartial({
+          funder: admin.publicKey,
+          funderAta: otherAta,
+          config: otherPool,
+          rewardMint: otherMint,
+          rewardVault: rewardVaultPda(otherPool),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+      await program.methods
+        .adjustPoints(new anchor.BN(7), 0)
+        .accountsPartial({
+          user: admin.publicKey,
+          config: otherPool,
+          userAccount: userAccountPda(admin.publicKey, otherPool),
+          leaderboard: null,
+        })
+        .rpc();
+
+      const claimFrom = (mint: anchor.web3.PublicKey) =>
+        program.methods
+          .claim()
+          .accountsPartial({
+            authority: admin.publicKey,
+            user: admin.publicKey,
+            rewardsAta: getAssociatedTokenAddressSync(mint, admin.publicKey),
+            config: otherPool,
+            userAccount: userAccountPda(admin.publicKey, otherPool),
+            tokenStakeAccount: null,
+            leaderboard: null,
+            rewardMint: mint,
+            rewardVault: rewardVaultPda(otherPool),
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .rpc();
+
+      // The pool only pays out in the mint it was created with
+      await expectError(claimFrom(rewardMint), "RewardMintMismatch");
+      await claimFrom(otherMint);
+
+      // 0 decimal mint, one token per point
+      const ata = await getAccount(provider.connection, otherAta);
+      expect(Number(ata.amount)).to.equal(7);
+      const poolConfig = await program.account.stakeConfig.fetch(otherPool);
+      expect(poolConfig.rewardMint.toBase58()).to.equal(otherMint.toBase58());
+    });
+  });
+  it("Reports pending rewards from a simulated view", async () => {
+    const mint = await mintCollectionNft(admin.publicKey);
+    await stake(mint);
+
+    const pending = await program.methods
+      .getPendingRewards()
+      .accountsPartial({
+        user: admin.publicKey,
+        config,
+        userAccount: userAccountPda(admin.publicKey),
+        rewardMint,
+      })
+      .remainingAccounts([
+        { pubkey: stakeAccountPda(mint), isWritable: false, isSigner: false },
+      ])
+      .view();
+
+    const user = await program.account.userAccount.fetch(
+      userAccountPda(admin.publicKey)
+    );
+    // Nothing has accrued within the same day, so this is just the banked points
+    expect(pending.points.toString()).to.equal(user.points.toString());
+    expect(pending.amount.toString()).to.equal(
+      user.points.muln(1_000_000).toString()
+    );
+
+    const [entry] = pending.stakes;
+    expect(entry.stakeAccount.toBase58()).to.equal(
+      stakeAccountPda(mint).toBase58()
+    );
+    expect(entry.tierMultiplierBps).to.equal(10_000);
+    expect(entry.pointsMultiplier).to.equal(10_000);
+    // The suite's config has no freeze period
+    expect(entry.secondsUntilUnlock.toNumber()).to.equal(0);
+
+    // The view runs the same accrual math as the unstake that follows it
+    const [unstaked] = (await eventsOf(await unstake(mint))).filter(
+      (e) => e.name === "nftUnstaked"
+    );
+    expect(unstaked.data.pointsEarned.toString()).to.equal(
+      entry.points.toString()
+    );
+  });
+  it("Unstakes one NFT and leaves the others staked", async () => {
+    const user = await fundedKeypair();
+    await program.methods
+      .initialize(null)
+      .accountsPartial({ user: user.publicKey, config })
+      .signers([user])
+      .rpc();
+    const mints = [];
+    for (let i = 0; i < 3; i++) {
+      const mint = await mintCollectionNft(user.publicKey);
+      await stake(mint, user);
+      mints.push(mint);
+    }
+    const [first, middle, last] = mints;
+    const stateOf = async (mint: anchor.web3.PublicKey) =>
+      (
+        (await provider.connection.getParsedAccountInfo(nftAta(mint, user.publicKey)))
+          .value.data as anchor.web3.ParsedAccountData
+      ).parsed.info.state;
+
+    await unstake(middle, user);
+
+    const account = await program.account.userAccount.fetch(
+      userAccountPda(user.publicKey)
+    );
+    expect(account.amountStaked).to.equal(2);
+    expect(await provider.connection.getAccountInfo(stakeAccountPda(middle))).to
+      .be.null;
+    expect(await stateOf(middle)).to.equal("initialized");
+    for (const mint of [first, last]) {
+      expect(await program.account.stakeAccount.fetch(stakeAccountPda(mint))).to
+        .not.be.null;
+      expect(await stateOf(mint)).to.equal("frozen");
+    }
+
+    await unstake(first, user);
+    await unstake(last, user);
+  });
+  describe("vault custody", () => {
+    const pool = configPda(2);
+    const options = { pool, vault: true };
+
+    before(async () => {
+      await initializeConfig(2, { custodyMode: { vaultTransfer: {} } });
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ config: pool })
+        .rpc();
+    });
+
+    const balanceOf = async (ata: anchor.web3.PublicKey) =>
+      (await provider.connection.getTokenAccountBalance(ata)).value.amount;
+
+    it("Moves the NFT into a vault and back", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+      const vault = nftAta(mint, stakeAccountPda(mint, pool));
+
+      await stake(mint, admin.payer, options);
+
+      expect(await balanceOf(nftAta(mint, admin.publicKey))).to.equal("0");
+      expect(await balanceOf(vault)).to.equal("1");
+      const stakeAccount = await program.account.stakeAccount.fetch(
+        stakeAccountPda(mint, pool)
+      );
+      expect(stakeAccount.custodyMode).to.deep.equal({ vaultTransfer: {} });
+
+      await unstake(mint, admin.payer, options);
+
+      expect(await balanceOf(nftAta(mint, admin.publicKey))).to.equal("1");
+      expect(await provider.connection.getAccountInfo(vault)).to.be.null;
+    });
+
+    it("Rejects unstaking a vault stake without its vault", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+      await stake(mint, admin.payer, options);
+
+      await expectError(
+        unstake(mint, admin.payer, { pool }),
+        "CustodyModeMismatch"
+      );
+      await expectError(
+        emergencyUnstake(mint, admin.payer, { pool }),
+        "CustodyModeMismatch"
+      );
+
+      await emergencyUnstake(mint, admin.payer, options);
+    });
+
+    it("Keeps the mode a stake was opened with after the config flips", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+      await stake(mint, admin.payer, options);
+
+      await updateConfig({ custodyMode: { freezeDelegated: {} } }, pool).rpc();
+
+      await unstake(mint, admin.payer, options);
+
+      await updateConfig({ custodyMode: { vaultTransfer: {} } }, pool).rpc();
+    });
+  });
+  describe("redemptions", () => {
+    const redemptionPda = (redemptionId: number) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [
+          Buffer.from("redemption"),
+          config.toBuffer(),
+          new anchor.BN(redemptionId).toArrayLike(Buffer, "le", 8),
+        ],
+        program.programId
+      )[0];
+
+    const createRedemption = (
+      redemptionId: number,
+      costPoints: number,
+      maxRedemptions: number
+    ) =>
+      program.methods
+        .createRedemption(new anchor.BN(redemptionId), costPoints, maxRedemptions)
+        .accountsPartial({ config, redemption: redemptionPda(redemptionId) })
+        .rpc();
+
+    const redeem = (redemptionId: number, user: anchor.web3.Keypair) =>
+      program.methods
+        .redeem()
+        .accountsPartial({
+          user: user.publicKey,
+          config,
+          userAccount: userAccountPda(user.publicKey),
+          redemption: redemptionPda(redemptionId),
+        })
+        .signers([user])
+        .rpc();
+
+    const newUser = async () => {
+      const user = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+      return user;
+    };
+
+    // Localnet can't accrue points, so the happy paths use free perks.
+    it("Redeems a perk and records a receipt", async () => {
+      const user = await newUser();
+      await createRedemption(1, 0, 10);
+
+      await redeem(1, user);
+
+      const redemption = await program.account.redemption.fetch(
+        redemptionPda(1)
+      );
+      expect(redemption.redeemed).to.equal(1);
+    });
+
+    it("Rejects a redeem the user can't afford", async () => {
+      const user = await newUser();
+      await createRedemption(2, 100, 10);
+
+      await expectError(redeem(2, user), "InsufficientPoints");
+    });
+
+    it("Rejects redeems once sold out", async () => {
+      await createRedemption(3, 0, 1);
+      await redeem(3, await newUser());
+
+      await expectError(redeem(3, await newUser()), "RedemptionSoldOut");
+    });
+
+    it("Rejects a second redeem by the same user", async () => {
+      const user = await newUser();
+      await createRedemption(4, 0, 10);
+      await redeem(4, user);
+
+      try {
+        await redeem(4, user);
+        expect.fail("expected the receipt init to fail");
+      } catch (err) {
+        const logs = (err as anchor.web3.SendTransactionError).logs ?? [];
+        expect(logs.join("\n")).to.include("already in use");
+      }
+    });
+  });
+  it("Only lets the wallet that staked an NFT unstake it", async () => {
+    const mint = await mintCollectionNft(admin.publicKey);
+    await stake(mint);
+
+    // Wallet B has its own user account and an empty ATA for the mint, so every PDA
+    // and token account derives correctly and only the stake's owner check is left
+    const intruder = await fundedKeypair();
+    await program.methods
+      .initialize(null)
+      .accountsPartial({ user: intruder.publicKey, config })
+      .signers([intruder])
+      .rpc();
+    await getOrCreateAssociatedTokenAccount(
+      provider.connection,
+      admin.payer,
+      mint,
+      intruder.publicKey
+    );
+
+    await expectError(unstake(mint, intruder), "NotStakeOwner");
+    await expectError(emergencyUnstake(mint, intruder), "NotStakeOwner");
+
+    await unstake(mint);
+    const balance = await provider.connection.getTokenAccountBalance(
+      nftAta(mint, admin.publicKey)
+    );
+    expect(balance.value.amount).to.equal("1");
+  });
+  describe("global stake cap", () => {
+    const totalStaked = async () =>
+      (await program.account.stakeConfig.fetch(config)).totalStaked;
+
+    const syncTotals = (total: number) =>
+      program.methods.syncTotals(total).accountsPartial({ config }).rpc();
+
+    after(async () => {
+      await updateConfig({ globalMaxStake: 0 }).rpc();
+    });
+
+    it("Caps stakes across users and frees room on unstake", async () => {
+      const other = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: other.publicKey, config })
+        .signers([other])
+        .rpc();
+      const ours = await mintCollectionNft(admin.publicKey);
+      const theirs = await mintCollectionNft(other.publicKey);
+      const extra = await mintCollectionNft(admin.publicKey);
+
+      // Other tests may leave NFTs staked, so the cap is relative to what's already counted
+      const before = await totalStaked();
+      await updateConfig({ globalMaxStake: before + 2 }).rpc();
+
+      await stake(ours);
+      await stake(theirs, other);
+      expect(await totalStaked()).to.equal(before + 2);
+      await expectError(stake(extra), "GlobalMaxStakeReached");
+
+      await unstake(theirs, other);
+      expect(await totalStaked()).to.equal(before + 1);
+      await stake(extra);
+
+      await unstake(ours);
+      await unstake(extra);
+      expect(await totalStaked()).to.equal(before);
+    });
+
+    it("Keeps the per-wallet cap separate from the global one", async () => {
+      const user = await fundedKeypair();
+      const first = await mintCollectionNft(user.publicKey);
+      const second = await mintCollectionNft(user.publicKey);
+
+      // The global cap is off, only the wallet's cap of one applies
+      await updateConfig({ maxStake: 1, globalMaxStake: 0 }).rpc();
+      try {
+        await stake(first, user);
+        await expectError(stake(second, user), "MaxStakeReached");
+        await unstake(first, user);
+      } finally {
+        await updateConfig({ maxStake: 5 }).rpc();
+      }
+    });
+
+    it("Lets the admin correct a drifted counter", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+      await stake(mint);
+      const counted = await totalStaked();
+
+      // Pretend the stake predates the counter, unstaking must not underflow
+      await syncTotals(0);
+      await unstake(mint);
+      expect(await totalStaked()).to.equal(0);
+
+      await syncTotals(counted - 1);
+      expect(await totalStaked()).to.equal(counted - 1);
+
+      const intruder = await fundedKeypair();
+      await expectError(
+        program.methods
+          .syncTotals(0)
+          .accountsPartial({ admin: intruder.publicKey, config })
+          .signers([intruder])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+  });
+  describe("referrals", () => {
+    const newUser = async (referrer: anchor.web3.PublicKey = null) => {
+      const user = await fundedKeypair();
+      await program.methods
+        .initialize(referrer)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+      return user;
+    };
+
+    const pointsOf = async (user: anchor.web3.PublicKey) =>
+      (await program.account.userAccount.fetch(userAccountPda(user))).points.toNumber();
+
+    before(async () => {
+      await updateConfig({ referralBonus: 50, referralBps: 1_000 }).rpc();
+    });
+
+    after(async () => {
+      await updateConfig({ referralBonus: 0, referralBps: 0 }).rpc();
+    });
+
+    it("Credits both sides once on the first stake", async () => {
+      const referrer = await newUser();
+      const user = await newUser(referrer.publicKey);
+      const first = await mintCollectionNft(user.publicKey);
+      const second = await mintCollectionNft(user.publicKey);
+
+      await stake(first, user, {}, { referrer: referrer.publicKey });
+      expect(await pointsOf(user.publicKey)).to.equal(50);
+      expect(await pointsOf(referrer.publicKey)).to.equal(50);
+
+      await expectError(
+        stake(second, user, {}, { referrer: referrer.publicKey }),
+        "ReferralAlreadyUsed"
+      );
+      expect(await pointsOf(referrer.publicKey)).to.equal(50);
+
+      await unstake(first, user);
+    });
+
+    it("Rejects self-referrals", async () => {
+      const user = await newUser();
+      const mint = await mintCollectionNft(user.publicKey);
+
+      await expectError(
+        stake(mint, user, {}, { referrer: user.publicKey }),
+        "SelfReferral"
+      );
+    });
+
+    it("Rejects a user account that isn't the referrer's", async () => {
+      const referrer = await newUser();
+      const bystander = await newUser();
+      const user = await newUser(referrer.publicKey);
+      const mint = await mintCollectionNft(user.publicKey);
+
+      await expectError(
+        stake(mint, user, {}, {
+          referrer: referrer.publicKey,
+          referrerAccount: userAccountPda(bystander.publicKey),
+        }),
+        "InvalidReferrer"
+      );
+    });
+
+    it("Only pays the first-stake bonus to the stored referrer", async () => {
+      const referrer = await newUser();
+      const other = await newUser();
+      const naming = async (user: anchor.web3.Keypair) => {
+        const mint = await mintCollectionNft(user.publicKey);
+        return {
+          mint,
+          sig: stake(mint, user, {}, {
+            referrer: other.publicKey,
+            referrerAccount: userAccountPda(other.publicKey),
+          }),
+        };
+      };
+
+      // Initialized with `referrer`, so naming someone else on the first stake is refused
+      const referred = await newUser(referrer.publicKey);
+      await expectError((await naming(referred)).sig, "InvalidReferrer");
+
+      // As is adding one to an account initialized without a referrer
+      const unreferred = await newUser();
+      await expectError((await naming(unreferred)).sig, "InvalidReferrer");
+      expect(await pointsOf(other.publicKey)).to.equal(0);
+
+      // A first stake that also creates the account stores the referrer it names
+      const fresh = await fundedKeypair();
+      const { mint, sig } = await naming(fresh);
+      await sig;
+      expect(await pointsOf(other.publicKey)).to.equal(50);
+      await unstake(mint, fresh);
+    });
+
+    it("Records the referrer at initialization and credits its cut on unstake", async () => {
+      const referrer = await newUser();
+      const user = await newUser(referrer.publicKey);
+      expect(
+        (
+          await program.account.userAccount.fetch(userAccountPda(user.publicKey))
+        ).referrer.toBase58()
+      ).to.equal(referrer.publicKey.toBase58());
+
+      const mint = await mintCollectionNft(user.publicKey);
+      await stake(mint, user);
+      const sig = await unstake(mint, user, {}, userAccountPda(referrer.publicKey));
+
+      // Localnet can't accrue points, so this pins the cut to the event rather than a fixed amount
+      const [event] = (await eventsOf(sig)).filter(
+        (e) => e.name === "nftUnstaked"
+      );
+      expect(event.data.referralPoints.toString()).to.equal(
+        event.data.pointsEarned.muln(1_000).divn(10_000).toString()
+      );
+      expect(await pointsOf(referrer.publicKey)).to.equal(
+        event.data.referralPoints.toNumber()
+      );
+    });
+
+    it("Rejects referring yourself at initialization", async () => {
+      const user = await fundedKeypair();
+
+      await expectError(
+        program.methods
+          .initialize(user.publicKey)
+          .accountsPartial({ user: user.publicKey, config })
+          .signers([user])
+          .rpc(),
+        "SelfReferral"
+      );
+    });
+
+    it("Rejects a referrer account that doesn't match the stored referrer", async () => {
+      const referrer = await newUser();
+      const bystander = await newUser();
+      const user = await newUser(referrer.publicKey);
+      const mint = await mintCollectionNft(user.publicKey);
+      await stake(mint, user);
+
+      await expectError(
+        unstake(mint, user, {}, userAccountPda(bystander.publicKey)),
+        "InvalidReferrer"
+      );
+
+      // Leaving the referrer out just skips its cut
+      const [event] = (await eventsOf(await unstake(mint, user))).filter(
+        (e) => e.name === "nftUnstaked"
+      );
+      expect(event.data.referralPoints.toNumber()).to.equal(0);
+    });
+  });
+  describe("admin transfer", () => {
+    // Its own pool, so handing over the admin doesn't lock the rest of the suite out of pool 0
+    const pool = configPda(3);
+
+    const accept = (signer: anchor.web3.Keypair) =>
+      program.methods
+        .acceptAdmin()
+        .accountsPartial({ newAdmin: signer.publicKey, config: pool })
+        .signers([signer])
+        .rpc();
+
+    before(async () => {
+      await initializeConfig(3);
+    });
+
+    it("Lets the admin cancel a proposal before it's accepted", async () => {
+      const typo = await fundedKeypair();
+
+      await expectError(
+        program.methods.cancelAdminTransfer().accountsPartial({ config: pool }).rpc(),
+        "NoPendingAdmin"
+      );
+
+      await program.methods
+        .proposeAdmin(typo.publicKey)
+        .accountsPartial({ config: pool })
+        .rpc();
+      await program.methods
+        .cancelAdminTransfer()
+        .accountsPartial({ config: pool })
+        .rpc();
+
+      expect((await program.account.stakeConfig.fetch(pool)).pendingAdmin).to.be
+        .null;
+      await expectError(accept(typo), "NoPendingAdmin");
+    });
+
+    it("Only hands over the admin once the proposed key accepts", async () => {
+      const newAdmin = await fundedKeypair();
+      const stranger = await fundedKeypair();
+
+      await expectError(accept(newAdmin), "NoPendingAdmin");
+
+      await program.methods
+        .proposeAdmin(newAdmin.publicKey)
+        .accountsPartial({ config: pool })
+        .rpc();
+      let account = await program.account.stakeConfig.fetch(pool);
+      expect(account.admin.toBase58()).to.equal(admin.publicKey.toBase58());
+      expect(account.pendingAdmin.toBase58()).to.equal(
+        newAdmin.publicKey.toBase58()
+      );
+
+      await expectError(accept(stranger), "NotPendingAdmin");
+
+      await accept(newAdmin);
+      account = await program.account.stakeConfig.fetch(pool);
+      expect(account.admin.toBase58()).to.equal(newAdmin.publicKey.toBase58());
+      expect(account.pendingAdmin).to.be.null;
+
+      await expectError(
+        updateConfig({ pointsPerStake: 1 }, pool).rpc(),
+        "Unauthorized"
+      );
+    });
+
+    it("Rejects proposals from anyone but the admin", async () => {
+      const intruder = await fundedKeypair();
+
+      await expectError(
+        program.methods
+          .proposeAdmin(intruder.publicKey)
+          .accountsPartial({ admin: intruder.publicKey, config: pool })
+          .signers([intruder])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+  });
+  describe("point adjustments", () => {
+    const adjust = (
+      user: anchor.web3.PublicKey,
+      delta: number,
+      signer: anchor.web3.Keypair = admin.payer
+    ) =>
+      program.methods
+        .adjustPoints(new anchor.BN(delta), 1)
+        .accountsPartial({
+          admin: signer.publicKey,
+          user,
+          config,
+          userAccount: userAccountPda(user),
+          leaderboard: null,
+        })
+        .signers([signer])
+        .rpc();
+
+    const pointsOf = async (user: anchor.web3.PublicKey) =>
+      (await program.account.userAccount.fetch(userAccountPda(user))).points.toNumber();
+
+    let user: anchor.web3.Keypair;
+
+    before(async () => {
+      user = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+      await updateConfig({ maxClawback: new anchor.BN(100) }).rpc();
+    });
+
+    after(async () => {
+      await updateConfig({ maxClawback: new anchor.BN(0) }).rpc();
+      await setPause(0).rpc();
+    });
+
+    it("Grants compensation points and logs the adjustment", async () => {
+      const [event] = (await eventsOf(await adjust(user.publicKey, 500))).filter(
+        (e) => e.name === "pointsAdjusted"
+      );
+
+      expect(await pointsOf(user.publicKey)).to.equal(500);
+      expect(event.data.user.toBase58()).to.equal(user.publicKey.toBase58());
+      expect(event.data.delta.toNumber()).to.equal(500);
+      expect(event.data.reasonCode).to.equal(1);
+    });
+
+    it("Claws back points up to the limit while unpaused", async () => {
+      await adjust(user.publicKey, -100);
+      expect(await pointsOf(user.publicKey)).to.equal(400);
+    });
+
+    it("Rejects clawbacks over the limit until claims are paused", async () => {
+      await expectError(adjust(user.publicKey, -101), "AdjustmentTooLarge");
+
+      // PAUSE_CLAIM
+      await setPause(1 << 1).rpc();
+      // Floors at 0 rather than failing
+      await adjust(user.publicKey, -1_000);
+      expect(await pointsOf(user.publicKey)).to.equal(0);
+    });
+
+    it("Rejects adjustments from a non-admin", async () => {
+      const intruder = await fundedKeypair();
+
+      await expectError(adjust(user.publicKey, 1, intruder), "Unauthorized");
+    });
+  });
+  describe("token staking", () => {
+    // Its own pool, the only one with a stake token
+    const pool = configPda(4);
+    let stakeMint: anchor.web3.PublicKey;
+    let user: anchor.web3.Keypair;
+    let userAta: anchor.web3.PublicKey;
+
+    const tokenStakePda = (owner: anchor.web3.PublicKey) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("token_stake"), pool.toBuffer(), owner.toBuffer()],
+        program.programId
+      )[0];
+
+    const tokenVaultPda = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("token_vault"), pool.toBuffer()],
+      program.programId
+    )[0];
+
+    const tokenAccounts = (signer: anchor.web3.Keypair) => ({
+      user: signer.publicKey,
+      mint: stakeMint,
+      userAta,
+      config: pool,
+      userAccount: userAccountPda(signer.publicKey, pool),
+      tokenStakeAccount: tokenStakePda(signer.publicKey),
+      tokenVault: tokenVaultPda,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    });
+
+    const stakeTokens = (amount: number) =>
+      program.methods
+        .stakeTokens(new anchor.BN(amount))
+        .accountsPartial(tokenAccounts(user))
+        .signers([user])
+        .rpc();
+
+    const unstakeTokens = (amount: number) =>
+      program.methods
+        .unstakeTokens(new anchor.BN(amount))
+        .accountsPartial(tokenAccounts(user))
+        .signers([user])
+        .rpc();
+
+    before(async () => {
+      user = await fundedKeypair();
+      stakeMint = await createMint(
+        provider.connection,
+        admin.payer,
+        admin.publicKey,
+        null,
+        6
+      );
+      userAta = (
+        await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          admin.payer,
+          stakeMint,
+          user.publicKey
+        )
+      ).address;
+      await mintTo(
+        provider.connection,
+        admin.payer,
+        stakeMint,
+        userAta,
+        admin.payer,
+        10_000_000
+      );
+
+      await initializeConfig(4, {
+        stakeTokenMint: stakeMint,
+        // 5 whole tokens
+        maxTokenStake: new anchor.BN(5_000_000),
+      });
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config: pool })
+        .signers([user])
+        .rpc();
+    });
+
+    it("Stakes tokens into the pool vault", async () => {
+      const [event] = (await eventsOf(await stakeTokens(2_000_000))).filter(
+        (e) => e.name === "tokensStaked"
+      );
+      await stakeTokens(1_000_000);
+
+      const account = await program.account.tokenStakeAccount.fetch(
+        tokenStakePda(user.publicKey)
+      );
+      expect(account.amount.toNumber()).to.equal(3_000_000);
+      expect(account.owner.toBase58()).to.equal(user.publicKey.toBase58());
+      expect(event.data.totalStaked.toNumber()).to.equal(2_000_000);
+
+      const vault = await getAccount(provider.connection, tokenVaultPda);
+      expect(Number(vault.amount)).to.equal(3_000_000);
+    });
+
+    it("Caps each user's stake in token units", async () => {
+      await expectError(stakeTokens(2_000_001), "MaxStakeReached");
+      await stakeTokens(2_000_000);
+    });
+
+    it("Rejects empty stakes and withdrawals over the staked amount", async () => {
+      await expectError(stakeTokens(0), "InvalidAmount");
+      await expectError(unstakeTokens(5_000_001), "InvalidAmount");
+    });
+
+    it("Unstakes in parts and closes the stake once empty", async () => {
+      const [event] = (await eventsOf(await unstakeTokens(1_000_000))).filter(
+        (e) => e.name === "tokensUnstaked"
+      );
+      expect(event.data.totalStaked.toNumber()).to.equal(4_000_000);
+
+      await unstakeTokens(4_000_000);
+      expect(
+        await provider.connection.getAccountInfo(tokenStakePda(user.publicKey))
+      ).to.be.null;
+
+      const ata = await getAccount(provider.connection, userAta);
+      expect(Number(ata.amount)).to.equal(10_000_000);
+    });
+
+    it("Rejects token stakes on pools without a stake token", async () => {
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+
+      const tx = program.methods
+        .stakeTokens(new anchor.BN(1))
+        .accountsPartial({
+          ...tokenAccounts(user),
+          config,
+          userAccount: userAccountPda(user.publicKey),
+          tokenStakeAccount: anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("token_stake"), config.toBuffer(), user.publicKey.toBuffer()],
+            program.programId
+          )[0],
+          tokenVault: anchor.web3.PublicKey.findProgramAddressSync(
+            [Buffer.from("token_vault"), config.toBuffer()],
+            program.programId
+          )[0],
+        })
+        .signers([user])
+        .rpc();
+
+      await expectError(tx, "TokenStakingDisabled");
+    });
+  });
+  describe("compounding", () => {
+    let user: anchor.web3.Keypair;
+
+    const compound = () =>
+      program.methods
+        .compound()
+        .accountsPartial({
+          user: user.publicKey,
+          config,
+          userAccount: userAccountPda(user.publicKey),
+          rewardMint,
+        })
+        .signers([user])
+        .rpc();
+
+    const grant = (points: number) =>
+      program.methods
+        .adjustPoints(new anchor.BN(points), 0)
+        .accountsPartial({
+          user: user.publicKey,
+          config,
+          userAccount: userAccountPda(user.publicKey),
+          leaderboard: null,
+        })
+        .rpc();
+
+    before(async () => {
+      user = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+    });
+
+    after(async () => {
+      await updateConfig({ compoundBoostBps: 0, maxCompoundBoostBps: 0, claimCooldown: 0 }).rpc();
+    });
+
+    it("Rejects compounding while the boost is off", async () => {
+      await grant(5);
+      await expectError(compound(), "CompoundingDisabled");
+    });
+
+    it("Moves points into the staked reward balance instead of paying out", async () => {
+      await updateConfig({ compoundBoostBps: 100, maxCompoundBoostBps: 1_000 }).rpc();
+
+      const [event] = (await eventsOf(await compound())).filter(
+        (e) => e.name === "rewardsCompounded"
+      );
+      expect(event.data.pointsBurned.toNumber()).to.equal(5);
+      // 6 decimal reward mint
+      expect(event.data.amount.toNumber()).to.equal(5_000_000);
+
+      const account = await program.account.userAccount.fetch(
+        userAccountPda(user.publicKey)
+      );
+      expect(account.points.toNumber()).to.equal(0);
+      expect(account.stakedRewardBalance.toNumber()).to.equal(5_000_000);
+
+      await expectError(claim(user), "NothingToClaim");
+    });
+
+    it("Shares the claim cooldown", async () => {
+      await updateConfig({ claimCooldown: 3_600 }).rpc();
+      await grant(5);
+
+      await expectError(compound(), "ClaimCooldownActive");
+      await expectError(claim(user), "ClaimCooldownActive");
+    });
+  });
+  describe("compressed NFTs", () => {
+    const merkleTree = generateSigner(umi);
+    const creators = [
+      { address: publicKey(admin.publicKey), verified: true, share: 100 },
+    ];
+    const metadata: MetadataArgsArgs = {
+      name: "cNFT",
+      symbol: "",
+      uri: "",
+      sellerFeeBasisPoints: 0,
+      primarySaleHappened: false,
+      isMutable: true,
+      editionNonce: none(),
+      tokenStandard: some(BubblegumTokenStandard.NonFungible),
+      collection: none(),
+      uses: none(),
+      tokenProgramVersion: TokenProgramVersion.Original,
+      creators,
+    };
+    const tree = new anchor.web3.PublicKey(merkleTree.publicKey);
+    const assetId = new anchor.web3.PublicKey(
+      findLeafAssetIdPda(umi, { merkleTree: merkleTree.publicKey, leafIndex: 0 })[0]
+    );
+    const stakePda = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("stake"), config.toBuffer(), assetId.toBuffer()],
+      program.programId
+    )[0];
+
+    let user: anchor.web3.Keypair;
+
+    // Read fresh before every call, each transfer moves the root
+    const leaf = async () => {
+      const account = await fetchMerkleTree(umi, merkleTree.publicKey);
+      return {
+        root: Array.from(getCurrentRoot(account.tree)),
+        dataHash: Array.from(hashMetadataData(metadata)),
+        creatorHash: Array.from(hashMetadataCreators(creators)),
+        nonce: new anchor.BN(0),
+        index: 0,
+      };
+    };
+
+    const treeAccounts = {
+      treeConfig: new anchor.web3.PublicKey(
+        findTreeConfigPda(umi, { merkleTree: merkleTree.publicKey })[0]
+      ),
+      merkleTree: tree,
+      logWrapper: new anchor.web3.PublicKey(SPL_NOOP_PROGRAM_ID),
+      compressionProgram: new anchor.web3.PublicKey(
+        SPL_ACCOUNT_COMPRESSION_PROGRAM_ID
+      ),
+      bubblegumProgram: new anchor.web3.PublicKey(MPL_BUBBLEGUM_PROGRAM_ID),
+    };
+
+    // Depth 3 with a depth 2 canopy, so the only proof node is leaf 0's sibling, still empty
+    const proof = [
+      {
+        pubkey: anchor.web3.PublicKey.default,
+        isSigner: false,
+        isWritable: false,
+      },
+    ];
+
+    const stakeCompressed = async (
+      leafCreators = creators.map((c) => ({
+        address: new anchor.web3.PublicKey(c.address),
+        verified: c.verified,
+        share: c.share,
+      }))
+    ) =>
+      program.methods
+        .stakeCompressed(await leaf(), leafCreators)
+        .accountsPartial({
+          user: user.publicKey,
+          leafDelegate: user.publicKey,
+          config,
+          userAccount: userAccountPda(user.publicKey),
+          stakeAccount: stakePda,
+          mintRecord: mintRecordPda(assetId),
+          ...treeAccounts,
+        })
+        .remainingAccounts(proof)
+        .signers([user])
+        .rpc();
+
+    before(async () => {
+      user = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+
+      await (
+        await createTree(umi, { merkleTree, maxDepth: 3, maxBufferSize: 8, canopyDepth: 2 })
+      ).sendAndConfirm(umi);
+      await mintV1(umi, {
+        leafOwner: publicKey(user.publicKey),
+        merkleTree: merkleTree.publicKey,
+        metadata,
+      }).sendAndConfirm(umi);
+    });
+
+    it("Rejects leaves without the pool's verified creator", async () => {
+      await expectError(
+        stakeCompressed([
+          { address: admin.publicKey, verified: false, share: 100 },
+        ]),
+        "UnverifiedNft"
+      );
+    });
+
+    it("Stakes a cNFT by moving the leaf to the stake account", async () => {
+      const [event] = (await eventsOf(await stakeCompressed())).filter(
+        (e) => e.name === "nftStaked"
+      );
+      expect(event.data.mint.toBase58()).to.equal(assetId.toBase58());
+
+      const stake = await program.account.stakeAccount.fetch(stakePda);
+      expect(stake.mint.toBase58()).to.equal(assetId.toBase58());
+      expect(stake.custodyMode).to.deep.equal({ compressed: {} });
+      expect(
+        (await program.account.userAccount.fetch(userAccountPda(user.publicKey)))
+          .amountStaked
+      ).to.equal(1);
+    });
+
+    it("Unstakes the cNFT back to its owner", async () => {
+      await program.methods
+        .unstakeCompressed(await leaf())
+        .accountsPartial({
+          user: user.publicKey,
+          stakeAccount: stakePda,
+          mintRecord: mintRecordPda(assetId),
+          config,
+          userAccount: userAccountPda(user.publicKey),
+          rewardsAta: getAssociatedTokenAddressSync(rewardMint, user.publicKey),
+          rewardMint,
+          rewardVault: rewardVaultPda(config),
+          ...treeAccounts,
+          rewardTokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts(proof)
+        .signers([user])
+        .rpc();
+
+      expect(await provider.connection.getAccountInfo(stakePda)).to.be.null;
+      expect(
+        (await program.account.userAccount.fetch(userAccountPda(user.publicKey)))
+          .amountStaked
+      ).to.equal(0);
+    });
+  });
+  describe("pool statistics", () => {
+    const stats = async () => {
+      const account = await program.account.stakeConfig.fetch(config);
+      return {
+        staked: account.totalStaked,
+        users: account.totalUsers,
+        distributed: account.totalPointsDistributed.toNumber(),
+      };
+    };
+
+    it("Tracks stakes, users and distributed points across cycles", async () => {
+      const user = await fundedKeypair();
+      const userAccount = userAccountPda(user.publicKey);
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+      const mint = await mintCollectionNft(user.publicKey);
+
+      // Other tests share pool 0, so everything is relative to where it starts
+      const start = await stats();
+
+      await stake(mint, user);
+      expect(await stats()).to.include({
+        staked: start.staked + 1,
+        users: start.users + 1,
+      });
+
+      await unstake(mint, user);
+      expect((await stats()).staked).to.equal(start.staked);
+
+      // Only the first stake counts the user
+      await stake(mint, user);
+      expect((await stats()).users).to.equal(start.users + 1);
+      await unstake(mint, user);
+
+      await program.methods
+        .adjustPoints(new anchor.BN(7), 0)
+        .accountsPartial({ user: user.publicKey, config, userAccount, leaderboard: null })
+        .rpc();
+      await claim(user);
+      expect((await stats()).distributed).to.equal(start.distributed + 7);
+
+      await program.methods
+        .closeUser()
+        .accountsPartial({ user: user.publicKey, config, userAccount })
+        .signers([user])
+        .rpc();
+      expect((await stats()).users).to.equal(start.users);
+    });
+  });
+  describe("claim delegates", () => {
+    let user: anchor.web3.Keypair;
+    let delegate: anchor.web3.Keypair;
+
+    const setClaimDelegate = (key: anchor.web3.PublicKey | null) =>
+      program.methods
+        .setClaimDelegate(key)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+
+    const grant = (points: number) =>
+      program.methods
+        .adjustPoints(new anchor.BN(points), 0)
+        .accountsPartial({
+          user: user.publicKey,
+          config,
+          userAccount: userAccountPda(user.publicKey),
+          leaderboard: null,
+        })
+        .rpc();
+
+    before(async () => {
+      user = await fundedKeypair();
+      delegate = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+    });
+
+    it("Rejects claims signed by anyone but the user or their delegate", async () => {
+      await grant(3);
+      await expectError(claim(user, delegate), "Unauthorized");
+    });
+
+    it("Lets the delegate claim into the user's ATA", async () => {
+      await setClaimDelegate(delegate.publicKey);
+
+      const [event] = (await eventsOf(await claim(user, delegate))).filter(
+        (e) => e.name === "rewardsClaimed"
+      );
+      expect(event.data.user.toBase58()).to.equal(user.publicKey.toBase58());
+      expect(event.data.claimedBy.toBase58()).to.equal(
+        delegate.publicKey.toBase58()
+      );
+
+      const ata = await getAccount(
+        provider.connection,
+        getAssociatedTokenAddressSync(rewardMint, user.publicKey)
+      );
+      // 6 decimal reward mint
+      expect(Number(ata.amount)).to.equal(3_000_000);
+    });
+
+    it("Stops the delegate once revoked", async () => {
+      await setClaimDelegate(null);
+      await grant(1);
+
+      await expectError(claim(user, delegate), "Unauthorized");
+      await claim(user);
+    });
+
+    const revokeClaimDelegate = (signer: anchor.web3.Keypair = user) =>
+      program.methods
+        .revokeClaimDelegate()
+        .accountsPartial({
+          user: signer.publicKey,
+          config,
+          userAccount: userAccountPda(user.publicKey),
+        })
+        .signers([signer])
+        .rpc();
+
+    it("Revokes the delegate with revoke_claim_delegate", async () => {
+      await expectError(revokeClaimDelegate(), "NoClaimDelegate");
+      await setClaimDelegate(delegate.publicKey);
+
+      // Only the user can revoke or replace their delegate
+      await expectError(revokeClaimDelegate(delegate), "ConstraintSeeds");
+      await revokeClaimDelegate();
+
+      await grant(1);
+      await expectError(claim(user, delegate), "Unauthorized");
+      await claim(user);
+    });
+
+    it("Doesn't let the delegate unstake", async () => {
+      await setClaimDelegate(delegate.publicKey);
+      const mint = await mintCollectionNft(user.publicKey);
+      await stake(mint, user);
+
+      await expectError(
+        program.methods
+          .unstake()
+          .accountsPartial({
+            user: delegate.publicKey,
+            config,
+            userAccount: userAccountPda(user.publicKey),
+            rewardsAta: getAssociatedTokenAddressSync(rewardMint, user.publicKey),
+            rewardMint,
+            rewardVault: rewardVaultPda(config),
+            rewardTokenProgram: TOKEN_PROGRAM_ID,
+            metadataProgram,
+            leaderboard: null,
+            ...nftAccounts(mint, user.publicKey),
+          })
+          .signers([delegate])
+          .rpc(),
+        "ConstraintAssociated"
+      );
+
+      await unstake(mint, user);
+      await revokeClaimDelegate();
+    });
+  });
+  describe("Token-2022 group members", () => {
+    let group: anchor.web3.PublicKey;
+
+    // A Token-2022 mint of `extension` whose pointer targets the mint itself, the admin
+    // holds every authority and `freezeAuthority` can freeze it.
+    const createExtensionMint = async (
+      extension: ExtensionType.GroupPointer | ExtensionType.GroupMemberPointer,
+      freezeAuthority: anchor.web3.PublicKey
+    ) => {
+      const mint = anchor.web3.Keypair.generate();
+      const space = getMintLen([extension]);
+      const pointer =
+        extension === ExtensionType.GroupPointer
+          ? createInitializeGroupPointerInstruction
+          : createInitializeGroupMemberPointerInstruction;
+      await provider.sendAndConfirm(
+        new anchor.web3.Transaction().add(
+          anchor.web3.SystemProgram.createAccount({
+            fromPubkey: admin.publicKey,
+            newAccountPubkey: mint.publicKey,
+            space,
+            lamports:
+              await provider.connection.getMinimumBalanceForRentExemption(space),
+            programId: TOKEN_2022_PROGRAM_ID,
+          }),
+          pointer(
+            mint.publicKey,
+            admin.publicKey,
+            mint.publicKey,
+            TOKEN_2022_PROGRAM_ID
+          ),
+          createInitializeMintInstruction(
+            mint.publicKey,
+            0,
+            admin.publicKey,
+            freezeAuthority,
+            TOKEN_2022_PROGRAM_ID
+          )
+        ),
+        [mint]
+      );
+      return mint.publicKey;
+    };
+
+    // Mints a 1/1 to the admin, a member of `memberOf` unless it's null.
+    const mintGroupNft = async (
+      memberOf: anchor.web3.PublicKey | null,
+      freezeAuthority = freezeAuthorityPda
+    ) => {
+      const mint = await createExtensionMint(
+        ExtensionType.GroupMemberPointer,
+        freezeAuthority
+      );
+      if (memberOf) {
+        await tokenGroupMemberInitializeWithRentTransfer(
+          provider.connection,
+          admin.payer,
+          mint,
+          admin.publicKey,
+          memberOf,
+          admin.publicKey,
+          [],
+          undefined,
+          TOKEN_2022_PROGRAM_ID
+        );
+      }
+      const ata = await createAssociatedTokenAccountIdempotent(
+        provider.connection,
+        admin.payer,
+        mint,
+        admin.publicKey,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      await mintTo(
+        provider.connection,
+        admin.payer,
+        mint,
+        ata,
+        admin.payer,
+        1,
+        [],
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      await setAuthority(
+        provider.connection,
+        admin.payer,
+        mint,
+        admin.payer,
+        AuthorityType.MintTokens,
+        null,
+        [],
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      return mint;
+    };
+
+    const isFrozen = async (mint: anchor.web3.PublicKey) =>
+      (
+        await getAccount(
+          provider.connection,
+          nftAta(mint, admin.publicKey, { tokenProgram: TOKEN_2022_PROGRAM_ID }),
+          undefined,
+          TOKEN_2022_PROGRAM_ID
+        )
+      ).isFrozen;
+
+    before(async () => {
+      group = await createExtensionMint(ExtensionType.GroupPointer, null);
+      await tokenGroupInitializeGroupWithRentTransfer(
+        provider.connection,
+        admin.payer,
+        group,
+        admin.publicKey,
+        admin.publicKey,
+        BigInt(10),
+        [],
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      await program.methods
+        .initCollectionConfig(10_000)
+        .accountsPartial({ collectionMint: group, config })
+        .rpc();
+    });
+
+    it("Freezes a group member in place and thaws it on unstake", async () => {
+      const options = { tokenProgram: TOKEN_2022_PROGRAM_ID, group };
+      const mint = await mintGroupNft(group);
+
+      await stake(mint, admin.payer, options);
+      expect(await isFrozen(mint)).to.be.true;
+      const stakeAccount = await program.account.stakeAccount.fetch(
+        stakeAccountPda(mint)
+      );
+      expect(stakeAccount.mint.toBase58()).to.equal(mint.toBase58());
+
+      await unstake(mint, admin.payer, options);
+      expect(await isFrozen(mint)).to.be.false;
+    });
+
+    it("Releases a group member through emergency_unstake", async () => {
+      const options = { tokenProgram: TOKEN_2022_PROGRAM_ID, group };
+      const mint = await mintGroupNft(group);
+
+      await stake(mint, admin.payer, options);
+      await emergencyUnstake(mint, admin.payer, options);
+
+      expect(await isFrozen(mint)).to.be.false;
+    });
+
+    it("Rejects a Token-2022 NFT outside the group", async () => {
+      const options = { tokenProgram: TOKEN_2022_PROGRAM_ID, group };
+
+      await expectError(
+        stake(await mintGroupNft(null), admin.payer, options),
+        "NotGroupMember"
+      );
+
+      const otherGroup = await createExtensionMint(ExtensionType.GroupPointer, null);
+      await tokenGroupInitializeGroupWithRentTransfer(
+        provider.connection,
+        admin.payer,
+        otherGroup,
+        admin.publicKey,
+        admin.publicKey,
+        BigInt(10),
+        [],
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      await expectError(
+        stake(await mintGroupNft(otherGroup), admin.payer, options),
+        "NotGroupMember"
+      );
+    });
+
+    it("Rejects freezing a group member the program can't thaw", async () => {
+      const mint = await mintGroupNft(group, admin.publicKey);
+
+      await expectError(
+        stake(mint, admin.payer, { tokenProgram: TOKEN_2022_PROGRAM_ID, group }),
+        "InvalidFreezeAuthority"
+      );
+    });
+
+    it("Rejects a group member frozen before staking", async () => {
+      const options = { tokenProgram: TOKEN_2022_PROGRAM_ID, group };
+      const mint = await mintGroupNft(group, admin.publicKey);
+      await freezeAccount(
+        provider.connection,
+        admin.payer,
+        nftAta(mint, admin.publicKey, options),
+        mint,
+        admin.payer,
+        [],
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+
+      await expectError(stake(mint, admin.payer, options), "TokenAccountFrozen");
+    });
+  });
+  describe("lockups", () => {
+    const stakeAccountOf = (mint: anchor.web3.PublicKey) =>
+      program.account.stakeAccount.fetch(stakeAccountPda(mint));
+
+    it("Unstakes a Flexible stake right away at the base rate", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+      await stake(mint, admin.payer, { lockup: { flexible: {} } });
+
+      const stakeAccount = await stakeAccountOf(mint);
+      expect(stakeAccount.lockup).to.deep.equal({ flexible: {} });
+      expect(stakeAccount.lockupSecs).to.equal(0);
+      expect(stakeAccount.pointsMultiplier).to.equal(10_000);
+
+      await unstake(mint);
+    });
+
+    for (const [lockup, days, multiplierBps] of [
+      [{ thirtyDay: {} }, 30, 15_000],
+      [{ ninetyDay: {} }, 90, 20_000],
+    ] as const) {
+      it(`Holds a ${days} day lockup at its own rate`, async () => {
+        const mint = await mintCollectionNft(admin.publicKey);
+        await stake(mint, admin.payer, { lockup });
+
+        const stakeAccount = await stakeAccountOf(mint);
+        expect(stakeAccount.lockup).to.deep.equal(lockup);
+        expect(stakeAccount.lockupSecs).to.equal(days * DAY);
+        expect(stakeAccount.pointsMultiplier).to.equal(multiplierBps);
+
+        await expectError(unstake(mint), "FreezePeriodNotPassed");
+        await emergencyUnstake(mint);
+      });
+    }
+
+    it("Keeps an open position's terms when the rates change", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+      await stake(mint, admin.payer, { lockup: { thirtyDay: {} } });
+
+      await updateConfig({ lockupOptions: lockupOptions(12_000, 18_000) }).rpc();
+
+      const stakeAccount = await stakeAccountOf(mint);
+      expect(stakeAccount.lockupSecs).to.equal(30 * DAY);
+      expect(stakeAccount.pointsMultiplier).to.equal(15_000);
+
+      // New stakes get the new rate
+      const next = await mintCollectionNft(admin.publicKey);
+      await stake(next, admin.payer, { lockup: { thirtyDay: {} } });
+      expect((await stakeAccountOf(next)).pointsMultiplier).to.equal(12_000);
+
+      await emergencyUnstake(mint);
+      await emergencyUnstake(next);
+      await updateConfig({ lockupOptions: lockupOptions() }).rpc();
+    });
+
+    it("Rejects lockups the pool doesn't offer and out of order rates", async () => {
+      const withoutNinety = lockupOptions();
+      withoutNinety[2] = { freezeSecs: 0, multiplierBps: 0 };
+      await updateConfig({ lockupOptions: withoutNinety }).rpc();
+
+      const mint = await mintCollectionNft(admin.publicKey);
+      await expectError(
+        stake(mint, admin.payer, { lockup: { ninetyDay: {} } }),
+        "InvalidLockupKind"
+      );
+
+      await expectError(
+        updateConfig({ lockupOptions: lockupOptions(15_000, 12_000) }).rpc(),
+        "InvalidLockupOptions"
+      );
+
+      await updateConfig({ lockupOptions: lockupOptions() }).rpc();
+    });
+  });
+  describe("blacklist", () => {
+    const blacklistPda = (wallet: anchor.web3.PublicKey) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("blacklist"), config.toBuffer(), wallet.toBuffer()],
+        program.programId
+      )[0];
+
+    const addToBlacklist = (wallet: anchor.web3.PublicKey) =>
+      program.methods
+        .addToBlacklist()
+        .accountsPartial({ wallet, config })
+        .rpc();
+
+    const removeFromBlacklist = (wallet: anchor.web3.PublicKey) =>
+      program.methods
+        .removeFromBlacklist()
+        .accountsPartial({ config, blacklist: blacklistPda(wallet) })
+        .rpc();
+
+    const forceUnstake = (
+      mint: anchor.web3.PublicKey,
+      user: anchor.web3.PublicKey,
+      signer: anchor.web3.Keypair = admin.payer
+    ) =>
+      program.methods
+        .forceUnstake()
+        .accountsPartial({
+          admin: signer.publicKey,
+          user,
+          config,
+          userAccount: userAccountPda(user),
+          metadataProgram,
+          ...nftAccounts(mint, user),
+        })
+        .signers([signer])
+        .rpc();
+
+    const stakedUser = async () => {
+      const user = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+      const mint = await mintCollectionNft(user.publicKey);
+      await stake(mint, user);
+      return { user, mint };
+    };
+
+    it("Blocks stakes and claims from a blacklisted wallet", async () => {
+      const { user, mint } = await stakedUser();
+      await addToBlacklist(user.publicKey);
+
+      const next = await mintCollectionNft(user.publicKey);
+      await expectError(stake(next, user), "WalletBlacklisted");
+      await expectError(claim(user), "WalletBlacklisted");
+
+      // Exiting still works, for no points
+      const [unstaked] = (await eventsOf(await unstake(mint, user))).filter(
+        (e) => e.name === "nftUnstaked"
+      );
+      expect(unstaked.data.pointsEarned.toNumber()).to.equal(0);
+
+      await removeFromBlacklist(user.publicKey);
+      expect(await provider.connection.getAccountInfo(blacklistPda(user.publicKey)))
+        .to.be.null;
+      await stake(next, user);
+      await unstake(next, user);
+    });
+
+    it("Force unstakes a blacklisted wallet's NFT back to it", async () => {
+      const { user, mint } = await stakedUser();
+
+      // Only blacklisted wallets can be force unstaked, and only by the admin
+      await expectError(
+        forceUnstake(mint, user.publicKey),
+        "AccountNotInitialized"
+      );
+      await addToBlacklist(user.publicKey);
+      const intruder = await fundedKeypair();
+      await expectError(
+        forceUnstake(mint, user.publicKey, intruder),
+        "Unauthorized"
+      );
+
+      const [event] = (
+        await eventsOf(await forceUnstake(mint, user.publicKey))
+      ).filter((e) => e.name === "forceUnstaked");
+      expect(event.data.user.toBase58()).to.equal(user.publicKey.toBase58());
+      expect(event.data.mint.toBase58()).to.equal(mint.toBase58());
+
+      expect(await provider.connection.getAccountInfo(stakeAccountPda(mint))).to
+        .be.null;
+      const ata = await getAccount(
+        provider.connection,
+        nftAta(mint, user.publicKey)
+      );
+      expect(ata.isFrozen).to.be.false;
+      expect(ata.amount.toString()).to.equal("1");
+      const userAccount = await program.account.userAccount.fetch(
+        userAccountPda(user.publicKey)
+      );
+      expect(userAccount.amountStaked).to.equal(0);
+      expect(userAccount.points.toNumber()).to.equal(0);
+
+      await removeFromBlacklist(user.publicKey);
+    });
+
+    it("Rejects blacklist changes from a non-admin", async () => {
+      const intruder = await fundedKeypair();
+
+      await expectError(
+        program.methods
+          .addToBlacklist()
+          .accountsPartial({
+            admin: intruder.publicKey,
+            wallet: intruder.publicKey,
+            config,
+          })
+          .signers([intruder])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+  });
+  describe("snapshots", () => {
+    const fundSnapshot = (amount: number) =>
+      program.methods
+        .fundSnapshot(new anchor.BN(amount))
+        .accountsPartial({
+          funder: admin.publicKey,
+          funderAta: adminRewardsAta,
+          config,
+          rewardMint,
+          rewardVault: rewardVaultPda(config),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+    const distributeSnapshot = (signer: anchor.web3.Keypair = admin.payer) =>
+      program.methods
+        .distributeSnapshot()
+        .accountsPartial({ admin: signer.publicKey, config })
+        .signers([signer])
+        .rpc();
+
+    const claimSnapshot = (
+      user: anchor.web3.Keypair,
+      mints: anchor.web3.PublicKey[]
+    ) =>
+      program.methods
+        .claimSnapshot()
+        .accountsPartial({
+          user: user.publicKey,
+          rewardsAta: getAssociatedTokenAddressSync(rewardMint, user.publicKey),
+          config,
+          userAccount: userAccountPda(user.publicKey),
+          rewardMint,
+          rewardVault: rewardVaultPda(config),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts(
+          mints.map((mint) => ({
+            pubkey: stakeAccountPda(mint),
+            isWritable: false,
+            isSigner: false,
+          }))
+        )
+        .signers([user])
+        .rpc();
+
+    const stakedUser = async () => {
+      const user = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+      const mint = await mintCollectionNft(user.publicKey);
+      await stake(mint, user);
+      return { user, mint };
+    };
+
+    // Stakes only count toward snapshots taken after the second they were opened in
+    const nextSecond = () => new Promise((resolve) => setTimeout(resolve, 1_100));
+
+    it("Pays each staker their share of a snapshot once", async () => {
+      const { user, mint } = await stakedUser();
+      await nextSecond();
+
+      await expectError(distributeSnapshot(), "NothingToDistribute");
+      await fundSnapshot(900_000);
+      await distributeSnapshot();
+
+      const account = await program.account.stakeConfig.fetch(config);
+      expect(account.snapshotPool.toNumber()).to.equal(0);
+      const share = Math.floor(
+        account.snapshotAmount.toNumber() / account.snapshotTotalWeight
+      );
+
+      const [event] = (await eventsOf(await claimSnapshot(user, [mint]))).filter(
+        (e) => e.name === "snapshotClaimed"
+      );
+      expect(event.data.weight).to.equal(1);
+      expect(event.data.amount.toNumber()).to.equal(share);
+      const rewards = await getAccount(
+        provider.connection,
+        getAssociatedTokenAddressSync(rewardMint, user.publicKey)
+      );
+      expect(Number(rewards.amount)).to.equal(share);
+
+      await expectError(claimSnapshot(user, [mint]), "SnapshotAlreadyClaimed");
+      await unstake(mint, user);
+    });
+
+    it("Leaves out stakes opened after the snapshot", async () => {
+      await fundSnapshot(900_000);
+      await distributeSnapshot();
+
+      const { user, mint } = await stakedUser();
+      await expectError(
+        claimSnapshot(user, [mint]),
+        "InvalidRemainingAccounts"
+      );
+      await expectError(claimSnapshot(user, []), "NothingToClaim");
+      await unstake(mint, user);
+    });
+
+    it("Rejects a snapshot from a non-admin", async () => {
+      const intruder = await fundedKeypair();
+
+      await fundSnapshot(1_000);
+      await expectError(distributeSnapshot(intruder), "Unauthorized");
+    });
+  });
+  describe("locked compounding", () => {
+    let user: anchor.web3.Keypair;
+
+    const compoundLocked = () =>
+      program.methods
+        .compoundLocked()
+        .accountsPartial({
+          user: user.publicKey,
+          config,
+          userAccount: userAccountPda(user.publicKey),
+        })
+        .signers([user])
+        .rpc();
+
+    const claimLocked = () =>
+      program.methods
+        .claimLocked()
+        .accountsPartial({
+          user: user.publicKey,
+          rewardsAta: getAssociatedTokenAddressSync(rewardMint, user.publicKey),
+          config,
+          userAccount: userAccountPda(user.publicKey),
+          rewardMint,
+          rewardVault: rewardVaultPda(config),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([user])
+        .rpc();
+
+    const grant = (points: number) =>
+      program.methods
+        .adjustPoints(new anchor.BN(points), 0)
+        .accountsPartial({
+          user: user.publicKey,
+          config,
+          userAccount: userAccountPda(user.publicKey),
+          leaderboard: null,
+        })
+        .rpc();
+
+    before(async () => {
+      user = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+    });
+
+    after(async () => {
+      await updateConfig({ compoundBonusBps: 0, compoundLockSecs: 0 }).rpc();
+    });
+
+    it("Rejects locking while the bonus is off", async () => {
+      await grant(10);
+      await expectError(compoundLocked(), "CompoundingDisabled");
+    });
+
+    it("Locks points with the bonus and rejects an early claim", async () => {
+      await updateConfig({ compoundBonusBps: 2_000, compoundLockSecs: DAY }).rpc();
+
+      const [event] = (await eventsOf(await compoundLocked())).filter(
+        (e) => e.name === "pointsLocked"
+      );
+      expect(event.data.pointsBurned.toNumber()).to.equal(10);
+      expect(event.data.pointsLocked.toNumber()).to.equal(12);
+      expect(event.data.lockedUntil.toNumber()).to.equal(
+        event.data.lockedAt.toNumber() + DAY
+      );
+
+      await expectError(claimLocked(), "CompoundStillLocked");
+    });
+
+    it("Extends one lock instead of opening another", async () => {
+      const before = await program.account.userAccount.fetch(
+        userAccountPda(user.publicKey)
+      );
+      await grant(5);
+      await compoundLocked();
+
+      const after = await program.account.userAccount.fetch(
+        userAccountPda(user.publicKey)
+      );
+      expect(after.lockedPoints.toNumber()).to.equal(18);
+      expect(after.lockedUntil.toNumber()).to.be.at.least(before.lockedUntil.toNumber());
+    });
+
+    it("Pays out once the lock has passed", async () => {
+      // A zero lock only applies to new compounds, the running lock holds
+      await updateConfig({ compoundLockSecs: 0 }).rpc();
+      await grant(1);
+      await compoundLocked();
+      await expectError(claimLocked(), "CompoundStillLocked");
+
+      // Starts over with a fresh user on a zero lock
+      user = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+      await grant(100);
+      await compoundLocked();
+
+      const [event] = (await eventsOf(await claimLocked())).filter(
+        (e) => e.name === "lockedRewardsClaimed"
+      );
+      expect(event.data.pointsBurned.toNumber()).to.equal(120);
+      // 6 decimal reward mint
+      expect(event.data.amount.toNumber()).to.equal(120_000_000);
+
+      await expectError(claimLocked(), "NothingToClaim");
+    });
+  });
+  describe("failed unstakes", () => {
+    it("Leaves the stake intact when the NFT can't be thawed", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+      await stake(mint);
+
+      const stakeBefore = await program.account.stakeAccount.fetch(
+        stakeAccountPda(mint)
+      );
+      const userBefore = await program.account.userAccount.fetch(
+        userAccountPda(admin.publicKey)
+      );
+      const configBefore = await program.account.stakeConfig.fetch(config);
+
+      // Releasing it as a group member skips the metadata thaw and fails
+      await expectError(
+        unstake(mint, admin.payer, {
+          group: new anchor.web3.PublicKey(collectionMint.publicKey),
+        }),
+        "InvalidFreezeAuthority"
+      );
+
+      const stakeAfter = await program.account.stakeAccount.fetch(
+        stakeAccountPda(mint)
+      );
+      expect(stakeAfter.stakedAt.toNumber()).to.equal(stakeBefore.stakedAt.toNumber());
+      const userAfter = await program.account.userAccount.fetch(
+        userAccountPda(admin.publicKey)
+      );
+      expect(userAfter.points.toNumber()).to.equal(userBefore.points.toNumber());
+      expect(userAfter.amountStaked).to.equal(userBefore.amountStaked);
+      const configAfter = await program.account.stakeConfig.fetch(config);
+      expect(configAfter.totalStaked).to.equal(configBefore.totalStaked);
+      const ata = await getAccount(provider.connection, nftAta(mint, admin.publicKey));
+      expect(ata.isFrozen).to.be.true;
+
+      // Nothing was lost, a well formed unstake still goes through
+      await unstake(mint);
+      expect(
+        (await getAccount(provider.connection, nftAta(mint, admin.publicKey))).isFrozen
+      ).to.be.false;
+    });
+  });
+  describe("restake cooldown", () => {
+    const timesStaked = async (mint: anchor.web3.PublicKey) =>
+      (await program.account.mintRecord.fetch(mintRecordPda(mint))).timesStaked;
+
+    after(async () => {
+      await updateConfig({ restakeCooldownSecs: 0 }).rpc();
+    });
+
+    it("Counts every stake of a mint with no cooldown set", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+
+      for (let i = 1; i <= 3; i++) {
+        await stake(mint);
+        expect(await timesStaked(mint)).to.equal(i);
+        await unstake(mint);
+      }
+
+      const record = await program.account.mintRecord.fetch(mintRecordPda(mint));
+      expect(record.lastUnstakedAt.toNumber()).to.be.greaterThan(0);
+    });
+
+    it("Rejects restaking a mint inside the cooldown", async () => {
+      await updateConfig({ restakeCooldownSecs: DAY }).rpc();
+      const mint = await mintCollectionNft(admin.publicKey);
+
+      // A mint that was never unstaked stakes right away
+      await stake(mint);
+      await unstake(mint);
+
+      await expectError(stake(mint), "RestakeCooldownActive");
+      await expectError(stakeBatch([mint]).rpc(), "RestakeCooldownActive");
+      expect(await timesStaked(mint)).to.equal(1);
+    });
+
+    it("Restakes again once the cooldown has passed", async () => {
+      await updateConfig({ restakeCooldownSecs: 1 }).rpc();
+      const mint = await mintCollectionNft(admin.publicKey);
+      await stake(mint);
+      await unstake(mint);
+
+      await new Promise((resolve) => setTimeout(resolve, 2_000));
+      await stake(mint);
+      expect(await timesStaked(mint)).to.equal(2);
+      await unstake(mint);
+    });
+  });
+  describe("stake receipts", () => {
+    const pool = configPda(6);
+    let receipt: anchor.web3.PublicKey;
+
+    const receiptAta = (owner: anchor.web3.PublicKey) =>
+      getAssociatedTokenAddressSync(receipt, owner, false, TOKEN_2022_PROGRAM_ID);
+    const receiptBalance = async (owner: anchor.web3.PublicKey) =>
+      Number(
+        (
+          await getAccount(
+            provider.connection,
+            receiptAta(owner),
+            undefined,
+            TOKEN_2022_PROGRAM_ID
+          )
+        ).amount
+      );
+
+    before(async () => {
+      // Non-transferable, no decimals, minted by the pool's config PDA
+      const mint = anchor.web3.Keypair.generate();
+      const space = getMintLen([ExtensionType.NonTransferable]);
+      await provider.sendAndConfirm(
+        new anchor.web3.Transaction().add(
+          anchor.web3.SystemProgram.createAccount({
+            fromPubkey: admin.publicKey,
+            newAccountPubkey: mint.publicKey,
+            space,
+            lamports:
+              await provider.connection.getMinimumBalanceForRentExemption(space),
+            programId: TOKEN_2022_PROGRAM_ID,
+          }),
+          createInitializeNonTransferableMintInstruction(
+            mint.publicKey,
+            TOKEN_2022_PROGRAM_ID
+          ),
+          createInitializeMintInstruction(
+            mint.publicKey,
+            0,
+            pool,
+            null,
+            TOKEN_2022_PROGRAM_ID
+          )
+        ),
+        [mint]
+      );
+      receipt = mint.publicKey;
+
+      await initializeConfig(6, { receiptMint: receipt });
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ config: pool })
+        .rpc();
+    });
+
+    it("Mints a receipt on stake and burns it on unstake", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+
+      await stake(mint, admin.payer, { pool, receipt });
+      expect(await receiptBalance(admin.publicKey)).to.equal(1);
+      expect(
+        (await program.account.stakeAccount.fetch(stakeAccountPda(mint, pool)))
+          .hasReceipt
+      ).to.equal(true);
+
+      await unstake(mint, admin.payer, { pool, receipt });
+      expect(await receiptBalance(admin.publicKey)).to.equal(0);
+    });
+
+    it("Rejects a stake without the receipt accounts", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+      await expectError(stake(mint, admin.payer, { pool }), "MissingReceipt");
+    });
+
+    it("Rejects an unstake once the receipt was burned", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+      await stake(mint, admin.payer, { pool, receipt });
+
+      await burn(
+        provider.connection,
+        admin.payer,
+        receiptAta(admin.publicKey),
+        receipt,
+        admin.payer,
+        1,
+        [],
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+
+      await expectError(
+        unstake(mint, admin.payer, { pool, receipt }),
+        "MissingReceipt"
+      );
+      await expectError(unstake(mint, admin.payer, { pool }), "MissingReceipt");
+
+      // The receipt accounts are still needed to show it's gone, then the NFT can come back
+      await expectError(emergencyUnstake(mint, admin.payer, { pool }), "MissingReceipt");
+      await emergencyUnstake(mint, admin.payer, { pool, receipt });
+    });
+
+    it("Burns the receipt on an emergency unstake", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+      await stake(mint, admin.payer, { pool, receipt });
+      expect(await receiptBalance(admin.publicKey)).to.equal(1);
+
+      await emergencyUnstake(mint, admin.payer, { pool, receipt });
+      expect(await receiptBalance(admin.publicKey)).to.equal(0);
+    });
+
+    it("Leaves stakes with a receipt to their owner rather than admin unstaking them", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+      await stake(mint, admin.payer, { pool, receipt });
+
+      await expectError(
+        program.methods
+          .adminUnstake()
+          .accountsPartial({
+            admin: admin.publicKey,
+            user: admin.publicKey,
+            config: pool,
+            userAccount: userAccountPda(admin.publicKey, pool),
+            rewardMint,
+            metadataProgram,
+            ...nftAccounts(mint, admin.publicKey, { pool }),
+          })
+          .rpc(),
+        "MissingReceipt"
+      );
+      expect(await receiptBalance(admin.publicKey)).to.equal(1);
+
+      await unstake(mint, admin.payer, { pool, receipt });
+    });
+  });
+  describe("treasury fee", () => {
+    let user: anchor.web3.Keypair;
+    let treasury: anchor.web3.PublicKey;
+
+    const grant = (points: number) =>
+      program.methods
+        .adjustPoints(new anchor.BN(points), 0)
+        .accountsPartial({
+          user: user.publicKey,
+          config,
+          userAccount: userAccountPda(user.publicKey),
+          leaderboard: null,
+        })
+        .rpc();
+
+    const balanceOf = async (ata: anchor.web3.PublicKey) =>
+      Number((await getAccount(provider.connection, ata)).amount);
+
+    const claimed = async (signature: string) =>
+      (await eventsOf(signature)).find((e) => e.name === "rewardsClaimed").data;
+
+    before(async () => {
+      user = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+
+      const owner = anchor.web3.Keypair.generate().publicKey;
+      treasury = (
+        await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          admin.payer,
+          rewardMint,
+          owner
+        )
+      ).address;
+      await updateConfig({ treasury: owner }).rpc();
+      await fundVault(100_000_000);
+    });
+
+    after(async () => {
+      await updateConfig({ treasury: admin.publicKey, feeBps: 0 }).rpc();
+    });
+
+    it("Pays no fee and needs no treasury at 0 bps", async () => {
+      await grant(4);
+      const event = await claimed(await claim(user));
+
+      expect(event.amount.toNumber()).to.equal(4_000_000);
+      expect(event.fee.toNumber()).to.equal(0);
+      expect(await balanceOf(treasury)).to.equal(0);
+    });
+
+    it("Pays 250 bps to the treasury on top of the claim", async () => {
+      await updateConfig({ feeBps: 250 }).rpc();
+      await grant(4);
+      const rewardsAta = getAssociatedTokenAddressSync(rewardMint, user.publicKey);
+      const before = await balanceOf(rewardsAta);
+
+      const event = await claimed(await claim(user, user, treasury));
+
+      // The user still gets every point's worth, the fee comes out of the vault
+      expect(event.amount.toNumber()).to.equal(4_000_000);
+      expect(event.fee.toNumber()).to.equal(100_000);
+      expect((await balanceOf(rewardsAta)) - before).to.equal(4_000_000);
+      expect(await balanceOf(treasury)).to.equal(100_000);
+    });
+
+    it("Rejects a missing or wrong treasury account", async () => {
+      await grant(4);
+
+      await expectError(claim(user), "InvalidTreasury");
+      await expectError(
+        claim(user, user, getAssociatedTokenAddressSync(rewardMint, admin.publicKey)),
+        "InvalidTreasury"
+      );
+      await expectError(updateConfig({ feeBps: 10_001 }).rpc(), "InvalidFeeBps");
+    });
+  });
+  describe("leaderboard", () => {
+    const leaderboard = leaderboardPda();
+
+    const ranking = async () =>
+      (await program.account.leaderboard.fetch(leaderboard)).entries
+        .filter((entry) => entry.points.toNumber() > 0)
+        .map((entry) => [entry.user.toBase58(), entry.points.toNumber()]);
+
+    const grant = (
+      user: anchor.web3.PublicKey,
+      points: number,
+      board: anchor.web3.PublicKey = null
+    ) =>
+      program.methods
+        .adjustPoints(new anchor.BN(points), 0)
+        .accountsPartial({ user, config, userAccount: userAccountPda(user), leaderboard: board })
+        .rpc();
+
+    const resize = (capacity: number, signer: anchor.web3.Keypair = admin.payer) =>
+      program.methods
+        .resizeLeaderboard(capacity)
+        .accountsPartial({ admin: signer.publicKey, config, leaderboard })
+        .signers([signer])
+        .rpc();
+
+    before(async () => {
+      await program.methods
+        .initLeaderboard()
+        .accountsPartial({ config })
+        .rpc();
+      await fundVault(100_000_000);
+    });
+
+    it("Ranks users by points paid out on claim and unstake", async () => {
+      const [first, second] = [await fundedKeypair(), await fundedKeypair()];
+      for (const user of [first, second]) {
+        await program.methods
+          .initialize(null)
+          .accountsPartial({ user: user.publicKey, config })
+          .signers([user])
+          .rpc();
+      }
+
+      await grant(first.publicKey, 5);
+      await claim(first, first, null, leaderboard);
+      await grant(second.publicKey, 8);
+      await claim(second, second, null, leaderboard);
+
+      expect(await ranking()).to.deep.equal([
+        [second.publicKey.toBase58(), 8],
+        [first.publicKey.toBase58(), 5],
+      ]);
+
+      // A claim made without the leaderboard still counts towards the next ranking, an
+      // unstake earning nothing re-ranks the total
+      await grant(first.publicKey, 2);
+      await claim(first);
+      expect((await ranking())[1][1]).to.equal(5);
+
+      const mint = await mintCollectionNft(first.publicKey);
+      await stake(mint, first);
+      await unstake(mint, first, {}, null, leaderboard);
+      expect(await ranking()).to.deep.equal([
+        [second.publicKey.toBase58(), 8],
+        [first.publicKey.toBase58(), 7],
+      ]);
+
+      await grant(first.publicKey, 2);
+      await claim(first, first, null, leaderboard);
+      expect((await ranking())[0]).to.deep.equal([first.publicKey.toBase58(), 9]);
+    });
+
+    it("Ranks users who claimed without it when their points are adjusted", async () => {
+      const user = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+
+      await grant(user.publicKey, 3);
+      await claim(user);
+      expect((await ranking()).map(([key]) => key)).to.not.include(user.publicKey.toBase58());
+
+      await grant(user.publicKey, 1, leaderboard);
+      expect((await ranking()).at(-1)).to.deep.equal([user.publicKey.toBase58(), 3]);
+    });
+
+    it("Resizing drops the lowest entries", async () => {
+      const before = await ranking();
+      expect((await program.account.leaderboard.fetch(leaderboard)).capacity).to.equal(100);
+
+      await resize(1);
+      expect(await ranking()).to.deep.equal(before.slice(0, 1));
+
+      await resize(100);
+      await expectError(resize(101), "LeaderboardFull");
+      await expectError(resize(50, await fundedKeypair()), "Unauthorized");
+      expect((await program.account.leaderboard.fetch(leaderboard)).capacity).to.equal(100);
+    });
+
+    it("Only the admin can create it", async () => {
+      const user = await fundedKeypair();
+      await expectError(
+        program.methods
+          .initLeaderboard()
+          .accountsPartial({ admin: user.publicKey, config: configPda(1) })
+          .signers([user])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+  });
+  describe("first stake without initialize", () => {
+    it("Creates the user account inside stake", async () => {
+      const user = await fundedKeypair();
+      const mint = await mintCollectionNft(user.publicKey);
+
+      await stake(mint, user);
+
+      const account = await program.account.userAccount.fetch(
+        userAccountPda(user.publicKey)
+      );
+      expect(account.owner.toBase58()).to.equal(user.publicKey.toBase58());
+      expect(account.amountStaked).to.equal(1);
+      expect(account.version).to.equal(5);
+
+      await unstake(mint, user);
+    });
+
+    it("Rejects staking into someone else's user account", async () => {
+      const [victim, attacker] = [await fundedKeypair(), await fundedKeypair()];
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: victim.publicKey, config })
+        .signers([victim])
+        .rpc();
+      const mint = await mintCollectionNft(attacker.publicKey);
+
+      // The seeds check runs first and already rejects the victim's account, the stored
+      // owner check behind it never gets the chance
+      await expectError(
+        program.methods
+          .stake({ flexible: {} }, null)
+          .accountsPartial({
+            user: attacker.publicKey,
+            rarityMap: null,
+            collectionMint: new anchor.web3.PublicKey(collectionMint.publicKey),
+            config,
+            collectionConfig: collectionConfigPda(
+              new anchor.web3.PublicKey(collectionMint.publicKey)
+            ),
+            userAccount: userAccountPda(victim.publicKey),
+            metadataProgram,
+            ...nftAccounts(mint, attacker.publicKey),
+          })
+          .signers([attacker])
+          .rpc(),
+        "ConstraintSeeds"
+      );
+
+      const account = await program.account.userAccount.fetch(
+        userAccountPda(victim.publicKey)
+      );
+      expect(account.amountStaked).to.equal(0);
+      expect(account.owner.toBase58()).to.equal(victim.publicKey.toBase58());
+    });
+  });
+  describe("minimum reward duration", () => {
+    after(async () => {
+      await updateConfig({ minRewardDuration: 0 }).rpc();
+    });
+
+    it("Pays no points for a stake unstaked before the minimum", async () => {
+      await updateConfig({ minRewardDuration: DAY }).rpc();
+      const user = await fundedKeypair();
+      const mint = await mintCollectionNft(user.publicKey);
+      await stake(mint, user);
+
+      const [event] = (await eventsOf(await unstake(mint, user))).filter(
+        (e) => e.name === "nftUnstaked"
+      );
+      expect(event.data.pointsEarned.toNumber()).to.equal(0);
+      expect(event.data.rewardsPaid.toNumber()).to.equal(0);
+
+      await expectError(claim(user), "NothingToClaim");
+    });
+  });
+  describe("set bonus", () => {
+    const pool = configPda(7);
+    let second: Awaited<ReturnType<typeof createCollection>>;
+
+    const setState = async (user: anchor.web3.PublicKey) => {
+      const account = await program.account.userAccount.fetch(
+        userAccountPda(user, pool)
+      );
+      return {
+        pieces: account.setPiecesStaked.slice(0, 2),
+        since: account.setCompleteSince.toNumber(),
+      };
+    };
+
+    before(async () => {
+      second = await createCollection();
+      const setCollections = Array(5).fill(anchor.web3.PublicKey.default);
+      setCollections[0] = new anchor.web3.PublicKey(collectionMint.publicKey);
+      setCollections[1] = new anchor.web3.PublicKey(second.publicKey);
+      await initializeConfig(7, { setCollections, setBonusBps: 5_000 });
+      await program.methods
+        .initCollectionConfig(10_000)
+        .accountsPartial({ collectionMint: setCollections[1], config: pool })
+        .rpc();
+    });
+
+    it("Rejects a set naming a collection twice", async () => {
+      const collection = new anchor.web3.PublicKey(collectionMint.publicKey);
+      const setCollections = Array(5).fill(anchor.web3.PublicKey.default);
+      setCollections[0] = collection;
+      setCollections[3] = collection;
+      await expectError(
+        initializeConfig(8, { setCollections }),
+        "InvalidSetCollections"
+      );
+    });
+
+    // Localnet can't warp the clock, the bonus amounts themselves are covered by the unit tests
+    it("Opens the bonus window once the last piece is staked and closes it on unstake", async () => {
+      const user = await fundedKeypair();
+      const first = await mintCollectionNft(user.publicKey);
+      const other = await mintCollectionNft(user.publicKey, { collection: second });
+      const otherOptions = {
+        pool,
+        collection: new anchor.web3.PublicKey(second.publicKey),
+      };
+
+      await stake(first, user, { pool });
+      // A partial set earns the base rate
+      expect(await setState(user.publicKey)).to.deep.equal({ pieces: [1, 0], since: 0 });
+      const stakeAccount = await program.account.stakeAccount.fetch(
+        stakeAccountPda(first, pool)
+      );
+      expect(stakeAccount.setPiece).to.equal(0);
+
+      await stake(other, user, otherOptions);
+      const complete = await setState(user.publicKey);
+      expect(complete.pieces).to.deep.equal([1, 1]);
+      expect(complete.since).to.be.greaterThan(0);
+
+      await unstake(other, user, otherOptions);
+      expect(await setState(user.publicKey)).to.deep.equal({ pieces: [1, 0], since: 0 });
+
+      await unstake(first, user, { pool });
+    });
+
+    it("Leaves NFTs outside the set out of it", async () => {
+      const user = await fundedKeypair();
+      const outsider = await createCollection();
+      const options = {
+        pool,
+        collection: new anchor.web3.PublicKey(outsider.publicKey),
+      };
+      await program.methods
+        .initCollectionConfig(10_000)
+        .accountsPartial({ collectionMint: options.collection, config: pool })
+        .rpc();
+      const mint = await mintCollectionNft(user.publicKey, { collection: outsider });
+
+      await stake(mint, user, options);
+      const stakeAccount = await program.account.stakeAccount.fetch(
+        stakeAccountPda(mint, pool)
+      );
+      expect(stakeAccount.setPiece).to.be.null;
+      expect(await setState(user.publicKey)).to.deep.equal({ pieces: [0, 0], since: 0 });
+
+      await unstake(mint, user, options);
+    });
+  });
+  describe("emission cap", () => {
+    const pool = configPda(9);
+
+    const credit = (points: number) =>
+      program.methods
+        .adjustPoints(new anchor.BN(points), 0)
+        .accountsPartial({
+          user: admin.publicKey,
+          config: pool,
+          userAccount: userAccountPda(admin.publicKey, pool),
+          leaderboard: null,
+        })
+        .rpc();
+
+    const claimFromPool = () =>
+      program.methods
+        .claim()
+        .accountsPartial({
+          authority: admin.publicKey,
+          user: admin.publicKey,
+          rewardsAta: adminRewardsAta,
+          config: pool,
+          userAccount: userAccountPda(admin.publicKey, pool),
+          tokenStakeAccount: null,
+          leaderboard: null,
+          rewardMint,
+          rewardVault: rewardVaultPda(pool),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+    before(async () => {
+      await initializeConfig(9, { maxEmission: new anchor.BN(10) });
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ config: pool })
+        .rpc();
+      await fundVault(100_000_000, pool);
+    });
+
+    it("Pays what's left under the cap and keeps the rest", async () => {
+      await credit(7);
+      await claimFromPool();
+
+      await credit(8);
+      const [event] = (await eventsOf(await claimFromPool())).filter(
+        (e) => e.name === "rewardsClaimed"
+      );
+      expect(event.data.pointsBurned.toNumber()).to.equal(3);
+      expect(event.data.amount.toNumber()).to.equal(3_000_000);
+      expect(event.data.remainingPoints.toNumber()).to.equal(5);
+
+      const poolConfig = await program.account.stakeConfig.fetch(pool);
+      expect(poolConfig.totalPointsDistributed.toNumber()).to.equal(10);
+    });
+
+    it("Rejects claims once the cap is reached", async () => {
+      await expectError(claimFromPool(), "EmissionCapReached");
+
+      const user = await program.account.userAccount.fetch(
+        userAccountPda(admin.publicKey, pool)
+      );
+      expect(user.points.toNumber()).to.equal(5);
+    });
+  });
+  describe("claim fee in SOL", () => {
+    let user: anchor.web3.Keypair;
+    const treasury = anchor.web3.Keypair.generate().publicKey;
+
+    const grant = (points: number) =>
+      program.methods
+        .adjustPoints(new anchor.BN(points), 0)
+        .accountsPartial({
+          user: user.publicKey,
+          config,
+          userAccount: userAccountPda(user.publicKey),
+          leaderboard: null,
+        })
+        .rpc();
+
+    before(async () => {
+      user = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+      await updateConfig({
+        treasury,
+        claimFeeLamports: new anchor.BN(1_000_000),
+      }).rpc();
+      await fundVault(100_000_000);
+    });
+
+    after(async () => {
+      await updateConfig({
+        treasury: admin.publicKey,
+        claimFeeLamports: new anchor.BN(0),
+      }).rpc();
+    });
+
+    it("Sends the claim fee to the treasury", async () => {
+      await grant(2);
+      const signature = await claim(user, user, null, null, treasury);
+
+      expect(await provider.connection.getBalance(treasury)).to.equal(1_000_000);
+      const event = (await eventsOf(signature)).find(
+        (e) => e.name === "rewardsClaimed"
+      ).data;
+      expect(event.claimFeeLamports.toNumber()).to.equal(1_000_000);
+    });
+
+    it("Rejects a claim without the treasury or the lamports to pay it", async () => {
+      await grant(2);
+      await expectError(claim(user), "InvalidTreasury");
+      await expectError(
+        claim(user, user, null, null, admin.publicKey),
+        "InvalidTreasury"
+      );
+
+      await updateConfig({
+        claimFeeLamports: new anchor.BN(1_000 * anchor.web3.LAMPORTS_PER_SOL),
+      }).rpc();
+      await expectError(
+        claim(user, user, null, null, treasury),
+        "InsufficientFeeFunds"
+      );
+    });
+  });
+  describe("rarity multipliers", () => {
+    // The program's merkle hashing: prefixed sha256, inner nodes over the sorted pair
+    const sha256 = (...parts: Buffer[]) =>
+      createHash("sha256").update(Buffer.concat(parts)).digest();
+    const rarityLeaf = (mint: anchor.web3.PublicKey, multiplierBps: number) => {
+      const bps = Buffer.alloc(2);
+      bps.writeUInt16LE(multiplierBps);
+      return sha256(Buffer.from([0]), mint.toBuffer(), bps);
+    };
+    const node = (a: Buffer, b: Buffer) =>
+      sha256(Buffer.from([1]), ...(Buffer.compare(a, b) <= 0 ? [a, b] : [b, a]));
+
+    let rare: anchor.web3.PublicKey;
+    let commonLeaf: Buffer;
+
+    before(async () => {
+      rare = await mintCollectionNft(admin.publicKey);
+      commonLeaf = rarityLeaf(anchor.web3.Keypair.generate().publicKey, 10_000);
+      const root = node(rarityLeaf(rare, 25_000), commonLeaf);
+      await program.methods
+        .setRarityRoot([...root])
+        .accountsPartial({ config, rarityMap: rarityMapPda() })
+        .rpc();
+    });
+
+    it("Rejects a proof that doesn't verify", async () => {
+      await expectError(
+        stake(rare, admin.payer, {
+          rarity: { multiplierBps: 50_000, proof: [[...commonLeaf]] },
+        }),
+        "InvalidRarityProof"
+      );
+    });
+
+    it("Stakes a rare NFT at its proven multiplier and a common one at 1x", async () => {
+      const common = await mintCollectionNft(admin.publicKey);
+      await stake(common);
+      await stake(rare, admin.payer, {
+        rarity: { multiplierBps: 25_000, proof: [[...commonLeaf]] },
+      });
+
+      const commonStake = await program.account.stakeAccount.fetch(
+        stakeAccountPda(common)
+      );
+      const rareStake = await program.account.stakeAccount.fetch(
+        stakeAccountPda(rare)
+      );
+      expect(rareStake.pointsMultiplier).to.equal(
+        (commonStake.pointsMultiplier * 25_000) / 10_000
+      );
+
+      await unstake(common);
+      await unstake(rare);
+    });
+  });
+  describe("points decay", () => {
+    after(async () => {
+      await updateConfig({ decayRateBpsPerDay: 0 }).rpc();
+    });
+
+    // Localnet can't warp whole days, the decay itself is covered by the unit tests
+    it("Starts the decay clock on first touch and leaves same-day points whole", async () => {
+      await updateConfig({ decayRateBpsPerDay: 100 }).rpc();
+      const user = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+      await program.methods
+        .adjustPoints(new anchor.BN(50), 0)
+        .accountsPartial({
+          user: user.publicKey,
+          config,
+          userAccount: userAccountPda(user.publicKey),
+          leaderboard: null,
+        })
+        .rpc();
+
+      const account = await program.account.userAccount.fetch(
+        userAccountPda(user.publicKey)
+      );
+      expect(account.lastPointsUpdateTs.toNumber()).to.be.greaterThan(0);
+      expect(account.points.toNumber()).to.equal(50);
+
+      await fundVault(100_000_000);
+      const [event] = (await eventsOf(await claim(user))).filter(
+        (e) => e.name === "rewardsClaimed"
+      );
+      expect(event.data.pointsBurned.toNumber()).to.equal(50);
+    });
+
+    it("Rejects a decay rate above 100%", async () => {
+      await expectError(
+        updateConfig({ decayRateBpsPerDay: 10_001 }).rpc(),
+        "InvalidDecayRate"
+      );
+    });
+  });
+  describe("delegate revocation", () => {
+    const delegateOf = async (mint: anchor.web3.PublicKey) =>
+      (await getAccount(provider.connection, nftAta(mint, admin.publicKey))).delegate;
+
+    it("Clears the stake's delegate on unstake", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+      await stake(mint);
+      expect(
+        (await delegateOf(mint)).equals(stakeAccountPda(mint))
+      ).to.be.true;
+
+      await unstake(mint);
+      expect(await delegateOf(mint)).to.be.null;
+    });
+
+    it("Clears the stake's delegate on emergency unstake", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+      await stake(mint);
+
+      await emergencyUnstake(mint);
+      expect(await delegateOf(mint)).to.be.null;
+    });
+
+    it("Rejects an NFT already delegated to someone else", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+      await approve(
+        provider.connection,
+        admin.payer,
+        nftAta(mint, admin.publicKey),
+        anchor.web3.Keypair.generate().publicKey,
+        admin.payer,
+        1
+      );
+
+      await expectError(stake(mint), "ExistingDelegate");
+    });
+  });
+  describe("admin unstake", () => {
+    const adminUnstake = (
+      mint: anchor.web3.PublicKey,
+      user: anchor.web3.PublicKey,
+      signer: anchor.web3.Keypair = admin.payer
+    ) =>
+      program.methods
+        .adminUnstake()
+        .accountsPartial({
+          admin: signer.publicKey,
+          user,
+          config,
+          userAccount: userAccountPda(user),
+          rewardMint,
+          metadataProgram,
+          ...nftAccounts(mint, user),
+        })
+        .signers([signer])
+        .rpc();
+
+    it("Returns a stake's NFT to its owner and banks its points", async () => {
+      const user = await fundedKeypair();
+      const mint = await mintCollectionNft(user.publicKey);
+      await stake(mint, user);
+
+      const intruder = await fundedKeypair();
+      await expectError(
+        adminUnstake(mint, user.publicKey, intruder),
+        "Unauthorized"
+      );
+      // The NFT only ever goes back to the stored owner
+      await expectError(
+        program.methods
+          .adminUnstake()
+          .accountsPartial({
+            user: admin.publicKey,
+            config,
+            userAccount: userAccountPda(admin.publicKey),
+            rewardMint,
+            metadataProgram,
+            ...nftAccounts(mint, user.publicKey),
+          })
+          .rpc(),
+        "ConstraintTokenOwner"
+      );
+
+      const [event] = (
+        await eventsOf(await adminUnstake(mint, user.publicKey))
+      ).filter((e) => e.name === "adminUnstaked");
+      expect(event.data.user.toBase58()).to.equal(user.publicKey.toBase58());
+      expect(event.data.mint.toBase58()).to.equal(mint.toBase58());
+
+      expect(await provider.connection.getAccountInfo(stakeAccountPda(mint))).to
+        .be.null;
+      const ata = await getAccount(provider.connection, nftAta(mint, user.publicKey));
+      expect(ata.isFrozen).to.be.false;
+      expect(ata.amount.toString()).to.equal("1");
+      const userAccount = await program.account.userAccount.fetch(
+        userAccountPda(user.publicKey)
+      );
+      expect(userAccount.amountStaked).to.equal(0);
+      expect(userAccount.points.toNumber()).to.equal(
+        event.data.pointsBanked.toNumber()
+      );
+    });
+  });
+  describe("non-NFT mints", () => {
+    // A plain SPL mint with `supply` minted to the admin, then all but one unit sent elsewhere
+    const plainMint = async (decimals: number, supply: number) => {
+      const mint = await createMint(
+        provider.connection,
+        admin.payer,
+        admin.publicKey,
+        null,
+        decimals
+      );
+      const ata = (
+        await getOrCreateAssociatedTokenAccount(
+          provider.connection,
+          admin.payer,
+          mint,
+          admin.publicKey
+        )
+      ).address;
+      await mintTo(provider.connection, admin.payer, mint, ata, admin.payer, 1);
+      if (supply > 1) {
+        const elsewhere = (
+          await getOrCreateAssociatedTokenAccount(
+            provider.connection,
+            admin.payer,
+            mint,
+            anchor.web3.Keypair.generate().publicKey
+          )
+        ).address;
+        await mintTo(provider.connection, admin.payer, mint, elsewhere, admin.payer, supply - 1);
+      }
+      return mint;
+    };
+
+    // Without metadata, so the mint checks are all that stand in the way
+    const stakePlain = (mint: anchor.web3.PublicKey) =>
+      program.methods
+        .stake({ flexible: {} }, null)
+        .accountsPartial({
+          user: admin.publicKey,
+          collectionMint: new anchor.web3.PublicKey(collectionMint.publicKey),
+          config,
+          collectionConfig: collectionConfigPda(
+            new anchor.web3.PublicKey(collectionMint.publicKey)
+          ),
+          userAccount: userAccountPda(admin.publicKey),
+          metadataProgram,
+          rarityMap: null,
+          ...nftAccounts(mint, admin.publicKey),
+          metadata: null,
+          masterEdition: null,
+        })
+        .rpc();
+
+    it("Rejects a fungible token account holding one unit", async () => {
+      await expectError(stakePlain(await plainMint(9, 1_000)), "NotAnNft");
+    });
+
+    it("Rejects a semi-fungible mint with a supply above one", async () => {
+      await expectError(stakePlain(await plainMint(0, 5)), "NotAnNft");
+    });
+
+    it("Still stakes a proper NFT", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+      await stake(mint);
+      await unstake(mint);
+    });
+  });
+  describe("lock boost", () => {
+    let user: anchor.web3.Keypair;
+    let userAta: anchor.web3.PublicKey;
+
+    const boostVault = anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("boost_vault"), config.toBuffer()],
+      program.programId
+    )[0];
+
+    const boostAccounts = () => ({
+      user: user.publicKey,
+      rewardMint,
+      userAta,
+      config,
+      userAccount: userAccountPda(user.publicKey),
+      boostVault,
+      tokenProgram: TOKEN_PROGRAM_ID,
+    });
+
+    const lockBoost = (amount: number) =>
+      program.methods
+        .lockBoost(new anchor.BN(amount))
+        .accountsPartial(boostAccounts())
+        .signers([user])
+        .rpc();
+
+    const unlockBoost = () =>
+      program.methods
+        .unlockBoost()
+        .accountsPartial(boostAccounts())
+        .signers([user])
+        .rpc();
+
+    const balance = async () =>
+      Number((await getAccount(provider.connection, userAta)).amount);
+
+    before(async () => {
+      user = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+      userAta = await createAssociatedTokenAccountIdempotent(
+        provider.connection,
+        admin.payer,
+        rewardMint,
+        user.publicKey
+      );
+      await mintTo(provider.connection, admin.payer, rewardMint, userAta, admin.payer, 5_000_000);
+    });
+
+    after(async () => {
+      await updateConfig({ lockBoostBps: 0, maxLockBoostBps: 0, boostLockSecs: 0 }).rpc();
+    });
+
+    it("Rejects locking while boosting is off", async () => {
+      await expectError(lockBoost(1_000_000), "BoostingDisabled");
+    });
+
+    // Localnet can't warp whole days, the boosted accrual itself is covered by the unit tests
+    it("Holds the tokens in the boost vault until the boost expires", async () => {
+      await updateConfig({ lockBoostBps: 500, maxLockBoostBps: 5_000, boostLockSecs: DAY }).rpc();
+      await expectError(lockBoost(0), "InvalidAmount");
+
+      const [event] = (await eventsOf(await lockBoost(2_000_000))).filter(
+        (e) => e.name === "boostLocked"
+      );
+      const account = await program.account.userAccount.fetch(
+        userAccountPda(user.publicKey)
+      );
+      expect(account.boostAmount.toNumber()).to.equal(2_000_000);
+      expect(account.boostExpiry.toNumber()).to.equal(event.data.lockedAt.toNumber() + DAY);
+      expect(Number((await getAccount(provider.connection, boostVault)).amount)).to.equal(2_000_000);
+      expect(await balance()).to.equal(3_000_000);
+
+      await expectError(lockBoost(1_000_000), "BoostAlreadyLocked");
+      await expectError(unlockBoost(), "BoostStillLocked");
+      await expectError(
+        program.methods
+          .closeUser()
+          .accountsPartial({ user: user.publicKey, config, userAccount: userAccountPda(user.publicKey) })
+          .signers([user])
+          .rpc(),
+        "OutstandingStakeOrRewards"
+      );
+    });
+
+    it("Returns the tokens once the boost expires", async () => {
+      // A zero lock expires the boost as soon as it's locked
+      await updateConfig({ boostLockSecs: 0 }).rpc();
+      const other = user;
+      user = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+      userAta = await createAssociatedTokenAccountIdempotent(
+        provider.connection,
+        admin.payer,
+        rewardMint,
+        user.publicKey
+      );
+      await mintTo(provider.connection, admin.payer, rewardMint, userAta, admin.payer, 1_000_000);
+
+      await lockBoost(1_000_000);
+      expect(await balance()).to.equal(0);
+      await unlockBoost();
+      expect(await balance()).to.equal(1_000_000);
+
+      const account = await program.account.userAccount.fetch(
+        userAccountPda(user.publicKey)
+      );
+      expect(account.boostAmount.toNumber()).to.equal(0);
+      await expectError(unlockBoost(), "NothingToClaim");
+
+      // Unlocking one user's boost leaves the others in the vault
+      expect(Number((await getAccount(provider.connection, boostVault)).amount)).to.equal(2_000_000);
+      user = other;
+    });
+  });
+  describe("short reward vault", () => {
+    // Its own pool so the vault holds exactly what the test funds
+    const pool = configPda(10);
+
+    const credit = (points: number) =>
+      program.methods
+        .adjustPoints(new anchor.BN(points), 0)
+        .accountsPartial({
+          user: admin.publicKey,
+          config: pool,
+          userAccount: userAccountPda(admin.publicKey, pool),
+          leaderboard: null,
+        })
+        .rpc();
+
+    const claimFromPool = () =>
+      program.methods
+        .claim()
+        .accountsPartial({
+          authority: admin.publicKey,
+          user: admin.publicKey,
+          rewardsAta: adminRewardsAta,
+          config: pool,
+          userAccount: userAccountPda(admin.publicKey, pool),
+          tokenStakeAccount: null,
+          treasuryAta: null,
+          treasury: null,
+          leaderboard: null,
+          rewardMint,
+          rewardVault: rewardVaultPda(pool),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+
+    before(async () => {
+      await initializeConfig(10);
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ config: pool })
+        .rpc();
+      // 3.5 points' worth, partial points aren't paid
+      await fundVault(3_500_000, pool);
+    });
+
+    it("Pays what the vault covers and keeps the rest", async () => {
+      await credit(5);
+      const [event] = (await eventsOf(await claimFromPool())).filter(
+        (e) => e.name === "rewardsClaimed"
+      );
+      expect(event.data.pointsBurned.toNumber()).to.equal(3);
+      expect(event.data.amount.toNumber()).to.equal(3_000_000);
+      expect(event.data.remainingPoints.toNumber()).to.equal(2);
+    });
+
+    it("Rejects claims the vault can't pay a whole point of", async () => {
+      await expectError(claimFromPool(), "InsufficientRewardVault");
+
+      // Topping the vault back up pays the kept points out in full
+      await fundVault(1_500_000, pool);
+      const [event] = (await eventsOf(await claimFromPool())).filter(
+        (e) => e.name === "rewardsClaimed"
+      );
+      expect(event.data.pointsBurned.toNumber()).to.equal(2);
+      expect(event.data.remainingPoints.toNumber()).to.equal(0);
+    });
+  });
+  describe("position receipts", () => {
+    // Tokenizing needs vault custody, the pool "vault custody" set up
+    const pool = configPda(2);
+    const options = { pool, vault: true };
+    let buyer: anchor.web3.Keypair;
+
+    const positionAta = (
+      positionMint: anchor.web3.PublicKey,
+      owner: anchor.web3.PublicKey
+    ) =>
+      getAssociatedTokenAddressSync(positionMint, owner, false, TOKEN_2022_PROGRAM_ID);
+
+    const tokenize = async (
+      mint: anchor.web3.PublicKey,
+      user: anchor.web3.Keypair = admin.payer
+    ) => {
+      const positionMint = anchor.web3.Keypair.generate();
+      await program.methods
+        .tokenizePosition()
+        .accountsPartial({
+          user: user.publicKey,
+          stakeAccount: stakeAccountPda(mint, pool),
+          config: pool,
+          userAccount: userAccountPda(user.publicKey, pool),
+          positionMint: positionMint.publicKey,
+          positionAta: positionAta(positionMint.publicKey, user.publicKey),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .signers([user, positionMint])
+        .rpc();
+      return positionMint.publicKey;
+    };
+
+    // Moves the admin's receipt to the buyer, who also gets an ATA for the NFT itself
+    const sellTo = async (
+      mint: anchor.web3.PublicKey,
+      positionMint: anchor.web3.PublicKey
+    ) => {
+      await createAssociatedTokenAccountIdempotent(
+        provider.connection,
+        admin.payer,
+        positionMint,
+        buyer.publicKey,
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      await transferChecked(
+        provider.connection,
+        admin.payer,
+        positionAta(positionMint, admin.publicKey),
+        positionMint,
+        positionAta(positionMint, buyer.publicKey),
+        admin.payer,
+        1,
+        0,
+        [],
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      await createAssociatedTokenAccountIdempotent(
+        provider.connection,
+        admin.payer,
+        mint,
+        buyer.publicKey
+      );
+    };
+
+    const amountStaked = async (user: anchor.web3.PublicKey) =>
+      (await program.account.userAccount.fetch(userAccountPda(user, pool)))
+        .amountStaked;
+
+    before(async () => {
+      buyer = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: buyer.publicKey, config: pool })
+        .signers([buyer])
+        .rpc();
+    });
+
+    it("Lets whoever holds the receipt unstake the position", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+      await stake(mint, admin.payer, options);
+      const staked = await amountStaked(admin.publicKey);
+
+      const positionMint = await tokenize(mint);
+      const stakeAccount = await program.account.stakeAccount.fetch(
+        stakeAccountPda(mint, pool)
+      );
+      expect(stakeAccount.positionMint.toBase58()).to.equal(positionMint.toBase58());
+      expect(await amountStaked(admin.publicKey)).to.equal(staked - 1);
+      await expectError(tokenize(mint), "AlreadyTokenized");
+
+      await sellTo(mint, positionMint);
+
+      // The original owner is locked out of every way back to the NFT
+      await expectError(
+        unstake(mint, admin.payer, { ...options, receipt: positionMint }),
+        "ReceiptRequired"
+      );
+      await expectError(unstake(mint, admin.payer, options), "ReceiptRequired");
+      await expectError(
+        emergencyUnstake(mint, admin.payer, options),
+        "ReceiptRequired"
+      );
+
+      const sig = await unstake(mint, buyer, { ...options, receipt: positionMint });
+      const [event] = (await eventsOf(sig)).filter(
+        (e) => e.name === "nftUnstaked"
+      );
+      expect(event.data.user.toBase58()).to.equal(buyer.publicKey.toBase58());
+      const rewards = await provider.connection.getTokenAccountBalance(
+        getAssociatedTokenAddressSync(rewardMint, buyer.publicKey)
+      );
+      expect(rewards.value.amount).to.equal(event.data.rewardsPaid.toString());
+
+      const nft = await getAccount(provider.connection, nftAta(mint, buyer.publicKey));
+      expect(nft.amount.toString()).to.equal("1");
+      const receipt = await getAccount(
+        provider.connection,
+        positionAta(positionMint, buyer.publicKey),
+        undefined,
+        TOKEN_2022_PROGRAM_ID
+      );
+      expect(receipt.amount.toString()).to.equal("0");
+      expect(await amountStaked(buyer.publicKey)).to.equal(0);
+    });
+
+    it("Hands the stake to the holder on detokenize", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+      await stake(mint, admin.payer, options);
+      const positionMint = await tokenize(mint);
+      await sellTo(mint, positionMint);
+
+      await program.methods
+        .detokenizePosition()
+        .accountsPartial({
+          user: buyer.publicKey,
+          stakeAccount: stakeAccountPda(mint, pool),
+          config: pool,
+          userAccount: userAccountPda(buyer.publicKey, pool),
+          positionMint,
+          positionAta: positionAta(positionMint, buyer.publicKey),
+          tokenProgram: TOKEN_2022_PROGRAM_ID,
+        })
+        .signers([buyer])
+        .rpc();
+
+      const stakeAccount = await program.account.stakeAccount.fetch(
+        stakeAccountPda(mint, pool)
+      );
+      expect(stakeAccount.owner.toBase58()).to.equal(buyer.publicKey.toBase58());
+      expect(stakeAccount.positionMint).to.be.null;
+      expect(await amountStaked(buyer.publicKey)).to.equal(1);
+
+      await unstake(mint, buyer, options);
+      expect(await amountStaked(buyer.publicKey)).to.equal(0);
+    });
+
+    it("Only tokenizes vault stakes", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+      await stake(mint);
+
+      const positionMint = anchor.web3.Keypair.generate();
+      await expectError(
+        program.methods
+          .tokenizePosition()
+          .accountsPartial({
+            stakeAccount: stakeAccountPda(mint),
+            config,
+            userAccount: userAccountPda(admin.publicKey),
+            positionMint: positionMint.publicKey,
+            positionAta: positionAta(positionMint.publicKey, admin.publicKey),
+            tokenProgram: TOKEN_2022_PROGRAM_ID,
+          })
+          .signers([positionMint])
+          .rpc(),
+        "CustodyModeMismatch"
+      );
+
+      await unstake(mint);
+    });
+  });
+  describe("claim_many", () => {
+    let keeper: anchor.web3.Keypair;
+    let users: anchor.web3.Keypair[];
+
+    const rewardsAta = (user: anchor.web3.PublicKey) =>
+      getAssociatedTokenAddressSync(rewardMint, user);
+
+    const grant = (user: anchor.web3.PublicKey, points: number) =>
+      program.methods
+        .adjustPoints(new anchor.BN(points), 0)
+        .accountsPartial({
+          user,
+          config,
+          userAccount: userAccountPda(user),
+          leaderboard: null,
+        })
+        .rpc();
+
+    const claimMany = (wallets: anchor.web3.PublicKey[]) =>
+      program.methods
+        .claimMany()
+        .accountsPartial({
+          authority: keeper.publicKey,
+          config,
+          rewardMint,
+          rewardVault: rewardVaultPda(config),
+          treasuryAta: null,
+          treasury: null,
+          leaderboard: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts(
+          wallets.flatMap((wallet) => [
+            { pubkey: userAccountPda(wallet), isWritable: true, isSigner: false },
+            { pubkey: rewardsAta(wallet), isWritable: true, isSigner: false },
+            {
+              pubkey: anchor.web3.PublicKey.findProgramAddressSync(
+                [Buffer.from("blacklist"), config.toBuffer(), wallet.toBuffer()],
+                program.programId
+              )[0],
+              isWritable: false,
+              isSigner: false,
+            },
+          ])
+        )
+        .signers([keeper])
+        .rpc();
+
+    before(async () => {
+      keeper = await fundedKeypair();
+      users = [await fundedKeypair(), await fundedKeypair()];
+      for (const user of users) {
+        await program.methods
+          .initialize(null)
+          .accountsPartial({ user: user.publicKey, config })
+          .signers([user])
+          .rpc();
+        await program.methods
+          .setClaimDelegate(keeper.publicKey)
+          .accountsPartial({ user: user.publicKey, config })
+          .signers([user])
+          .rpc();
+        await createAssociatedTokenAccountIdempotent(
+          provider.connection,
+          admin.payer,
+          rewardMint,
+          user.publicKey
+        );
+      }
+    });
+
+    after(async () => {
+      await updateConfig({ claimCooldown: 0 }).rpc();
+    });
+
+    it("Claims for every user the keeper is delegate of", async () => {
+      const wallets = users.map((user) => user.publicKey);
+      for (const wallet of wallets) {
+        await grant(wallet, 2);
+      }
+
+      const events = (await eventsOf(await claimMany(wallets))).filter(
+        (e) => e.name === "rewardsClaimed"
+      );
+      expect(events.map((e) => e.data.user.toBase58())).to.deep.equal(
+        wallets.map((wallet) => wallet.toBase58())
+      );
+      for (const wallet of wallets) {
+        const ata = await getAccount(provider.connection, rewardsAta(wallet));
+        // 6 decimal reward mint
+        expect(Number(ata.amount)).to.equal(2_000_000);
+      }
+    });
+
+    it("Skips users in their cooldown instead of failing the batch", async () => {
+      // `cooling` was just claimed for above, `ready` never was
+      const [cooling] = users;
+      const ready = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: ready.publicKey, config })
+        .signers([ready])
+        .rpc();
+      await program.methods
+        .setClaimDelegate(keeper.publicKey)
+        .accountsPartial({ user: ready.publicKey, config })
+        .signers([ready])
+        .rpc();
+      await createAssociatedTokenAccountIdempotent(
+        provider.connection,
+        admin.payer,
+        rewardMint,
+        ready.publicKey
+      );
+      await grant(cooling.publicKey, 1);
+      await grant(ready.publicKey, 1);
+      await updateConfig({ claimCooldown: 3_600 }).rpc();
+
+      const events = (
+        await eventsOf(await claimMany([cooling.publicKey, ready.publicKey]))
+      ).filter((e) => e.name === "rewardsClaimed");
+      expect(events.length).to.equal(1);
+      expect(events[0].data.user.toBase58()).to.equal(ready.publicKey.toBase58());
+
+      const account = await program.account.userAccount.fetch(
+        userAccountPda(cooling.publicKey)
+      );
+      expect(account.points.toNumber()).to.equal(1);
+    });
+
+    it("Rejects users who didn't make the keeper their delegate", async () => {
+      const stranger = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: stranger.publicKey, config })
+        .signers([stranger])
+        .rpc();
+      await createAssociatedTokenAccountIdempotent(
+        provider.connection,
+        admin.payer,
+        rewardMint,
+        stranger.publicKey
+      );
+
+      await expectError(claimMany([stranger.publicKey]), "Unauthorized");
+      await expectError(claimMany([]), "InvalidBatchSize");
+    });
+
+    it("Forfeits expired points as claim would, then skips the user", async () => {
+      const EXPIRY_SECS = 3;
+      const stale = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: stale.publicKey, config })
+        .signers([stale])
+        .rpc();
+      await program.methods
+        .setClaimDelegate(keeper.publicKey)
+        .accountsPartial({ user: stale.publicKey, config })
+        .signers([stale])
+        .rpc();
+      await createAssociatedTokenAccountIdempotent(
+        provider.connection,
+        admin.payer,
+        rewardMint,
+        stale.publicKey
+      );
+      await updateConfig({ pointsExpirySecs: new anchor.BN(EXPIRY_SECS) }).rpc();
+      try {
+        await grant(stale.publicKey, 4);
+        await new Promise((resolve) => setTimeout(resolve, EXPIRY_SECS * 1_000 + 1_500));
+
+        const events = await eventsOf(await claimMany([stale.publicKey]));
+        expect(events.filter((e) => e.name === "rewardsClaimed")).to.be.empty;
+        const [expired] = events.filter((e) => e.name === "pointsExpired");
+        expect(expired.data.pointsForfeited.toNumber()).to.equal(4);
+
+        const account = await program.account.userAccount.fetch(userAccountPda(stale.publicKey));
+        expect(account.points.toNumber()).to.equal(0);
+        expect(account.pointsUpdatedAt.toNumber()).to.equal(expired.data.expiredAt.toNumber());
+      } finally {
+        await updateConfig({ pointsExpirySecs: new anchor.BN(0) }).rpc();
+      }
+    });
+  });
+  describe("allowlist", () => {
+    const allowlistPda = (wallet: anchor.web3.PublicKey) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("allowlist"), config.toBuffer(), wallet.toBuffer()],
+        program.programId
+      )[0];
+
+    const allowWallet = (wallet: anchor.web3.PublicKey) =>
+      program.methods.allowWallet().accountsPartial({ wallet, config }).rpc();
+
+    const disallowWallet = (wallet: anchor.web3.PublicKey) =>
+      program.methods
+        .disallowWallet()
+        .accountsPartial({ config, allowlistEntry: allowlistPda(wallet) })
+        .rpc();
+
+    const initializeUser = (
+      user: anchor.web3.Keypair,
+      allowlistEntry: anchor.web3.PublicKey | null = allowlistPda(user.publicKey)
+    ) =>
+      program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config, allowlistEntry })
+        .signers([user])
+        .rpc();
+
+    beforeEach(async () => {
+      await updateConfig({ gated: true }).rpc();
+    });
+
+    afterEach(async () => {
+      await updateConfig({ gated: false }).rpc();
+    });
+
+    it("Only lets allowlisted wallets in while gated", async () => {
+      const outsider = await fundedKeypair();
+      await expectError(initializeUser(outsider), "NotAllowlisted");
+      await expectError(initializeUser(outsider, null), "NotAllowlisted");
+      // `stake` creates the user account too, it's gated the same way
+      await expectError(
+        stake(await mintCollectionNft(outsider.publicKey), outsider),
+        "NotAllowlisted"
+      );
+
+      const member = await fundedKeypair();
+      await allowWallet(member.publicKey);
+      await initializeUser(member);
+      const mint = await mintCollectionNft(member.publicKey);
+      await stake(mint, member);
+      await unstake(mint, member);
+    });
+
+    it("Ignores the allowlist once ungated", async () => {
+      await updateConfig({ gated: false }).rpc();
+      const user = await fundedKeypair();
+      await initializeUser(user, null);
+      const mint = await mintCollectionNft(user.publicKey);
+      await stake(mint, user);
+      await unstake(mint, user);
+    });
+
+    it("Rejects stakes once a wallet's entry is closed", async () => {
+      const user = await fundedKeypair();
+      await allowWallet(user.publicKey);
+      await initializeUser(user);
+      const mint = await mintCollectionNft(user.publicKey);
+      await stake(mint, user);
+
+      const [updated] = (await eventsOf(await disallowWallet(user.publicKey))).filter(
+        (e) => e.name === "allowlistUpdated"
+      );
+      expect(updated.data.allowed).to.equal(false);
+      expect(await provider.connection.getAccountInfo(allowlistPda(user.publicKey)))
+        .to.be.null;
+
+      await expectError(
+        stake(await mintCollectionNft(user.publicKey), user),
+        "NotAllowlisted"
+      );
+      // Open stakes can still leave
+      await unstake(mint, user);
+    });
+
+    it("Rejects allowlist changes from a non-admin", async () => {
+      const intruder = await fundedKeypair();
+      await expectError(
+        program.methods
+          .allowWallet()
+          .accountsPartial({ admin: intruder.publicKey, wallet: intruder.publicKey, config })
+          .signers([intruder])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+  });
+  describe("user positions", () => {
+    const positionsPda = (user: anchor.web3.PublicKey) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("positions"), config.toBuffer(), user.toBuffer()],
+        program.programId
+      )[0];
+
+    const listed = async (user: anchor.web3.PublicKey) =>
+      (await program.account.userPositions.fetch(positionsPda(user))).mints
+        .map((mint) => mint.toBase58())
+        .sort();
+
+    it("Lists open stakes and drops the unstaked one in any position", async () => {
+      const user = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+      expect(await listed(user.publicKey)).to.deep.equal([]);
+
+      const mints = [];
+      for (let i = 0; i < 3; i++) {
+        const mint = await mintCollectionNft(user.publicKey);
+        await stake(mint, user);
+        mints.push(mint);
+      }
+      expect(await listed(user.publicKey)).to.deep.equal(
+        mints.map((mint) => mint.toBase58()).sort()
+      );
+
+      await unstake(mints[1], user);
+      expect(await listed(user.publicKey)).to.deep.equal(
+        [mints[0], mints[2]].map((mint) => mint.toBase58()).sort()
+      );
+
+      await unstake(mints[0], user);
+      await unstake(mints[2], user);
+      expect(await listed(user.publicKey)).to.deep.equal([]);
+    });
+
+    it("Keeps max_stake within the positions capacity", async () => {
+      // MAX_POSITIONS
+      await expectError(updateConfig({ maxStake: 33 }).rpc(), "MaxStakeExceedsCapacity");
+      await updateConfig({ maxStake: 32 }).rpc();
+      await updateConfig({ maxStake: 5 }).rpc();
+    });
+  });
+  describe("claim_requires_unlocked", () => {
+    let user: anchor.web3.Keypair;
+
+    const grant = (points: number) =>
+      program.methods
+        .adjustPoints(new anchor.BN(points), 0)
+        .accountsPartial({
+          user: user.publicKey,
+          config,
+          userAccount: userAccountPda(user.publicKey),
+          leaderboard: null,
+        })
+        .rpc();
+
+    // The user's stake accounts, in `UserPositions` order
+    const claimChecking = (mints: anchor.web3.PublicKey[]) =>
+      program.methods
+        .claim()
+        .accountsPartial({
+          authority: user.publicKey,
+          user: user.publicKey,
+          rewardsAta: getAssociatedTokenAddressSync(rewardMint, user.publicKey),
+          config,
+          userAccount: userAccountPda(user.publicKey),
+          tokenStakeAccount: null,
+          rewardMint,
+          rewardVault: rewardVaultPda(config),
+          treasuryAta: null,
+          treasury: null,
+          leaderboard: null,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .remainingAccounts(
+          mints.map((mint) => ({
+            pubkey: stakeAccountPda(mint),
+            isSigner: false,
+            isWritable: false,
+          }))
+        )
+        .signers([user])
+        .rpc();
+
+    beforeEach(async () => {
+      user = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+      await grant(5);
+      await updateConfig({ claimRequiresUnlocked: true }).rpc();
+    });
+
+    afterEach(async () => {
+      await updateConfig({
+        claimRequiresUnlocked: false,
+        freezePeriodSecs: new anchor.BN(0),
+      }).rpc();
+    });
+
+    it("Rejects claims while a position is still locked", async () => {
+      const unlocked = await mintCollectionNft(user.publicKey);
+      await stake(unlocked, user);
+      await updateConfig({ freezePeriodSecs: new anchor.BN(DAY) }).rpc();
+      const locked = await mintCollectionNft(user.publicKey);
+      await stake(locked, user);
+
+      await expectError(claimChecking([unlocked, locked]), "PositionsStillLocked");
+      // Every listed position has to be passed
+      await expectError(claimChecking([unlocked]), "InvalidRemainingAccounts");
+
+      await emergencyUnstake(locked, user);
+      await claimChecking([unlocked]);
+      await unstake(unlocked, user);
+    });
+
+    it("Ignores locks while the flag is off", async () => {
+      await updateConfig({ freezePeriodSecs: new anchor.BN(DAY) }).rpc();
+      const locked = await mintCollectionNft(user.publicKey);
+      await stake(locked, user);
+
+      await updateConfig({ claimRequiresUnlocked: false }).rpc();
+      await claim(user);
+      await emergencyUnstake(locked, user);
+    });
+
+    it("Lets a user with no positions claim leftover points", async () => {
+      await claimChecking([]);
+      const account = await program.account.userAccount.fetch(
+        userAccountPda(user.publicKey)
+      );
+      expect(account.points.toNumber()).to.equal(0);
+    });
+  });
+  describe("harvest", () => {
+    let user: anchor.web3.Keypair;
+
+    const harvest = (
+      mint: anchor.web3.PublicKey,
+      restartLock: boolean,
+      lockup: anchor.IdlTypes<NftStaking>["lockupKind"] | null = null
+    ) =>
+      program.methods
+        .harvest(restartLock, lockup)
+        .accountsPartial({
+          user: user.publicKey,
+          stakeAccount: stakeAccountPda(mint),
+          config,
+          userAccount: userAccountPda(user.publicKey),
+          rewardMint,
+          referrerAccount: null,
+        })
+        .signers([user])
+        .rpc();
+
+    beforeEach(async () => {
+      user = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+    });
+
+    // Localnet can't move the clock a whole day, so a harvest here never has points to bank.
+    // Banking, relocking and snapshot eligibility after a harvest are covered by the unit tests
+    it("Rejects a harvest with nothing to bank and leaves the stake as it was", async () => {
+      const mint = await mintCollectionNft(user.publicKey);
+      await stake(mint, user);
+      const staked = await program.account.stakeAccount.fetch(stakeAccountPda(mint));
+
+      await expectError(harvest(mint, false), "NothingToClaim");
+      await expectError(harvest(mint, true, { thirtyDay: {} }), "NothingToClaim");
+
+      const account = await program.account.stakeAccount.fetch(stakeAccountPda(mint));
+      expect(account.stakedAt.toNumber()).to.equal(staked.stakedAt.toNumber());
+      expect(account.openedAt.toNumber()).to.equal(staked.stakedAt.toNumber());
+      expect(account.unlockedAt.toNumber()).to.equal(staked.unlockedAt.toNumber());
+      expect(account.lockup).to.deep.equal(staked.lockup);
+
+      await unstake(mint, user);
+    });
+
+    it("Only takes a lockup with a restart", async () => {
+      const mint = await mintCollectionNft(user.publicKey);
+      await stake(mint, user);
+
+      await expectError(harvest(mint, false, { thirtyDay: {} }), "InvalidLockupKind");
+
+      await unstake(mint, user);
+    });
+  });
+  describe("unstake destination", () => {
+    const unstakeTo = (
+      mint: anchor.web3.PublicKey,
+      user: anchor.web3.Keypair,
+      destination: anchor.web3.PublicKey,
+      options: NftOptions = {}
+    ) =>
+      program.methods
+        .unstake()
+        .accountsPartial({
+          user: user.publicKey,
+          config: options.pool ?? config,
+          userAccount: userAccountPda(user.publicKey, options.pool),
+          rewardsAta: getAssociatedTokenAddressSync(rewardMint, user.publicKey),
+          rewardMint,
+          rewardVault: rewardVaultPda(options.pool ?? config),
+          rewardTokenProgram: TOKEN_PROGRAM_ID,
+          metadataProgram,
+          leaderboard: null,
+          destination,
+          ...nftAccounts(mint, user.publicKey, options),
+        })
+        .signers([user])
+        .rpc();
+
+    // `wallet`'s ATA for `mint`, created empty
+    const ataOf = (mint: anchor.web3.PublicKey, wallet: anchor.web3.PublicKey) =>
+      createAssociatedTokenAccountIdempotent(provider.connection, admin.payer, mint, wallet);
+
+    const amountIn = async (ata: anchor.web3.PublicKey) =>
+      Number((await getAccount(provider.connection, ata)).amount);
+
+    it("Sends a frozen-in-place NFT on to another wallet", async () => {
+      const user = await fundedKeypair();
+      const mint = await mintCollectionNft(user.publicKey);
+      await stake(mint, user);
+
+      const newWallet = anchor.web3.Keypair.generate().publicKey;
+      const destination = await ataOf(mint, newWallet);
+      await unstakeTo(mint, user, destination);
+
+      expect(await amountIn(destination)).to.equal(1);
+      expect(await amountIn(nftAta(mint, user.publicKey))).to.equal(0);
+      const released = await getAccount(provider.connection, destination);
+      expect(released.isFrozen).to.equal(false);
+    });
+
+    it("Sends a vaulted NFT on to another wallet", async () => {
+      const options = { pool: configPda(2), vault: true };
+      const mint = await mintCollectionNft(admin.publicKey);
+      await stake(mint, admin.payer, options);
+
+      const destination = await ataOf(mint, anchor.web3.Keypair.generate().publicKey);
+      await unstakeTo(mint, admin.payer, destination, options);
+
+      expect(await amountIn(destination)).to.equal(1);
+      expect(await amountIn(nftAta(mint, admin.publicKey))).to.equal(0);
+    });
+
+    it("Rejects a destination for another mint", async () => {
+      const user = await fundedKeypair();
+      const mint = await mintCollectionNft(user.publicKey);
+      const other = await mintCollectionNft(user.publicKey);
+      await stake(mint, user);
+
+      await expectError(
+        unstakeTo(mint, user, nftAta(other, user.publicKey)),
+        "DestinationMintMismatch"
+      );
+
+      // Left out, the NFT comes back to the staking wallet as before
+      await unstake(mint, user);
+      expect(await amountIn(nftAta(mint, user.publicKey))).to.equal(1);
+    });
+  });
+  describe("epoch snapshots", () => {
+    // Localnet's clock can't be warped, epochs are kept short and waited out
+    const EPOCH_SECS = 2;
+    const nextEpoch = () => new Promise((resolve) => setTimeout(resolve, EPOCH_SECS * 1_000 + 200));
+
+    const snapshotEpoch = () =>
+      program.methods
+        .snapshotEpoch()
+        .accountsPartial({ config, epochHistory: epochHistoryPda() })
+        .rpc();
+
+    before(async () => {
+      await program.methods
+        .initEpochHistory()
+        .accountsPartial({ config })
+        .rpc();
+      await updateConfig({ epochLengthSecs: EPOCH_SECS }).rpc();
+    });
+
+    after(async () => {
+      await updateConfig({ epochLengthSecs: 0 }).rpc();
+    });
+
+    it("Rejects a snapshot before the first epoch ends", async () => {
+      await expectError(snapshotEpoch(), "EpochNotElapsed");
+    });
+
+    it("Records total_staked once per epoch", async () => {
+      await nextEpoch();
+      const [first] = (await eventsOf(await snapshotEpoch())).filter(
+        (e) => e.name === "epochSnapshotTaken"
+      );
+      const totalStaked = (await program.account.stakeConfig.fetch(config)).totalStaked;
+      expect(first.data.totalStaked).to.equal(totalStaked);
+
+      // A second call in the same epoch is rejected, bots can't spam it
+      await expectError(snapshotEpoch(), "EpochNotElapsed");
+
+      const user = await fundedKeypair();
+      await stake(await mintCollectionNft(user.publicKey), user);
+      await nextEpoch();
+      await snapshotEpoch();
+
+      const pool = await program.account.stakeConfig.fetch(config);
+      const history = await program.account.epochHistory.fetch(epochHistoryPda());
+      const firstEpoch = first.data.epoch.toNumber();
+      const secondEpoch = pool.currentEpoch.toNumber();
+      expect(secondEpoch).to.be.greaterThan(firstEpoch);
+
+      expect(history.entries[firstEpoch % 64].totalStaked).to.equal(totalStaked);
+      expect(history.entries[secondEpoch % 64].epoch.toNumber()).to.equal(secondEpoch);
+      expect(history.entries[secondEpoch % 64].totalStaked).to.equal(totalStaked + 1);
+    });
+
+    it("Is off while epoch_length_secs is 0", async () => {
+      await updateConfig({ epochLengthSecs: 0 }).rpc();
+      await nextEpoch();
+      await expectError(snapshotEpoch(), "EpochNotElapsed");
+      await updateConfig({ epochLengthSecs: EPOCH_SECS }).rpc();
+    });
+  });
+  describe("stake rate limit", () => {
+    const WINDOW_SECS = 3;
+
+    before(async () => {
+      await updateConfig({ maxStakesPerWindow: 2, windowLen: WINDOW_SECS }).rpc();
+    });
+
+    after(async () => {
+      await updateConfig({ maxStakesPerWindow: 0, windowLen: 0 }).rpc();
+    });
+
+    it("Limits how often one wallet can stake until the window passes", async () => {
+      const user = await fundedKeypair();
+      const mints = [];
+      for (let i = 0; i < 3; i++) {
+        mints.push(await mintCollectionNft(user.publicKey));
+      }
+
+      await stake(mints[0], user);
+      await stake(mints[1], user);
+      await expectError(stake(mints[2], user), "StakeRateLimited");
+
+      // Unstaking doesn't give a stake back within the window
+      await unstake(mints[0], user);
+      await expectError(stake(mints[2], user), "StakeRateLimited");
+
+      // Other wallets have their own window
+      const other = await fundedKeypair();
+      await stake(await mintCollectionNft(other.publicKey), other);
+
+      await new Promise((resolve) => setTimeout(resolve, WINDOW_SECS * 1_000 + 500));
+      await stake(mints[2], user);
+      const account = await program.account.userAccount.fetch(userAccountPda(user.publicKey));
+      expect(account.stakesInWindow).to.equal(1);
+    });
+  });
+  describe("full set bonus", () => {
+    const pool = configPda(11);
+    let collection: anchor.web3.PublicKey;
+    let full: Awaited<ReturnType<typeof createCollection>>;
+
+    const positionsPda = (user: anchor.web3.PublicKey) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("positions"), pool.toBuffer(), user.toBuffer()],
+        program.programId
+      )[0];
+
+    const fullSetSince = async (user: anchor.web3.PublicKey) =>
+      (await program.account.userPositions.fetch(positionsPda(user))).fullSets[0].since.toNumber();
+
+    const setFullSetBonus = (collectionMint: anchor.web3.PublicKey, size: number, bps: number) =>
+      program.methods
+        .setFullSetBonus(size, bps)
+        .accountsPartial({ collectionMint, config: pool })
+        .rpc();
+
+    before(async () => {
+      full = await createCollection();
+      collection = new anchor.web3.PublicKey(full.publicKey);
+      const setCollections = Array(5).fill(anchor.web3.PublicKey.default);
+      setCollections[0] = collection;
+      await initializeConfig(11, { setCollections });
+      await program.methods
+        .initCollectionConfig(10_000)
+        .accountsPartial({ collectionMint: collection, config: pool })
+        .rpc();
+      await setFullSetBonus(collection, 2, 10_000);
+    });
+
+    it("Only counts collections in the set", async () => {
+      // The pool's own collection isn't one of its set collections
+      await expectError(
+        setFullSetBonus(new anchor.web3.PublicKey(collectionMint.publicKey), 2, 10_000),
+        "InvalidFullSet"
+      );
+      // Nobody can stake more than `max_stake` pieces
+      await expectError(setFullSetBonus(collection, 6, 10_000), "InvalidFullSet");
+
+      const config = await program.account.collectionConfig.fetch(
+        collectionConfigPda(collection, pool)
+      );
+      expect(config.collectionSize).to.equal(2);
+      expect(config.fullSetBonusBps).to.equal(10_000);
+    });
+
+    // As with the set bonus, the amounts themselves are covered by the unit tests
+    it("Starts on the last piece and drops as soon as one leaves", async () => {
+      const user = await fundedKeypair();
+      const options = { pool, collection };
+      const [first, last] = [
+        await mintCollectionNft(user.publicKey, { collection: full }),
+        await mintCollectionNft(user.publicKey, { collection: full }),
+      ];
+
+      await stake(first, user, options);
+      // One short of the threshold
+      expect(await fullSetSince(user.publicKey)).to.equal(0);
+
+      await stake(last, user, options);
+      expect(await fullSetSince(user.publicKey)).to.be.greaterThan(0);
+      const positions = await program.account.userPositions.fetch(positionsPda(user.publicKey));
+      expect(positions.fullSets[0].bonusBps).to.equal(10_000);
+
+      await unstake(first, user, options);
+      expect(await fullSetSince(user.publicKey)).to.equal(0);
+
+      // Restaking the piece completes it again
+      await stake(first, user, options);
+      expect(await fullSetSince(user.publicKey)).to.be.greaterThan(0);
+
+      await unstake(first, user, options);
+      await unstake(last, user, options);
+    });
+  });
+  describe("unstake many", () => {
+    const account = (pubkey: anchor.web3.PublicKey, isWritable = false) => ({
+      pubkey,
+      isWritable,
+      isSigner: false,
+    });
+
+    // `dropAccounts` trims the tail of remaining_accounts to build malformed batches
+    const unstakeMany = (
+      mints: anchor.web3.PublicKey[],
+      user: anchor.web3.Keypair,
+      dropAccounts = 0
+    ) => {
+      const remainingAccounts = mints.flatMap((mint) => [
+        account(stakeAccountPda(mint), true),
+        account(mint),
+        account(getAssociatedTokenAddressSync(mint, user.publicKey), true),
+        account(
+          new anchor.web3.PublicKey(
+            findMetadataPda(umi, { mint: publicKey(mint) })[0]
+          )
+        ),
+        account(
+          new anchor.web3.PublicKey(
+            findMasterEditionPda(umi, { mint: publicKey(mint) })[0]
+          )
+        ),
+        account(mintRecordPda(mint), true),
+      ]);
+      return program.methods
+        .unstakeMany()
+        .accountsPartial({
+          user: user.publicKey,
+          config,
+          userAccount: userAccountPda(user.publicKey),
+          rewardsAta: getAssociatedTokenAddressSync(rewardMint, user.publicKey),
+          rewardMint,
+          rewardVault: rewardVaultPda(config),
+          rewardTokenProgram: TOKEN_PROGRAM_ID,
+          tokenProgram: TOKEN_PROGRAM_ID,
+          metadataProgram,
+          leaderboard: null,
+          referrerAccount: null,
+        })
+        .remainingAccounts(
+          remainingAccounts.slice(0, remainingAccounts.length - dropAccounts)
+        )
+        .preInstructions([
+          anchor.web3.ComputeBudgetProgram.setComputeUnitLimit({
+            units: 1_000_000,
+          }),
+        ])
+        .signers([user]);
+    };
+
+    const stakedMints = async (user: anchor.web3.Keypair, lockups: NftOptions["lockup"][]) => {
+      const mints = [];
+      for (const lockup of lockups) {
+        const mint = await mintCollectionNft(user.publicKey);
+        await stake(mint, user, { lockup });
+        mints.push(mint);
+      }
+      return mints;
+    };
+
+    const batchEvent = async (signature: string) =>
+      (await eventsOf(signature)).find((e) => e.name === "batchUnstaked");
+
+    // The count `unstake_many` returns, the first byte of its return data
+    const processedBy = async (signature: string) => {
+      const tx = await provider.connection.getTransaction(signature, {
+        commitment: "confirmed",
+        maxSupportedTransactionVersion: 0,
+      });
+      return Buffer.from(tx.meta.returnData.data[0], "base64")[0];
+    };
+
+    it("Unstakes the unlocked stakes of a mixed batch and skips the locked one", async () => {
+      const user = await fundedKeypair();
+      const mints = await stakedMints(user, [{ flexible: {} }, { thirtyDay: {} }, { flexible: {} }]);
+
+      const signature = await unstakeMany(mints, user).rpc();
+      const event = await batchEvent(signature);
+      expect(await processedBy(signature)).to.equal(2);
+      expect(event.data.mints.map((m) => m.toBase58())).to.deep.equal(
+        [mints[0], mints[2]].map((m) => m.toBase58())
+      );
+      expect(event.data.skippedLocked.map((m) => m.toBase58())).to.deep.equal([
+        mints[1].toBase58(),
+      ]);
+
+      const userAccount = await program.account.userAccount.fetch(userAccountPda(user.publicKey));
+      expect(userAccount.amountStaked).to.equal(1);
+      for (const mint of [mints[0], mints[2]]) {
+        expect(await provider.connection.getAccountInfo(stakeAccountPda(mint))).to.be.null;
+        const ata = await getAccount(provider.connection, getAssociatedTokenAddressSync(mint, user.publicKey));
+        expect(ata.isFrozen).to.equal(false);
+      }
+      const locked = await getAccount(provider.connection, getAssociatedTokenAddressSync(mints[1], user.publicKey));
+      expect(locked.isFrozen).to.equal(true);
+    });
+
+    it("Succeeds without unstaking anything when every stake is locked", async () => {
+      const user = await fundedKeypair();
+      const mints = await stakedMints(user, [{ thirtyDay: {} }, { ninetyDay: {} }]);
+
+      const signature = await unstakeMany(mints, user).rpc();
+      const event = await batchEvent(signature);
+      expect(await processedBy(signature)).to.equal(0);
+      expect(event.data.mints).to.be.empty;
+      expect(event.data.skippedLocked).to.have.length(2);
+      expect(event.data.pointsEarned.toNumber()).to.equal(0);
+
+      const userAccount = await program.account.userAccount.fetch(userAccountPda(user.publicKey));
+      expect(userAccount.amountStaked).to.equal(2);
+    });
+
+    it("Rejects malformed batches", async () => {
+      const user = await fundedKeypair();
+      const [mint] = await stakedMints(user, [{ flexible: {} }]);
+      const other = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: other.publicKey, config })
+        .signers([other])
+        .rpc();
+
+      await expectError(unstakeMany([mint], user, 1).rpc(), "InvalidBatchSize");
+      await expectError(unstakeMany([mint, mint], user, 1).rpc(), "InvalidRemainingAccounts");
+      await expectError(unstakeMany([mint, mint], user).rpc(), "InvalidRemainingAccounts");
+      // Someone else's stake
+      await expectError(unstakeMany([mint], other).rpc(), "NotStakeOwner");
+
+      await unstake(mint, user);
+    });
+  });
+  describe("initialize_config authority", () => {
+    it("Rejects a pool from anyone but the upgrade authority", async () => {
+      const squatter = await fundedKeypair();
+      await expectError(initializeConfig(12, {}, rewardMint, squatter), "NotUpgradeAuthority");
+      expect(await provider.connection.getAccountInfo(configPda(12))).to.be.null;
+    });
+
+    it("Lets the upgrade authority create it, once", async () => {
+      await initializeConfig(12);
+      const pool = await program.account.stakeConfig.fetch(configPda(12));
+      expect(pool.admin.toBase58()).to.equal(admin.publicKey.toBase58());
+
+      // The config PDA already exists, so a second init fails before touching it
+      await expectError(initializeConfig(12, { pointsPerStake: 99 }), "already in use");
+      expect((await program.account.stakeConfig.fetch(configPda(12))).pointsPerStake).to.equal(10);
+    });
+  });
+  describe("transfer_mint_authority", () => {
+    const pool = configPda(13);
+    const multisig = anchor.web3.Keypair.generate().publicKey;
+    let poolMint: anchor.web3.PublicKey;
+
+    const transferMintAuthority = (
+      signer: anchor.web3.Keypair = admin.payer,
+      mint: anchor.web3.PublicKey = poolMint,
+      config: anchor.web3.PublicKey = pool
+    ) =>
+      program.methods
+        .transferMintAuthority(multisig)
+        .accountsPartial({
+          admin: signer.publicKey,
+          config,
+          rewardMint: mint,
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .signers([signer])
+        .rpc();
+
+    before(async () => {
+      // A reward mint handed to the pool's config, the way pools minted rewards before the vault
+      poolMint = await createMint(provider.connection, admin.payer, pool, null, 6);
+      await initializeConfig(13, {}, poolMint);
+    });
+
+    it("Only lets the admin move it", async () => {
+      await expectError(transferMintAuthority(await fundedKeypair()), "Unauthorized");
+    });
+
+    it("Rejects a reward mint the config doesn't control", async () => {
+      await expectError(transferMintAuthority(admin.payer, rewardMint, configPda(0)), "ConstraintMintMintAuthority");
+    });
+
+    it("Hands the mint authority to the new pubkey", async () => {
+      const events = await eventsOf(await transferMintAuthority());
+
+      const mint = await getMint(provider.connection, poolMint);
+      expect(mint.mintAuthority.toBase58()).to.equal(multisig.toBase58());
+      const event = events.find((e) => e.name === "mintAuthorityTransferred");
+      expect(event.data.newAuthority.toBase58()).to.equal(multisig.toBase58());
+      expect(event.data.rewardMint.toBase58()).to.equal(poolMint.toBase58());
+
+      // The config no longer holds it, so a second transfer can't go through
+      await expectError(transferMintAuthority(), "ConstraintMintMintAuthority");
+    });
+  });
+  describe("points expiry", () => {
+    const EXPIRY_SECS = 3;
+    let user: anchor.web3.Keypair;
+
+    const grant = (points: number) =>
+      program.methods
+        .adjustPoints(new anchor.BN(points), 0)
+        .accountsPartial({
+          user: user.publicKey,
+          config,
+          userAccount: userAccountPda(user.publicKey),
+          leaderboard: null,
+        })
+        .rpc();
+    const waitOutExpiry = () =>
+      new Promise((resolve) => setTimeout(resolve, EXPIRY_SECS * 1_000 + 1_500));
+
+    before(async () => {
+      user = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+      await updateConfig({ pointsExpirySecs: new anchor.BN(EXPIRY_SECS) }).rpc();
+    });
+
+    after(async () => {
+      await updateConfig({ pointsExpirySecs: new anchor.BN(0) }).rpc();
+    });
+
+    it("Pays out points claimed before they expire", async () => {
+      await grant(5);
+      const [claimed] = (await eventsOf(await claim(user))).filter(
+        (e) => e.name === "rewardsClaimed"
+      );
+      expect(claimed.data.pointsBurned.toNumber()).to.equal(5);
+    });
+
+    it("Forfeits the whole balance once it expires", async () => {
+      await grant(5);
+      await waitOutExpiry();
+
+      // The forfeit leaves nothing to claim, so the claim reverts with it
+      await expectError(claim(user), "NothingToClaim");
+
+      // The next credit forfeits the stale points before adding its own
+      const events = await eventsOf(await grant(7));
+      const expired = events.find((e) => e.name === "pointsExpired");
+      expect(expired.data.pointsForfeited.toNumber()).to.equal(5);
+      expect(expired.data.user.toBase58()).to.equal(user.publicKey.toBase58());
+
+      const account = await program.account.userAccount.fetch(userAccountPda(user.publicKey));
+      expect(account.points.toNumber()).to.equal(7);
+      expect(account.pointsUpdatedAt.toNumber()).to.equal(expired.data.expiredAt.toNumber());
+    });
+
+    it("Starts a fresh window with the credit after expiry", async () => {
+      const [claimed] = (await eventsOf(await claim(user))).filter(
+        (e) => e.name === "rewardsClaimed"
+      );
+      expect(claimed.data.pointsBurned.toNumber()).to.equal(7);
+    });
+  });
+  describe("validate_config", () => {
+    // Matched loosely, the client may camelCase IDL error names
+    const errorCode = (name: string) =>
+      program.idl.errors.find((e) => e.name.toLowerCase() === name.toLowerCase()).code;
+    const validateConfig = (overrides: Partial<anchor.IdlTypes<NftStaking>["initializeConfigArgs"]> = {}) =>
+      program.methods.validateConfig(initializeConfigArgs(14, overrides)).view();
+
+    it("Accepts args initialize_config would", async () => {
+      expect(await validateConfig()).to.be.null;
+    });
+
+    it("Reports the error code of the first failing check", async () => {
+      expect(await validateConfig({ pointsPerStake: 0 })).to.equal(errorCode("invalidPointsPerStake"));
+      // The suite's 90 day lockup outlasts a 30 day accrual cap
+      expect(await validateConfig({ maxStakeDuration: 30 })).to.equal(errorCode("freezeExceedsStakeDuration"));
+      expect(await validateConfig({ maxStakesPerWindow: 2 })).to.equal(errorCode("invalidStakeWindow"));
+      expect(await validateConfig({ feeBps: 10_001 })).to.equal(errorCode("invalidFeeBps"));
+    });
+
+    it("Rejects inconsistent configs at write time", async () => {
+      await expectError(initializeConfig(14, { compoundBoostBps: 100 }), "InvalidBoostCap");
+      expect(await provider.connection.getAccountInfo(configPda(14))).to.be.null;
+
+      await expectError(updateConfig({ maxStakeDuration: 30 }).rpc(), "FreezeExceedsStakeDuration");
+      // Fields moved together are checked as a whole
+      await updateConfig({ maxStakesPerWindow: 2, windowLen: 60 }).rpc();
+      await expectError(updateConfig({ windowLen: 0 }).rpc(), "InvalidStakeWindow");
+      await updateConfig({ maxStakesPerWindow: 0, windowLen: 0 }).rpc();
+    });
+  });
+  describe("governance weight", () => {
+    let user: anchor.web3.Keypair;
+
+    const governanceWeight = async () =>
+      (
+        await program.methods
+          .getPendingRewards()
+          .accountsPartial({
+            user: user.publicKey,
+            config,
+            userAccount: userAccountPda(user.publicKey),
+            rewardMint,
+          })
+          .view()
+      ).governanceWeight.toNumber();
+    const sleep = (secs: number) => new Promise((resolve) => setTimeout(resolve, secs * 1_000));
+
+    before(async () => {
+      user = await fundedKeypair();
+      await program.methods
+        .initialize(null)
+        .accountsPartial({ user: user.publicKey, config })
+        .signers([user])
+        .rpc();
+      // 1,000 weight a second per NFT, drained a thousand times faster once idle
+      await updateConfig({
+        weightPerDay: new anchor.BN(1_000 * DAY),
+        weightDecayPerDay: new anchor.BN(1_000_000 * DAY),
+      }).rpc();
+    });
+
+    after(async () => {
+      await updateConfig({ weightPerDay: new anchor.BN(0), weightDecayPerDay: new anchor.BN(0) }).rpc();
+    });
+
+    it("Grows while staked and decays to zero, not below, once idle", async () => {
+      const mint = await mintCollectionNft(user.publicKey);
+      await stake(mint, user);
+      await sleep(2);
+      expect(await governanceWeight()).to.be.greaterThan(0);
+
+      await unstake(mint, user);
+      const settled = await program.account.userAccount.fetch(userAccountPda(user.publicKey));
+      // Unstaking pays out the points, the weight isn't spent with them
+      expect(settled.governanceWeight.toNumber()).to.be.greaterThan(0);
+
+      await sleep(2);
+      expect(await governanceWeight()).to.equal(0);
+
+      // Restaking settles the idle stretch at the floor, then grows from zero again
+      const again = await mintCollectionNft(user.publicKey);
+      await stake(again, user);
+      const account = await program.account.userAccount.fetch(userAccountPda(user.publicKey));
+      expect(account.governanceWeight.toNumber()).to.equal(0);
+      expect(account.weightUpdatedAt.toNumber()).to.be.greaterThan(settled.weightUpdatedAt.toNumber());
+    });
+  });
+  describe("crank_accrue", () => {
+    it("Lets anyone settle a stake's finished days", async () => {
+      const mint = await mintCollectionNft(admin.publicKey);
+      await stake(mint);
+
+      // Takes no signer, whoever pays the fee can crank any stake
+      const sig = await program.methods
+        .crankAccrue()
+        .accountsPartial({ config, stakeAccount: stakeAccountPda(mint) })
+        .rpc();
+
+      // Not a whole day in, so nothing settles yet and the stake is already caught up
+      const [accrued] = (await eventsOf(sig)).filter((e) => e.name === "pointsAccrued");
+      expect(accrued.data.daysAccrued).to.equal(0);
+      expect(accrued.data.caughtUp).to.be.true;
+
+      const account = await program.account.stakeAccount.fetch(stakeAccountPda(mint));
+      expect(account.lastAccruedAt.toNumber()).to.equal(account.stakedAt.toNumber());
+      expect(account.accruedPoints.toNumber()).to.equal(0);
+
+      await unstake(mint);
+    });
+  });
 });